@@ -1,3 +1,395 @@
-fn main() {
-    println!("Benteng CLI");
+use anyhow::{bail, Context, Result};
+use benteng_kms_daemon::{protocol::SealedShare, Identity, PeerKey, RemoteKms};
+use benteng_sdk_core::{
+    crypto::{kem, kms::DualControlConfig, shamir::{self, CustodianShare}, sig},
+    policy::Policy,
+    policy_bundle::{PolicyDocument, SignedPolicyBundle, SignerSet, TrustAnchor},
+    policy_delta::SignedPolicyDelta,
+    policy_freshness::BundleTimestamp,
+};
+use clap::{Parser, Subcommand};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+#[derive(Parser)]
+#[command(name = "benteng", version, about = "Benteng CLI")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Policy bundle tooling
+    #[command(subcommand)]
+    Policy(PolicyCommand),
+    /// HSM-B master secret ceremonies
+    #[command(subcommand)]
+    Ceremony(CeremonyCommand),
+}
+
+#[derive(Subcommand)]
+enum CeremonyCommand {
+    /// Generate a custodian's Kyber768 key pair, which their share files are
+    /// sealed to
+    Keygen {
+        #[arg(long)]
+        public_key: PathBuf,
+        #[arg(long)]
+        secret_key: PathBuf,
+    },
+    /// Generate a new master secret and write one sealed share file per
+    /// custodian
+    Generate {
+        #[arg(long)]
+        threshold: u8,
+        /// Custodian as id=public_key_file, repeatable
+        #[arg(long = "custodian", required = true)]
+        custodians: Vec<String>,
+        #[arg(long)]
+        out_dir: PathBuf,
+    },
+    /// Re-split an existing secret to a new custodian set and threshold
+    Reshare {
+        /// Existing share as share_file=custodian_secret_key_file, repeatable
+        #[arg(long = "share", required = true)]
+        shares: Vec<String>,
+        #[arg(long)]
+        threshold: u8,
+        /// Custodian as id=public_key_file, repeatable
+        #[arg(long = "custodian", required = true)]
+        custodians: Vec<String>,
+        #[arg(long)]
+        out_dir: PathBuf,
+    },
+    /// Check that shares reconstruct their secret
+    Verify {
+        /// Share as share_file=custodian_secret_key_file, repeatable
+        #[arg(long = "share", required = true)]
+        shares: Vec<String>,
+    },
+    /// Submit a share to a running KMS daemon towards unsealing its HSM-B
+    Unseal {
+        #[arg(long)]
+        share: PathBuf,
+        /// The custodian's Kyber768 secret key file
+        #[arg(long)]
+        custodian_key: PathBuf,
+        /// Daemon URL, e.g. http://10.0.0.5:7443
+        #[arg(long)]
+        kms_url: String,
+        /// Admin identity the daemon pins
+        #[arg(long)]
+        admin_id: String,
+        /// The admin's ML-DSA secret key file
+        #[arg(long)]
+        admin_key: PathBuf,
+        #[arg(long, default_value = "kmsd")]
+        server_id: String,
+        /// The daemon's ML-DSA public key file
+        #[arg(long)]
+        server_public_key: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum PolicyCommand {
+    /// Generate an ML-DSA signer key pair
+    Keygen {
+        #[arg(long)]
+        public_key: PathBuf,
+        #[arg(long)]
+        secret_key: PathBuf,
+    },
+    /// Create an unsigned bundle from a JSON array of policies
+    Init {
+        #[arg(long)]
+        policies: PathBuf,
+        #[arg(long)]
+        version: u64,
+        #[arg(long, default_value_t = 86400)]
+        ttl_secs: u64,
+        /// Create a shard holding only this tenant's policies
+        #[arg(long)]
+        tenant: Option<String>,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Create an unsigned delta from one bundle version to the next
+    Diff {
+        #[arg(long)]
+        base: PathBuf,
+        #[arg(long)]
+        target: PathBuf,
+        #[arg(long, default_value_t = 86400)]
+        ttl_secs: u64,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Add this signer's signature to a (partially) signed bundle or delta in place
+    Sign {
+        #[arg(long)]
+        bundle: PathBuf,
+        #[arg(long)]
+        kid: String,
+        #[arg(long)]
+        secret_key: PathBuf,
+    },
+    /// Issue a signed freshness timestamp announcing a bundle or delta
+    Timestamp {
+        #[arg(long)]
+        bundle: PathBuf,
+        #[arg(long, default_value_t = 3600)]
+        ttl_secs: u64,
+        #[arg(long)]
+        kid: String,
+        #[arg(long)]
+        secret_key: PathBuf,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Check a bundle against a signer set
+    Verify {
+        #[arg(long)]
+        bundle: PathBuf,
+        /// Registered signer as kid=public_key_file, repeatable
+        #[arg(long = "signer", required = true)]
+        signers: Vec<String>,
+        #[arg(long)]
+        threshold: usize,
+    },
+}
+
+fn read_bundle(path: &PathBuf) -> Result<SignedPolicyBundle> {
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    Ok(SignedPolicyBundle::from_json(&data)?)
+}
+
+fn write_bundle(path: &PathBuf, bundle: &SignedPolicyBundle) -> Result<()> {
+    std::fs::write(path, bundle.to_json()?).with_context(|| format!("writing {}", path.display()))
+}
+
+/// Write secret material readable only by its owner
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).with_context(|| format!("writing {}", path.display()))?;
+    // An existing file keeps its mode on open
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(data).with_context(|| format!("writing {}", path.display()))
+}
+
+fn read_document(path: &PathBuf) -> Result<PolicyDocument> {
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    match PolicyDocument::parse_all(&data)?.as_slice() {
+        [document] => Ok(document.clone()),
+        _ => bail!("{} must hold a single document", path.display()),
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("reading {}", path.display()))
+}
+
+/// Open a sealed share file with its custodian's secret key
+fn open_share(path: &Path, secret_key: &Path) -> Result<CustodianShare> {
+    let sealed: SealedShare = serde_json::from_slice(&read_file(path)?)?;
+    let secret_key = Zeroizing::new(read_file(secret_key)?);
+    sealed.open(&secret_key).with_context(|| format!("opening {}", path.display()))
+}
+
+/// Open `share_file=custodian_secret_key_file` arguments
+fn open_shares(args: &[String]) -> Result<Vec<CustodianShare>> {
+    args.iter()
+        .map(|arg| {
+            let Some((share, secret_key)) = arg.split_once('=') else {
+                bail!("share must be share_file=custodian_secret_key_file: {}", arg);
+            };
+            open_share(Path::new(share), Path::new(secret_key))
+        })
+        .collect()
+}
+
+/// Parse `id=public_key_file` custodian arguments
+fn custodian_keys(args: &[String]) -> Result<Vec<(String, Vec<u8>)>> {
+    args.iter()
+        .map(|arg| {
+            let Some((id, public_key)) = arg.split_once('=') else {
+                bail!("custodian must be id=public_key_file: {}", arg);
+            };
+            Ok((id.to_string(), read_file(Path::new(public_key))?))
+        })
+        .collect()
+}
+
+/// Seal each share to its custodian's key and write it to
+/// `<out_dir>/<custodian>.share.json` for hand-over. Only the custodian can
+/// open it, so the out_dir never holds a share in the clear.
+fn distribute_shares(out_dir: &Path, shares: &[CustodianShare], custodians: &[(String, Vec<u8>)]) -> Result<()> {
+    std::fs::create_dir_all(out_dir).with_context(|| format!("creating {}", out_dir.display()))?;
+    for share in shares {
+        let Some((_, public_key)) = custodians.iter().find(|(id, _)| *id == share.custodian) else {
+            bail!("no public key for custodian {}", share.custodian);
+        };
+        let sealed = SealedShare::seal(share, public_key)
+            .with_context(|| format!("sealing the share for {}", share.custodian))?;
+        let path = out_dir.join(format!("{}.share.json", share.custodian));
+        write_private(&path, &serde_json::to_vec_pretty(&sealed)?)?;
+        println!("Wrote share for {} to {}", share.custodian, path.display());
+    }
+    Ok(())
+}
+
+fn run_ceremony(command: CeremonyCommand) -> Result<()> {
+    match command {
+        CeremonyCommand::Keygen { public_key, secret_key } => {
+            let (pk, sk) = kem::kyber768_keypair()?;
+            std::fs::write(&public_key, pk)?;
+            write_private(&secret_key, &sk)?;
+            println!("Wrote {} and {}", public_key.display(), secret_key.display());
+        }
+        CeremonyCommand::Generate { threshold, custodians, out_dir } => {
+            let custodians = custodian_keys(&custodians)?;
+            let ids: Vec<String> = custodians.iter().map(|(id, _)| id.clone()).collect();
+            let secret = shamir::generate_secret()?;
+            let shares = shamir::split(&secret, threshold, &ids)?;
+            distribute_shares(&out_dir, &shares, &custodians)?;
+            println!(
+                "Secret {} split {}-of-{}",
+                shamir::secret_id(&secret)?, threshold, ids.len()
+            );
+        }
+        CeremonyCommand::Reshare { shares, threshold, custodians, out_dir } => {
+            let custodians = custodian_keys(&custodians)?;
+            let ids: Vec<String> = custodians.iter().map(|(id, _)| id.clone()).collect();
+            let shares = shamir::reshare(&open_shares(&shares)?, threshold, &ids)?;
+            distribute_shares(&out_dir, &shares, &custodians)?;
+            println!(
+                "Secret {} re-shared {}-of-{}",
+                shares[0].secret_id, threshold, ids.len()
+            );
+        }
+        CeremonyCommand::Verify { shares } => {
+            let secret = shamir::combine(&open_shares(&shares)?)?;
+            println!("Shares reconstruct secret {}", shamir::secret_id(&secret)?);
+        }
+        CeremonyCommand::Unseal { share, custodian_key, kms_url, admin_id, admin_key, server_id, server_public_key } => {
+            let share = open_share(&share, &custodian_key)?;
+            let identity = Identity {
+                id: admin_id,
+                signing_key: Zeroizing::new(std::fs::read(&admin_key)
+                    .with_context(|| format!("reading {}", admin_key.display()))?),
+            };
+            let server = PeerKey {
+                id: server_id,
+                public_key: std::fs::read(&server_public_key)
+                    .with_context(|| format!("reading {}", server_public_key.display()))?,
+            };
+            let kms = RemoteKms::new(&kms_url, &DualControlConfig::default(), identity, server)?;
+            let held = tokio::runtime::Runtime::new()?.block_on(kms.submit_share(&share))?;
+            println!("Share from {} accepted; HSM-B holds {} of {}", share.custodian, held, share.threshold);
+            if held >= share.threshold as usize {
+                println!("HSM-B unsealed");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_policy(command: PolicyCommand) -> Result<()> {
+    match command {
+        PolicyCommand::Keygen { public_key, secret_key } => {
+            let (pk, sk) = sig::dilithium3_keypair()?;
+            std::fs::write(&public_key, pk)?;
+            write_private(&secret_key, &sk)?;
+            println!("Wrote {} and {}", public_key.display(), secret_key.display());
+        }
+        PolicyCommand::Init { policies, version, ttl_secs, tenant, out } => {
+            let data = std::fs::read(&policies)
+                .with_context(|| format!("reading {}", policies.display()))?;
+            let policies: Vec<Policy> = serde_json::from_slice(&data)?;
+            let bundle = match &tenant {
+                Some(tenant) => SignedPolicyBundle::shard(tenant, &policies, version, ttl_secs),
+                None => SignedPolicyBundle::unsigned(policies, version, ttl_secs),
+            };
+            write_bundle(&out, &bundle)?;
+            println!("Wrote unsigned bundle v{} to {}", version, out.display());
+        }
+        PolicyCommand::Diff { base, target, ttl_secs, out } => {
+            let delta = SignedPolicyDelta::diff(&read_bundle(&base)?, &read_bundle(&target)?, ttl_secs)?;
+            std::fs::write(&out, delta.to_json()?)
+                .with_context(|| format!("writing {}", out.display()))?;
+            println!(
+                "Wrote unsigned delta v{} -> v{} ({} changes) to {}",
+                delta.base_version, delta.version, delta.changes.len(), out.display()
+            );
+        }
+        PolicyCommand::Sign { bundle: path, kid, secret_key } => {
+            let sk = std::fs::read(&secret_key)
+                .with_context(|| format!("reading {}", secret_key.display()))?;
+            let (data, version, signatures) = match read_document(&path)? {
+                PolicyDocument::Bundle(mut bundle) => {
+                    bundle.add_signature(kid.clone(), &sk)?;
+                    (bundle.to_json()?, bundle.version, bundle.signatures.len())
+                }
+                PolicyDocument::Delta(mut delta) => {
+                    delta.add_signature(kid.clone(), &sk)?;
+                    (delta.to_json()?, delta.version, delta.signatures.len())
+                }
+                PolicyDocument::Timestamp(_) => bail!("use `policy timestamp` to issue timestamps"),
+            };
+            std::fs::write(&path, data).with_context(|| format!("writing {}", path.display()))?;
+            println!("Signed v{} as {} ({} signatures)", version, kid, signatures);
+        }
+        PolicyCommand::Timestamp { bundle: path, ttl_secs, kid, secret_key, out } => {
+            let (tenant_id, version, state_hash) = match read_document(&path)? {
+                PolicyDocument::Bundle(bundle) => (bundle.tenant_id.clone(), bundle.version, bundle.state_hash()?),
+                PolicyDocument::Delta(delta) => (delta.tenant_id, delta.version, delta.state_hash),
+                PolicyDocument::Timestamp(_) => bail!("{} is already a timestamp", path.display()),
+            };
+            let sk = std::fs::read(&secret_key)
+                .with_context(|| format!("reading {}", secret_key.display()))?;
+            let mut timestamp = BundleTimestamp::unsigned(tenant_id, version, state_hash, ttl_secs);
+            timestamp.add_signature(kid, &sk)?;
+            std::fs::write(&out, timestamp.to_json()?)
+                .with_context(|| format!("writing {}", out.display()))?;
+            println!("Wrote timestamp for v{} to {}", version, out.display());
+        }
+        PolicyCommand::Verify { bundle: path, signers, threshold } => {
+            let bundle = read_bundle(&path)?;
+            let mut anchors = Vec::new();
+            for signer in signers {
+                let Some((kid, key_file)) = signer.split_once('=') else {
+                    bail!("signer must be kid=public_key_file: {}", signer);
+                };
+                anchors.push(TrustAnchor {
+                    kid: kid.to_string(),
+                    public_key: std::fs::read(key_file)
+                        .with_context(|| format!("reading {}", key_file))?,
+                });
+            }
+
+            let signer_set = SignerSet::new(anchors, threshold)?;
+            let valid = signer_set.valid_signers(&bundle)?;
+            println!("Valid signers: {}", valid.join(", "));
+
+            signer_set.verify(&bundle)?;
+            if !bundle.is_valid() {
+                bail!("bundle v{} is outside its validity window", bundle.version);
+            }
+            println!("Bundle v{} meets {}-of-N threshold", bundle.version, threshold);
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Policy(command) => run_policy(command),
+        Command::Ceremony(command) => run_ceremony(command),
+    }
 }
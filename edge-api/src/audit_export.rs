@@ -5,9 +5,12 @@ use zip::CompressionMethod;
 use chrono::{DateTime, Utc};
 use serde_json;
 use sha2::{Sha256, Digest};
+use std::sync::Arc;
+use benteng_sdk_core::crypto::key_catalog::KeyCatalog;
 
 pub struct AuditPackExporter {
     output_path: String,
+    key_catalog: Option<Arc<KeyCatalog>>,
 }
 
 #[derive(serde::Serialize)]
@@ -25,7 +28,16 @@ struct FileChecksum {
 
 impl AuditPackExporter {
     pub fn new(output_path: String) -> Self {
-        Self { output_path }
+        Self {
+            output_path,
+            key_catalog: None,
+        }
+    }
+    
+    /// Include the KMS key catalog in audit packs
+    pub fn with_key_catalog(mut self, catalog: Arc<KeyCatalog>) -> Self {
+        self.key_catalog = Some(catalog);
+        self
     }
     
     pub async fn generate_audit_pack(
@@ -88,7 +100,7 @@ impl AuditPackExporter {
         });
         
         // 5. Export policy snapshots
-        let policies = self.export_policy_snapshots(tenant_id).await?;
+        let policies = self.export_policy_snapshots(_tenant_id).await?;
         zip.start_file("policy_snapshots.json", options)?;
         let policies_json = serde_json::to_string_pretty(&policies)?;
         zip.write_all(policies_json.as_bytes())?;
@@ -108,20 +120,18 @@ impl AuditPackExporter {
         });
         
         // 7. Add proof PDFs if available
-        for pdf in std::fs::read_dir("formal/proofs").unwrap_or_else(|_| {
+        for entry in std::fs::read_dir("formal/proofs").unwrap_or_else(|_| {
             std::fs::read_dir(".").unwrap()
-        }) {
-            if let Ok(entry) = pdf {
-                if entry.path().extension().map_or(false, |ext| ext == "pdf") {
-                    let filename = entry.file_name().to_string_lossy().to_string();
-                    let content = std::fs::read(entry.path())?;
-                    zip.start_file(&format!("proofs/{}", filename), options)?;
-                    zip.write_all(&content)?;
-                    metadata.checksums.push(FileChecksum {
-                        filename: format!("proofs/{}", filename),
-                        sha256: Self::hash_content(&content),
-                    });
-                }
+        }).flatten() {
+            if entry.path().extension().is_some_and(|ext| ext == "pdf") {
+                let filename = entry.file_name().to_string_lossy().to_string();
+                let content = std::fs::read(entry.path())?;
+                zip.start_file(format!("proofs/{}", filename), options)?;
+                zip.write_all(&content)?;
+                metadata.checksums.push(FileChecksum {
+                    filename: format!("proofs/{}", filename),
+                    sha256: Self::hash_content(&content),
+                });
             }
         }
         
@@ -177,9 +187,12 @@ impl AuditPackExporter {
     }
     
     async fn export_key_catalog(&self) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
-        // Implementation would export key catalog
+        let keys = match &self.key_catalog {
+            Some(catalog) => catalog.list()?,
+            None => vec![],
+        };
         Ok(serde_json::json!({
-            "keys": []
+            "keys": keys
         }))
     }
 }
@@ -191,10 +204,13 @@ mod tests {
     
     #[tokio::test]
     async fn test_audit_pack_creation() {
+        let catalog = Arc::new(KeyCatalog::temporary().unwrap());
+        catalog.create(b"tenant123", b"policy", Default::default()).unwrap();
+        
         let temp_file = NamedTempFile::new().unwrap();
         let exporter = AuditPackExporter::new(
             temp_file.path().to_string_lossy().to_string()
-        );
+        ).with_key_catalog(catalog);
         
         let result = exporter.generate_audit_pack(
             Utc::now() - chrono::Duration::days(7),
@@ -203,5 +219,10 @@ mod tests {
         ).await;
         
         assert!(result.is_ok());
+        
+        let mut zip = zip::ZipArchive::new(File::open(temp_file.path()).unwrap()).unwrap();
+        let key_catalog: serde_json::Value = serde_json::from_reader(zip.by_name("key_catalog.json").unwrap()).unwrap();
+        assert_eq!(key_catalog["keys"][0]["tenant_id"], hex::encode("tenant123"));
+        assert_eq!(key_catalog["keys"][0]["state"], "pending");
     }
 }
@@ -0,0 +1,393 @@
+//! Client signing keys the edge verifies envelopes against
+//! Keys are enrolled per tenant and looked up by KID. Only active, unexpired
+//! keys may sign; suspension can be lifted, revocation is final. Service
+//! keys, such as the one a `RewrapService` re-signs with, sign for every tenant.
+
+use crate::config::ClientKeySettings;
+use benteng_sdk_core::crypto::sig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sled::Db;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+type Result<T> = std::result::Result<T, ClientKeyError>;
+
+/// Tenant of a configured key that is enrolled as a service key
+pub const SERVICE_TENANT: &str = "*";
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientKeyError {
+    #[error("Unknown client key {0}")]
+    NotFound(String),
+    #[error("Client key {0} already exists")]
+    Conflict(String),
+    #[error("Invalid enrollment: {0}")]
+    Invalid(String),
+    #[error("Device attestation rejected: {0}")]
+    Attestation(String),
+    #[error("Client key {0} is revoked")]
+    Revoked(String),
+    #[error("Client key store: {0}")]
+    Storage(String),
+}
+
+fn storage_error(e: impl std::fmt::Display) -> ClientKeyError {
+    ClientKeyError::Storage(e.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientKeyState {
+    Active,
+    /// Temporarily not allowed to sign
+    Suspended,
+    /// Permanently withdrawn
+    Revoked,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientKeyRecord {
+    pub kid: String,
+    /// Hex tenant ID
+    pub tenant_id: String,
+    /// Hex ML-DSA-65 public key
+    pub public_key: String,
+    pub state: ClientKeyState,
+    pub enrolled_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// SHA-256 of the attestation evidence presented at enrollment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation_hash: Option<String>,
+    /// The registry's attestation verifier accepted that evidence. Only
+    /// then does a signature from this key count as device-attested.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub attested: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_changed_at: Option<u64>,
+    /// Signs for every tenant; `tenant_id` is empty
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub service: bool,
+}
+
+impl ClientKeyRecord {
+    /// Whether the key may sign at `now`
+    pub fn is_usable(&self, now: u64) -> bool {
+        self.state == ClientKeyState::Active && self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
+
+/// A key to enroll for a tenant
+#[derive(Debug, Clone, Default)]
+pub struct Enrollment {
+    /// Random when unset
+    pub kid: Option<String>,
+    pub public_key: Vec<u8>,
+    pub expires_at: Option<u64>,
+    /// Device attestation evidence, required when the registry has a verifier
+    pub attestation: Option<Vec<u8>>,
+}
+
+/// Checks device attestation evidence binding a client key to a device
+pub trait AttestationVerifier: Send + Sync {
+    fn verify(&self, tenant_id: &[u8], public_key: &[u8], evidence: &[u8]) -> std::result::Result<(), String>;
+}
+
+/// sled-backed client key registry
+pub struct ClientKeyRegistry {
+    db: Db,
+    /// Serializes read-modify-write updates
+    lock: Mutex<()>,
+    attestation: Option<Arc<dyn AttestationVerifier>>,
+}
+
+impl ClientKeyRegistry {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let db = sled::open(path).map_err(storage_error)?;
+        Ok(Self { db, lock: Mutex::new(()), attestation: None })
+    }
+
+    /// Registry that lives only as long as the process
+    pub fn temporary() -> Result<Self> {
+        let db = sled::Config::new().temporary(true).open().map_err(storage_error)?;
+        Ok(Self { db, lock: Mutex::new(()), attestation: None })
+    }
+
+    /// Require enrollments to carry attestation evidence `verifier` accepts
+    pub fn with_attestation_verifier(mut self, verifier: Arc<dyn AttestationVerifier>) -> Self {
+        self.attestation = Some(verifier);
+        self
+    }
+
+    /// Registry at the configured store, or a temporary one, with the
+    /// configured keys enrolled if missing
+    pub fn open(settings: &ClientKeySettings) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let registry = match &settings.store_path {
+            Some(path) => Self::new(path)?,
+            None => Self::temporary()?,
+        };
+
+        for seed in &settings.keys {
+            if registry.get(&seed.kid)?.is_some() {
+                continue;
+            }
+            let public_key = std::fs::read(&seed.public_key_file)
+                .map_err(|e| format!("reading {}: {}", seed.public_key_file.display(), e))?;
+            let enrollment = Enrollment {
+                kid: Some(seed.kid.clone()),
+                public_key,
+                ..Default::default()
+            };
+            if seed.tenant_id == SERVICE_TENANT {
+                registry.enroll_service(enrollment)?;
+            } else {
+                registry.enroll(&hex::decode(&seed.tenant_id)?, enrollment)?;
+            }
+        }
+        Ok(registry)
+    }
+
+    fn key(kid: &str) -> String {
+        format!("client-key:{}", kid)
+    }
+
+    fn put(&self, record: &ClientKeyRecord) -> Result<()> {
+        let value = serde_json::to_vec(record).map_err(storage_error)?;
+        self.db.insert(Self::key(&record.kid).as_bytes(), value).map_err(storage_error)?;
+        self.db.flush().map_err(storage_error)?;
+        Ok(())
+    }
+
+    pub fn get(&self, kid: &str) -> Result<Option<ClientKeyRecord>> {
+        self.db.get(Self::key(kid).as_bytes())
+            .map_err(storage_error)?
+            .map(|value| serde_json::from_slice(&value).map_err(storage_error))
+            .transpose()
+    }
+
+    /// Keys enrolled for a tenant, ordered by KID
+    pub fn list(&self, tenant_id: &[u8]) -> Result<Vec<ClientKeyRecord>> {
+        let tenant_id = hex::encode(tenant_id);
+        let mut records = Vec::new();
+        for item in self.db.scan_prefix(b"client-key:") {
+            let (_, value) = item.map_err(storage_error)?;
+            let record: ClientKeyRecord = serde_json::from_slice(&value).map_err(storage_error)?;
+            if record.tenant_id == tenant_id {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Enroll an active key for a tenant
+    pub fn enroll(&self, tenant_id: &[u8], enrollment: Enrollment) -> Result<ClientKeyRecord> {
+        sig::dilithium3_check_public_key(&enrollment.public_key)
+            .map_err(|_| ClientKeyError::Invalid("not an ML-DSA-65 public key".into()))?;
+
+        let attested = match (&self.attestation, &enrollment.attestation) {
+            (Some(verifier), Some(evidence)) => {
+                verifier.verify(tenant_id, &enrollment.public_key, evidence)
+                    .map_err(ClientKeyError::Attestation)?;
+                true
+            }
+            (Some(_), None) => return Err(ClientKeyError::Attestation("evidence required".into())),
+            (None, _) => false,
+        };
+
+        self.insert(hex::encode(tenant_id), false, enrollment, attested)
+    }
+
+    /// Enroll an active service key, which signs for every tenant. Service
+    /// keys are operator-held, so no attestation is asked for.
+    pub fn enroll_service(&self, enrollment: Enrollment) -> Result<ClientKeyRecord> {
+        sig::dilithium3_check_public_key(&enrollment.public_key)
+            .map_err(|_| ClientKeyError::Invalid("not an ML-DSA-65 public key".into()))?;
+        self.insert(String::new(), true, enrollment, false)
+    }
+
+    fn insert(
+        &self,
+        tenant_id: String,
+        service: bool,
+        enrollment: Enrollment,
+        attested: bool,
+    ) -> Result<ClientKeyRecord> {
+        let kid = enrollment.kid.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        if kid.is_empty() {
+            return Err(ClientKeyError::Invalid("empty key ID".into()));
+        }
+
+        let _guard = self.lock.lock().map_err(storage_error)?;
+        if self.get(&kid)?.is_some() {
+            return Err(ClientKeyError::Conflict(kid));
+        }
+        let record = ClientKeyRecord {
+            kid,
+            tenant_id,
+            public_key: hex::encode(&enrollment.public_key),
+            state: ClientKeyState::Active,
+            enrolled_at: now_secs(),
+            expires_at: enrollment.expires_at,
+            attestation_hash: enrollment.attestation.as_ref()
+                .map(|evidence| hex::encode(Sha256::digest(evidence))),
+            attested,
+            state_changed_at: None,
+            service,
+        };
+        self.put(&record)?;
+        Ok(record)
+    }
+
+    /// Move a key to `state`. Revoked keys stay revoked.
+    pub fn set_state(&self, kid: &str, state: ClientKeyState) -> Result<ClientKeyRecord> {
+        let _guard = self.lock.lock().map_err(storage_error)?;
+        let mut record = self.get(kid)?.ok_or_else(|| ClientKeyError::NotFound(kid.to_string()))?;
+        if record.state == ClientKeyState::Revoked && state != ClientKeyState::Revoked {
+            return Err(ClientKeyError::Revoked(kid.to_string()));
+        }
+        if record.state != state {
+            record.state = state;
+            record.state_changed_at = Some(now_secs());
+            self.put(&record)?;
+        }
+        Ok(record)
+    }
+
+    pub fn revoke(&self, kid: &str) -> Result<ClientKeyRecord> {
+        self.set_state(kid, ClientKeyState::Revoked)
+    }
+
+    /// Record of `kid` if it may sign for the tenant right now
+    pub fn signing_key(&self, tenant_id: &[u8], kid: &str) -> Result<Option<ClientKeyRecord>> {
+        let Some(record) = self.get(kid)? else {
+            return Ok(None);
+        };
+        let signs_for_tenant = record.service || record.tenant_id == hex::encode(tenant_id);
+        if !signs_for_tenant || !record.is_usable(now_secs()) {
+            return Ok(None);
+        }
+        Ok(Some(record))
+    }
+
+    /// Public key of `kid` if it may sign for the tenant right now
+    pub fn public_key(&self, tenant_id: &[u8], kid: &str) -> Result<Option<Vec<u8>>> {
+        self.signing_key(tenant_id, kid)?
+            .map(|record| hex::decode(&record.public_key).map_err(storage_error))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct PrefixAttestation;
+
+    impl AttestationVerifier for PrefixAttestation {
+        fn verify(&self, _tenant_id: &[u8], public_key: &[u8], evidence: &[u8]) -> std::result::Result<(), String> {
+            if evidence.starts_with(b"device:") && evidence.ends_with(&public_key[..8]) {
+                Ok(())
+            } else {
+                Err("unknown device".into())
+            }
+        }
+    }
+
+    #[test]
+    fn test_client_key_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let (public_key, _) = sig::dilithium3_keypair().unwrap();
+
+        {
+            let registry = ClientKeyRegistry::new(dir.path()).unwrap();
+            let record = registry.enroll(b"tenant", Enrollment {
+                kid: Some("phone-1".into()),
+                public_key: public_key.clone(),
+                ..Default::default()
+            }).unwrap();
+            assert_eq!(record.state, ClientKeyState::Active);
+            assert!(matches!(
+                registry.enroll(b"tenant", Enrollment { kid: Some("phone-1".into()), public_key: public_key.clone(), ..Default::default() }),
+                Err(ClientKeyError::Conflict(_))
+            ));
+            assert!(matches!(
+                registry.enroll(b"tenant", Enrollment { public_key: vec![1, 2, 3], ..Default::default() }),
+                Err(ClientKeyError::Invalid(_))
+            ));
+        }
+
+        // Persisted, and scoped to the tenant
+        let registry = ClientKeyRegistry::new(dir.path()).unwrap();
+        assert_eq!(registry.public_key(b"tenant", "phone-1").unwrap(), Some(public_key.clone()));
+        assert_eq!(registry.public_key(b"other", "phone-1").unwrap(), None);
+        assert_eq!(registry.list(b"tenant").unwrap().len(), 1);
+        assert!(registry.list(b"other").unwrap().is_empty());
+
+        registry.set_state("phone-1", ClientKeyState::Suspended).unwrap();
+        assert_eq!(registry.public_key(b"tenant", "phone-1").unwrap(), None);
+        registry.set_state("phone-1", ClientKeyState::Active).unwrap();
+        assert!(registry.public_key(b"tenant", "phone-1").unwrap().is_some());
+        registry.revoke("phone-1").unwrap();
+        assert_eq!(registry.public_key(b"tenant", "phone-1").unwrap(), None);
+        assert!(matches!(registry.set_state("phone-1", ClientKeyState::Active), Err(ClientKeyError::Revoked(_))));
+        assert!(matches!(registry.revoke("missing"), Err(ClientKeyError::NotFound(_))));
+
+        // Expired keys can't sign
+        let expired = registry.enroll(b"tenant", Enrollment {
+            public_key: public_key.clone(),
+            expires_at: Some(now_secs() - 1),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(registry.public_key(b"tenant", &expired.kid).unwrap(), None);
+
+        // Service keys sign for every tenant but belong to none
+        registry.enroll_service(Enrollment {
+            kid: Some("rewrap-service".into()),
+            public_key: public_key.clone(),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(registry.public_key(b"tenant", "rewrap-service").unwrap(), Some(public_key.clone()));
+        assert_eq!(registry.public_key(b"other", "rewrap-service").unwrap(), Some(public_key));
+        assert!(registry.list(b"tenant").unwrap().iter().all(|record| !record.service));
+        registry.revoke("rewrap-service").unwrap();
+        assert_eq!(registry.public_key(b"tenant", "rewrap-service").unwrap(), None);
+    }
+
+    #[test]
+    fn test_attestation_gated_enrollment() {
+        let registry = ClientKeyRegistry::temporary().unwrap()
+            .with_attestation_verifier(Arc::new(PrefixAttestation));
+        let (public_key, _) = sig::dilithium3_keypair().unwrap();
+
+        let enrollment = |attestation: Option<Vec<u8>>| Enrollment {
+            public_key: public_key.clone(),
+            attestation,
+            ..Default::default()
+        };
+        assert!(matches!(registry.enroll(b"tenant", enrollment(None)), Err(ClientKeyError::Attestation(_))));
+        assert!(matches!(
+            registry.enroll(b"tenant", enrollment(Some(b"device:bogus".to_vec()))),
+            Err(ClientKeyError::Attestation(_))
+        ));
+
+        let evidence = [b"device:".as_slice(), &public_key[..8]].concat();
+        let record = registry.enroll(b"tenant", enrollment(Some(evidence.clone()))).unwrap();
+        assert_eq!(record.attestation_hash, Some(hex::encode(Sha256::digest(&evidence))));
+        assert!(record.attested);
+
+        // Evidence no verifier checked is recorded but does not attest
+        let registry = ClientKeyRegistry::temporary().unwrap();
+        let record = registry.enroll(b"tenant", enrollment(Some(evidence))).unwrap();
+        assert!(record.attestation_hash.is_some());
+        assert!(!record.attested);
+    }
+}
@@ -0,0 +1,646 @@
+//! Edge API server configuration
+//! Read from a TOML file, then overridden by `BENTENG_*` environment
+//! variables. Every setting has a default, so an empty file is valid.
+
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+type ConfigError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// `BENTENG_BIND`
+    pub bind: String,
+    /// How long shutdown waits for in-flight requests.
+    /// `BENTENG_SHUTDOWN_TIMEOUT_SECS`
+    pub shutdown_timeout_secs: u64,
+    /// Bearer token for the admin endpoints and `/metrics`; they are refused without one.
+    /// `BENTENG_ADMIN_TOKEN`
+    pub admin_token: Option<String>,
+    pub kms: KmsSettings,
+    pub policy: PolicySettings,
+    pub rate_limit: RateLimitSettings,
+    pub replay: ReplaySettings,
+    pub transparency: TransparencySettings,
+    pub client_keys: ClientKeySettings,
+    pub metrics: MetricsSettings,
+    pub telemetry: TelemetrySettings,
+    /// Serve HTTPS instead of HTTP
+    pub tls: Option<TlsSettings>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0:3000".to_string(),
+            shutdown_timeout_secs: 30,
+            admin_token: None,
+            kms: KmsSettings::default(),
+            policy: PolicySettings::default(),
+            rate_limit: RateLimitSettings::default(),
+            replay: ReplaySettings::default(),
+            transparency: TransparencySettings::default(),
+            client_keys: ClientKeySettings::default(),
+            metrics: MetricsSettings::default(),
+            telemetry: TelemetrySettings::default(),
+            tls: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KmsSettings {
+    /// KMS daemon to use instead of an in-process KMS. `BENTENG_KMS_URL`
+    pub url: Option<String>,
+    /// Our ML-DSA secret key file for the daemon. `BENTENG_KMS_CLIENT_KEY`
+    pub client_key_file: Option<PathBuf>,
+    /// The daemon's pinned public key file. `BENTENG_KMS_SERVER_PUBLIC_KEY`
+    pub server_public_key_file: Option<PathBuf>,
+    /// `BENTENG_KMS_CLIENT_ID`
+    pub client_id: String,
+    /// `BENTENG_KMS_SERVER_ID`
+    pub server_id: String,
+    /// `BENTENG_KMS_TIMEOUT_MS`
+    pub timeout_ms: u64,
+    /// `BENTENG_KMS_REQUIRE_QUORUM`
+    pub require_quorum: bool,
+    /// `BENTENG_KMS_APPROVAL_STORE`
+    pub approval_store_path: Option<PathBuf>,
+    /// `BENTENG_KMS_KEY_CATALOG`
+    pub key_catalog_path: Option<PathBuf>,
+    /// `BENTENG_APPROVAL_WEBHOOK_URL`
+    pub approval_webhook_url: Option<String>,
+    /// `BENTENG_APPROVAL_EVENTS_FILE`
+    pub approval_events_file: Option<PathBuf>,
+    /// Quorum approvers of an in-process KMS as `id=public_key_file`
+    /// entries. `BENTENG_KMS_APPROVERS`
+    pub approvers: Vec<String>,
+    /// Scopes given a key at startup when they have no active one
+    pub bootstrap_keys: Vec<KeyScope>,
+}
+
+impl Default for KmsSettings {
+    fn default() -> Self {
+        Self {
+            url: None,
+            client_key_file: None,
+            server_public_key_file: None,
+            client_id: "edge-api".to_string(),
+            server_id: "kmsd".to_string(),
+            timeout_ms: 5000,
+            require_quorum: false,
+            approval_store_path: None,
+            key_catalog_path: None,
+            approval_webhook_url: None,
+            approval_events_file: None,
+            approvers: Vec::new(),
+            bootstrap_keys: Vec::new(),
+        }
+    }
+}
+
+/// Hex tenant and policy IDs
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyScope {
+    pub tenant_id: String,
+    pub policy_id: String,
+}
+
+/// Signed policy bundle source; see `PolicyLoaderConfig`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicySettings {
+    /// `BENTENG_POLICY_DIR`
+    pub dir: Option<PathBuf>,
+    /// `BENTENG_POLICY_URL`
+    pub url: Option<String>,
+    /// `kid=public_key_file` entries. `BENTENG_POLICY_SIGNERS`, or
+    /// `BENTENG_POLICY_TRUST_KID` with `BENTENG_POLICY_TRUST_KEY_FILE`
+    pub signers: Vec<String>,
+    /// Signatures required; all signers when unset. `BENTENG_POLICY_THRESHOLD`
+    pub threshold: Option<usize>,
+    /// Default 60. `BENTENG_POLICY_REFRESH_SECS`
+    pub refresh_secs: Option<u64>,
+    /// Stage new bundles for shadow evaluation. `BENTENG_POLICY_SHADOW`
+    pub shadow: bool,
+    /// Where the high-water mark is persisted. `BENTENG_POLICY_STATE_DIR`
+    pub state_dir: Option<PathBuf>,
+    /// `BENTENG_POLICY_TIMESTAMP_SIGNERS`
+    pub timestamp_signers: Vec<String>,
+    /// Default 1. `BENTENG_POLICY_TIMESTAMP_THRESHOLD`
+    pub timestamp_threshold: Option<usize>,
+    /// `BENTENG_POLICY_TIMESTAMP_URL`
+    pub timestamp_url: Option<String>,
+    /// Keep serving stale policy. `BENTENG_POLICY_FAIL_OPEN`
+    pub fail_open: bool,
+    /// Tenants to subscribe to; all when unset. `BENTENG_POLICY_TENANTS`
+    pub tenants: Option<Vec<String>>,
+}
+
+/// Token buckets per route for each tenant, and optionally for each client
+/// key and client network. A request must fit in every bucket it falls in;
+/// the tenant and client key buckets only count requests that authenticated.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitSettings {
+    /// Tenant bucket size. `BENTENG_RATE_LIMIT_BURST`
+    pub burst: f64,
+    /// Tenant refill rate. `BENTENG_RATE_LIMIT_PER_SEC`
+    pub per_sec: f64,
+    /// Tenant limits for particular tenants or routes
+    pub overrides: Vec<RateLimitOverride>,
+    /// Per client key; unlimited when unset.
+    /// `BENTENG_RATE_LIMIT_CLIENT_KEY` as `burst/per_sec`
+    pub client_key: Option<RateLimit>,
+    /// Per client /24 (IPv4) or /64 (IPv6), hashed with a rotating salt;
+    /// unlimited when unset. `BENTENG_RATE_LIMIT_IP` as `burst/per_sec`
+    pub ip: Option<RateLimit>,
+    /// Header carrying the client address when behind a load balancer,
+    /// e.g. `x-forwarded-for`. `BENTENG_RATE_LIMIT_CLIENT_IP_HEADER`
+    pub client_ip_header: Option<String>,
+    /// How often the IP hashing salt changes
+    pub salt_rotation_hours: i64,
+    /// Buckets untouched this long are dropped. `BENTENG_RATE_LIMIT_IDLE_SECS`
+    pub idle_secs: u64,
+    /// Buckets held at most; the longest idle go first.
+    /// `BENTENG_RATE_LIMIT_MAX_BUCKETS`
+    pub max_buckets: usize,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            burst: 100.0,
+            per_sec: 10.0,
+            overrides: Vec::new(),
+            client_key: None,
+            ip: None,
+            client_ip_header: None,
+            salt_rotation_hours: 24,
+            idle_secs: 600,
+            max_buckets: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub burst: f64,
+    pub per_sec: f64,
+}
+
+impl std::str::FromStr for RateLimit {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, ConfigError> {
+        let (burst, per_sec) = s.split_once('/').ok_or("rate limits are written burst/per_sec")?;
+        Ok(Self { burst: burst.trim().parse()?, per_sec: per_sec.trim().parse()? })
+    }
+}
+
+/// Tenant limit for one tenant, one route, or one route of one tenant. The
+/// most specific match applies.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitOverride {
+    /// Hex tenant ID
+    pub tenant_id: Option<String>,
+    /// `verify` or `decrypt`
+    pub route: Option<String>,
+    pub burst: f64,
+    pub per_sec: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplaySettings {
+    /// Replay window for envelopes without a configured policy; policies
+    /// set their own `replay_ttl_ms`. `BENTENG_REPLAY_TTL_SECS`
+    pub ttl_secs: u64,
+    /// sled directory, so seen envelopes survive a restart.
+    /// `BENTENG_REPLAY_PATH`
+    pub path: Option<PathBuf>,
+    /// Redis shared by every replica, e.g. `redis://cache:6379/0`.
+    /// `BENTENG_REPLAY_REDIS_URL`
+    pub redis_url: Option<String>,
+    /// Shards of the in-memory store. `BENTENG_REPLAY_SHARDS`
+    pub shards: usize,
+}
+
+impl Default for ReplaySettings {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 300,
+            path: None,
+            redis_url: None,
+            shards: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransparencySettings {
+    /// JSON-lines file the log is kept in; in memory when unset.
+    /// `BENTENG_TLOG_PATH`
+    pub path: Option<PathBuf>,
+    /// ML-DSA secret key file checkpoints are signed with; an ephemeral key
+    /// when unset. `BENTENG_TLOG_SIGNING_KEY`
+    pub signing_key_file: Option<PathBuf>,
+    /// Key ID recorded in checkpoints, default `benteng-tlog`.
+    /// `BENTENG_TLOG_KEY_ID`
+    pub key_id: Option<String>,
+    /// Longest an entry waits for a signed checkpoint.
+    /// `BENTENG_TLOG_CHECKPOINT_INTERVAL_MS`
+    pub checkpoint_interval_ms: u64,
+    /// Entries that trigger a checkpoint before the interval is up.
+    /// `BENTENG_TLOG_CHECKPOINT_BATCH`
+    pub checkpoint_batch: usize,
+}
+
+impl Default for TransparencySettings {
+    fn default() -> Self {
+        Self {
+            path: None,
+            signing_key_file: None,
+            key_id: None,
+            checkpoint_interval_ms: 1000,
+            checkpoint_batch: 256,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientKeySettings {
+    /// sled directory; a temporary registry when unset.
+    /// `BENTENG_CLIENT_KEY_STORE`
+    pub store_path: Option<PathBuf>,
+    /// Keys enrolled at startup if missing. `BENTENG_CLIENT_KEYS` adds
+    /// `tenant_id_hex/kid=public_key_file,...`; `*` as the tenant enrolls a
+    /// service key, e.g. the rewrap service's
+    pub keys: Vec<ClientKeySeed>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsSettings {
+    /// Distinct tenant labels before the rest are counted as `other`.
+    /// `BENTENG_METRICS_MAX_TENANTS`
+    pub max_tenant_labels: usize,
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        Self { max_tenant_labels: 100 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetrySettings {
+    /// OTLP/HTTP traces URL, e.g. `http://collector:4318/v1/traces`; spans
+    /// are not exported without one. `BENTENG_OTLP_ENDPOINT`
+    pub otlp_endpoint: Option<String>,
+    /// `service.name` of the exported spans. `BENTENG_OTLP_SERVICE_NAME`
+    pub service_name: String,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "benteng-edge-api".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientKeySeed {
+    /// Hex tenant ID, or `*` for a service key
+    pub tenant_id: String,
+    pub kid: String,
+    pub public_key_file: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsSettings {
+    /// PEM certificate chain. `BENTENG_TLS_CERT`
+    pub cert_path: PathBuf,
+    /// PEM private key. `BENTENG_TLS_KEY`
+    pub key_path: PathBuf,
+    /// Offer only the X25519MLKEM768 hybrid group, refusing classical-only
+    /// clients. `BENTENG_TLS_HYBRID_ONLY`
+    #[serde(default)]
+    pub hybrid_only: bool,
+    /// PEM CA bundle client certificates are verified against; enables
+    /// mTLS. `BENTENG_TLS_CLIENT_CA`
+    #[serde(default)]
+    pub client_ca_path: Option<PathBuf>,
+    /// Refuse connections without a client certificate.
+    /// `BENTENG_TLS_REQUIRE_CLIENT_CERT`
+    #[serde(default)]
+    pub require_client_cert: bool,
+    /// Tenant each client certificate may act for
+    #[serde(default)]
+    pub client_certs: Vec<ClientCertTenant>,
+}
+
+/// A client certificate, by SHA-256 fingerprint of its DER encoding, and the
+/// hex tenant ID it is bound to
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientCertTenant {
+    pub fingerprint: String,
+    pub tenant_id: String,
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str, target: &mut T) -> Result<(), ConfigError>
+where
+    T::Err: std::fmt::Display,
+{
+    if let Some(value) = env(name) {
+        *target = value.parse().map_err(|e| format!("{}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+fn flag_env(name: &str, target: &mut bool) {
+    if let Some(value) = env(name) {
+        *target = matches!(value.as_str(), "1" | "true");
+    }
+}
+
+fn list_env(name: &str) -> Option<Vec<String>> {
+    env(name).map(|value| {
+        value.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+impl ServerConfig {
+    /// Settings from `path`, if given, with environment overrides applied
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut config = match path {
+            Some(path) => Self::from_toml(
+                &std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path.display(), e))?
+            )?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Override settings from `BENTENG_*` variables
+    pub fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(bind) = env("BENTENG_BIND") {
+            self.bind = bind;
+        }
+        parse_env("BENTENG_SHUTDOWN_TIMEOUT_SECS", &mut self.shutdown_timeout_secs)?;
+        if let Some(token) = env("BENTENG_ADMIN_TOKEN") {
+            self.admin_token = Some(token);
+        }
+
+        self.kms.apply_env()?;
+        self.policy.apply_env()?;
+
+        parse_env("BENTENG_RATE_LIMIT_BURST", &mut self.rate_limit.burst)?;
+        parse_env("BENTENG_RATE_LIMIT_PER_SEC", &mut self.rate_limit.per_sec)?;
+        if let Some(limit) = env("BENTENG_RATE_LIMIT_CLIENT_KEY") {
+            self.rate_limit.client_key = Some(limit.parse().map_err(|e| format!("BENTENG_RATE_LIMIT_CLIENT_KEY: {}", e))?);
+        }
+        if let Some(limit) = env("BENTENG_RATE_LIMIT_IP") {
+            self.rate_limit.ip = Some(limit.parse().map_err(|e| format!("BENTENG_RATE_LIMIT_IP: {}", e))?);
+        }
+        if let Some(header) = env("BENTENG_RATE_LIMIT_CLIENT_IP_HEADER") {
+            self.rate_limit.client_ip_header = Some(header);
+        }
+        parse_env("BENTENG_RATE_LIMIT_IDLE_SECS", &mut self.rate_limit.idle_secs)?;
+        parse_env("BENTENG_RATE_LIMIT_MAX_BUCKETS", &mut self.rate_limit.max_buckets)?;
+        parse_env("BENTENG_REPLAY_TTL_SECS", &mut self.replay.ttl_secs)?;
+        if let Some(path) = env("BENTENG_REPLAY_PATH") {
+            self.replay.path = Some(path.into());
+        }
+        if let Some(url) = env("BENTENG_REPLAY_REDIS_URL") {
+            self.replay.redis_url = Some(url);
+        }
+        parse_env("BENTENG_REPLAY_SHARDS", &mut self.replay.shards)?;
+        if let Some(path) = env("BENTENG_TLOG_PATH") {
+            self.transparency.path = Some(path.into());
+        }
+        if let Some(path) = env("BENTENG_TLOG_SIGNING_KEY") {
+            self.transparency.signing_key_file = Some(path.into());
+        }
+        if let Some(kid) = env("BENTENG_TLOG_KEY_ID") {
+            self.transparency.key_id = Some(kid);
+        }
+        parse_env("BENTENG_TLOG_CHECKPOINT_INTERVAL_MS", &mut self.transparency.checkpoint_interval_ms)?;
+        parse_env("BENTENG_TLOG_CHECKPOINT_BATCH", &mut self.transparency.checkpoint_batch)?;
+
+        parse_env("BENTENG_METRICS_MAX_TENANTS", &mut self.metrics.max_tenant_labels)?;
+        if let Some(endpoint) = env("BENTENG_OTLP_ENDPOINT") {
+            self.telemetry.otlp_endpoint = Some(endpoint);
+        }
+        if let Some(name) = env("BENTENG_OTLP_SERVICE_NAME") {
+            self.telemetry.service_name = name;
+        }
+        if let Some(path) = env("BENTENG_CLIENT_KEY_STORE") {
+            self.client_keys.store_path = Some(path.into());
+        }
+        for entry in list_env("BENTENG_CLIENT_KEYS").unwrap_or_default() {
+            let parsed = entry.split_once('=')
+                .and_then(|(name, path)| name.split_once('/').map(|(tenant, kid)| (tenant, kid, path)));
+            let Some((tenant_id, kid, path)) = parsed else {
+                return Err(format!("BENTENG_CLIENT_KEYS entries must be tenant_hex/kid=public_key_file: {}", entry).into());
+            };
+            self.client_keys.keys.push(ClientKeySeed {
+                tenant_id: tenant_id.to_string(),
+                kid: kid.to_string(),
+                public_key_file: path.into(),
+            });
+        }
+
+        match (env("BENTENG_TLS_CERT"), env("BENTENG_TLS_KEY"), &mut self.tls) {
+            (Some(cert_path), Some(key_path), Some(tls)) => {
+                tls.cert_path = cert_path.into();
+                tls.key_path = key_path.into();
+            }
+            (Some(cert_path), Some(key_path), tls @ None) => {
+                *tls = Some(TlsSettings {
+                    cert_path: cert_path.into(),
+                    key_path: key_path.into(),
+                    hybrid_only: false,
+                    client_ca_path: None,
+                    require_client_cert: false,
+                    client_certs: Vec::new(),
+                });
+            }
+            (None, None, _) => {}
+            _ => return Err("BENTENG_TLS_CERT and BENTENG_TLS_KEY must be set together".into()),
+        }
+        if let Some(tls) = &mut self.tls {
+            flag_env("BENTENG_TLS_HYBRID_ONLY", &mut tls.hybrid_only);
+            if let Some(path) = env("BENTENG_TLS_CLIENT_CA") {
+                tls.client_ca_path = Some(path.into());
+            }
+            flag_env("BENTENG_TLS_REQUIRE_CLIENT_CERT", &mut tls.require_client_cert);
+        }
+        Ok(())
+    }
+}
+
+impl KmsSettings {
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(url) = env("BENTENG_KMS_URL") {
+            self.url = Some(url);
+        }
+        if let Some(path) = env("BENTENG_KMS_CLIENT_KEY") {
+            self.client_key_file = Some(path.into());
+        }
+        if let Some(path) = env("BENTENG_KMS_SERVER_PUBLIC_KEY") {
+            self.server_public_key_file = Some(path.into());
+        }
+        if let Some(id) = env("BENTENG_KMS_CLIENT_ID") {
+            self.client_id = id;
+        }
+        if let Some(id) = env("BENTENG_KMS_SERVER_ID") {
+            self.server_id = id;
+        }
+        parse_env("BENTENG_KMS_TIMEOUT_MS", &mut self.timeout_ms)?;
+        flag_env("BENTENG_KMS_REQUIRE_QUORUM", &mut self.require_quorum);
+        if let Some(path) = env("BENTENG_KMS_APPROVAL_STORE") {
+            self.approval_store_path = Some(path.into());
+        }
+        if let Some(path) = env("BENTENG_KMS_KEY_CATALOG") {
+            self.key_catalog_path = Some(path.into());
+        }
+        if let Some(url) = env("BENTENG_APPROVAL_WEBHOOK_URL") {
+            self.approval_webhook_url = Some(url);
+        }
+        if let Some(path) = env("BENTENG_APPROVAL_EVENTS_FILE") {
+            self.approval_events_file = Some(path.into());
+        }
+        if let Some(approvers) = list_env("BENTENG_KMS_APPROVERS") {
+            self.approvers = approvers;
+        }
+        Ok(())
+    }
+}
+
+impl PolicySettings {
+    /// Settings from the environment alone
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let mut settings = Self::default();
+        settings.apply_env()?;
+        Ok(settings)
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(dir) = env("BENTENG_POLICY_DIR") {
+            self.dir = Some(dir.into());
+        }
+        if let Some(url) = env("BENTENG_POLICY_URL") {
+            self.url = Some(url);
+        }
+        if let Some(signers) = list_env("BENTENG_POLICY_SIGNERS") {
+            self.signers = signers;
+        } else if let Some(kid) = env("BENTENG_POLICY_TRUST_KID") {
+            let key_file = env("BENTENG_POLICY_TRUST_KEY_FILE")
+                .ok_or("BENTENG_POLICY_TRUST_KEY_FILE is required with BENTENG_POLICY_TRUST_KID")?;
+            self.signers = vec![format!("{}={}", kid, key_file)];
+        }
+        if let Some(threshold) = env("BENTENG_POLICY_THRESHOLD") {
+            self.threshold = Some(threshold.parse()?);
+        }
+        if let Some(secs) = env("BENTENG_POLICY_REFRESH_SECS") {
+            self.refresh_secs = Some(secs.parse()?);
+        }
+        flag_env("BENTENG_POLICY_SHADOW", &mut self.shadow);
+        if let Some(dir) = env("BENTENG_POLICY_STATE_DIR") {
+            self.state_dir = Some(dir.into());
+        }
+        if let Some(signers) = list_env("BENTENG_POLICY_TIMESTAMP_SIGNERS") {
+            self.timestamp_signers = signers;
+        }
+        if let Some(threshold) = env("BENTENG_POLICY_TIMESTAMP_THRESHOLD") {
+            self.timestamp_threshold = Some(threshold.parse()?);
+        }
+        if let Some(url) = env("BENTENG_POLICY_TIMESTAMP_URL") {
+            self.timestamp_url = Some(url);
+        }
+        flag_env("BENTENG_POLICY_FAIL_OPEN", &mut self.fail_open);
+        if let Some(tenants) = list_env("BENTENG_POLICY_TENANTS") {
+            self.tenants = Some(tenants);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_config() {
+        let config = ServerConfig::from_toml(r#"
+            bind = "127.0.0.1:8443"
+
+            [kms]
+            require_quorum = true
+            bootstrap_keys = [{ tenant_id = "abababab", policy_id = "12121212" }]
+
+            [policy]
+            dir = "/etc/benteng/policies"
+            signers = ["root=/etc/benteng/root.pub"]
+
+            [rate_limit]
+            burst = 5.0
+            ip = { burst = 20.0, per_sec = 2.0 }
+            overrides = [{ tenant_id = "abababab", route = "decrypt", burst = 1.0, per_sec = 0.5 }]
+
+            [tls]
+            cert_path = "/etc/benteng/tls/cert.pem"
+            key_path = "/etc/benteng/tls/key.pem"
+            client_ca_path = "/etc/benteng/tls/clients.pem"
+            client_certs = [{ fingerprint = "00ff", tenant_id = "abababab" }]
+        "#).unwrap();
+
+        assert_eq!(config.bind, "127.0.0.1:8443");
+        assert!(config.kms.require_quorum);
+        assert_eq!(config.kms.client_id, "edge-api");
+        assert_eq!(config.kms.bootstrap_keys[0].policy_id, "12121212");
+        assert_eq!(config.policy.signers.len(), 1);
+        assert_eq!(config.rate_limit.burst, 5.0);
+        assert_eq!(config.rate_limit.per_sec, 10.0);
+        assert_eq!(config.rate_limit.ip, Some(RateLimit { burst: 20.0, per_sec: 2.0 }));
+        assert_eq!(config.rate_limit.overrides[0].route.as_deref(), Some("decrypt"));
+        assert!(config.rate_limit.client_key.is_none());
+        assert_eq!("50/5".parse::<RateLimit>().unwrap(), RateLimit { burst: 50.0, per_sec: 5.0 });
+        assert!("50".parse::<RateLimit>().is_err());
+        assert_eq!(config.replay.ttl_secs, 300);
+        let tls = config.tls.unwrap();
+        assert!(!tls.require_client_cert);
+        assert_eq!(tls.client_certs[0].tenant_id, "abababab");
+
+        assert!(ServerConfig::from_toml("bnid = \"typo\"").is_err());
+        assert_eq!(ServerConfig::from_toml("").unwrap().bind, "0.0.0.0:3000");
+        ServerConfig::from_toml(include_str!("../edge-api.example.toml")).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use benteng_kms_daemon::{Identity, NotifierSettings, PeerKey, RemoteKms};
+use benteng_sdk_core::crypto::approval::RequestSubmission;
+use benteng_sdk_core::crypto::kms::{
+    DualControlConfig, DualControlKms, KmsGate, RewrapRequest, RewrappedDek, WrappedDek,
+};
+use benteng_sdk_core::crypto::dek_cache::DekCacheStats;
+use benteng_sdk_core::crypto::key_catalog::KeyOptions;
+use benteng_sdk_core::crypto::quorum::Approver;
+use benteng_sdk_core::BentengError;
+use crate::config::KmsSettings;
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+type BackendError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The KMS the edge decrypts through: in-process for development, or a
+/// separate daemon so key material stays out of the edge
+pub enum KmsBackend {
+    Local(Box<DualControlKms>),
+    Remote(RemoteKms),
+}
+
+impl KmsBackend {
+    /// KMS for `settings`: the daemon at `settings.url`, or an in-process KMS
+    /// with the bootstrap scopes given a key when they have none
+    pub async fn open(settings: &KmsSettings) -> Result<Self, BackendError> {
+        let config = DualControlConfig {
+            require_quorum: settings.require_quorum,
+            approval_store_path: settings.approval_store_path.clone(),
+            key_catalog_path: settings.key_catalog_path.clone(),
+            timeout_ms: settings.timeout_ms,
+            ..Default::default()
+        };
+        if let Some(url) = &settings.url {
+            return Self::remote(url, &config, settings);
+        }
+
+        let approvers = settings.approvers.iter()
+            .map(|entry| {
+                let (id, path) = entry.split_once('=')
+                    .ok_or_else(|| format!("kms.approvers entries must be id=public_key_file: {}", entry))?;
+                let public_key = std::fs::read(path).map_err(|e| format!("reading {}: {}", path, e))?;
+                Ok(Approver { id: id.to_string(), public_key })
+            })
+            .collect::<Result<Vec<_>, BackendError>>()?;
+        let notifiers = NotifierSettings {
+            webhook_url: settings.approval_webhook_url.clone(),
+            events_file: settings.approval_events_file.clone(),
+        };
+        let kms = notifiers.apply(DualControlKms::connect(config)?.with_approvers(approvers)?)?;
+
+        for scope in &settings.bootstrap_keys {
+            let tenant_id = hex::decode(&scope.tenant_id)?;
+            let policy_id = hex::decode(&scope.policy_id)?;
+            if kms.key_catalog().active_key(&tenant_id, &policy_id)?.is_none() {
+                kms.generate_key(&tenant_id, &policy_id, KeyOptions::default()).await?;
+            }
+        }
+        Ok(Self::Local(Box::new(kms)))
+    }
+
+    /// Remote KMS authenticated with the configured key files
+    fn remote(endpoint: &str, config: &DualControlConfig, settings: &KmsSettings) -> Result<Self, BackendError> {
+        let read = |path: &Option<PathBuf>, what: &str| -> Result<Vec<u8>, BackendError> {
+            let path = path.as_ref().ok_or_else(|| format!("{} must be set for a remote KMS", what))?;
+            Ok(std::fs::read(path).map_err(|e| format!("reading {}: {}", path.display(), e))?)
+        };
+
+        let identity = Identity {
+            id: settings.client_id.clone(),
+            signing_key: Zeroizing::new(read(&settings.client_key_file, "kms.client_key_file")?),
+        };
+        let server = PeerKey {
+            id: settings.server_id.clone(),
+            public_key: read(&settings.server_public_key_file, "kms.server_public_key_file")?,
+        };
+        Ok(Self::Remote(RemoteKms::new(endpoint, config, identity, server)?))
+    }
+
+    /// DEK cache counters; only known for an in-process KMS
+    pub fn dek_cache_stats(&self) -> Option<DekCacheStats> {
+        match self {
+            Self::Local(kms) => Some(kms.dek_cache_stats()),
+            Self::Remote(_) => None,
+        }
+    }
+}
+
+impl KmsGate for KmsBackend {
+    async fn dual_decrypt(
+        &self,
+        kem_ciphertext: &[u8],
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+    ) -> Result<[u8; 32], BentengError> {
+        match self {
+            Self::Local(kms) => kms.dual_decrypt(kem_ciphertext, kid, policy_id, tenant_id, path).await,
+            Self::Remote(kms) => kms.dual_decrypt(kem_ciphertext, kid, policy_id, tenant_id, path).await,
+        }
+    }
+
+    async fn dual_decrypt_with_caching(
+        &self,
+        kem_ciphertext: &[u8],
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+        cache_dek: bool,
+    ) -> Result<[u8; 32], BentengError> {
+        match self {
+            Self::Local(kms) => {
+                kms.dual_decrypt_with_caching(kem_ciphertext, kid, policy_id, tenant_id, path, cache_dek).await
+            }
+            Self::Remote(kms) => {
+                kms.dual_decrypt_with_caching(kem_ciphertext, kid, policy_id, tenant_id, path, cache_dek).await
+            }
+        }
+    }
+
+    async fn generate_dek(
+        &self,
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+    ) -> Result<WrappedDek, BentengError> {
+        match self {
+            Self::Local(kms) => kms.generate_dek(kid, policy_id, tenant_id, path).await,
+            Self::Remote(kms) => kms.generate_dek(kid, policy_id, tenant_id, path).await,
+        }
+    }
+
+    async fn rewrap(&self, request: RewrapRequest) -> Result<RewrappedDek, BentengError> {
+        match self {
+            Self::Local(kms) => kms.rewrap(request).await,
+            Self::Remote(kms) => kms.rewrap(request).await,
+        }
+    }
+
+    async fn check_quorum(&self, request_id: &[u8]) -> Result<bool, BentengError> {
+        match self {
+            Self::Local(kms) => kms.check_quorum(request_id).await,
+            Self::Remote(kms) => kms.check_quorum(request_id).await,
+        }
+    }
+
+    async fn submit_request(&self, submission: RequestSubmission) -> Result<(), BentengError> {
+        match self {
+            Self::Local(kms) => kms.submit_request(submission).await,
+            Self::Remote(kms) => kms.submit_request(submission).await,
+        }
+    }
+}
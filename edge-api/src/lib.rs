@@ -1,14 +1,1092 @@
+pub mod config;
 pub mod salt_rotation;
 pub mod audit_export;
+pub mod policy_loader;
+pub mod kms_backend;
+pub mod client_keys;
+pub mod rewrap;
+pub mod tls;
+pub mod tlog;
+pub mod metrics;
+pub mod telemetry;
+pub mod replay;
+pub mod rate_limit;
+pub mod problem;
 
-use axum::{Router, routing::{get, post}};
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json},
+    routing::{get, post},
+    Extension, Router,
+};
+use benteng_sdk_core::{
+    envelope::{Envelope, kms_decrypt::decrypt_with_kms_caching, operations::EnvelopeOps},
+    policy::{Policy, PolicyRequest, PolicyRule},
+    policy_bundle::{PolicyDistributor, ShadowReport},
+    policy_freshness::Freshness,
+    ErrorCode,
+};
+use config::{RateLimitSettings, ServerConfig};
+use kms_backend::KmsBackend;
+use client_keys::{ClientKeyError, ClientKeyRecord, ClientKeyRegistry, ClientKeyState, Enrollment};
+use policy_loader::{PolicyLoader, PolicyLoaderConfig};
+use tls::{TlsConnection, TlsTerminator};
+use metrics::{Metrics, TenantVerified};
+use problem::Problem;
+use replay::{MemoryReplayStore, ReplayKey, ReplayStore};
+use rate_limit::{RateLimitDecision, RateLimitRequest, RateLimiter};
+use benteng_transparency::{TransparencyLog, LogEntry};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use benteng_sdk_core::crypto::kms::{DualControlKms, DualControlConfig};
-use benteng_transparency::TransparencyLog;
-use std::collections::HashMap;
+use tokio::task::JoinHandle;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tower_http::trace::TraceLayer;
+use tracing::Instrument;
+use sha2::{Sha256, Digest};
 
-pub async fn run_server() -> Result<(), Box<dyn std::error::Error>> {
-    // Server implementation from main.rs
-    Ok(())
+/// Requester recorded with quorum requests from this edge
+const DECRYPT_REQUESTER: &str = "edge-api";
+
+/// Envelope age limit when no policy is deployed
+const DEFAULT_MAX_AGE_MS: u64 = 30000;
+
+#[derive(Clone)]
+pub struct AppState {
+    kms: Arc<KmsBackend>,
+    client_keys: Option<Arc<ClientKeyRegistry>>,
+    /// SHA-256 of the bearer token for the admin endpoints
+    admin_token_hash: Option<[u8; 32]>,
+    checkpointer: Arc<tlog::Checkpointer>,
+    policy_distributor: Arc<RwLock<PolicyDistributor>>,
+    shadow_report: Arc<RwLock<ShadowReport>>,
+    replay: Arc<ReplayStore>,
+    /// Replay window of the default policy
+    replay_ttl: Duration,
+    rate_limiter: Arc<RateLimiter>,
+    metrics: Arc<Metrics>,
+}
+
+impl AppState {
+    pub fn new(kms: Arc<KmsBackend>) -> Self {
+        Self {
+            kms,
+            client_keys: None,
+            admin_token_hash: None,
+            checkpointer: Arc::new(tlog::Checkpointer::new(
+                Arc::new(RwLock::new(TransparencyLog::new())),
+                Duration::from_secs(1),
+                256,
+            )),
+            policy_distributor: Arc::new(RwLock::new(PolicyDistributor::new())),
+            shadow_report: Arc::new(RwLock::new(ShadowReport::new())),
+            replay: Arc::new(ReplayStore::Memory(MemoryReplayStore::new(16))),
+            replay_ttl: Duration::from_secs(300),
+            rate_limiter: Arc::new(RateLimiter::new(RateLimitSettings::default())),
+            metrics: Arc::new(Metrics::new(100)),
+        }
+    }
+    
+    /// Replace the metrics, e.g. with a different tenant label budget
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+    
+    /// Replace the in-memory transparency log, e.g. with a persistent one
+    pub fn with_transparency_log(mut self, log: TransparencyLog) -> Self {
+        self.checkpointer = Arc::new(self.checkpointer.with_log(Arc::new(RwLock::new(log))));
+        self
+    }
+    
+    /// Sign checkpoints every `interval`, or once `batch` entries wait
+    pub fn with_checkpoint_schedule(mut self, interval: Duration, batch: usize) -> Self {
+        self.checkpointer = Arc::new(tlog::Checkpointer::new(self.checkpointer.log(), interval, batch));
+        self
+    }
+    
+    /// Token buckets applied per tenant, route, client key and client network
+    pub fn with_rate_limit(mut self, rate_limit: RateLimitSettings) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(rate_limit));
+        self
+    }
+    
+    /// How long an envelope without a configured policy is refused as a
+    /// replay; configured policies set their own `replay_ttl_ms`
+    pub fn with_replay_ttl(mut self, ttl: Duration) -> Self {
+        self.replay_ttl = ttl;
+        self
+    }
+    
+    /// Replace the replay store, e.g. with one shared by every replica
+    pub fn with_replay_store(mut self, store: ReplayStore) -> Self {
+        self.replay = Arc::new(store);
+        self
+    }
+    
+    /// Replace the policy distributor, e.g. with one holding a trust anchor
+    pub fn with_policy_distributor(mut self, distributor: PolicyDistributor) -> Self {
+        self.policy_distributor = Arc::new(RwLock::new(distributor));
+        self
+    }
+    
+    /// Registry of client keys envelopes are verified against. Without
+    /// one, no envelope verifies.
+    pub fn with_client_keys(mut self, client_keys: Arc<ClientKeyRegistry>) -> Self {
+        self.client_keys = Some(client_keys);
+        self
+    }
+    
+    /// Bearer token for the admin endpoints; they refuse every request without one
+    pub fn with_admin_token(mut self, token: &str) -> Self {
+        self.admin_token_hash = Some(Sha256::digest(token.as_bytes()).into());
+        self
+    }
+    
+    /// Transparency log shared by the handlers, e.g. for a `RewrapService`
+    pub fn transparency_log(&self) -> Arc<RwLock<TransparencyLog>> {
+        self.checkpointer.log()
+    }
+    
+    /// Appends to the transparency log and signs its checkpoints
+    pub fn checkpointer(&self) -> Arc<tlog::Checkpointer> {
+        self.checkpointer.clone()
+    }
+    
+    /// KMS the handlers decrypt through
+    pub fn kms(&self) -> Arc<KmsBackend> {
+        self.kms.clone()
+    }
+    
+    /// Metrics served at `/metrics`
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+    
+    /// Envelopes currently held for replay detection, when the store can tell
+    pub fn replay_cache_len(&self) -> Option<usize> {
+        self.replay.entries()
+    }
+    
+    /// Rate limiter for the verify and decrypt routes
+    pub fn rate_limiter(&self) -> Arc<RateLimiter> {
+        self.rate_limiter.clone()
+    }
+    
+    /// Policy distributor holding the active and candidate bundles
+    pub fn policy_distributor(&self) -> Arc<RwLock<PolicyDistributor>> {
+        self.policy_distributor.clone()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: String,
+    version: String,
+    timestamp: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct VerifyResponse {
+    decision: String,
+    claims: HashMap<String, String>,
+    kid: String,
+    receipt: ReceiptInfo,
+}
+
+#[derive(Debug, Serialize)]
+struct DecryptResponse {
+    decision: String,
+    kid: String,
+    receipt: ReceiptInfo,
+}
+
+#[derive(Debug, Serialize)]
+struct ReceiptInfo {
+    tlog_hash: String,
+    #[serde(flatten)]
+    log: tlog::LogReceipt,
+}
+
+async fn health() -> impl IntoResponse {
+    let response = HealthResponse {
+        status: "healthy".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+    Json(response)
+}
+
+#[derive(Debug, Serialize)]
+struct PolicyStatusResponse {
+    active_version: u64,
+    candidate_version: Option<u64>,
+    high_water_mark: Option<u64>,
+    /// Active version of each tenant shard
+    shards: BTreeMap<String, u64>,
+    freshness: Freshness,
+}
+
+async fn policy_status(State(state): State<AppState>) -> impl IntoResponse {
+    let distributor = state.policy_distributor.read().await;
+    Json(PolicyStatusResponse {
+        active_version: distributor.current_version(),
+        candidate_version: distributor.candidate_version(),
+        high_water_mark: distributor.high_water_mark().map(|m| m.version),
+        shards: distributor.shard_versions(),
+        freshness: distributor.freshness(),
+    })
+}
+
+async fn shadow_report(State(state): State<AppState>) -> impl IntoResponse {
+    let report = state.shadow_report.read().await.clone();
+    Json(report)
+}
+
+/// Activate the staged bundles, e.g. once their shadow report looks right.
+/// The shadow report starts over for the next candidate.
+async fn promote_policy(State(state): State<AppState>, headers: HeaderMap) -> axum::response::Response {
+    if !admin_authorized(&state, &headers) {
+        return rejection(ErrorCode::AdminRequired);
+    }
+    
+    {
+        let mut distributor = state.policy_distributor.write().await;
+        if !distributor.has_staged() {
+            return Problem::new(ErrorCode::NotFound).with_detail("No staged policy bundle").into_response();
+        }
+        if let Err(e) = distributor.activate_next() {
+            tracing::error!("Policy promotion failed: {}", e);
+            return rejection(ErrorCode::InternalError);
+        }
+        tracing::info!(version = distributor.current_version(), "Promoted staged policy");
+    }
+    *state.shadow_report.write().await = ShadowReport::new();
+    
+    policy_status(State(state)).await.into_response()
+}
+
+/// Evaluate the staged candidate policy alongside the live one and record any
+/// divergence. Never influences the live decision.
+async fn shadow_evaluate(state: &AppState, live: &Policy, request: &PolicyRequest<'_>) {
+    let evaluation = {
+        let distributor = state.policy_distributor.read().await;
+        distributor.shadow_evaluate(live, request)
+    };
+    
+    if let Some(evaluation) = evaluation {
+        if evaluation.is_divergent() {
+            tracing::warn!(
+                candidate_version = evaluation.candidate_version,
+                path = request.path,
+                live = ?evaluation.live,
+                candidate = ?evaluation.candidate,
+                "Shadow policy divergence"
+            );
+        }
+        state.shadow_report.write().await.record(request, &evaluation);
+    }
+}
+
+/// Policy for an envelope, and whether it was deployed rather than
+/// synthesized. The permissive default built from the envelope is only used
+/// when no policy source is configured at all.
+fn envelope_policy(state: &AppState, distributor: &PolicyDistributor, envelope: &Envelope) -> Result<(Policy, bool), ErrorCode> {
+    let tenant_id = String::from_utf8_lossy(&envelope.tenant_id);
+    let policy_id = String::from_utf8_lossy(&envelope.policy_id);
+    match distributor.get_policy(&tenant_id, &policy_id) {
+        Some(policy) => Ok((policy.clone(), true)),
+        // Once a bundle is deployed, only its policies are honoured
+        None if distributor.has_active_bundle() => Err(ErrorCode::UnknownPolicy),
+        // A configured source that has nothing active, e.g. after a refused rollback
+        None if distributor.expects_bundles() => Err(ErrorCode::PolicyUnavailable),
+        None => Ok((Policy {
+            tenant_id: tenant_id.into_owned(),
+            policy_id: policy_id.into_owned(),
+            path: envelope.path.clone(),
+            required_algs: envelope.aad_ext.required_algs.clone(),
+            max_age_ms: DEFAULT_MAX_AGE_MS,
+            max_body_bytes: 65536,
+            require_device_attest: false,
+            hybrid_allowed: true,
+            replay_ttl_ms: state.replay_ttl.as_millis() as u64,
+            cache_dek: true,
+            version: 1,
+        }, false)),
+    }
+}
+
+/// Result codes recorded in the transparency log
+mod rc {
+    pub const OK: u16 = 0;
+    pub const UNKNOWN_CLIENT_KEY: u16 = 1;
+    pub const INVALID_SIGNATURE: u16 = 2;
+    pub const POLICY_VIOLATION: u16 = 3;
+}
+
+fn rejection(code: ErrorCode) -> axum::response::Response {
+    Problem::new(code).into_response()
+}
+
+/// Record a verification outcome and return the receipt
+#[tracing::instrument(name = "log_append", skip_all, fields(rc = result))]
+async fn log_verify(state: &AppState, envelope: &Envelope, sig_hash: [u8; 32], now_ms: u64, result: u16) -> Option<ReceiptInfo> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"verify");
+    hasher.update(&envelope.tenant_id);
+    hasher.update(&envelope.policy_id);
+    hasher.update(sig_hash);
+    let hash = hasher.finalize();
+    let mut hdr_h = [0u8; 32];
+    hdr_h.copy_from_slice(&hash);
+    
+    let entry = LogEntry {
+        v: 1,
+        ten: envelope.tenant_id.clone(),
+        typ: "verify".to_string(),
+        ts: now_ms,
+        hdr_h,
+        sig_h: sig_hash,
+        kid: format!("btk/ten-{}/server-sig/ML-DSA-65/v1", 
+            hex::encode(&envelope.tenant_id[..4.min(envelope.tenant_id.len())])),
+        pol: envelope.policy_id.clone(),
+        rc: result,
+    };
+    match state.checkpointer.append(entry).await {
+        Ok(log) => Some(ReceiptInfo { tlog_hash: hex::encode(hash), log }),
+        Err(e) => {
+            tracing::error!("Transparency log append failed: {}", e);
+            None
+        }
+    }
+}
+
+fn decode_envelope(body: &[u8]) -> Result<Envelope, ciborium::de::Error<std::io::Error>> {
+    tracing::info_span!("cbor_decode", body.size = body.len()).in_scope(|| ciborium::from_reader(body))
+}
+
+/// Span for deciding on `envelope`; IDs are recorded as hashes
+fn decision_span(endpoint: &'static str, envelope: &Envelope) -> tracing::Span {
+    tracing::info_span!(
+        "decision",
+        endpoint,
+        tenant.hash = %telemetry::id_hash(&envelope.tenant_id),
+        policy.hash = %telemetry::id_hash(&envelope.policy_id),
+        decision = tracing::field::Empty,
+    )
+}
+
+/// Take a token from the client network's bucket, or refuse the request
+/// with 429. Runs before the request authenticates, so nothing it claims is
+/// trusted yet.
+async fn rate_limit_network(
+    state: &AppState,
+    route: &'static str,
+    envelope: &Envelope,
+    client_ip: Option<IpAddr>,
+) -> Result<Option<RateLimitDecision>, axum::response::Response> {
+    let limit = state.rate_limiter.check_network(route, client_ip)
+        .instrument(tracing::info_span!("rate_limit", scope = "network"))
+        .await;
+    match limit {
+        Some(limit) if !limit.allowed => Err(rate_limited(state, route, envelope, limit, false)),
+        limit => Ok(limit),
+    }
+}
+
+/// Take a token from the tenant's and client key's buckets. Reports
+/// `network` instead when it is the tighter one.
+fn rate_limit(
+    state: &AppState,
+    route: &'static str,
+    envelope: &Envelope,
+    network: Option<RateLimitDecision>,
+) -> RateLimitDecision {
+    let request = RateLimitRequest {
+        route,
+        tenant_id: &envelope.tenant_id,
+        client_kid: envelope.signer.as_deref(),
+    };
+    tracing::info_span!("rate_limit", scope = "tenant")
+        .in_scope(|| state.rate_limiter.check(&request))
+        .tightest(network)
+}
+
+/// 429 for a request `limit` refused
+fn rate_limited(
+    state: &AppState,
+    route: &'static str,
+    envelope: &Envelope,
+    limit: RateLimitDecision,
+    verified: bool,
+) -> axum::response::Response {
+    state.metrics.record_rate_limited(route, &envelope.tenant_id, verified);
+    let mut response = rejection(ErrorCode::RateLimited);
+    limit.apply(&mut response);
+    if verified {
+        response.extensions_mut().insert(TenantVerified);
+    }
+    response
+}
+
+/// Rate limit headers for a response that did not get past the network
+/// bucket's check to the tenant's
+fn apply_network_limit(network: Option<RateLimitDecision>, response: &mut axum::response::Response) {
+    if let Some(network) = network {
+        if !response.headers().contains_key("ratelimit-limit") {
+            network.apply(response);
+        }
+    }
+}
+
+async fn verify(
+    State(state): State<AppState>,
+    tls: Option<Extension<TlsConnection>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> axum::response::Response {
+    let envelope = match decode_envelope(&body) {
+        Ok(env) => env,
+        Err(_) => {
+            let response = rejection(ErrorCode::InvalidEnvelope);
+            state.metrics.record_decision("verify", None, &response);
+            return response;
+        }
+    };
+    
+    let tenant_id = envelope.tenant_id.clone();
+    let client_ip = state.rate_limiter.client_ip(&headers, peer.map(|ConnectInfo(addr)| addr));
+    let span = decision_span("verify", &envelope);
+    let response = async {
+        let network = match rate_limit_network(&state, "verify", &envelope, client_ip).await {
+            Ok(network) => network,
+            Err(response) => return response,
+        };
+        let mut response = verify_envelope(&state, tls, envelope, body.len(), network).await;
+        apply_network_limit(network, &mut response);
+        response
+    }.instrument(span.clone()).await;
+    span.record("decision", metrics::decision_reason(&response));
+    state.metrics.record_decision("verify", Some(&tenant_id), &response);
+    response
+}
+
+async fn verify_envelope(
+    state: &AppState,
+    tls: Option<Extension<TlsConnection>>,
+    envelope: Envelope,
+    body_len: usize,
+    network: Option<RateLimitDecision>,
+) -> axum::response::Response {
+    if let Some(Extension(connection)) = &tls {
+        if let Err(reason) = connection.authorize(&envelope.tenant_id) {
+            return Problem::new(ErrorCode::TenantForbidden).with_detail(reason).into_response();
+        }
+    }
+    
+    let sig_hash = {
+        let mut hasher = Sha256::new();
+        hasher.update(&envelope.sig);
+        let hash = hasher.finalize();
+        let mut arr = [0u8; 32];
+        arr.copy_from_slice(&hash);
+        arr
+    };
+    
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    
+    let signer = match check_signature(state, &envelope) {
+        Ok(signer) => signer,
+        Err(code) => {
+            let rc = match code {
+                ErrorCode::UnknownClientKey => Some(rc::UNKNOWN_CLIENT_KEY),
+                ErrorCode::InvalidSignature => Some(rc::INVALID_SIGNATURE),
+                _ => None,
+            };
+            if let Some(rc) = rc {
+                log_verify(state, &envelope, sig_hash, now_ms, rc).await;
+            }
+            return rejection(code);
+        }
+    };
+    
+    // Tenant and client key buckets only count requests that authenticated
+    let limit = rate_limit(state, "verify", &envelope, network);
+    if !limit.allowed {
+        return rate_limited(state, "verify", &envelope, limit, true);
+    }
+    let mut response = verified_envelope(state, &envelope, sig_hash, now_ms, body_len, signer.attested).await;
+    limit.apply(&mut response);
+    response.extensions_mut().insert(TenantVerified);
+    response
+}
+
+/// Check the envelope's signature against the signer's key in the client
+/// key registry and return the signer's record. Only signatures from keys
+/// registered for the tenant count.
+fn check_signature(state: &AppState, envelope: &Envelope) -> Result<ClientKeyRecord, ErrorCode> {
+    let signer = match (&state.client_keys, envelope.signer.as_deref()) {
+        (Some(registry), Some(kid)) => match registry.signing_key(&envelope.tenant_id, kid) {
+            Ok(record) => record,
+            Err(e) => {
+                tracing::error!("Client key lookup failed: {}", e);
+                return Err(ErrorCode::ClientKeyRegistryUnavailable);
+            }
+        },
+        _ => None,
+    };
+    let Some(signer) = signer else {
+        return Err(ErrorCode::UnknownClientKey);
+    };
+    let Ok(client_key) = hex::decode(&signer.public_key) else {
+        tracing::error!(kid = %signer.kid, "Client key record holds a malformed public key");
+        return Err(ErrorCode::ClientKeyRegistryUnavailable);
+    };
+    
+    let started = Instant::now();
+    let verified = tracing::info_span!("signature", client.kid = envelope.signer.as_deref())
+        .in_scope(|| EnvelopeOps::verify(envelope, &client_key));
+    state.metrics.observe_sig_verify(started.elapsed());
+    match verified {
+        Ok(()) => Ok(signer),
+        Err(e) => {
+            tracing::warn!(signer = ?envelope.signer, "Signature verification failed: {}", e);
+            Err(ErrorCode::InvalidSignature)
+        }
+    }
+}
+
+/// Policy, replay and logging for an envelope whose signature verified.
+/// `device_attested` is whether the signing key was enrolled with evidence
+/// the registry's attestation verifier accepted.
+async fn verified_envelope(
+    state: &AppState,
+    envelope: &Envelope,
+    sig_hash: [u8; 32],
+    now_ms: u64,
+    body_len: usize,
+    device_attested: bool,
+) -> axum::response::Response {
+    let policy_span = tracing::info_span!("policy", policy.version = tracing::field::Empty);
+    let evaluated = async {
+        let (policy, configured) = {
+            let distributor = state.policy_distributor.read().await;
+            
+            match distributor.check_freshness() {
+                Ok(freshness) if freshness.is_degraded() => {
+                    tracing::warn!(?freshness, "Serving stale policy (fail-open)");
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::error!("Rejecting request: {}", e);
+                    return Err(rejection(ErrorCode::PolicyExpired));
+                }
+            }
+            
+            envelope_policy(state, &distributor, envelope).map_err(rejection)?
+        };
+        
+        tracing::Span::current().record("policy.version", policy.version);
+        
+        let request = PolicyRequest::from_envelope(envelope, body_len, device_attested);
+        shadow_evaluate(state, &policy, &request).await;
+        
+        let mut violations = policy.evaluate(&request);
+        if !configured {
+            // The default policy is built from the envelope, so only its limits apply
+            violations.retain(|rule| !matches!(rule, PolicyRule::Tenant | PolicyRule::PolicyId));
+        }
+        Ok((violations, Duration::from_millis(policy.replay_ttl_ms)))
+    }.instrument(policy_span).await;
+    let (violations, replay_ttl) = match evaluated {
+        Ok(evaluated) => evaluated,
+        Err(response) => return response,
+    };
+    
+    if !violations.is_empty() {
+        log_verify(state, envelope, sig_hash, now_ms, rc::POLICY_VIOLATION).await;
+        return Problem::new(ErrorCode::PolicyViolation)
+            .with_detail(format!("{:?}", violations))
+            .into_response();
+    }
+    
+    // Checked once the policy has set the window
+    let key = ReplayKey { route: "verify", tenant_id: &envelope.tenant_id, nonce: &envelope.nonce };
+    let fresh = state.replay.check_and_insert(&key, replay_ttl)
+        .instrument(tracing::info_span!("replay_check", ttl_ms = replay_ttl.as_millis() as u64))
+        .await;
+    match fresh {
+        Ok(true) => {}
+        Ok(false) => return rejection(ErrorCode::ReplayDetected),
+        Err(e) => {
+            tracing::error!("Replay check failed: {}", e);
+            return rejection(ErrorCode::ReplayStoreUnavailable);
+        }
+    }
+    
+    let Some(receipt) = log_verify(state, envelope, sig_hash, now_ms, rc::OK).await else {
+        return rejection(ErrorCode::TransparencyLogUnavailable);
+    };
+    
+    let mut claims = HashMap::new();
+    claims.insert("alg".to_string(), envelope.aad_ext.required_algs.clone());
+    claims.insert("age_ms".to_string(), now_ms.saturating_sub(envelope.ts_epoch_ms).to_string());
+    claims.insert("path".to_string(), envelope.path.clone());
+    if let Some(signer) = &envelope.signer {
+        claims.insert("client_kid".to_string(), signer.clone());
+    }
+    
+    let response = VerifyResponse {
+        decision: "OK".to_string(),
+        claims,
+        kid: format!("btk/ten-{}/server-sig/ML-DSA-65/v1", 
+            hex::encode(&envelope.tenant_id[..4.min(envelope.tenant_id.len())])),
+        receipt,
+    };
+    
+    (StatusCode::OK, Json(response)).into_response()
+}
+
+async fn decrypt(
+    State(state): State<AppState>,
+    tls: Option<Extension<TlsConnection>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> axum::response::Response {
+    let envelope = match decode_envelope(&body) {
+        Ok(env) => env,
+        Err(_) => {
+            let response = rejection(ErrorCode::InvalidEnvelope);
+            state.metrics.record_decision("decrypt", None, &response);
+            return response;
+        }
+    };
+    
+    let tenant_id = envelope.tenant_id.clone();
+    let client_ip = state.rate_limiter.client_ip(&headers, peer.map(|ConnectInfo(addr)| addr));
+    let span = decision_span("decrypt", &envelope);
+    let response = async {
+        let network = match rate_limit_network(&state, "decrypt", &envelope, client_ip).await {
+            Ok(network) => network,
+            Err(response) => return response,
+        };
+        let mut response = decrypt_envelope(&state, tls, envelope, network).await;
+        apply_network_limit(network, &mut response);
+        response
+    }.instrument(span.clone()).await;
+    span.record("decision", metrics::decision_reason(&response));
+    state.metrics.record_decision("decrypt", Some(&tenant_id), &response);
+    response
+}
+
+async fn decrypt_envelope(
+    state: &AppState,
+    tls: Option<Extension<TlsConnection>>,
+    envelope: Envelope,
+    network: Option<RateLimitDecision>,
+) -> axum::response::Response {
+    if let Some(Extension(connection)) = &tls {
+        if let Err(reason) = connection.authorize(&envelope.tenant_id) {
+            return Problem::new(ErrorCode::TenantForbidden).with_detail(reason).into_response();
+        }
+    }
+    
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    
+    // The policy sets the age limit and replay window, and whether the KMS
+    // may cache the DEK
+    let policy = async {
+        let distributor = state.policy_distributor.read().await;
+        let (policy, configured) = envelope_policy(state, &distributor, &envelope)?;
+        if configured {
+            tracing::Span::current().record("policy.version", policy.version);
+        }
+        Ok(policy)
+    }.instrument(tracing::info_span!("policy", policy.version = tracing::field::Empty)).await;
+    let (max_age_ms, replay_ttl, cache_dek) = match policy {
+        Ok(policy) => (policy.max_age_ms, Duration::from_millis(policy.replay_ttl_ms), policy.cache_dek),
+        Err(code) => return rejection(code),
+    };
+    
+    if now_ms > envelope.ts_epoch_ms.saturating_add(max_age_ms) {
+        return rejection(ErrorCode::EnvelopeExpired);
+    }
+    
+    // The tenant and client key buckets only count envelopes signed by a key
+    // registered for the tenant. Without a registry nothing authenticates
+    // the envelope's tenant before the KMS call, so only the network bucket
+    // applies.
+    let limit = match state.client_keys {
+        Some(_) => {
+            if let Err(code) = check_signature(state, &envelope) {
+                return rejection(code);
+            }
+            let limit = rate_limit(state, "decrypt", &envelope, network);
+            if !limit.allowed {
+                return rate_limited(state, "decrypt", &envelope, limit, true);
+            }
+            Some(limit)
+        }
+        None => None,
+    };
+    let mut response = decrypted_envelope(state, &envelope, now_ms, replay_ttl, cache_dek).await;
+    if let Some(limit) = limit {
+        limit.apply(&mut response);
+        response.extensions_mut().insert(TenantVerified);
+    }
+    response
+}
+
+/// KMS decrypt, replay and logging for an envelope that passed the policy
+async fn decrypted_envelope(
+    state: &AppState,
+    envelope: &Envelope,
+    now_ms: u64,
+    replay_ttl: Duration,
+    cache_dek: bool,
+) -> axum::response::Response {
+    let started = Instant::now();
+    let decrypted = decrypt_with_kms_caching(envelope, state.kms.as_ref(), DECRYPT_REQUESTER, cache_dek)
+        .instrument(tracing::info_span!("kms_decrypt", kem.kid = envelope.kid.as_deref()))
+        .await;
+    state.metrics.observe_kem_decapsulate(started.elapsed());
+    match decrypted {
+        Ok(_plaintext) => {
+            // Spent only once it decrypts, so a request waiting on quorum can be
+            // retried
+            let key = ReplayKey { route: "decrypt", tenant_id: &envelope.tenant_id, nonce: &envelope.nonce };
+            let fresh = state.replay.check_and_insert(&key, replay_ttl)
+                .instrument(tracing::info_span!("replay_check", ttl_ms = replay_ttl.as_millis() as u64))
+                .await;
+            match fresh {
+                Ok(true) => {}
+                Ok(false) => return rejection(ErrorCode::ReplayDetected),
+                Err(e) => {
+                    tracing::error!("Replay check failed: {}", e);
+                    return rejection(ErrorCode::ReplayStoreUnavailable);
+                }
+            }
+            
+            let receipt = {
+                let mut hasher = Sha256::new();
+                hasher.update(b"decrypt");
+                hasher.update(&envelope.tenant_id);
+                hasher.update(&envelope.policy_id);
+                hasher.update(&envelope.nonce);
+                let hash = hasher.finalize();
+                let mut hdr_h = [0u8; 32];
+                hdr_h.copy_from_slice(&hash);
+                
+                let mut sig_hasher = Sha256::new();
+                sig_hasher.update(&envelope.sig);
+                let sig_hash_result = sig_hasher.finalize();
+                let mut sig_h = [0u8; 32];
+                sig_h.copy_from_slice(&sig_hash_result);
+                
+                let entry = LogEntry {
+                    v: 1,
+                    ten: envelope.tenant_id.clone(),
+                    typ: "decrypt".to_string(),
+                    ts: now_ms,
+                    hdr_h,
+                    sig_h,
+                    kid: format!("btk/ten-{}/server-kem/ML-KEM-768/v1",
+                        hex::encode(&envelope.tenant_id[..4.min(envelope.tenant_id.len())])),
+                    pol: envelope.policy_id.clone(),
+                    rc: 0,
+                };
+                match state.checkpointer.append(entry).await {
+                    Ok(log) => ReceiptInfo { tlog_hash: hex::encode(hash), log },
+                    Err(e) => {
+                        tracing::error!("No transparency receipt: {}", e);
+                        return rejection(ErrorCode::TransparencyLogUnavailable);
+                    }
+                }
+            };
+            
+            let response = DecryptResponse {
+                decision: "OK".to_string(),
+                kid: format!("btk/ten-{}/server-kem/ML-KEM-768/v1",
+                    hex::encode(&envelope.tenant_id[..4.min(envelope.tenant_id.len())])),
+                receipt,
+            };
+            
+            // Only the tenant's KMS key yields a DEK that opens the envelope
+            let mut response = (StatusCode::OK, Json(response)).into_response();
+            response.extensions_mut().insert(TenantVerified);
+            response
+        }
+        Err(e) => {
+            tracing::error!("Decrypt failed: {:?}", e);
+            Problem::from(e).into_response()
+        }
+    }
+}
+
+/// Check the admin bearer token
+fn admin_authorized(state: &AppState, headers: &HeaderMap) -> bool {
+    let token = headers.get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match (state.admin_token_hash, token) {
+        (Some(expected), Some(token)) => <[u8; 32]>::from(Sha256::digest(token.as_bytes())) == expected,
+        _ => false,
+    }
+}
+
+fn client_key_error(e: ClientKeyError) -> axum::response::Response {
+    let code = match e {
+        ClientKeyError::NotFound(_) => ErrorCode::ClientKeyNotFound,
+        ClientKeyError::Conflict(_) => ErrorCode::ClientKeyConflict,
+        ClientKeyError::Revoked(_) => ErrorCode::ClientKeyRevoked,
+        ClientKeyError::Invalid(_) => ErrorCode::InvalidClientKey,
+        ClientKeyError::Attestation(_) => ErrorCode::AttestationFailed,
+        ClientKeyError::Storage(_) => {
+            tracing::error!("Client key registry failed: {}", e);
+            return rejection(ErrorCode::InternalError);
+        }
+    };
+    Problem::new(code).with_detail(e.to_string()).into_response()
+}
+
+/// Registry for an admin request, or why it is refused
+fn admin_registry(state: &AppState, headers: &HeaderMap) -> Result<Arc<ClientKeyRegistry>, ErrorCode> {
+    if !admin_authorized(state, headers) {
+        return Err(ErrorCode::AdminRequired);
+    }
+    state.client_keys.clone()
+        .ok_or(ErrorCode::ClientKeyRegistryUnavailable)
+}
+
+#[derive(Debug, Deserialize)]
+struct EnrollRequest {
+    #[serde(default)]
+    kid: Option<String>,
+    /// Hex ML-DSA-65 public key
+    public_key: String,
+    #[serde(default)]
+    expires_at: Option<u64>,
+    /// Hex attestation evidence
+    #[serde(default)]
+    attestation: Option<String>,
+}
+
+async fn enroll_client_key(
+    State(state): State<AppState>,
+    Path(tenant): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<EnrollRequest>,
+) -> impl IntoResponse {
+    let registry = match admin_registry(&state, &headers) {
+        Ok(registry) => registry,
+        Err(code) => return rejection(code),
+    };
+    let decoded = (
+        hex::decode(&tenant),
+        hex::decode(&request.public_key),
+        request.attestation.as_deref().map(hex::decode).transpose(),
+    );
+    let (Ok(tenant_id), Ok(public_key), Ok(attestation)) = decoded else {
+        return Problem::new(ErrorCode::InvalidRequest)
+            .with_detail("Tenant, public key and attestation must be hex")
+            .into_response();
+    };
+    
+    match registry.enroll(&tenant_id, Enrollment {
+        kid: request.kid,
+        public_key,
+        expires_at: request.expires_at,
+        attestation,
+    }) {
+        Ok(record) => {
+            tracing::info!(kid = %record.kid, tenant = %record.tenant_id, "Client key enrolled");
+            (StatusCode::CREATED, Json(record)).into_response()
+        }
+        Err(e) => client_key_error(e),
+    }
+}
+
+async fn list_client_keys(
+    State(state): State<AppState>,
+    Path(tenant): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let registry = match admin_registry(&state, &headers) {
+        Ok(registry) => registry,
+        Err(code) => return rejection(code),
+    };
+    let Ok(tenant_id) = hex::decode(&tenant) else {
+        return Problem::new(ErrorCode::InvalidRequest).with_detail("Tenant must be hex").into_response();
+    };
+    match registry.list(&tenant_id) {
+        Ok(keys) => Json(serde_json::json!({ "keys": keys })).into_response(),
+        Err(e) => client_key_error(e),
+    }
+}
+
+/// `revoke`, `suspend` or `reactivate` a client key
+async fn update_client_key(
+    State(state): State<AppState>,
+    Path((kid, action)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let registry = match admin_registry(&state, &headers) {
+        Ok(registry) => registry,
+        Err(code) => return rejection(code),
+    };
+    let target = match action.as_str() {
+        "revoke" => ClientKeyState::Revoked,
+        "suspend" => ClientKeyState::Suspended,
+        "reactivate" => ClientKeyState::Active,
+        _ => return Problem::new(ErrorCode::NotFound).with_detail("Unknown action").into_response(),
+    };
+    match registry.set_state(&kid, target) {
+        Ok(record) => {
+            tracing::info!(kid = %record.kid, state = ?record.state, "Client key updated");
+            Json(record).into_response()
+        }
+        Err(e) => client_key_error(e),
+    }
+}
+
+pub fn app(state: AppState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/metrics", get(metrics::get_metrics))
+        .route("/pqc/verify", post(verify))
+        .route("/pqc/decrypt", post(decrypt))
+        .route("/policy/status", get(policy_status))
+        .route("/policy/shadow", get(shadow_report))
+        .route("/admin/policy/promote", post(promote_policy))
+        .route("/tlog/checkpoint", get(tlog::get_checkpoint))
+        .route("/tlog/entry/:index", get(tlog::get_entry))
+        .route("/tlog/proof/inclusion", get(tlog::get_inclusion_proof))
+        .route("/tlog/proof/consistency", get(tlog::get_consistency_proof))
+        .route("/admin/tenants/:tenant/client-keys", get(list_client_keys).post(enroll_client_key))
+        .route("/admin/client-keys/:kid/:action", post(update_client_key))
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::request_span))
+        .with_state(state)
+}
+
+/// A running server; dropping it leaves the server running
+pub struct ServerHandle {
+    local_addr: SocketAddr,
+    handle: axum_server::Handle,
+    shutdown_timeout: Duration,
+    tls: Option<Arc<TlsTerminator>>,
+    server: JoinHandle<std::io::Result<()>>,
+    background: Vec<JoinHandle<()>>,
+}
+
+impl ServerHandle {
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+    
+    /// Re-read the TLS certificate, key and client bindings from disk.
+    /// A no-op for plain HTTP.
+    pub fn reload_tls(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match &self.tls {
+            Some(tls) => tls.reload(),
+            None => Ok(()),
+        }
+    }
+    
+    /// Stop accepting connections and wait for in-flight requests, up to
+    /// the configured shutdown timeout
+    pub async fn shutdown(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.handle.graceful_shutdown(Some(self.shutdown_timeout));
+        self.wait().await
+    }
+    
+    /// Wait for the server to stop
+    pub async fn wait(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let result = self.server.await;
+        // Wait for the aborts too, so state such as the policy store's
+        // lock is released on return
+        for task in self.background {
+            task.abort();
+            let _ = task.await;
+        }
+        Ok(result??)
+    }
+}
+
+/// Start the edge API for `config` and return once it is listening
+pub async fn run_server(config: ServerConfig) -> Result<ServerHandle, Box<dyn std::error::Error + Send + Sync>> {
+    let kms = KmsBackend::open(&config.kms).await?;
+    
+    let mut state = AppState::new(Arc::new(kms))
+        .with_client_keys(Arc::new(ClientKeyRegistry::open(&config.client_keys)?))
+        .with_transparency_log(tlog::open_log(&config.transparency)?)
+        .with_checkpoint_schedule(
+            Duration::from_millis(config.transparency.checkpoint_interval_ms),
+            config.transparency.checkpoint_batch,
+        )
+        .with_rate_limit(config.rate_limit.clone())
+        .with_replay_ttl(Duration::from_secs(config.replay.ttl_secs))
+        .with_replay_store(ReplayStore::open(&config.replay).await?)
+        .with_metrics(Metrics::new(config.metrics.max_tenant_labels));
+    if let Some(token) = &config.admin_token {
+        state = state.with_admin_token(token);
+    }
+    
+    let mut background = Vec::new();
+    if let Some(policy_config) = PolicyLoaderConfig::from_settings(&config.policy)? {
+        state = state.with_policy_distributor(policy_config.distributor()?);
+        
+        let loader = PolicyLoader::new(&policy_config, state.policy_distributor());
+        if let Err(e) = loader.refresh().await {
+            tracing::error!("Initial policy load failed: {}", e);
+        }
+        background.push(tokio::spawn(loader.run()));
+    }
+    
+    // Bind up front so address errors surface here rather than in the task
+    let listener = std::net::TcpListener::bind(&config.bind)
+        .map_err(|e| format!("binding {}: {}", config.bind, e))?;
+    listener.set_nonblocking(true)?;
+    let local_addr = listener.local_addr()?;
+    
+    let handle = axum_server::Handle::new();
+    background.push(tokio::spawn(state.rate_limiter().salt_rotator().start_rotation()));
+    background.push(tokio::spawn(state.checkpointer().run()));
+    let service = app(state).into_make_service_with_connect_info::<SocketAddr>();
+    let tls = config.tls.as_ref().map(TlsTerminator::new).transpose()?.map(Arc::new);
+    let server = match &tls {
+        Some(tls) => {
+            let server = axum_server::from_tcp(listener)
+                .acceptor(tls.acceptor())
+                .handle(handle.clone());
+            tokio::spawn(async move { server.serve(service).await })
+        }
+        None => {
+            let server = axum_server::from_tcp(listener).handle(handle.clone());
+            tokio::spawn(async move { server.serve(service).await })
+        }
+    };
+    
+    Ok(ServerHandle {
+        local_addr,
+        handle,
+        shutdown_timeout: Duration::from_secs(config.shutdown_timeout_secs),
+        tls,
+        server,
+        background,
+    })
 }
@@ -0,0 +1,284 @@
+//! Prometheus metrics for the edge API
+//! Decisions are counted per endpoint, reason and tenant. Tenant labels are
+//! bounded: the first `max_tenant_labels` tenants that authenticate get
+//! their own label and the rest share `other`. A request that did not
+//! authenticate always counts as `unverified`, whatever tenant it names.
+//! Labels are hashes of the full tenant ID, as in trace attributes.
+//! Served at `/metrics` to admin token holders.
+
+use crate::problem::Problem;
+use crate::telemetry::id_hash;
+use crate::AppState;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use benteng_sdk_core::ErrorCode;
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Label for tenants beyond the label budget
+const OTHER_TENANT: &str = "other";
+
+/// Tenant label when the request could not be parsed
+const UNKNOWN_TENANT: &str = "unknown";
+
+/// Tenant label when the request did not authenticate as a labelled tenant
+const UNVERIFIED_TENANT: &str = "unverified";
+
+/// Reason label of an accepted request
+const OK_REASON: &str = "ok";
+
+/// Latency buckets from 100µs to 2.5s
+const LATENCY_BUCKETS: &[f64] = &[0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+/// Code a request was rejected with, attached to the response for the
+/// decision counter. Codes come from a fixed catalogue, so labels stay
+/// bounded.
+#[derive(Debug, Clone, Copy)]
+pub struct RejectionReason(pub ErrorCode);
+
+/// Attached to a response once the request authenticated as its tenant:
+/// its signature verified, or its envelope decrypted
+#[derive(Debug, Clone, Copy)]
+pub struct TenantVerified;
+
+/// Hands out tenant labels up to a fixed budget
+struct TenantLabels {
+    max: usize,
+    seen: Mutex<HashSet<String>>,
+}
+
+impl TenantLabels {
+    /// Label for the tenant; only verified requests carry a tenant's label
+    fn label(&self, tenant_id: &[u8], verified: bool) -> String {
+        if !verified {
+            return UNVERIFIED_TENANT.to_string();
+        }
+        let tenant = id_hash(tenant_id);
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        if seen.contains(&tenant) || seen.len() < self.max {
+            seen.insert(tenant.clone());
+            return tenant;
+        }
+        OTHER_TENANT.to_string()
+    }
+}
+
+/// Reason label of the decision carried by `response`
+pub(crate) fn decision_reason(response: &Response) -> &str {
+    match response.extensions().get::<RejectionReason>() {
+        Some(RejectionReason(code)) => code.as_str(),
+        None if response.status().is_success() => OK_REASON,
+        None => response.status().canonical_reason().unwrap_or("error"),
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    tenants: TenantLabels,
+    decisions: IntCounterVec,
+    rate_limited: IntCounterVec,
+    sig_verify_seconds: Histogram,
+    kem_decapsulate_seconds: Histogram,
+    dek_cache_hits: IntCounter,
+    dek_cache_misses: IntCounter,
+    dek_cache_hit_ratio: Gauge,
+    dek_cache_entries: IntGauge,
+    replay_cache_entries: IntGauge,
+    rate_limit_buckets: IntGauge,
+    tlog_entries: IntGauge,
+    tlog_checkpoint_age_seconds: Gauge,
+}
+
+impl Metrics {
+    /// Metrics in their own registry, with at most `max_tenant_labels`
+    /// distinct tenant labels
+    pub fn new(max_tenant_labels: usize) -> Self {
+        let registry = Registry::new_custom(Some("benteng".to_string()), None)
+            .expect("valid registry prefix");
+
+        let decisions = IntCounterVec::new(
+            Opts::new("decisions_total", "Verify and decrypt decisions"),
+            &["endpoint", "reason", "tenant"],
+        ).unwrap();
+        let rate_limited = IntCounterVec::new(
+            Opts::new("rate_limit_rejections_total", "Requests refused by the rate limiter"),
+            &["endpoint", "tenant"],
+        ).unwrap();
+        let sig_verify_seconds = Histogram::with_opts(
+            HistogramOpts::new("signature_verify_seconds", "Envelope signature verification latency")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+        ).unwrap();
+        let kem_decapsulate_seconds = Histogram::with_opts(
+            HistogramOpts::new("kem_decapsulate_seconds", "KEM decapsulation and DEK recovery latency through the KMS")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+        ).unwrap();
+        let dek_cache_hits = IntCounter::new("kms_dek_cache_hits_total", "KMS DEK cache hits").unwrap();
+        let dek_cache_misses = IntCounter::new("kms_dek_cache_misses_total", "KMS DEK cache misses").unwrap();
+        let dek_cache_hit_ratio = Gauge::new("kms_dek_cache_hit_ratio", "KMS DEK cache hits over lookups").unwrap();
+        let dek_cache_entries = IntGauge::new("kms_dek_cache_entries", "DEKs held in the KMS cache").unwrap();
+        let replay_cache_entries = IntGauge::new("replay_cache_entries", "Envelopes held for replay detection").unwrap();
+        let rate_limit_buckets = IntGauge::new("rate_limit_buckets", "Token buckets held by the rate limiter").unwrap();
+        let tlog_entries = IntGauge::new("tlog_entries", "Transparency log size").unwrap();
+        let tlog_checkpoint_age_seconds = Gauge::new(
+            "tlog_checkpoint_age_seconds",
+            "Time since the latest transparency checkpoint",
+        ).unwrap();
+
+        registry.register(Box::new(decisions.clone())).unwrap();
+        registry.register(Box::new(rate_limited.clone())).unwrap();
+        registry.register(Box::new(sig_verify_seconds.clone())).unwrap();
+        registry.register(Box::new(kem_decapsulate_seconds.clone())).unwrap();
+        registry.register(Box::new(dek_cache_hits.clone())).unwrap();
+        registry.register(Box::new(dek_cache_misses.clone())).unwrap();
+        registry.register(Box::new(dek_cache_hit_ratio.clone())).unwrap();
+        registry.register(Box::new(dek_cache_entries.clone())).unwrap();
+        registry.register(Box::new(replay_cache_entries.clone())).unwrap();
+        registry.register(Box::new(rate_limit_buckets.clone())).unwrap();
+        registry.register(Box::new(tlog_entries.clone())).unwrap();
+        registry.register(Box::new(tlog_checkpoint_age_seconds.clone())).unwrap();
+
+        Self {
+            registry,
+            tenants: TenantLabels { max: max_tenant_labels, seen: Mutex::new(HashSet::new()) },
+            decisions,
+            rate_limited,
+            sig_verify_seconds,
+            kem_decapsulate_seconds,
+            dek_cache_hits,
+            dek_cache_misses,
+            dek_cache_hit_ratio,
+            dek_cache_entries,
+            replay_cache_entries,
+            rate_limit_buckets,
+            tlog_entries,
+            tlog_checkpoint_age_seconds,
+        }
+    }
+
+    /// Count the decision carried by `response`
+    pub fn record_decision(&self, endpoint: &str, tenant_id: Option<&[u8]>, response: &Response) {
+        let verified = response.extensions().get::<TenantVerified>().is_some();
+        let tenant = match tenant_id {
+            Some(tenant_id) => self.tenants.label(tenant_id, verified),
+            None => UNKNOWN_TENANT.to_string(),
+        };
+        let reason = decision_reason(response);
+        self.decisions.with_label_values(&[endpoint, reason, &tenant]).inc();
+    }
+
+    /// Count a rate limited request. Its tenant only gets a label of its own
+    /// once the request has authenticated.
+    pub fn record_rate_limited(&self, endpoint: &str, tenant_id: &[u8], verified: bool) {
+        let tenant = self.tenants.label(tenant_id, verified);
+        self.rate_limited.with_label_values(&[endpoint, &tenant]).inc();
+    }
+
+    pub fn observe_sig_verify(&self, elapsed: Duration) {
+        self.sig_verify_seconds.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_kem_decapsulate(&self, elapsed: Duration) {
+        self.kem_decapsulate_seconds.observe(elapsed.as_secs_f64());
+    }
+
+    /// Refresh the gauges sampled from other components
+    async fn sample(&self, state: &AppState) {
+        if let Some(stats) = state.kms().dek_cache_stats() {
+            // The cache keeps its own running totals; catch the counters up
+            self.dek_cache_hits.inc_by(stats.hits.saturating_sub(self.dek_cache_hits.get()));
+            self.dek_cache_misses.inc_by(stats.misses.saturating_sub(self.dek_cache_misses.get()));
+            let lookups = stats.hits + stats.misses;
+            if lookups > 0 {
+                self.dek_cache_hit_ratio.set(stats.hits as f64 / lookups as f64);
+            }
+            self.dek_cache_entries.set(stats.entries as i64);
+        }
+
+        if let Some(entries) = state.replay_cache_len() {
+            self.replay_cache_entries.set(entries as i64);
+        }
+        self.rate_limit_buckets.set(state.rate_limiter().buckets() as i64);
+
+        let log = state.transparency_log();
+        let log = log.read().await;
+        self.tlog_entries.set(log.len() as i64);
+        if let Some(checkpoint) = log.get_latest_checkpoint() {
+            let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+            self.tlog_checkpoint_age_seconds.set(now_ms.saturating_sub(checkpoint.ts) as f64 / 1000.0);
+        }
+    }
+
+    /// Prometheus text exposition of every metric
+    pub async fn render(&self, state: &AppState) -> Result<String, prometheus::Error> {
+        self.sample(state).await;
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+pub(crate) async fn get_metrics(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if !crate::admin_authorized(&state, &headers) {
+        return Problem::new(ErrorCode::AdminRequired).into_response();
+    }
+    match state.metrics().render(&state).await {
+        Ok(text) => ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], text).into_response(),
+        Err(e) => {
+            tracing::error!("Encoding metrics failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_labels_are_bounded() {
+        let metrics = Metrics::new(2);
+        let mut verified = StatusCode::OK.into_response();
+        verified.extensions_mut().insert(TenantVerified);
+        let unverified = StatusCode::UNAUTHORIZED.into_response();
+
+        // Unauthenticated requests don't use up the budget, and never carry
+        // a tenant's label
+        metrics.record_decision("verify", Some(&[9u8; 16]), &unverified);
+        for tenant in [[1u8; 16], [2u8; 16], [3u8; 16]] {
+            metrics.record_decision("verify", Some(&tenant), &verified);
+        }
+        for tenant in [[1u8; 16], [2u8; 16], [3u8; 16], [1u8; 16]] {
+            metrics.record_rate_limited("verify", &tenant, true);
+        }
+        for tenant in [[1u8; 16], [9u8; 16]] {
+            metrics.record_rate_limited("verify", &tenant, false);
+        }
+
+        let families = metrics.registry.gather();
+        let family = families.iter()
+            .find(|family| family.name() == "benteng_rate_limit_rejections_total")
+            .unwrap();
+        let mut counts: Vec<(String, f64)> = family.get_metric().iter()
+            .map(|metric| {
+                let tenant = metric.get_label().iter().find(|l| l.name() == "tenant").unwrap();
+                (tenant.value().to_string(), metric.get_counter().get_value())
+            })
+            .collect();
+        counts.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut expected = vec![
+            (id_hash(&[1u8; 16]), 2.0),
+            (id_hash(&[2u8; 16]), 1.0),
+            ("other".to_string(), 1.0),
+            ("unverified".to_string(), 2.0),
+        ];
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(counts, expected);
+    }
+}
@@ -0,0 +1,566 @@
+use benteng_sdk_core::policy_bundle::{PolicyDistributor, PolicyDocument, SignerSet, TrustAnchor};
+use benteng_sdk_core::policy_freshness::{ExpiryBehaviour, SledHighWaterMarkStore};
+use crate::config::PolicySettings;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::time;
+
+type LoaderError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Placeholder in source URLs replaced by each subscribed tenant
+const TENANT_PLACEHOLDER: &str = "{tenant}";
+
+/// Where signed policy bundles are fetched from
+#[derive(Debug, Clone)]
+pub enum PolicySource {
+    /// Directory of `*.json` files, each holding a bundle, shard, delta or
+    /// timestamp, or an array of them
+    Directory(PathBuf),
+    /// URL serving one document or an array of them. A `{tenant}`
+    /// placeholder is fetched once per subscribed tenant.
+    Url(String),
+}
+
+/// What happens to a bundle once it has been verified
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleActivation {
+    /// Activate newer bundles as soon as they verify
+    Immediate,
+    /// Keep newer bundles staged for shadow evaluation until promoted through
+    /// `POST /admin/policy/promote`. The first bundle is still activated,
+    /// since there is nothing live to compare against.
+    Shadow,
+}
+
+#[derive(Debug, Clone)]
+pub struct PolicyLoaderConfig {
+    pub source: PolicySource,
+    pub signer_set: SignerSet,
+    pub refresh_interval: Duration,
+    pub activation: BundleActivation,
+    /// Directory for the persistent high-water mark
+    pub state_dir: Option<PathBuf>,
+    /// Signers of the freshness timestamp; enables freeze detection
+    pub timestamp_signers: Option<SignerSet>,
+    /// Timestamp location for URL sources, with the same `{tenant}` expansion
+    pub timestamp_url: Option<String>,
+    pub expiry_behaviour: ExpiryBehaviour,
+    /// Tenants this edge serves; `None` serves every tenant
+    pub tenants: Option<Vec<String>>,
+}
+
+fn signer_set(entries: &[String], threshold: Option<usize>) -> Result<SignerSet, LoaderError> {
+    let mut anchors = Vec::new();
+    for entry in entries {
+        let (kid, key_file) = entry.split_once('=')
+            .ok_or("policy signer entries must be kid=public_key_file")?;
+        anchors.push(TrustAnchor {
+            kid: kid.to_string(),
+            public_key: std::fs::read(key_file).map_err(|e| format!("reading {}: {}", key_file, e))?,
+        });
+    }
+
+    let threshold = threshold.unwrap_or(anchors.len());
+    Ok(SignerSet::new(anchors, threshold)?)
+}
+
+impl PolicyLoaderConfig {
+    /// Read loader settings from the environment; see `PolicySettings` for
+    /// the variables. Returns `None` when no policy source is configured.
+    pub fn from_env() -> Result<Option<Self>, LoaderError> {
+        Self::from_settings(&PolicySettings::from_env()?)
+    }
+
+    /// Loader configuration for `settings`. Returns `None` when no policy
+    /// source is configured.
+    pub fn from_settings(settings: &PolicySettings) -> Result<Option<Self>, LoaderError> {
+        let source = if let Some(dir) = &settings.dir {
+            PolicySource::Directory(dir.clone())
+        } else if let Some(url) = &settings.url {
+            PolicySource::Url(url.clone())
+        } else {
+            return Ok(None);
+        };
+
+        if settings.signers.is_empty() {
+            return Err("policy signers are required with a policy source".into());
+        }
+
+        let timestamp_signers = if settings.timestamp_signers.is_empty() {
+            None
+        } else {
+            Some(signer_set(&settings.timestamp_signers, Some(settings.timestamp_threshold.unwrap_or(1)))?)
+        };
+
+        Ok(Some(Self {
+            source,
+            signer_set: signer_set(&settings.signers, settings.threshold)?,
+            refresh_interval: Duration::from_secs(settings.refresh_secs.unwrap_or(60)),
+            activation: if settings.shadow { BundleActivation::Shadow } else { BundleActivation::Immediate },
+            state_dir: settings.state_dir.clone(),
+            timestamp_signers,
+            timestamp_url: settings.timestamp_url.clone(),
+            expiry_behaviour: if settings.fail_open { ExpiryBehaviour::FailOpen } else { ExpiryBehaviour::FailClosed },
+            tenants: settings.tenants.clone(),
+        }))
+    }
+
+    /// Build a distributor enforcing this configuration
+    pub fn distributor(&self) -> Result<PolicyDistributor, LoaderError> {
+        let mut distributor = PolicyDistributor::with_signer_set(self.signer_set.clone())
+            .with_expiry_behaviour(self.expiry_behaviour);
+
+        if let Some(dir) = &self.state_dir {
+            distributor = distributor
+                .with_high_water_mark_store(Box::new(SledHighWaterMarkStore::open(dir)?))?;
+        }
+        if let Some(signers) = &self.timestamp_signers {
+            distributor = distributor.with_timestamp_signers(signers.clone());
+        }
+        if let Some(tenants) = &self.tenants {
+            distributor = distributor.with_subscriptions(tenants.iter().cloned());
+        }
+
+        Ok(distributor)
+    }
+}
+
+/// Fetches signed bundles and hands them to the `PolicyDistributor`
+pub struct PolicyLoader {
+    source: PolicySource,
+    timestamp_url: Option<String>,
+    activation: BundleActivation,
+    refresh_interval: Duration,
+    tenants: Option<Vec<String>>,
+    distributor: Arc<RwLock<PolicyDistributor>>,
+    client: reqwest::Client,
+    fetch_failures: AtomicU64,
+}
+
+impl PolicyLoader {
+    pub fn new(config: &PolicyLoaderConfig, distributor: Arc<RwLock<PolicyDistributor>>) -> Self {
+        Self {
+            source: config.source.clone(),
+            timestamp_url: config.timestamp_url.clone(),
+            activation: config.activation,
+            refresh_interval: config.refresh_interval,
+            tenants: config.tenants.clone(),
+            distributor,
+            client: reqwest::Client::new(),
+            fetch_failures: AtomicU64::new(0),
+        }
+    }
+    
+    /// Files and URLs that could not be fetched, since the loader started
+    pub fn fetch_failures(&self) -> u64 {
+        self.fetch_failures.load(Ordering::Relaxed)
+    }
+    
+    fn fetch_failed(&self, source: &str, error: &dyn std::fmt::Display) {
+        self.fetch_failures.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!(source, "Policy fetch failed: {}", error);
+    }
+
+    /// Fetch documents from the source, record timestamps, and stage every
+    /// newer bundle, shard and delta that verifies. Full bundles are staged
+    /// before deltas so a delta can build on a bundle from the same fetch.
+    /// Sources that cannot be fetched are logged and counted while the rest
+    /// still refresh; only a refresh where every source fails is an error.
+    /// Returns the active all-tenant bundle version afterwards.
+    pub async fn refresh(&self) -> Result<u64, LoaderError> {
+        let documents = self.fetch().await?;
+
+        let mut distributor = self.distributor.write().await;
+
+        let mut timestamps = Vec::new();
+        let mut bundles = Vec::new();
+        let mut deltas = Vec::new();
+        for document in documents {
+            match document {
+                PolicyDocument::Timestamp(t) if distributor.serves(t.tenant_id.as_deref()) => timestamps.push(t),
+                PolicyDocument::Bundle(b) if distributor.serves(b.tenant_id.as_deref()) => bundles.push(b),
+                PolicyDocument::Delta(d) if distributor.serves(d.tenant_id.as_deref()) => deltas.push(d),
+                _ => {}
+            }
+        }
+        bundles.sort_by_key(|b| b.version);
+        deltas.sort_by_key(|d| d.version);
+
+        for timestamp in timestamps {
+            let version = timestamp.bundle_version;
+            let tenant = timestamp.tenant_id.clone();
+            if let Err(e) = distributor.update_timestamp(timestamp) {
+                tracing::warn!(version, ?tenant, "Policy timestamp rejected: {}", e);
+            }
+        }
+
+        for bundle in bundles {
+            let tenant = bundle.tenant_id.clone();
+            let version = bundle.version;
+            if version <= distributor.latest_version(tenant.as_deref()) {
+                continue;
+            }
+
+            match distributor.update_bundle(bundle) {
+                Ok(()) => tracing::info!(version, ?tenant, "Policy bundle verified"),
+                Err(e) => tracing::warn!(version, ?tenant, "Policy bundle rejected: {}", e),
+            }
+        }
+
+        for delta in deltas {
+            let tenant = delta.tenant_id.clone();
+            let version = delta.version;
+            if version <= distributor.latest_version(tenant.as_deref()) {
+                continue;
+            }
+
+            match distributor.update_delta(delta) {
+                Ok(()) => tracing::info!(version, ?tenant, "Policy delta verified"),
+                Err(e) => tracing::warn!(version, ?tenant, "Policy delta rejected: {}", e),
+            }
+        }
+
+        match self.activation {
+            BundleActivation::Immediate => distributor.activate_next()?,
+            BundleActivation::Shadow => distributor.activate_initial()?,
+        }
+
+        let freshness = distributor.freshness();
+        if freshness.is_degraded() {
+            tracing::warn!(?freshness, "Policy state is not fresh");
+        }
+
+        Ok(distributor.current_version())
+    }
+
+    /// Refresh on the configured interval until the task is dropped
+    pub async fn run(self) {
+        let mut interval = time::interval(self.refresh_interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.refresh().await {
+                tracing::error!("Policy refresh failed: {}", e);
+            }
+        }
+    }
+
+    async fn fetch(&self) -> Result<Vec<PolicyDocument>, LoaderError> {
+        match &self.source {
+            PolicySource::Directory(dir) => {
+                let mut documents = Vec::new();
+
+                for entry in std::fs::read_dir(dir)?.flatten() {
+                    let path = entry.path();
+                    if path.extension().is_none_or(|ext| ext != "json") {
+                        continue;
+                    }
+
+                    let data = match std::fs::read(&path) {
+                        Ok(data) => data,
+                        Err(e) => {
+                            self.fetch_failed(&path.display().to_string(), &e);
+                            continue;
+                        }
+                    };
+                    match PolicyDocument::parse_all(&data) {
+                        Ok(parsed) => documents.extend(parsed),
+                        Err(e) => tracing::warn!("Skipping {}: {}", path.display(), e),
+                    }
+                }
+
+                Ok(documents)
+            }
+            PolicySource::Url(url) => {
+                let mut urls = self.expand(url);
+                if let Some(timestamp_url) = &self.timestamp_url {
+                    urls.extend(self.expand(timestamp_url));
+                }
+
+                // One tenant's source failing leaves the others to refresh
+                let mut documents = Vec::new();
+                let mut failed = 0;
+                for url in &urls {
+                    match self.fetch_url(url).await {
+                        Ok(parsed) => documents.extend(parsed),
+                        Err(e) => {
+                            self.fetch_failed(url, &e);
+                            failed += 1;
+                        }
+                    }
+                }
+                if failed == urls.len() {
+                    return Err(format!("all {} policy sources failed", failed).into());
+                }
+                Ok(documents)
+            }
+        }
+    }
+
+    async fn fetch_url(&self, url: &str) -> Result<Vec<PolicyDocument>, LoaderError> {
+        let body = self.client
+            .get(url)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(PolicyDocument::parse_all(&body)?)
+    }
+
+    /// One URL per subscribed tenant when the URL has a `{tenant}` placeholder
+    fn expand(&self, url: &str) -> Vec<String> {
+        match &self.tenants {
+            Some(tenants) if url.contains(TENANT_PLACEHOLDER) => tenants
+                .iter()
+                .map(|tenant| url.replace(TENANT_PLACEHOLDER, tenant))
+                .collect(),
+            _ => vec![url.to_string()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use benteng_sdk_core::policy_bundle::SignedPolicyBundle;
+    use benteng_sdk_core::policy_delta::SignedPolicyDelta;
+    use benteng_sdk_core::policy_freshness::BundleTimestamp;
+    use benteng_sdk_core::{crypto::sig, policy::Policy};
+    use tempfile::tempdir;
+
+    const KID: &str = "btk/policy-signer/v1";
+
+    fn bundle(version: u64, signing_key: &[u8]) -> SignedPolicyBundle {
+        let policy = Policy {
+            tenant_id: "tenant1".to_string(),
+            policy_id: "policy1".to_string(),
+            path: "/test".to_string(),
+            required_algs: "kyber+dilithium".to_string(),
+            max_age_ms: 30000,
+            max_body_bytes: 65536,
+            require_device_attest: false,
+            hybrid_allowed: true,
+            replay_ttl_ms: 30000,
+            cache_dek: true,
+            version: version as u32,
+        };
+
+        SignedPolicyBundle::create(vec![policy], version, 3600, KID.to_string(), signing_key)
+            .unwrap()
+    }
+
+    fn config(source: PolicySource, public_key: Vec<u8>, activation: BundleActivation) -> PolicyLoaderConfig {
+        PolicyLoaderConfig {
+            source,
+            signer_set: SignerSet::single(TrustAnchor { kid: KID.to_string(), public_key }),
+            refresh_interval: Duration::from_secs(60),
+            activation,
+            state_dir: None,
+            timestamp_signers: None,
+            timestamp_url: None,
+            expiry_behaviour: ExpiryBehaviour::FailClosed,
+            tenants: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_directory_loads_newest_verified_bundle() {
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        let (_, rogue_sk) = sig::dilithium3_keypair().unwrap();
+
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("v1.json"), bundle(1, &sk).to_json().unwrap()).unwrap();
+        std::fs::write(dir.path().join("v2.json"), bundle(2, &sk).to_json().unwrap()).unwrap();
+        std::fs::write(dir.path().join("v3.json"), bundle(3, &rogue_sk).to_json().unwrap()).unwrap();
+        std::fs::write(dir.path().join("junk.json"), b"not a bundle").unwrap();
+
+        let config = config(
+            PolicySource::Directory(dir.path().to_path_buf()),
+            pk,
+            BundleActivation::Immediate,
+        );
+        let distributor = Arc::new(RwLock::new(config.distributor().unwrap()));
+        let loader = PolicyLoader::new(&config, distributor.clone());
+
+        assert_eq!(loader.refresh().await.unwrap(), 2);
+
+        let distributor = distributor.read().await;
+        assert_eq!(distributor.get_policy("tenant1", "policy1").unwrap().version, 2);
+        assert_eq!(distributor.candidate_version(), None);
+    }
+
+    #[tokio::test]
+    async fn test_shadow_activation_keeps_candidate_staged() {
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("v1.json"), bundle(1, &sk).to_json().unwrap()).unwrap();
+
+        let config = config(
+            PolicySource::Directory(dir.path().to_path_buf()),
+            pk,
+            BundleActivation::Shadow,
+        );
+        let distributor = Arc::new(RwLock::new(config.distributor().unwrap()));
+        let loader = PolicyLoader::new(&config, distributor.clone());
+
+        // First bundle goes live
+        assert_eq!(loader.refresh().await.unwrap(), 1);
+
+        std::fs::write(dir.path().join("v2.json"), bundle(2, &sk).to_json().unwrap()).unwrap();
+        assert_eq!(loader.refresh().await.unwrap(), 1);
+        assert_eq!(distributor.read().await.candidate_version(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_url_source() {
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        let body = bundle(5, &sk).to_json().unwrap();
+
+        let app = axum::Router::new()
+            .route("/bundle.json", axum::routing::get(move || async move { body }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config = config(
+            PolicySource::Url(format!("http://{}/bundle.json", addr)),
+            pk,
+            BundleActivation::Immediate,
+        );
+        let distributor = Arc::new(RwLock::new(config.distributor().unwrap()));
+        let loader = PolicyLoader::new(&config, distributor.clone());
+
+        assert_eq!(loader.refresh().await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_shards_with_deltas_from_url() {
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        let policies = vec![bundle(1, &sk).policies[0].clone()];
+
+        let mut v1 = SignedPolicyBundle::shard("tenant1", &policies, 1, 3600);
+        v1.add_signature(KID.to_string(), &sk).unwrap();
+        let v2 = SignedPolicyBundle::shard(
+            "tenant1",
+            &[Policy { max_age_ms: 5000, ..policies[0].clone() }],
+            2,
+            3600,
+        );
+        let mut delta = SignedPolicyDelta::diff(&v1, &v2, 3600).unwrap();
+        delta.add_signature(KID.to_string(), &sk).unwrap();
+
+        // Served as one array: the shard and the delta on top of it
+        let body = format!(
+            "[{}, {}]",
+            String::from_utf8(v1.to_json().unwrap()).unwrap(),
+            String::from_utf8(delta.to_json().unwrap()).unwrap(),
+        );
+        let app = axum::Router::new().route(
+            "/shards/tenant1.json",
+            axum::routing::get(move || async move { body }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut config = config(
+            PolicySource::Url(format!("http://{}/shards/{{tenant}}.json", addr)),
+            pk,
+            BundleActivation::Immediate,
+        );
+        config.tenants = Some(vec!["tenant1".to_string()]);
+        let distributor = Arc::new(RwLock::new(config.distributor().unwrap()));
+        let loader = PolicyLoader::new(&config, distributor.clone());
+
+        loader.refresh().await.unwrap();
+
+        let distributor = distributor.read().await;
+        assert_eq!(distributor.shard_versions().get("tenant1"), Some(&2));
+        assert_eq!(distributor.get_policy("tenant1", "policy1").unwrap().max_age_ms, 5000);
+    }
+
+    #[tokio::test]
+    async fn test_failing_tenant_source_does_not_block_others() {
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        let mut shard = SignedPolicyBundle::shard("tenant1", &bundle(1, &sk).policies, 3, 3600);
+        shard.add_signature(KID.to_string(), &sk).unwrap();
+        let body = shard.to_json().unwrap();
+
+        // tenant2's source is down
+        let app = axum::Router::new().route(
+            "/shards/tenant1.json",
+            axum::routing::get(move || async move { body }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut config = config(
+            PolicySource::Url(format!("http://{}/shards/{{tenant}}.json", addr)),
+            pk,
+            BundleActivation::Immediate,
+        );
+        config.tenants = Some(vec!["tenant2".to_string(), "tenant1".to_string()]);
+        let distributor = Arc::new(RwLock::new(config.distributor().unwrap()));
+        let loader = PolicyLoader::new(&config, distributor.clone());
+
+        loader.refresh().await.unwrap();
+        assert_eq!(loader.fetch_failures(), 1);
+        assert_eq!(distributor.read().await.shard_versions().get("tenant1"), Some(&3));
+
+        // Nothing reachable at all is an error
+        config.tenants = Some(vec!["tenant2".to_string()]);
+        let loader = PolicyLoader::new(&config, distributor);
+        assert!(loader.refresh().await.is_err());
+        assert_eq!(loader.fetch_failures(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_restart_refuses_rollback_and_reads_timestamp() {
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        let (ts_pk, ts_sk) = sig::dilithium3_keypair().unwrap();
+
+        let bundles = tempdir().unwrap();
+        let state = tempdir().unwrap();
+        let v2 = bundle(2, &sk);
+        std::fs::write(bundles.path().join("v2.json"), v2.to_json().unwrap()).unwrap();
+
+        let mut timestamp = BundleTimestamp::unsigned(None, 2, v2.state_hash().unwrap(), 300);
+        timestamp.add_signature("btk/timestamp/v1".to_string(), &ts_sk).unwrap();
+        std::fs::write(bundles.path().join("timestamp.json"), timestamp.to_json().unwrap()).unwrap();
+
+        let mut config = config(
+            PolicySource::Directory(bundles.path().to_path_buf()),
+            pk,
+            BundleActivation::Immediate,
+        );
+        config.state_dir = Some(state.path().to_path_buf());
+        config.timestamp_signers = Some(SignerSet::single(TrustAnchor {
+            kid: "btk/timestamp/v1".to_string(),
+            public_key: ts_pk,
+        }));
+
+        {
+            let distributor = Arc::new(RwLock::new(config.distributor().unwrap()));
+            let loader = PolicyLoader::new(&config, distributor.clone());
+            assert_eq!(loader.refresh().await.unwrap(), 2);
+            assert!(distributor.read().await.check_freshness().is_ok());
+        }
+
+        // After a restart, an attacker swaps in an older, validly signed bundle
+        std::fs::remove_file(bundles.path().join("v2.json")).unwrap();
+        std::fs::write(bundles.path().join("v1.json"), bundle(1, &sk).to_json().unwrap()).unwrap();
+
+        let distributor = Arc::new(RwLock::new(config.distributor().unwrap()));
+        let loader = PolicyLoader::new(&config, distributor.clone());
+        assert_eq!(loader.refresh().await.unwrap(), 0);
+        assert!(!distributor.read().await.has_active_bundle());
+    }
+}
@@ -0,0 +1,93 @@
+//! RFC 9457 problem details
+//! Every error response is `application/problem+json` with a stable `code`
+//! from the catalogue in sdk-core. The status always follows from the code.
+
+use crate::metrics::RejectionReason;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use benteng_sdk_core::{BentengError, ErrorCode};
+use serde::{Deserialize, Serialize};
+
+pub const CONTENT_TYPE: &str = "application/problem+json";
+
+/// Namespace of the problem type URIs
+const TYPE_PREFIX: &str = "urn:benteng:error:";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Problem {
+    /// `urn:benteng:error:<code>`
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub code: ErrorCode,
+    /// Always `REJECTED`, as in the responses before problem details
+    pub decision: String,
+}
+
+impl Problem {
+    pub fn new(code: ErrorCode) -> Self {
+        Self {
+            problem_type: format!("{}{}", TYPE_PREFIX, code),
+            title: code.title().to_string(),
+            status: code.http_status(),
+            detail: None,
+            code,
+            decision: "REJECTED".to_string(),
+        }
+    }
+
+    /// Explanation specific to this occurrence
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Only the code: messages from the crypto and KMS layers stay in the logs
+impl From<BentengError> for Problem {
+    fn from(e: BentengError) -> Self {
+        Self::new(e.code())
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let code = self.code;
+        let mut response = (status, axum::Json(self)).into_response();
+        response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+        response.extensions_mut().insert(RejectionReason(code));
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_problem_response() {
+        let response = Problem::new(ErrorCode::QuorumNotMet).with_detail("1 of 2 approvals").into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], CONTENT_TYPE);
+        assert_eq!(crate::metrics::decision_reason(&response), "quorum_not_met");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "type": "urn:benteng:error:quorum_not_met",
+            "title": "KMS quorum not met",
+            "status": 403,
+            "detail": "1 of 2 approvals",
+            "code": "quorum_not_met",
+            "decision": "REJECTED",
+        }));
+
+        // KMS messages are not echoed to the caller
+        let problem = Problem::from(BentengError::KeyNotFound("Unknown key k1".into()));
+        assert_eq!((problem.status, problem.detail), (404, None));
+    }
+}
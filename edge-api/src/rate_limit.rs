@@ -0,0 +1,352 @@
+//! Request rate limiting
+//! Every request draws a token from its client network's bucket for the
+//! route when that limit is set. Once the request has authenticated, it
+//! also draws from its tenant's bucket and, when that limit is set, its
+//! client key's. Unauthenticated requests therefore cannot drain another
+//! tenant's buckets. Buckets are keyed on full IDs, so tenants never share
+//! one, and idle buckets are evicted to bound memory.
+
+use crate::config::{RateLimit, RateLimitSettings};
+use crate::salt_rotation::SaltRotator;
+use axum::http::{HeaderMap, HeaderValue};
+use axum::response::Response;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often idle buckets are swept out
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What an authenticated request is limited by
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitRequest<'a> {
+    pub route: &'static str,
+    pub tenant_id: &'a [u8],
+    /// Key the envelope is signed with
+    pub client_kid: Option<&'a str>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_sec).min(limit.burst);
+        self.updated = now;
+    }
+}
+
+fn secs(tokens: f64, per_sec: f64) -> Duration {
+    Duration::try_from_secs_f64(tokens.max(0.0) / per_sec).unwrap_or(Duration::MAX)
+}
+
+/// Outcome for the tightest bucket a request fell in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    /// Bucket size
+    pub limit: u64,
+    /// Whole tokens left
+    pub remaining: u64,
+    /// Until the bucket is full again
+    pub reset: Duration,
+    /// Until a refused request would fit
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitDecision {
+    /// Add `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset`,
+    /// and `Retry-After` when refused
+    pub fn apply(&self, response: &mut Response) {
+        let ceil_secs = |d: Duration| d.as_secs() + u64::from(d.subsec_nanos() > 0);
+        let headers = response.headers_mut();
+        headers.insert("ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("ratelimit-reset", HeaderValue::from(ceil_secs(self.reset)));
+        if let Some(retry_after) = self.retry_after {
+            headers.insert("retry-after", HeaderValue::from(ceil_secs(retry_after).max(1)));
+        }
+    }
+
+    /// Whichever of the two decisions is closer to refusing, or refused
+    /// longest
+    pub fn tightest(self, other: Option<Self>) -> Self {
+        match other {
+            Some(other) if Self::order(&other) < Self::order(&self) => other,
+            _ => self,
+        }
+    }
+
+    fn order(&self) -> (std::cmp::Reverse<Option<Duration>>, u64) {
+        (std::cmp::Reverse(self.retry_after), self.remaining)
+    }
+}
+
+struct Buckets {
+    buckets: HashMap<String, Bucket>,
+    next_sweep: Instant,
+}
+
+pub struct RateLimiter {
+    settings: RateLimitSettings,
+    salt: Arc<SaltRotator>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(settings: RateLimitSettings) -> Self {
+        Self {
+            salt: Arc::new(SaltRotator::new(settings.salt_rotation_hours)),
+            buckets: Mutex::new(Buckets { buckets: HashMap::new(), next_sweep: Instant::now() + SWEEP_INTERVAL }),
+            settings,
+        }
+    }
+
+    /// Salt client networks are hashed with; run its rotation in the
+    /// background
+    pub fn salt_rotator(&self) -> Arc<SaltRotator> {
+        self.salt.clone()
+    }
+
+    /// Client address: the first one in the configured header, else the peer
+    pub fn client_ip(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<IpAddr> {
+        match &self.settings.client_ip_header {
+            Some(name) => headers.get(name.as_str())
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .and_then(|ip| ip.trim().parse().ok()),
+            None => peer.map(|addr| addr.ip()),
+        }
+    }
+
+    /// Tenant limit for `route`: the most specific override, else the default
+    fn tenant_limit(&self, route: &str, tenant_id: &[u8]) -> RateLimit {
+        let tenant = hex::encode(tenant_id);
+        self.settings.overrides.iter()
+            .filter_map(|o| {
+                let tenant_matches = o.tenant_id.as_ref().is_none_or(|t| t.eq_ignore_ascii_case(&tenant));
+                let route_matches = o.route.as_ref().is_none_or(|r| r == route);
+                let specificity = 2 * u8::from(o.tenant_id.is_some()) + u8::from(o.route.is_some());
+                (tenant_matches && route_matches).then_some((specificity, o))
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, o)| RateLimit { burst: o.burst, per_sec: o.per_sec })
+            .unwrap_or(RateLimit { burst: self.settings.burst, per_sec: self.settings.per_sec })
+    }
+
+    /// Take a token from the tenant's bucket for the route and the client
+    /// key's, or from neither if either is empty. Only for requests whose
+    /// tenant and key have been authenticated.
+    pub fn check(&self, request: &RateLimitRequest<'_>) -> RateLimitDecision {
+        let route = request.route;
+        let tenant = hex::encode(request.tenant_id);
+        let mut scopes = vec![(format!("{}/tenant/{}", route, tenant), self.tenant_limit(route, request.tenant_id))];
+        if let (Some(limit), Some(kid)) = (self.settings.client_key, request.client_kid) {
+            scopes.push((format!("{}/client-key/{}/{}", route, tenant, kid), limit));
+        }
+        self.take(&scopes).expect("the tenant bucket always applies")
+    }
+
+    /// Take a token from the client network's bucket for the route, if that
+    /// limit is set and the address is known
+    pub async fn check_network(&self, route: &'static str, client_ip: Option<IpAddr>) -> Option<RateLimitDecision> {
+        let (Some(limit), Some(ip)) = (self.settings.ip, client_ip) else {
+            return None;
+        };
+        let network = self.salt.hash_ip(&ip.to_string()).await;
+        self.take(&[(format!("{}/ip/{}", route, network), limit)])
+    }
+
+    /// Take a token from every bucket in `scopes`, or from none if any is
+    /// empty. `None` when there are no scopes.
+    fn take(&self, scopes: &[(String, RateLimit)]) -> Option<RateLimitDecision> {
+        let now = Instant::now();
+        let mut state = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if now >= state.next_sweep {
+            let idle = Duration::from_secs(self.settings.idle_secs);
+            state.buckets.retain(|_, bucket| now.duration_since(bucket.updated) < idle);
+            state.next_sweep = now + SWEEP_INTERVAL;
+        }
+
+        let mut tokens = Vec::with_capacity(scopes.len());
+        for (key, limit) in scopes {
+            let bucket = state.buckets.entry(key.clone())
+                .or_insert(Bucket { tokens: limit.burst, updated: now });
+            bucket.refill(limit, now);
+            tokens.push(bucket.tokens);
+        }
+        let allowed = tokens.iter().all(|t| *t >= 1.0);
+        if allowed {
+            for ((key, _), t) in scopes.iter().zip(tokens.iter_mut()) {
+                *t -= 1.0;
+                if let Some(bucket) = state.buckets.get_mut(key) {
+                    bucket.tokens = *t;
+                }
+            }
+        }
+        self.evict_excess(&mut state.buckets);
+        drop(state);
+
+        // Report the bucket that is closest to refusing, or refused longest
+        let decisions = scopes.iter().zip(tokens).map(|((_, limit), tokens)| RateLimitDecision {
+            allowed,
+            limit: limit.burst as u64,
+            remaining: tokens.max(0.0) as u64,
+            reset: secs(limit.burst - tokens, limit.per_sec),
+            retry_after: (!allowed && tokens < 1.0).then(|| secs(1.0 - tokens, limit.per_sec)),
+        });
+        decisions.min_by_key(RateLimitDecision::order)
+    }
+
+    /// Drop the longest idle buckets once over the cap, down to 90% of it so
+    /// the scan is not repeated on every request
+    fn evict_excess(&self, buckets: &mut HashMap<String, Bucket>) {
+        let max = self.settings.max_buckets.max(1);
+        if buckets.len() <= max {
+            return;
+        }
+        let keep = max - max / 10;
+        let mut by_age: Vec<(Instant, String)> = buckets.iter()
+            .map(|(key, bucket)| (bucket.updated, key.clone()))
+            .collect();
+        let evict = by_age.len() - keep;
+        by_age.select_nth_unstable(evict - 1);
+        for (_, key) in &by_age[..evict] {
+            buckets.remove(key);
+        }
+    }
+
+    /// Buckets currently held
+    pub fn buckets(&self) -> usize {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner()).buckets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RateLimitOverride;
+
+    fn request(tenant_id: &[u8]) -> RateLimitRequest<'_> {
+        RateLimitRequest { route: "verify", tenant_id, client_kid: None }
+    }
+
+    #[test]
+    fn test_tenants_routes_and_overrides() {
+        let limiter = RateLimiter::new(RateLimitSettings {
+            burst: 2.0,
+            per_sec: 0.5,
+            overrides: vec![
+                RateLimitOverride { tenant_id: None, route: Some("decrypt".into()), burst: 1.0, per_sec: 0.5 },
+                RateLimitOverride { tenant_id: Some(hex::encode([0xCDu8; 16])), route: None, burst: 3.0, per_sec: 0.5 },
+            ],
+            ..Default::default()
+        });
+
+        // Tenants sharing a prefix still have their own buckets
+        let mut first = [0xABu8; 16];
+        let second = [0xABu8; 16];
+        first[15] = 0;
+        for _ in 0..2 {
+            assert!(limiter.check(&request(&first)).allowed);
+        }
+        let refused = limiter.check(&request(&first));
+        assert!(!refused.allowed);
+        assert_eq!(refused.remaining, 0);
+        assert_eq!(refused.retry_after.map(|d| d.as_secs_f64().round()), Some(2.0));
+        assert!(limiter.check(&request(&second)).allowed);
+
+        // Routes are limited separately, with route overrides
+        let decrypt = RateLimitRequest { route: "decrypt", ..request(&first) };
+        assert_eq!(limiter.check(&decrypt).limit, 1);
+        assert!(!limiter.check(&decrypt).allowed);
+
+        // A tenant override beats a route override
+        let tenant = [0xCDu8; 16];
+        let decrypt = RateLimitRequest { route: "decrypt", ..request(&tenant) };
+        assert_eq!(limiter.check(&decrypt).limit, 3);
+    }
+
+    #[tokio::test]
+    async fn test_client_key_and_ip_buckets() {
+        let limiter = RateLimiter::new(RateLimitSettings {
+            client_key: Some(RateLimit { burst: 2.0, per_sec: 0.1 }),
+            ip: Some(RateLimit { burst: 1.0, per_sec: 0.1 }),
+            ..Default::default()
+        });
+        let tenant = [0xABu8; 16];
+        let signed = |kid| RateLimitRequest { client_kid: Some(kid), ..request(&tenant) };
+        let network = |ip: &str| limiter.check_network("verify", Some(ip.parse().unwrap()));
+
+        let first = network("10.0.0.1").await.unwrap();
+        assert!(first.allowed);
+        assert_eq!((first.limit, first.remaining), (1, 0));
+
+        // Same /24, so the same network bucket
+        let refused = network("10.0.0.2").await.unwrap();
+        assert!(!refused.allowed);
+        assert_eq!(refused.retry_after.map(|d| d.as_secs_f64().round()), Some(10.0));
+        assert!(network("10.0.1.1").await.unwrap().allowed);
+        assert_eq!(limiter.check_network("verify", None).await, None);
+
+        // The network bucket is separate from the tenant's and client key's
+        let keyed = limiter.check(&signed("client-1"));
+        assert!(keyed.allowed);
+        // The emptier client key bucket is the one reported
+        assert_eq!((keyed.limit, keyed.remaining), (2, 1));
+        assert_eq!(keyed.tightest(Some(first)), first);
+        assert_eq!(keyed.tightest(Some(refused)), refused);
+        assert_eq!(keyed.tightest(None), keyed);
+        assert!(limiter.check(&signed("client-1")).allowed);
+        assert!(!limiter.check(&signed("client-1")).allowed);
+        assert!(limiter.check(&signed("client-2")).allowed);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("203.0.113.9, 10.0.0.1"));
+        let peer = Some(SocketAddr::from(([127, 0, 0, 1], 4000)));
+        assert_eq!(limiter.client_ip(&headers, peer), Some(IpAddr::from([127, 0, 0, 1])));
+        let behind_proxy = RateLimiter::new(RateLimitSettings {
+            client_ip_header: Some("x-forwarded-for".into()),
+            ..Default::default()
+        });
+        assert_eq!(behind_proxy.client_ip(&headers, peer), Some(IpAddr::from([203, 0, 113, 9])));
+    }
+
+    #[test]
+    fn test_buckets_are_bounded() {
+        let limiter = RateLimiter::new(RateLimitSettings { max_buckets: 10, ..Default::default() });
+        for tenant in 0..25u8 {
+            limiter.check(&request(&[tenant; 16]));
+        }
+        assert!(limiter.buckets() <= 10);
+
+        // Idle buckets go at the next sweep
+        let limiter = RateLimiter::new(RateLimitSettings { idle_secs: 0, ..Default::default() });
+        limiter.check(&request(&[1u8; 16]));
+        limiter.buckets.lock().unwrap().next_sweep = Instant::now();
+        limiter.check(&request(&[2u8; 16]));
+        assert_eq!(limiter.buckets(), 1);
+    }
+
+    #[test]
+    fn test_headers() {
+        let decision = RateLimitDecision {
+            allowed: false,
+            limit: 100,
+            remaining: 0,
+            reset: Duration::from_millis(9500),
+            retry_after: Some(Duration::from_millis(100)),
+        };
+        let mut response = Response::new(axum::body::Body::empty());
+        decision.apply(&mut response);
+        let header = |name| response.headers()[name].to_str().unwrap().to_string();
+        assert_eq!(header("ratelimit-limit"), "100");
+        assert_eq!(header("ratelimit-remaining"), "0");
+        assert_eq!(header("ratelimit-reset"), "10");
+        assert_eq!(header("retry-after"), "1");
+    }
+}
@@ -0,0 +1,278 @@
+//! Replay detection for verified envelopes
+//! An envelope is identified by its route, tenant and AEAD nonce and refused
+//! if it is seen again on that route within its policy's replay TTL. The sled store survives a
+//! restart; the Redis store is shared by every edge replica.
+
+use crate::config::ReplaySettings;
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+type Result<T> = std::result::Result<T, ReplayStoreError>;
+
+/// How often expired entries are swept out
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Namespace of the Redis keys
+const REDIS_PREFIX: &str = "benteng:replay:";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayStoreError {
+    #[error("Replay store: {0}")]
+    Storage(String),
+}
+
+fn storage_error(e: impl std::fmt::Display) -> ReplayStoreError {
+    ReplayStoreError::Storage(e.to_string())
+}
+
+/// What makes an envelope unique
+#[derive(Debug, Clone, Copy)]
+pub struct ReplayKey<'a> {
+    /// Route the envelope was presented to; verifying an envelope does not
+    /// spend it for decryption
+    pub route: &'static str,
+    pub tenant_id: &'a [u8],
+    pub nonce: &'a [u8],
+}
+
+impl ReplayKey<'_> {
+    fn encode(&self) -> String {
+        format!("{}:{}:{}", self.route, hex::encode(self.tenant_id), hex::encode(self.nonce))
+    }
+}
+
+/// Where seen envelopes are remembered
+pub enum ReplayStore {
+    Memory(MemoryReplayStore),
+    Sled(SledReplayStore),
+    Redis(RedisReplayStore),
+}
+
+impl ReplayStore {
+    /// Store for `settings`: Redis when a URL is set, sled when a path is,
+    /// otherwise in memory
+    pub async fn open(settings: &ReplaySettings) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        match (&settings.redis_url, &settings.path) {
+            (Some(_), Some(_)) => Err("replay.redis_url and replay.path are mutually exclusive".into()),
+            (Some(url), None) => Ok(Self::Redis(RedisReplayStore::connect(url).await?)),
+            (None, Some(path)) => Ok(Self::Sled(SledReplayStore::open(path)?)),
+            (None, None) => Ok(Self::Memory(MemoryReplayStore::new(settings.shards))),
+        }
+    }
+
+    /// Remember `key` for `ttl`. False if it was already seen within its TTL.
+    pub async fn check_and_insert(&self, key: &ReplayKey<'_>, ttl: Duration) -> Result<bool> {
+        let key = key.encode();
+        match self {
+            Self::Memory(store) => Ok(store.check_and_insert(&key, ttl)),
+            Self::Sled(store) => store.check_and_insert(&key, ttl),
+            Self::Redis(store) => store.check_and_insert(&key, ttl).await,
+        }
+    }
+
+    /// Entries held, including expired ones not yet swept. Unknown for
+    /// Redis, which expires keys itself.
+    pub fn entries(&self) -> Option<usize> {
+        match self {
+            Self::Memory(store) => Some(store.len()),
+            Self::Sled(store) => Some(store.len()),
+            Self::Redis(_) => None,
+        }
+    }
+}
+
+struct Shard {
+    expiries: HashMap<String, Instant>,
+    next_sweep: Instant,
+}
+
+/// In-process store, split into shards so requests rarely contend and
+/// sweeps only lock one shard
+pub struct MemoryReplayStore {
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+}
+
+impl MemoryReplayStore {
+    pub fn new(shards: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            shards: (0..shards.max(1))
+                .map(|_| Mutex::new(Shard { expiries: HashMap::new(), next_sweep: now + SWEEP_INTERVAL }))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn check_and_insert(&self, key: &str, ttl: Duration) -> bool {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        let mut shard = self.shards[index].lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if now >= shard.next_sweep {
+            shard.expiries.retain(|_, expires| *expires > now);
+            shard.next_sweep = now + SWEEP_INTERVAL;
+        }
+
+        match shard.expiries.get(key) {
+            Some(expires) if *expires > now => false,
+            _ => {
+                shard.expiries.insert(key.to_string(), now + ttl);
+                true
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.shards.iter()
+            .map(|shard| shard.lock().unwrap_or_else(|e| e.into_inner()).expiries.len())
+            .sum()
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn expiry(value: &[u8]) -> u64 {
+    value.try_into().map(u64::from_be_bytes).unwrap_or(0)
+}
+
+/// sled-backed store holding each key's expiry in Unix milliseconds
+pub struct SledReplayStore {
+    db: sled::Db,
+    next_sweep: Mutex<Instant>,
+}
+
+impl SledReplayStore {
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let db = sled::open(path).map_err(storage_error)?;
+        Ok(Self { db, next_sweep: Mutex::new(Instant::now()) })
+    }
+
+    fn check_and_insert(&self, key: &str, ttl: Duration) -> Result<bool> {
+        let now = now_ms();
+        self.sweep(now)?;
+
+        let expires = (now + ttl.as_millis() as u64).to_be_bytes();
+        loop {
+            let current = self.db.get(key).map_err(storage_error)?;
+            if current.as_deref().is_some_and(|value| expiry(value) > now) {
+                return Ok(false);
+            }
+            // Another request may have claimed the key since the read
+            let swapped = self.db.compare_and_swap(key, current, Some(&expires[..])).map_err(storage_error)?;
+            if swapped.is_ok() {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Drop expired keys, at most once per sweep interval
+    fn sweep(&self, now: u64) -> Result<()> {
+        {
+            let mut next_sweep = self.next_sweep.lock().unwrap_or_else(|e| e.into_inner());
+            if Instant::now() < *next_sweep {
+                return Ok(());
+            }
+            *next_sweep = Instant::now() + SWEEP_INTERVAL;
+        }
+        for entry in self.db.iter() {
+            let (key, value) = entry.map_err(storage_error)?;
+            if expiry(&value) <= now {
+                // Leave keys that were claimed again meanwhile
+                let _ = self.db.compare_and_swap(key, Some(value), None::<&[u8]>).map_err(storage_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.db.len()
+    }
+}
+
+/// Store in Redis, or anything speaking its protocol, so every replica
+/// sees every envelope. Keys expire on their own.
+pub struct RedisReplayStore {
+    connection: ConnectionManager,
+}
+
+impl RedisReplayStore {
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(storage_error)?;
+        let config = ConnectionManagerConfig::new()
+            .set_connection_timeout(Duration::from_secs(2))
+            .set_response_timeout(Duration::from_secs(2));
+        let connection = ConnectionManager::new_with_config(client, config).await.map_err(storage_error)?;
+        Ok(Self { connection })
+    }
+
+    async fn check_and_insert(&self, key: &str, ttl: Duration) -> Result<bool> {
+        // SET NX answers nil when the key is already held
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(format!("{}{}", REDIS_PREFIX, key))
+            .arg(1)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(storage_error)?;
+        Ok(reply.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TENANT: [u8; 16] = [0xAB; 16];
+
+    async fn check_store(store: ReplayStore) {
+        let first = ReplayKey { route: "verify", tenant_id: &TENANT, nonce: &[1u8; 12] };
+        let ttl = Duration::from_millis(200);
+        assert!(store.check_and_insert(&first, ttl).await.unwrap());
+        assert!(!store.check_and_insert(&first, ttl).await.unwrap());
+
+        // The same nonce under another tenant is a different envelope
+        let other_tenant = ReplayKey { route: "verify", tenant_id: &[0xCDu8; 16], nonce: &[1u8; 12] };
+        assert!(store.check_and_insert(&other_tenant, ttl).await.unwrap());
+        // And so is the same envelope on another route
+        let other_route = ReplayKey { route: "decrypt", ..first };
+        assert!(store.check_and_insert(&other_route, ttl).await.unwrap());
+        assert!(!store.check_and_insert(&other_route, ttl).await.unwrap());
+        assert_eq!(store.entries(), Some(3));
+
+        // Each key keeps the TTL it was inserted with
+        let short = ReplayKey { route: "verify", tenant_id: &TENANT, nonce: &[2u8; 12] };
+        assert!(store.check_and_insert(&short, Duration::from_millis(1)).await.unwrap());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(store.check_and_insert(&short, ttl).await.unwrap());
+        assert!(!store.check_and_insert(&first, ttl).await.unwrap());
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(store.check_and_insert(&first, ttl).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        check_store(ReplayStore::Memory(MemoryReplayStore::new(4))).await;
+    }
+
+    #[tokio::test]
+    async fn test_sled_store_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(ReplayStore::Sled(SledReplayStore::open(dir.path()).unwrap())).await;
+
+        let store = ReplayStore::Sled(SledReplayStore::open(dir.path()).unwrap());
+        let key = ReplayKey { route: "verify", tenant_id: &TENANT, nonce: &[1u8; 12] };
+        assert!(!store.check_and_insert(&key, Duration::from_secs(60)).await.unwrap());
+    }
+}
@@ -0,0 +1,236 @@
+//! Envelope rewrap for key rotation
+//! Moves stored envelopes to a tenant's current KMS key. Only envelopes whose
+//! signature verifies against a key registered for their tenant are rewrapped,
+//! so the service never re-signs a forged envelope. The DEK stays in the KMS
+//! and the payload ciphertext is left as is; the updated envelope is re-signed
+//! with the service key and every rewrap goes to the transparency log.
+//! The KMS authorizes a rewrap like a decryption of the old envelope, and
+//! `/pqc/verify` accepts the result once the service key is enrolled with
+//! `ClientKeyRegistry::enroll_service`.
+
+use crate::client_keys::ClientKeyRegistry;
+use benteng_sdk_core::crypto::approval::RequestSubmission;
+use benteng_sdk_core::crypto::kms::{KmsGate, RewrapRequest};
+use benteng_sdk_core::envelope::{operations::EnvelopeOps, Envelope};
+use benteng_sdk_core::BentengError;
+use benteng_transparency::{LogEntry, TransparencyLog};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use zeroize::Zeroizing;
+
+pub struct RewrapService<K> {
+    kms: Arc<K>,
+    transparency_log: Arc<RwLock<TransparencyLog>>,
+    client_keys: Arc<ClientKeyRegistry>,
+    signer: String,
+    signing_key: Zeroizing<Vec<u8>>,
+}
+
+impl<K: KmsGate> RewrapService<K> {
+    /// `signer` names the ML-DSA service key that re-signs rewrapped envelopes.
+    /// Incoming envelopes are verified against `client_keys`.
+    pub fn new(
+        kms: Arc<K>,
+        transparency_log: Arc<RwLock<TransparencyLog>>,
+        client_keys: Arc<ClientKeyRegistry>,
+        signer: impl Into<String>,
+        signing_key: Zeroizing<Vec<u8>>,
+    ) -> Self {
+        Self {
+            kms,
+            transparency_log,
+            client_keys,
+            signer: signer.into(),
+            signing_key,
+        }
+    }
+
+    /// Rewrap an envelope to `new_kid`, or to the active key for its tenant
+    /// and policy. Returns the updated envelope and its log index.
+    pub async fn rewrap(&self, envelope: &Envelope, new_kid: Option<&str>) -> Result<(Envelope, usize), BentengError> {
+        self.verify(envelope)?;
+
+        // Approvers review the rewrap as a request from the service
+        self.kms.submit_request(RequestSubmission::from_envelope(envelope, &self.signer)?).await?;
+
+        let rewrapped = self.kms.rewrap(RewrapRequest {
+            kem_ciphertext: envelope.kem_ct.clone(),
+            wrapped_dek: envelope.wrapped_dek.clone(),
+            kid: envelope.kid.clone(),
+            new_kid: new_kid.map(str::to_string),
+            tenant_id: envelope.tenant_id.clone(),
+            policy_id: envelope.policy_id.clone(),
+            path: envelope.path.clone(),
+        }).await?;
+
+        let mut updated = envelope.clone();
+        updated.kid = Some(rewrapped.kid);
+        updated.kem_ct = rewrapped.kem_ciphertext;
+        updated.wrapped_dek = Some(rewrapped.wrapped_dek);
+        EnvelopeOps::sign(&mut updated, &self.signer, &self.signing_key)?;
+
+        let index = self.record(envelope, &updated).await?;
+        Ok((updated, index))
+    }
+
+    /// Check the envelope's signature against its signer's registered key
+    fn verify(&self, envelope: &Envelope) -> Result<(), BentengError> {
+        let Some(kid) = envelope.signer.as_deref() else {
+            return Err(BentengError::InvalidSignature);
+        };
+        let public_key = self.client_keys.public_key(&envelope.tenant_id, kid)
+            .map_err(|e| {
+                tracing::error!("Client key lookup failed: {}", e);
+                BentengError::InternalError
+            })?
+            .ok_or(BentengError::InvalidSignature)?;
+        EnvelopeOps::verify(envelope, &public_key)
+    }
+
+    /// Log the rewrap, linking the previous signature to the new one
+    async fn record(&self, previous: &Envelope, updated: &Envelope) -> Result<usize, BentengError> {
+        let previous_sig_h: [u8; 32] = Sha256::digest(&previous.sig).into();
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"rewrap");
+        hasher.update(&updated.tenant_id);
+        hasher.update(&updated.policy_id);
+        hasher.update(previous_sig_h);
+
+        let entry = LogEntry {
+            v: 1,
+            ten: updated.tenant_id.clone(),
+            typ: "rewrap".to_string(),
+            ts: chrono::Utc::now().timestamp_millis() as u64,
+            hdr_h: hasher.finalize().into(),
+            sig_h: Sha256::digest(&updated.sig).into(),
+            kid: updated.kid.clone().unwrap_or_default(),
+            pol: updated.policy_id.clone(),
+            rc: 0,
+        };
+
+        self.transparency_log.write().await
+            .append(entry)
+            .map_err(|_| BentengError::InternalError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_keys::{ClientKeyRegistry, Enrollment};
+    use benteng_sdk_core::crypto::key_catalog::KeyOptions;
+    use benteng_sdk_core::crypto::kms::{DualControlConfig, DualControlKms};
+    use benteng_sdk_core::crypto::quorum::{Approver, QuorumDenial};
+    use benteng_sdk_core::crypto::sig;
+    use benteng_sdk_core::envelope::kms_decrypt::decrypt_with_kms;
+
+    #[tokio::test]
+    async fn test_rewrap_keeps_payload_ciphertext() {
+        let kms = Arc::new(DualControlKms::new(DualControlConfig {
+            require_quorum: false,
+            ..Default::default()
+        }).unwrap());
+        let old = kms.generate_key(b"tenant", b"policy", KeyOptions::default()).await.unwrap();
+
+        let (client_pk, client_sk) = sig::dilithium3_keypair().unwrap();
+        let registry = Arc::new(ClientKeyRegistry::temporary().unwrap());
+        registry.enroll(b"tenant", Enrollment {
+            kid: Some("client-1".into()),
+            public_key: client_pk.clone(),
+            ..Default::default()
+        }).unwrap();
+        let wrapped = kms.generate_dek(None, b"policy", b"tenant", "/archive").await.unwrap();
+        let mut envelope = EnvelopeOps::encrypt_and_sign_with_dek(
+            b"archived record", b"tenant", b"policy", "/archive", &wrapped, &client_sk, true,
+        ).unwrap();
+        EnvelopeOps::sign(&mut envelope, "client-1", &client_sk).unwrap();
+
+        let new = kms.rotate_key(b"tenant", b"policy").await.unwrap();
+        let (service_pk, service_sk) = sig::dilithium3_keypair().unwrap();
+        let log = Arc::new(RwLock::new(TransparencyLog::new()));
+        let service = RewrapService::new(
+            kms.clone(), log.clone(), registry.clone(), "rewrap-service", Zeroizing::new(service_sk),
+        );
+
+        // Envelopes not signed by a registered key are refused
+        let (_, rogue_sk) = sig::dilithium3_keypair().unwrap();
+        let mut forged = envelope.clone();
+        EnvelopeOps::sign(&mut forged, "client-1", &rogue_sk).unwrap();
+        assert!(matches!(service.rewrap(&forged, None).await, Err(BentengError::InvalidSignature)));
+        let mut unknown = envelope.clone();
+        EnvelopeOps::sign(&mut unknown, "client-2", &client_sk).unwrap();
+        assert!(matches!(service.rewrap(&unknown, None).await, Err(BentengError::InvalidSignature)));
+        assert!(log.read().await.is_empty());
+
+        let (updated, index) = service.rewrap(&envelope, None).await.unwrap();
+        assert_eq!(updated.ct, envelope.ct);
+        assert_eq!(updated.kid.as_deref(), Some(new.kid.as_str()));
+        assert_eq!(updated.signer.as_deref(), Some("rewrap-service"));
+        EnvelopeOps::verify(&updated, &service_pk).unwrap();
+        assert!(EnvelopeOps::verify(&updated, &client_pk).is_err());
+
+        // The enrolled service key verifies for the envelope's tenant
+        registry.enroll_service(Enrollment {
+            kid: Some("rewrap-service".into()),
+            public_key: service_pk,
+            ..Default::default()
+        }).unwrap();
+        let signer_pk = registry.public_key(&updated.tenant_id, "rewrap-service").unwrap().unwrap();
+        EnvelopeOps::verify(&updated, &signer_pk).unwrap();
+
+        let log = log.read().await;
+        let entry = log.get_entry(index).unwrap();
+        assert_eq!(entry.typ, "rewrap");
+        assert_eq!(entry.kid, new.kid);
+
+        // The old key is no longer needed
+        kms.destroy_key(&old.kid).await.unwrap();
+        assert!(decrypt_with_kms(&envelope, kms.as_ref(), "test").await.is_err());
+        let updated = Envelope::from_cbor(&updated.to_cbor().unwrap()).unwrap();
+        assert_eq!(decrypt_with_kms(&updated, kms.as_ref(), "test").await.unwrap(), b"archived record");
+    }
+
+    #[tokio::test]
+    async fn test_rewrap_of_denied_request_fails() {
+        let (approver_pk, approver_sk) = sig::dilithium3_keypair().unwrap();
+        let kms = Arc::new(DualControlKms::new(DualControlConfig {
+            quorum_threshold: 1,
+            ..Default::default()
+        }).unwrap().with_approvers(vec![Approver { id: "approver1".into(), public_key: approver_pk }]).unwrap());
+        kms.generate_key(b"tenant", b"policy", KeyOptions::default()).await.unwrap();
+
+        let (client_pk, client_sk) = sig::dilithium3_keypair().unwrap();
+        let registry = Arc::new(ClientKeyRegistry::temporary().unwrap());
+        registry.enroll(b"tenant", Enrollment {
+            kid: Some("client-1".into()),
+            public_key: client_pk,
+            ..Default::default()
+        }).unwrap();
+        let wrapped = kms.generate_dek(None, b"policy", b"tenant", "/archive").await.unwrap();
+        let mut envelope = EnvelopeOps::encrypt_and_sign_with_dek(
+            b"archived record", b"tenant", b"policy", "/archive", &wrapped, &client_sk, true,
+        ).unwrap();
+        EnvelopeOps::sign(&mut envelope, "client-1", &client_sk).unwrap();
+        kms.rotate_key(b"tenant", b"policy").await.unwrap();
+
+        let (_, service_sk) = sig::dilithium3_keypair().unwrap();
+        let log = Arc::new(RwLock::new(TransparencyLog::new()));
+        let service = RewrapService::new(
+            kms.clone(), log.clone(), registry, "rewrap-service", Zeroizing::new(service_sk),
+        );
+
+        // Submitted for review, but not yet approved
+        let err = service.rewrap(&envelope, None).await.unwrap_err();
+        assert!(matches!(err, BentengError::QuorumNotMet(_)));
+        let request_id = DualControlKms::request_id(&envelope.kem_ct, b"policy", b"tenant", "/archive").unwrap();
+        let request = kms.get_request(&request_id).await.unwrap().unwrap();
+        assert_eq!(request.context.requester, "rewrap-service");
+
+        kms.deny(QuorumDenial::sign(&request_id, "approver1", "legal hold", &approver_sk).unwrap()).await.unwrap();
+        let err = service.rewrap(&envelope, None).await.unwrap_err();
+        assert!(matches!(err, BentengError::RequestDenied(_)));
+        assert!(log.read().await.is_empty());
+    }
+}
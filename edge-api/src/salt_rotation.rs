@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc, Duration};
 use sha2::{Sha256, Digest};
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::time;
@@ -42,8 +43,9 @@ impl SaltRotator {
             
             if should_rotate {
                 let new_salt = Salt::generate(self.rotation_interval);
+                let created_at = new_salt.created_at;
                 *self.current_salt.write().await = new_salt;
-                tracing::info!("Salt rotated successfully");
+                tracing::info!(%created_at, "Salt rotated successfully");
             }
         }
     }
@@ -51,11 +53,18 @@ impl SaltRotator {
     pub async fn hash_ip(&self, ip: &str) -> String {
         let salt = self.current_salt.read().await;
         
-        // Extract /24 prefix
-        let prefix = ip.split('.')
-            .take(3)
-            .collect::<Vec<_>>()
-            .join(".");
+        // Extract /24 prefix, or /64 for IPv6
+        let prefix = match ip.parse::<IpAddr>().map(|ip| ip.to_canonical()) {
+            Ok(IpAddr::V6(ip)) => {
+                let s = ip.segments();
+                format!("{:x}:{:x}:{:x}:{:x}::", s[0], s[1], s[2], s[3])
+            }
+            Ok(IpAddr::V4(ip)) => ip.octets()[..3].iter().map(|o| o.to_string()).collect::<Vec<_>>().join("."),
+            Err(_) => ip.split('.')
+                .take(3)
+                .collect::<Vec<_>>()
+                .join("."),
+        };
         
         // Hash with salt
         let mut hasher = Sha256::new();
@@ -99,5 +108,13 @@ mod tests {
         let hash3 = rotator.hash_ip("192.168.2.100").await;
         // Different /24 should produce different hash
         assert_ne!(hash1, hash3);
+        
+        // IPv4-mapped addresses count as IPv4
+        assert_eq!(rotator.hash_ip("::ffff:192.168.1.7").await, hash1);
+        
+        // IPv6 is grouped by /64
+        let hash4 = rotator.hash_ip("2001:db8:1:2::1").await;
+        assert_eq!(rotator.hash_ip("2001:db8:1:2:ffff::9").await, hash4);
+        assert_ne!(rotator.hash_ip("2001:db8:1:3::1").await, hash4);
     }
 }
@@ -0,0 +1,91 @@
+//! OpenTelemetry tracing for the edge API
+//! Request spans continue the caller's trace from the W3C `traceparent`
+//! header and are exported over OTLP/HTTP. Span attributes name tenants and
+//! policies by hash only and never carry key material.
+
+use crate::config::TelemetrySettings;
+use axum::http::{HeaderMap, Request};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use sha2::{Digest, Sha256};
+use tracing::Subscriber;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Span exporter for the process. Flush or shut it down before exit, or the
+/// last batch is lost.
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+impl Telemetry {
+    /// Exporter for `settings`, or `None` when no endpoint is configured
+    pub fn init(settings: &TelemetrySettings) -> Result<Option<Self>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(endpoint) = &settings.otlp_endpoint else {
+            return Ok(None);
+        };
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name(settings.service_name.clone()).build())
+            .build();
+        Ok(Some(Self { provider }))
+    }
+
+    /// Layer that turns `tracing` spans into exported OpenTelemetry spans
+    pub fn layer<S>(&self) -> OpenTelemetryLayer<S, SdkTracer>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.provider.tracer("benteng-edge-api"))
+    }
+
+    /// Export every finished span now. Blocks until the collector answers.
+    pub fn force_flush(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.provider.force_flush()?)
+    }
+
+    /// Flush and stop the exporter. Blocks until the collector answers.
+    pub fn shutdown(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.provider.shutdown()?)
+    }
+}
+
+/// Tenant or policy ID as a span attribute or metric label: the first 8
+/// bytes of its SHA-256
+pub fn id_hash(id: &[u8]) -> String {
+    hex::encode(&Sha256::digest(id)[..8])
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Root span of a request, joined to the caller's trace when it sent a
+/// `traceparent`
+pub fn request_span<B>(request: &Request<B>) -> tracing::Span {
+    let span = tracing::info_span!(
+        "request",
+        http.request.method = %request.method(),
+        url.path = %request.uri().path(),
+    );
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(request.headers()));
+    // Only fails when no OpenTelemetry layer is installed
+    let _ = span.set_parent(parent);
+    span
+}
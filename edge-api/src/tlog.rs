@@ -0,0 +1,351 @@
+//! Transparency log over HTTP
+//! Serves signed checkpoints, entries and RFC 9162 inclusion and consistency
+//! proofs, so clients can check their receipts without trusting the edge.
+//! Checkpoints are signed in batches by a `Checkpointer`, off the append path.
+
+use crate::config::TransparencySettings;
+use crate::problem::Problem;
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Json, Response},
+};
+use benteng_sdk_core::crypto::sig;
+use benteng_sdk_core::ErrorCode;
+use benteng_transparency::{leaf_hash, Checkpoint, CheckpointSigner, LogEntry, TransparencyLog};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, RwLock};
+use zeroize::Zeroizing;
+
+/// Key ID used when none is configured
+const DEFAULT_KEY_ID: &str = "benteng-tlog";
+
+/// Log for `settings`, with its checkpoint signer
+pub fn open_log(settings: &TransparencySettings) -> Result<TransparencyLog, Box<dyn std::error::Error + Send + Sync>> {
+    let secret_key = match &settings.signing_key_file {
+        Some(path) => std::fs::read(path).map_err(|e| format!("reading {}: {}", path.display(), e))?,
+        None => {
+            tracing::warn!("No transparency signing key configured; checkpoints use an ephemeral key");
+            sig::dilithium3_keypair()?.1
+        }
+    };
+    let signer = CheckpointSigner {
+        kid: settings.key_id.clone().unwrap_or_else(|| DEFAULT_KEY_ID.to_string()),
+        secret_key: Zeroizing::new(secret_key),
+    };
+
+    let log = match &settings.path {
+        Some(path) => TransparencyLog::open(path)?,
+        None => TransparencyLog::new(),
+    };
+    Ok(log.with_signer(signer))
+}
+
+/// Checkpoint with hashes and signature in hex
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointView {
+    pub tree_size: usize,
+    pub root_hash: String,
+    pub ts: u64,
+    pub ver: u8,
+    pub kid: String,
+    pub signature: String,
+}
+
+impl From<&Checkpoint> for CheckpointView {
+    fn from(checkpoint: &Checkpoint) -> Self {
+        Self {
+            tree_size: checkpoint.tree_size,
+            root_hash: hex::encode(checkpoint.root_hash),
+            ts: checkpoint.ts,
+            ver: checkpoint.ver,
+            kid: checkpoint.kid.clone(),
+            signature: hex::encode(&checkpoint.signature),
+        }
+    }
+}
+
+impl TryFrom<&CheckpointView> for Checkpoint {
+    type Error = hex::FromHexError;
+
+    fn try_from(view: &CheckpointView) -> Result<Self, Self::Error> {
+        let mut root_hash = [0u8; 32];
+        hex::decode_to_slice(&view.root_hash, &mut root_hash)?;
+        Ok(Self {
+            tree_size: view.tree_size,
+            root_hash,
+            ts: view.ts,
+            ver: view.ver,
+            kid: view.kid.clone(),
+            signature: hex::decode(&view.signature)?,
+        })
+    }
+}
+
+/// Where an entry landed. Once a signed checkpoint covers it, the receipt
+/// carries that checkpoint and the entry's audit path to its root; until
+/// then, the time a covering checkpoint is due.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogReceipt {
+    pub leaf_index: usize,
+    pub leaf_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<CheckpointView>,
+    /// Audit path, leaf to root
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inclusion_proof: Vec<String>,
+    /// Milliseconds since the epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint_due_ms: Option<u64>,
+}
+
+/// Appends entries and signs checkpoints over them in batches: every
+/// interval, or sooner once a batch of entries is waiting. Signing happens
+/// outside the log's lock.
+pub struct Checkpointer {
+    log: Arc<RwLock<TransparencyLog>>,
+    interval: Duration,
+    batch: usize,
+    wake: Notify,
+}
+
+impl Checkpointer {
+    pub fn new(log: Arc<RwLock<TransparencyLog>>, interval: Duration, batch: usize) -> Self {
+        Self {
+            log,
+            interval: interval.max(Duration::from_millis(1)),
+            batch: batch.max(1),
+            wake: Notify::new(),
+        }
+    }
+
+    /// Same schedule over another log
+    pub fn with_log(&self, log: Arc<RwLock<TransparencyLog>>) -> Self {
+        Self::new(log, self.interval, self.batch)
+    }
+
+    pub fn log(&self) -> Arc<RwLock<TransparencyLog>> {
+        self.log.clone()
+    }
+
+    /// Append an entry and return its receipt
+    #[tracing::instrument(name = "log_append", skip_all)]
+    pub async fn append(&self, entry: LogEntry) -> Result<LogReceipt, String> {
+        let mut log = self.log.write().await;
+        let leaf_index = log.append(entry)?;
+        if log.uncheckpointed() >= self.batch {
+            self.wake.notify_one();
+        }
+        self.receipt_in(&log, leaf_index)
+    }
+
+    /// Receipt for an entry appended earlier
+    #[tracing::instrument(name = "log_checkpoint", skip_all)]
+    pub async fn receipt(&self, leaf_index: usize) -> Result<LogReceipt, String> {
+        let log = self.log.read().await;
+        self.receipt_in(&log, leaf_index)
+    }
+
+    fn receipt_in(&self, log: &TransparencyLog, leaf_index: usize) -> Result<LogReceipt, String> {
+        let entry = log.get_entry(leaf_index).ok_or("No such entry")?;
+        let mut receipt = LogReceipt {
+            leaf_index,
+            leaf_hash: hex::encode(leaf_hash(entry)),
+            checkpoint: None,
+            inclusion_proof: Vec::new(),
+            checkpoint_due_ms: None,
+        };
+        match log.get_latest_checkpoint() {
+            Some(checkpoint) if checkpoint.tree_size > leaf_index => {
+                let proof = log.inclusion_proof(leaf_index, checkpoint.tree_size)?;
+                receipt.inclusion_proof = proof.iter().map(hex::encode).collect();
+                receipt.checkpoint = Some(CheckpointView::from(checkpoint));
+            }
+            _ => {
+                let due = chrono::Utc::now() + self.interval;
+                receipt.checkpoint_due_ms = Some(due.timestamp_millis() as u64);
+            }
+        }
+        Ok(receipt)
+    }
+
+    /// Sign a checkpoint over the entries so far, unless the latest one
+    /// already covers them
+    pub async fn checkpoint(&self) -> Result<Option<Checkpoint>, String> {
+        let (mut checkpoint, signer) = {
+            let log = self.log.read().await;
+            if log.is_empty() || log.uncheckpointed() == 0 {
+                return Ok(None);
+            }
+            (log.draft_checkpoint()?, log.signer())
+        };
+        if let Some(signer) = signer {
+            checkpoint.sign(&signer)?;
+        }
+        self.log.write().await.install_checkpoint(checkpoint.clone())?;
+        Ok(Some(checkpoint))
+    }
+
+    /// Sign checkpoints until the task is dropped
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(self.interval) => {}
+                _ = self.wake.notified() => {}
+            }
+            if let Err(e) = self.checkpoint().await {
+                tracing::error!("Checkpoint failed: {}", e);
+            }
+        }
+    }
+}
+
+fn tlog_error(code: ErrorCode) -> Response {
+    Problem::new(code).into_response()
+}
+
+/// Latest signed checkpoint; one is signed now if the log has none yet
+pub(crate) async fn get_checkpoint(State(state): State<AppState>) -> Response {
+    let checkpointer = state.checkpointer();
+    let log = checkpointer.log();
+    let unsigned = log.read().await.get_latest_checkpoint().is_none();
+    if unsigned {
+        if let Err(e) = checkpointer.checkpoint().await {
+            tracing::error!("Checkpoint failed: {}", e);
+            return tlog_error(ErrorCode::InternalError);
+        }
+    }
+    let log = log.read().await;
+    match log.get_latest_checkpoint() {
+        Some(checkpoint) => Json(CheckpointView::from(checkpoint)).into_response(),
+        None => tlog_error(ErrorCode::LogEmpty),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EntryResponse {
+    pub index: usize,
+    pub leaf_hash: String,
+    pub entry: LogEntry,
+}
+
+pub(crate) async fn get_entry(State(state): State<AppState>, Path(index): Path<usize>) -> Response {
+    let log = state.transparency_log();
+    let log = log.read().await;
+    match log.get_entry(index) {
+        Some(entry) => Json(EntryResponse {
+            index,
+            leaf_hash: hex::encode(leaf_hash(entry)),
+            entry: entry.clone(),
+        }).into_response(),
+        None => tlog_error(ErrorCode::LogEntryNotFound),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct InclusionQuery {
+    index: usize,
+    size: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InclusionProof {
+    pub index: usize,
+    pub tree_size: usize,
+    pub leaf_hash: String,
+    /// Audit path, leaf to root
+    pub hashes: Vec<String>,
+}
+
+pub(crate) async fn get_inclusion_proof(State(state): State<AppState>, Query(query): Query<InclusionQuery>) -> Response {
+    let log = state.transparency_log();
+    let log = log.read().await;
+    let proof = match log.inclusion_proof(query.index, query.size) {
+        Ok(proof) => proof,
+        Err(e) => return Problem::new(ErrorCode::InvalidProofRequest).with_detail(e).into_response(),
+    };
+    let leaf_hash = log.get_entry(query.index).map(leaf_hash).unwrap_or_default();
+    Json(InclusionProof {
+        index: query.index,
+        tree_size: query.size,
+        leaf_hash: hex::encode(leaf_hash),
+        hashes: proof.iter().map(hex::encode).collect(),
+    }).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ConsistencyQuery {
+    from: usize,
+    to: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConsistencyProof {
+    pub from: usize,
+    pub to: usize,
+    pub hashes: Vec<String>,
+}
+
+pub(crate) async fn get_consistency_proof(State(state): State<AppState>, Query(query): Query<ConsistencyQuery>) -> Response {
+    let log = state.transparency_log();
+    let log = log.read().await;
+    match log.consistency_proof(query.from, query.to) {
+        Ok(proof) => Json(ConsistencyProof {
+            from: query.from,
+            to: query.to,
+            hashes: proof.iter().map(hex::encode).collect(),
+        }).into_response(),
+        Err(e) => Problem::new(ErrorCode::InvalidProofRequest).with_detail(e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use benteng_transparency::verify_inclusion;
+
+    fn entry(i: u8) -> LogEntry {
+        LogEntry {
+            v: 1,
+            ten: b"tenant".to_vec(),
+            typ: "verify".to_string(),
+            ts: i as u64,
+            hdr_h: [0; 32],
+            sig_h: [i; 32],
+            kid: "btk/test/key/v1".to_string(),
+            pol: b"policy".to_vec(),
+            rc: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_receipts_before_and_after_checkpoint() {
+        let (public_key, secret_key) = sig::dilithium3_keypair().unwrap();
+        let log = TransparencyLog::new().with_signer(CheckpointSigner {
+            kid: "tlog-1".to_string(),
+            secret_key: Zeroizing::new(secret_key),
+        });
+        let checkpointer = Checkpointer::new(Arc::new(RwLock::new(log)), Duration::from_secs(60), 10);
+
+        let pending = checkpointer.append(entry(0)).await.unwrap();
+        checkpointer.append(entry(1)).await.unwrap();
+        assert!(pending.checkpoint.is_none() && pending.checkpoint_due_ms.is_some());
+
+        let signed = checkpointer.checkpoint().await.unwrap().unwrap();
+        assert!(signed.verify(&public_key));
+        assert!(checkpointer.checkpoint().await.unwrap().is_none());
+
+        // Covered entries get an audit path to the signed root
+        let receipt = checkpointer.receipt(0).await.unwrap();
+        let checkpoint = Checkpoint::try_from(receipt.checkpoint.as_ref().unwrap()).unwrap();
+        let proof: Vec<[u8; 32]> = receipt.inclusion_proof.iter()
+            .map(|h| hex::decode(h).unwrap().try_into().unwrap())
+            .collect();
+        let leaf: [u8; 32] = hex::decode(&receipt.leaf_hash).unwrap().try_into().unwrap();
+        assert!(verify_inclusion(&leaf, 0, checkpoint.tree_size, &proof, &checkpoint.root_hash));
+        assert!(receipt.checkpoint_due_ms.is_none());
+        assert!(checkpointer.receipt(2).await.is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! TLS termination for the edge API
+//! rustls with the X25519MLKEM768 hybrid group preferred, so the transport
+//! around envelopes is post-quantum too. Certificates can be reloaded
+//! without a restart, and client certificates can be bound to tenants.
+
+use crate::config::TlsSettings;
+use axum::middleware::AddExtension;
+use axum::Extension;
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use rustls::crypto::{aws_lc_rs, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::RootCertStore;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+use tower::Layer;
+
+type TlsError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Certificate fingerprint to tenant ID
+type TenantMap = HashMap<[u8; 32], Vec<u8>>;
+
+/// Crypto provider preferring the hybrid group. Classical groups remain as
+/// a fallback unless `hybrid_only` is set.
+pub fn crypto_provider(hybrid_only: bool) -> CryptoProvider {
+    let mut provider = aws_lc_rs::default_provider();
+    provider.kx_groups = if hybrid_only {
+        vec![aws_lc_rs::kx_group::X25519MLKEM768]
+    } else {
+        vec![
+            aws_lc_rs::kx_group::X25519MLKEM768,
+            aws_lc_rs::kx_group::X25519,
+            aws_lc_rs::kx_group::SECP256R1,
+            aws_lc_rs::kx_group::SECP384R1,
+        ]
+    };
+    provider
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("reading {}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("{} holds no certificates", path.display()).into());
+    }
+    Ok(certs)
+}
+
+/// SHA-256 of a certificate's DER encoding
+pub fn fingerprint(cert: &[u8]) -> [u8; 32] {
+    Sha256::digest(cert).into()
+}
+
+fn server_config(settings: &TlsSettings) -> Result<rustls::ServerConfig, TlsError> {
+    let provider = Arc::new(crypto_provider(settings.hybrid_only));
+    let versions: &[&rustls::SupportedProtocolVersion] = if settings.hybrid_only {
+        // The hybrid group only exists in TLS 1.3
+        &[&rustls::version::TLS13]
+    } else {
+        rustls::DEFAULT_VERSIONS
+    };
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(versions)?;
+
+    let builder = match &settings.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(ca_path)? {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if settings.require_client_cert {
+                verifier.build()?
+            } else {
+                verifier.allow_unauthenticated().build()?
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let key = PrivateKeyDer::from_pem_file(&settings.key_path)
+        .map_err(|e| format!("reading {}: {}", settings.key_path.display(), e))?;
+    let mut config = builder.with_single_cert(read_certs(&settings.cert_path)?, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn tenant_map(settings: &TlsSettings) -> Result<TenantMap, TlsError> {
+    let mut tenants = HashMap::new();
+    for binding in &settings.client_certs {
+        let fingerprint: [u8; 32] = hex::decode(&binding.fingerprint)?
+            .try_into()
+            .map_err(|_| format!("client certificate fingerprint must be SHA-256: {}", binding.fingerprint))?;
+        tenants.insert(fingerprint, hex::decode(&binding.tenant_id)?);
+    }
+    Ok(tenants)
+}
+
+/// Client certificate presented on a connection
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    pub fingerprint: [u8; 32],
+    /// Tenant the certificate is bound to, if any
+    pub tenant_id: Option<Vec<u8>>,
+}
+
+/// TLS details of the connection a request arrived on. Only present on
+/// TLS connections.
+#[derive(Debug, Clone, Default)]
+pub struct TlsConnection {
+    pub client_certificate: Option<ClientCertificate>,
+}
+
+impl TlsConnection {
+    /// Whether the connection may act for `tenant_id`. Connections without a
+    /// client certificate are left to envelope signatures; a certificate
+    /// must be bound to the tenant.
+    pub fn authorize(&self, tenant_id: &[u8]) -> Result<(), &'static str> {
+        match &self.client_certificate {
+            None => Ok(()),
+            Some(ClientCertificate { tenant_id: None, .. }) => Err("Client certificate not bound to a tenant"),
+            Some(ClientCertificate { tenant_id: Some(bound), .. }) if bound.as_slice() == tenant_id => Ok(()),
+            Some(_) => Err("Client certificate not valid for tenant"),
+        }
+    }
+}
+
+/// rustls configuration and client certificate bindings, reloadable in place
+pub struct TlsTerminator {
+    settings: TlsSettings,
+    rustls: RustlsConfig,
+    tenants: Arc<RwLock<Arc<TenantMap>>>,
+}
+
+impl TlsTerminator {
+    pub fn new(settings: &TlsSettings) -> Result<Self, TlsError> {
+        Ok(Self {
+            settings: settings.clone(),
+            rustls: RustlsConfig::from_config(Arc::new(server_config(settings)?)),
+            tenants: Arc::new(RwLock::new(Arc::new(tenant_map(settings)?))),
+        })
+    }
+
+    /// Re-read the certificate, key, client CA and bindings. New connections
+    /// use them; established ones keep their session. On error the current
+    /// configuration stays in place.
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let config = server_config(&self.settings)?;
+        let tenants = tenant_map(&self.settings)?;
+        self.rustls.reload_from_config(Arc::new(config));
+        *self.tenants.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(tenants);
+        Ok(())
+    }
+
+    /// Acceptor for `axum_server` that terminates TLS and attaches a
+    /// `TlsConnection` to each request
+    pub fn acceptor(&self) -> TlsConnectionAcceptor {
+        TlsConnectionAcceptor {
+            inner: RustlsAcceptor::new(self.rustls.clone()),
+            tenants: self.tenants.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TlsConnectionAcceptor {
+    inner: RustlsAcceptor,
+    tenants: Arc<RwLock<Arc<TenantMap>>>,
+}
+
+impl<I, S> Accept<I, S> for TlsConnectionAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, TlsConnection>;
+    type Future = Pin<Box<dyn Future<Output = io::Result<(Self::Stream, Self::Service)>> + Send>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let acceptor = self.inner.clone();
+        let tenants = self.tenants.read().unwrap_or_else(|e| e.into_inner()).clone();
+
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+            let client_certificate = stream.get_ref().1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| {
+                    let fingerprint = fingerprint(cert);
+                    ClientCertificate {
+                        fingerprint,
+                        tenant_id: tenants.get(&fingerprint).cloned(),
+                    }
+                });
+            let service = Extension(TlsConnection { client_certificate }).layer(service);
+            Ok((stream, service))
+        })
+    }
+}
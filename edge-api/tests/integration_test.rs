@@ -1,19 +1,41 @@
+// These envelopes are only verified, never decrypted through the KMS
+#![allow(deprecated)]
+
 use benteng_sdk_core::{
     envelope::operations::EnvelopeOps,
     crypto::{kem, sig},
+    ErrorCode,
 };
-use reqwest;
+use benteng_edge_api::config::{ClientKeySeed, ServerConfig};
+use benteng_edge_api::problem::Problem;
+use benteng_edge_api::telemetry::id_hash;
 use serde_json::Value;
 
 #[tokio::test]
 async fn test_verify_endpoint() {
     // Generate keys
     let (kem_pk, _kem_sk) = kem::kyber768_keypair().unwrap();
-    let (_sig_pk, sig_sk) = sig::dilithium3_keypair().unwrap();
-    
+    let (sig_pk, sig_sk) = sig::dilithium3_keypair().unwrap();
+    let (rogue_pk, rogue_sk) = sig::dilithium3_keypair().unwrap();
+
+    // Register the client key with the server
+    let dir = tempfile::tempdir().unwrap();
+    let key_path = dir.path().join("client.pub");
+    std::fs::write(&key_path, &sig_pk).unwrap();
+    let mut config = ServerConfig {
+        bind: "127.0.0.1:0".to_string(),
+        admin_token: Some("test-admin-token".to_string()),
+        ..Default::default()
+    };
+    config.client_keys.keys.push(ClientKeySeed {
+        tenant_id: hex::encode([0xABu8; 16]),
+        kid: "client-1".to_string(),
+        public_key_file: key_path,
+    });
+
     // Create envelope
     let payload = b"Integration test payload";
-    let envelope = EnvelopeOps::encrypt_and_sign(
+    let mut envelope = EnvelopeOps::encrypt_and_sign(
         payload,
         &[0xABu8; 16], // tenant_id
         &[0x12u8; 8],  // policy_id
@@ -22,30 +44,138 @@ async fn test_verify_endpoint() {
         &sig_sk,
         false, // not hybrid
     ).unwrap();
-    
+    EnvelopeOps::sign(&mut envelope, "client-1", &sig_sk).unwrap();
+
     // Serialize to CBOR
     let mut cbor_data = Vec::new();
     ciborium::into_writer(&envelope, &mut cbor_data).unwrap();
-    
-    // Start server in background
-    tokio::spawn(async {
-        benteng_edge_api::run_server().await
-    });
-    
-    // Wait for server to start
-    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-    
+
+    // Start server; it is listening once run_server returns
+    let server = benteng_edge_api::run_server(config).await.unwrap();
+    let base = format!("http://{}", server.local_addr());
+
     // Call verify endpoint
     let client = reqwest::Client::new();
-    let response = client.post("http://localhost:3000/pqc/verify")
+    let response = client.post(format!("{}/pqc/verify", base))
         .body(cbor_data.clone())
         .header("Content-Type", "application/cbor")
         .send()
         .await
         .unwrap();
-    
+
     assert_eq!(response.status(), 200);
-    
+
     let json: Value = response.json().await.unwrap();
     assert_eq!(json["decision"], "OK");
+    assert_eq!(json["claims"]["client_kid"], "client-1");
+
+    // A signature from another key under the registered ID is rejected
+    let mut forged = envelope.clone();
+    EnvelopeOps::sign(&mut forged, "client-1", &rogue_sk).unwrap();
+    let mut forged_data = Vec::new();
+    ciborium::into_writer(&forged, &mut forged_data).unwrap();
+    let response = client.post(format!("{}/pqc/verify", base))
+        .body(forged_data)
+        .header("Content-Type", "application/cbor")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let problem: Problem = response.json().await.unwrap();
+    assert_eq!(problem.code, ErrorCode::InvalidSignature);
+
+    // So is a key that was never registered for the tenant
+    let mut unknown = envelope.clone();
+    EnvelopeOps::sign(&mut unknown, "client-2", &rogue_sk).unwrap();
+    let mut unknown_data = Vec::new();
+    ciborium::into_writer(&unknown, &mut unknown_data).unwrap();
+    let response = client.post(format!("{}/pqc/verify", base))
+        .body(unknown_data.clone())
+        .header("Content-Type", "application/cbor")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+    let problem: Problem = response.json().await.unwrap();
+    assert_eq!((problem.code, problem.status), (ErrorCode::UnknownClientKey, 401));
+
+    // Enrollment needs the admin credential
+    let keys_url = format!("{}/admin/tenants/{}/client-keys", base, hex::encode([0xABu8; 16]));
+    let enroll = serde_json::json!({ "kid": "client-2", "public_key": hex::encode(&rogue_pk) });
+    let response = client.post(&keys_url).json(&enroll).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    let response = client.post(&keys_url)
+        .bearer_auth("test-admin-token")
+        .json(&enroll)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+
+    let response = client.get(&keys_url).bearer_auth("test-admin-token").send().await.unwrap();
+    let json: Value = response.json().await.unwrap();
+    assert_eq!(json["keys"].as_array().unwrap().len(), 2);
+
+    // Re-signing an accepted envelope does not make it new
+    let response = client.post(format!("{}/pqc/verify", base))
+        .body(unknown_data)
+        .header("Content-Type", "application/cbor")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 409);
+
+    // The newly enrolled key verifies
+    let mut fresh = EnvelopeOps::encrypt_and_sign(
+        payload, &[0xABu8; 16], &[0x12u8; 8], "/test/integration", &kem_pk, &rogue_sk, false,
+    ).unwrap();
+    EnvelopeOps::sign(&mut fresh, "client-2", &rogue_sk).unwrap();
+    let response = client.post(format!("{}/pqc/verify", base))
+        .body(fresh.to_cbor().unwrap())
+        .header("Content-Type", "application/cbor")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // A revoked key no longer does
+    let response = client.post(format!("{}/admin/client-keys/client-1/revoke", base))
+        .bearer_auth("test-admin-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.post(format!("{}/pqc/verify", base))
+        .body(cbor_data)
+        .header("Content-Type", "application/cbor")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    // Decisions are broken down by reason, so attacks stand out
+    let tenant = id_hash(&[0xAB; 16]);
+    let response = client.get(format!("{}/metrics", base)).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    let metrics = client.get(format!("{}/metrics", base))
+        .bearer_auth("test-admin-token")
+        .send().await.unwrap()
+        .text().await.unwrap();
+    for line in [
+        format!(r#"benteng_decisions_total{{endpoint="verify",reason="ok",tenant="{}"}} 2"#, tenant),
+        r#"benteng_decisions_total{endpoint="verify",reason="invalid_signature",tenant="unverified"} 1"#.to_string(),
+        r#"benteng_decisions_total{endpoint="verify",reason="unknown_client_key",tenant="unverified"} 2"#.to_string(),
+        format!(r#"benteng_decisions_total{{endpoint="verify",reason="replay_detected",tenant="{}"}} 1"#, tenant),
+        "benteng_signature_verify_seconds_count 4".to_string(),
+        "benteng_replay_cache_entries 2".to_string(),
+        "benteng_tlog_entries 5".to_string(),
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "missing {}\n{}", line, metrics);
+    }
+
+    // Graceful shutdown releases the listener
+    let addr = server.local_addr();
+    server.shutdown().await.unwrap();
+    assert!(client.get(format!("http://{}/health", addr)).send().await.is_err());
 }
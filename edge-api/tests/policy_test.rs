@@ -0,0 +1,195 @@
+use benteng_edge_api::client_keys::{AttestationVerifier, ClientKeyRegistry, Enrollment};
+use benteng_edge_api::config::{ClientKeySeed, ServerConfig};
+use benteng_edge_api::kms_backend::KmsBackend;
+use benteng_edge_api::problem::Problem;
+use benteng_edge_api::{app, AppState};
+use benteng_sdk_core::{
+    crypto::{key_catalog::KeyOptions, kms::{DualControlConfig, DualControlKms, KmsGate}, sig},
+    envelope::operations::EnvelopeOps,
+    policy::Policy,
+    policy_bundle::{PolicyDistributor, SignedPolicyBundle, TrustAnchor},
+    ErrorCode,
+};
+use std::sync::Arc;
+
+const KID: &str = "btk/policy-signer/v1";
+
+fn bundle(version: u64, signing_key: &[u8]) -> SignedPolicyBundle {
+    let policy = Policy {
+        tenant_id: "tenant-000000001".to_string(),
+        policy_id: "policy01".to_string(),
+        path: "/policy".to_string(),
+        required_algs: "kyber+dilithium".to_string(),
+        max_age_ms: 30000,
+        max_body_bytes: 65536,
+        require_device_attest: false,
+        hybrid_allowed: true,
+        replay_ttl_ms: 30000,
+        cache_dek: true,
+        version: version as u32,
+    };
+    SignedPolicyBundle::create(vec![policy], version, 3600, KID.to_string(), signing_key).unwrap()
+}
+
+#[tokio::test]
+async fn test_verify_fails_closed_after_refused_rollback() {
+    let tenant = *b"tenant-000000001";
+    let policy = *b"policy01";
+    let (bundle_pk, bundle_sk) = sig::dilithium3_keypair().unwrap();
+    let (client_pk, client_sk) = sig::dilithium3_keypair().unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundles = dir.path().join("bundles");
+    std::fs::create_dir(&bundles).unwrap();
+    std::fs::write(bundles.join("v2.json"), bundle(2, &bundle_sk).to_json().unwrap()).unwrap();
+    std::fs::write(dir.path().join("bundle.pub"), &bundle_pk).unwrap();
+    std::fs::write(dir.path().join("client.pub"), &client_pk).unwrap();
+
+    let mut config = ServerConfig {
+        bind: "127.0.0.1:0".to_string(),
+        ..Default::default()
+    };
+    config.policy.dir = Some(bundles.clone());
+    config.policy.signers = vec![format!("{}={}", KID, dir.path().join("bundle.pub").display())];
+    config.policy.state_dir = Some(dir.path().join("state"));
+    config.client_keys.keys.push(ClientKeySeed {
+        tenant_id: hex::encode(tenant),
+        kid: "client-1".to_string(),
+        public_key_file: dir.path().join("client.pub"),
+    });
+
+    let kms = DualControlKms::connect(DualControlConfig::default()).unwrap();
+    kms.generate_key(&tenant, &policy, KeyOptions::default()).await.unwrap();
+    let wrapped = kms.generate_dek(None, &policy, &tenant, "/policy").await.unwrap();
+    let sign = || {
+        let mut envelope = EnvelopeOps::encrypt_and_sign_with_dek(
+            b"policy payload", &tenant, &policy, "/policy", &wrapped, &client_sk, false,
+        ).unwrap();
+        EnvelopeOps::sign(&mut envelope, "client-1", &client_sk).unwrap();
+        envelope.to_cbor().unwrap()
+    };
+    let client = reqwest::Client::new();
+
+    let server = benteng_edge_api::run_server(config.clone()).await.unwrap();
+    let url = format!("http://{}/pqc/verify", server.local_addr());
+    assert_eq!(client.post(&url).body(sign()).send().await.unwrap().status(), 200);
+    server.shutdown().await.unwrap();
+
+    // After a restart, an attacker swaps in an older, validly signed bundle
+    std::fs::remove_file(bundles.join("v2.json")).unwrap();
+    std::fs::write(bundles.join("v1.json"), bundle(1, &bundle_sk).to_json().unwrap()).unwrap();
+
+    let server = benteng_edge_api::run_server(config).await.unwrap();
+    let url = format!("http://{}/pqc/verify", server.local_addr());
+    let response = client.post(&url).body(sign()).send().await.unwrap();
+    assert_eq!(response.status(), 503);
+    let problem: Problem = response.json().await.unwrap();
+    assert_eq!(problem.code, ErrorCode::PolicyUnavailable);
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_admin_promotes_shadow_candidate() {
+    let (bundle_pk, bundle_sk) = sig::dilithium3_keypair().unwrap();
+    let mut distributor = PolicyDistributor::with_trust_anchor(TrustAnchor {
+        kid: KID.to_string(),
+        public_key: bundle_pk,
+    });
+    distributor.update_bundle(bundle(1, &bundle_sk)).unwrap();
+    distributor.activate_initial().unwrap();
+    distributor.update_bundle(bundle(2, &bundle_sk)).unwrap();
+    distributor.activate_initial().unwrap();
+
+    let kms = DualControlKms::new(DualControlConfig::default()).unwrap();
+    let state = AppState::new(Arc::new(KmsBackend::Local(Box::new(kms))))
+        .with_policy_distributor(distributor)
+        .with_admin_token("admin-token");
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app(state)).await.unwrap() });
+
+    let client = reqwest::Client::new();
+    let promote = |token: &'static str| client.post(format!("{}/admin/policy/promote", base)).bearer_auth(token).send();
+
+    let response = promote("wrong").await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    let status: serde_json::Value = promote("admin-token").await.unwrap().json().await.unwrap();
+    assert_eq!(status["active_version"], 2);
+    assert!(status["candidate_version"].is_null());
+
+    // Nothing left to promote
+    let response = promote("admin-token").await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+struct AcceptAll;
+
+impl AttestationVerifier for AcceptAll {
+    fn verify(&self, _tenant_id: &[u8], _public_key: &[u8], _evidence: &[u8]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_device_attestation_comes_from_enrollment() {
+    let tenant = *b"tenant-000000001";
+    let policy_id = *b"policy01";
+    let (bundle_pk, bundle_sk) = sig::dilithium3_keypair().unwrap();
+    let mut policy = bundle(1, &bundle_sk).policies.remove(0);
+    policy.require_device_attest = true;
+    let mut distributor = PolicyDistributor::with_trust_anchor(TrustAnchor {
+        kid: KID.to_string(),
+        public_key: bundle_pk,
+    });
+    distributor.update_bundle(SignedPolicyBundle::create(vec![policy], 1, 3600, KID.to_string(), &bundle_sk).unwrap()).unwrap();
+    distributor.activate_initial().unwrap();
+
+    // One key enrolled with evidence nobody checked, one through a verifier
+    let (unchecked_pk, unchecked_sk) = sig::dilithium3_keypair().unwrap();
+    let (attested_pk, attested_sk) = sig::dilithium3_keypair().unwrap();
+    let evidence = Some(b"device evidence".to_vec());
+    let registry = ClientKeyRegistry::temporary().unwrap();
+    registry.enroll(&tenant, Enrollment {
+        kid: Some("unchecked".into()),
+        public_key: unchecked_pk,
+        attestation: evidence.clone(),
+        ..Default::default()
+    }).unwrap();
+    let registry = registry.with_attestation_verifier(Arc::new(AcceptAll));
+    registry.enroll(&tenant, Enrollment {
+        kid: Some("attested".into()),
+        public_key: attested_pk,
+        attestation: evidence,
+        ..Default::default()
+    }).unwrap();
+
+    let kms = DualControlKms::new(DualControlConfig::default()).unwrap();
+    kms.generate_key(&tenant, &policy_id, KeyOptions::default()).await.unwrap();
+    let wrapped = kms.generate_dek(None, &policy_id, &tenant, "/policy").await.unwrap();
+    let state = AppState::new(Arc::new(KmsBackend::Local(Box::new(kms))))
+        .with_policy_distributor(distributor)
+        .with_client_keys(Arc::new(registry));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/pqc/verify", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app(state)).await.unwrap() });
+
+    // The envelope's own attestation hash is the client's to choose
+    let sign = |kid: &str, signing_key: &[u8]| {
+        let mut envelope = EnvelopeOps::encrypt_and_sign_with_dek(
+            b"policy payload", &tenant, &policy_id, "/policy", &wrapped, signing_key, false,
+        ).unwrap();
+        envelope.aad_ext.device_attest_hash = Some(vec![0x42; 32]);
+        EnvelopeOps::sign(&mut envelope, kid, signing_key).unwrap();
+        envelope.to_cbor().unwrap()
+    };
+    let client = reqwest::Client::new();
+
+    let response = client.post(&url).body(sign("unchecked", &unchecked_sk)).send().await.unwrap();
+    assert_eq!(response.status(), 403);
+    let problem: Problem = response.json().await.unwrap();
+    assert_eq!(problem.code, ErrorCode::PolicyViolation);
+
+    let response = client.post(&url).body(sign("attested", &attested_sk)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+}
@@ -0,0 +1,116 @@
+use benteng_edge_api::kms_backend::KmsBackend;
+use benteng_edge_api::problem::{Problem, CONTENT_TYPE};
+use benteng_edge_api::{app, AppState};
+use benteng_sdk_core::{
+    crypto::{key_catalog::KeyOptions, kms::{DualControlConfig, DualControlKms, KmsGate}, sig},
+    envelope::operations::EnvelopeOps,
+    ErrorCode,
+};
+use std::sync::Arc;
+
+async fn problem(response: reqwest::Response) -> Problem {
+    assert_eq!(response.headers()["content-type"], CONTENT_TYPE);
+    let status = response.status().as_u16();
+    let problem: Problem = response.json().await.unwrap();
+    assert_eq!(problem.status, status);
+    problem
+}
+
+#[tokio::test]
+async fn test_errors_are_problem_details_with_stable_codes() {
+    // Decryption needs two approvals this test never gives
+    let tenant = [0xABu8; 16];
+    let policy = [0x12u8; 8];
+    let kms = DualControlKms::connect(DualControlConfig::default()).unwrap();
+    kms.generate_key(&tenant, &policy, KeyOptions::default()).await.unwrap();
+    let wrapped = kms.generate_dek(None, &policy, &tenant, "/problem").await.unwrap();
+    let state = AppState::new(Arc::new(KmsBackend::Local(Box::new(kms))))
+        .with_admin_token("admin-token");
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app(state)).await.unwrap() });
+
+    let (_, sig_sk) = sig::dilithium3_keypair().unwrap();
+    let envelope = EnvelopeOps::encrypt_and_sign_with_dek(
+        b"problem payload", &tenant, &policy, "/problem", &wrapped, &sig_sk, false,
+    ).unwrap();
+    let client = reqwest::Client::new();
+    let post = |route: &str, body: Vec<u8>| client.post(format!("{}/pqc/{}", base, route)).body(body).send();
+
+    // A missing quorum is an authorization failure, not a bad request
+    let problem_details = problem(post("decrypt", envelope.to_cbor().unwrap()).await.unwrap()).await;
+    assert_eq!(problem_details.code, ErrorCode::QuorumNotMet);
+    assert_eq!(problem_details.status, 403);
+    assert_eq!(problem_details.problem_type, "urn:benteng:error:quorum_not_met");
+    assert!(problem_details.detail.is_none());
+
+    // A hostile timestamp neither overflows the age check nor skips quorum
+    let mut future = envelope.clone();
+    future.ts_epoch_ms = u64::MAX;
+    let problem_details = problem(post("decrypt", future.to_cbor().unwrap()).await.unwrap()).await;
+    assert_eq!(problem_details.code, ErrorCode::QuorumNotMet);
+
+    // Each failure has its own code
+    let mut other_tenant = envelope.clone();
+    other_tenant.tenant_id = vec![0xCD; 16];
+    let cases = [
+        ("decrypt", other_tenant.to_cbor().unwrap(), ErrorCode::KeyNotFound),
+        ("verify", b"not cbor".to_vec(), ErrorCode::InvalidEnvelope),
+        ("verify", envelope.to_cbor().unwrap(), ErrorCode::UnknownClientKey),
+    ];
+    for (route, body, code) in cases {
+        let problem_details = problem(post(route, body).await.unwrap()).await;
+        assert_eq!((problem_details.code, problem_details.status), (code, code.http_status()));
+    }
+
+    let response = client.get(format!("{}/tlog/entry/99", base)).send().await.unwrap();
+    assert_eq!(problem(response).await.code, ErrorCode::LogEntryNotFound);
+
+    // Decisions are counted by code; tenants that never authenticated get no label
+    let metrics = client.get(format!("{}/metrics", base))
+        .bearer_auth("admin-token")
+        .send().await.unwrap()
+        .text().await.unwrap();
+    for line in [
+        r#"benteng_decisions_total{endpoint="decrypt",reason="quorum_not_met",tenant="unverified"} 2"#,
+        r#"benteng_decisions_total{endpoint="decrypt",reason="key_not_found",tenant="unverified"} 1"#,
+        r#"benteng_decisions_total{endpoint="verify",reason="invalid_envelope",tenant="unknown"} 1"#,
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "missing {}\n{}", line, metrics);
+    }
+}
+
+#[tokio::test]
+async fn test_decrypt_refuses_replays_and_stale_envelopes() {
+    let tenant = [0xABu8; 16];
+    let policy = [0x12u8; 8];
+    let kms = DualControlKms::connect(DualControlConfig {
+        require_quorum: false,
+        ..Default::default()
+    }).unwrap();
+    kms.generate_key(&tenant, &policy, KeyOptions::default()).await.unwrap();
+    let wrapped = kms.generate_dek(None, &policy, &tenant, "/replay").await.unwrap();
+    let state = AppState::new(Arc::new(KmsBackend::Local(Box::new(kms))));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/pqc/decrypt", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app(state)).await.unwrap() });
+
+    let (_, sig_sk) = sig::dilithium3_keypair().unwrap();
+    let envelope = EnvelopeOps::encrypt_and_sign_with_dek(
+        b"replayed payload", &tenant, &policy, "/replay", &wrapped, &sig_sk, false,
+    ).unwrap();
+    let client = reqwest::Client::new();
+    let post = |body: Vec<u8>| client.post(&url).body(body).send();
+
+    assert_eq!(post(envelope.to_cbor().unwrap()).await.unwrap().status(), 200);
+    let response = post(envelope.to_cbor().unwrap()).await.unwrap();
+    assert_eq!(problem(response).await.code, ErrorCode::ReplayDetected);
+
+    // Without a policy, the default 30s age limit applies
+    let mut stale = envelope.clone();
+    stale.ts_epoch_ms -= 60_000;
+    let response = post(stale.to_cbor().unwrap()).await.unwrap();
+    assert_eq!(problem(response).await.code, ErrorCode::EnvelopeExpired);
+}
@@ -0,0 +1,141 @@
+// These envelopes are only verified, never decrypted through the KMS
+#![allow(deprecated)]
+
+use benteng_edge_api::config::{ClientKeySeed, RateLimit, RateLimitOverride, ServerConfig};
+use benteng_edge_api::telemetry::id_hash;
+use benteng_sdk_core::{
+    envelope::operations::EnvelopeOps,
+    crypto::{kem, sig},
+};
+
+fn header(response: &reqwest::Response, name: &str) -> Option<String> {
+    response.headers().get(name).map(|value| value.to_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn test_limits_per_tenant_route_and_network() {
+    let dir = tempfile::tempdir().unwrap();
+    let (sig_pk, sig_sk) = sig::dilithium3_keypair().unwrap();
+    let client_pub_path = dir.path().join("client.pub");
+    std::fs::write(&client_pub_path, &sig_pk).unwrap();
+
+    let mut config = ServerConfig {
+        bind: "127.0.0.1:0".to_string(),
+        admin_token: Some("test-admin-token".to_string()),
+        ..Default::default()
+    };
+    config.rate_limit.burst = 2.0;
+    config.rate_limit.per_sec = 0.01;
+    config.rate_limit.overrides.push(RateLimitOverride {
+        tenant_id: None,
+        route: Some("decrypt".to_string()),
+        burst: 1.0,
+        per_sec: 0.01,
+    });
+    config.client_keys.keys.push(ClientKeySeed {
+        tenant_id: hex::encode([0xABu8; 16]),
+        kid: "client-1".to_string(),
+        public_key_file: client_pub_path.clone(),
+    });
+    let server = benteng_edge_api::run_server(config).await.unwrap();
+    let base = format!("http://{}", server.local_addr());
+
+    let (kem_pk, _) = kem::kyber768_keypair().unwrap();
+    let envelope = |tenant: [u8; 16]| {
+        let mut envelope = EnvelopeOps::encrypt_and_sign(
+            b"limited payload", &tenant, &[0x12u8; 8], "/limited", &kem_pk, &sig_sk, false,
+        ).unwrap();
+        EnvelopeOps::sign(&mut envelope, "client-1", &sig_sk).unwrap();
+        envelope.to_cbor().unwrap()
+    };
+    let client = reqwest::Client::new();
+    let post = |route: &str, body: Vec<u8>| {
+        client.post(format!("{}/pqc/{}", base, route))
+            .body(body)
+            .header("Content-Type", "application/cbor")
+            .send()
+    };
+
+    // Forged requests are refused before they reach the tenant's bucket
+    let (_, rogue_sk) = sig::dilithium3_keypair().unwrap();
+    for _ in 0..3 {
+        let mut forged = EnvelopeOps::encrypt_and_sign(
+            b"limited payload", &[0xAB; 16], &[0x12u8; 8], "/limited", &kem_pk, &rogue_sk, false,
+        ).unwrap();
+        EnvelopeOps::sign(&mut forged, "client-1", &rogue_sk).unwrap();
+        let response = post("verify", forged.to_cbor().unwrap()).await.unwrap();
+        assert_eq!(response.status(), 401);
+        assert!(header(&response, "ratelimit-limit").is_none());
+    }
+
+    // The tenant's verify bucket holds two tokens
+    for remaining in ["1", "0"] {
+        let response = post("verify", envelope([0xAB; 16])).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(header(&response, "ratelimit-limit").as_deref(), Some("2"));
+        assert_eq!(header(&response, "ratelimit-remaining").as_deref(), Some(remaining));
+        assert!(header(&response, "retry-after").is_none());
+    }
+    let response = post("verify", envelope([0xAB; 16])).await.unwrap();
+    assert_eq!(response.status(), 429);
+    assert_eq!(header(&response, "ratelimit-remaining").as_deref(), Some("0"));
+    let retry_after: u64 = header(&response, "retry-after").unwrap().parse().unwrap();
+    assert!((90..=100).contains(&retry_after), "{}", retry_after);
+
+    // A tenant sharing the first four bytes has its own bucket
+    let response = post("verify", envelope([0xAB, 0xAB, 0xAB, 0xAB, 0xCD, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0])).await.unwrap();
+    assert_ne!(response.status(), 429);
+
+    // Decrypt also checks the signature before the tenant's bucket
+    let mut forged = EnvelopeOps::encrypt_and_sign(
+        b"limited payload", &[0xAB; 16], &[0x12u8; 8], "/limited", &kem_pk, &rogue_sk, false,
+    ).unwrap();
+    EnvelopeOps::sign(&mut forged, "client-1", &rogue_sk).unwrap();
+    for _ in 0..2 {
+        let response = post("decrypt", forged.to_cbor().unwrap()).await.unwrap();
+        assert_eq!(response.status(), 401);
+        assert!(header(&response, "ratelimit-limit").is_none());
+    }
+
+    // Decrypt is limited separately, here to one token
+    let response = post("decrypt", envelope([0xAB; 16])).await.unwrap();
+    assert_ne!(response.status(), 429);
+    assert_eq!(header(&response, "ratelimit-limit").as_deref(), Some("1"));
+    let response = post("decrypt", envelope([0xAB; 16])).await.unwrap();
+    assert_eq!(response.status(), 429);
+    assert!(header(&response, "retry-after").is_some());
+
+    let metrics = client.get(format!("{}/metrics", base))
+        .bearer_auth("test-admin-token")
+        .send().await.unwrap()
+        .text().await.unwrap();
+    let tenant = id_hash(&[0xAB; 16]);
+    for line in [
+        format!(r#"benteng_rate_limit_rejections_total{{endpoint="verify",tenant="{}"}} 1"#, tenant),
+        format!(r#"benteng_rate_limit_rejections_total{{endpoint="decrypt",tenant="{}"}} 1"#, tenant),
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "missing {}\n{}", line, metrics);
+    }
+    server.shutdown().await.unwrap();
+
+    // Client networks share a bucket across tenants
+    let mut config = ServerConfig {
+        bind: "127.0.0.1:0".to_string(),
+        ..Default::default()
+    };
+    config.rate_limit.ip = Some(RateLimit { burst: 1.0, per_sec: 0.01 });
+    config.rate_limit.client_ip_header = Some("x-forwarded-for".to_string());
+    let server = benteng_edge_api::run_server(config).await.unwrap();
+    let base = format!("http://{}", server.local_addr());
+    let from = |ip: &str, tenant: [u8; 16]| {
+        client.post(format!("{}/pqc/verify", base))
+            .body(envelope(tenant))
+            .header("Content-Type", "application/cbor")
+            .header("x-forwarded-for", format!("{}, 10.0.0.1", ip))
+            .send()
+    };
+    assert_ne!(from("203.0.113.5", [0x01; 16]).await.unwrap().status(), 429);
+    assert_eq!(from("203.0.113.9", [0x02; 16]).await.unwrap().status(), 429);
+    assert_ne!(from("198.51.100.1", [0x03; 16]).await.unwrap().status(), 429);
+    server.shutdown().await.unwrap();
+}
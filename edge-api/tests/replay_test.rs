@@ -0,0 +1,121 @@
+// These envelopes are only verified, never decrypted through the KMS
+#![allow(deprecated)]
+
+use benteng_edge_api::config::{ClientKeySeed, ServerConfig};
+use benteng_sdk_core::{
+    envelope::operations::EnvelopeOps,
+    crypto::{kem, sig},
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+type Keys = Arc<Mutex<HashMap<Vec<u8>, Instant>>>;
+
+/// Next RESP command as its arguments, or `None` once the client hangs up
+async fn read_command(reader: &mut BufReader<TcpStream>) -> Option<Vec<Vec<u8>>> {
+    let mut line = String::new();
+    reader.read_line(&mut line).await.ok()?;
+    let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).await.ok()?;
+        let len: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+        let mut arg = vec![0u8; len + 2];
+        reader.read_exact(&mut arg).await.ok()?;
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Some(args)
+}
+
+/// Just enough of Redis for `SET key value NX PX ttl`
+async fn serve_redis(stream: TcpStream, keys: Keys) {
+    let mut reader = BufReader::new(stream);
+    while let Some(args) = read_command(&mut reader).await {
+        let reply: &[u8] = match args.iter().map(|a| a.to_ascii_uppercase()).collect::<Vec<_>>().as_slice() {
+            [set, _, _, nx, px, _] if set == b"SET" && nx == b"NX" && px == b"PX" => {
+                let ttl_ms: u64 = String::from_utf8_lossy(&args[5]).parse().unwrap();
+                let now = Instant::now();
+                let mut keys = keys.lock().unwrap();
+                match keys.get(&args[1]) {
+                    Some(expires) if *expires > now => b"$-1\r\n",
+                    _ => {
+                        keys.insert(args[1].clone(), now + Duration::from_millis(ttl_ms));
+                        b"+OK\r\n"
+                    }
+                }
+            }
+            [ping] if ping == b"PING" => b"+PONG\r\n",
+            _ => b"-ERR unknown command\r\n",
+        };
+        if reader.get_mut().write_all(reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_replicas_share_replay_state() {
+    let keys = Keys::default();
+    let redis = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let redis_url = format!("redis://{}/", redis.local_addr().unwrap());
+    let held = keys.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = redis.accept().await {
+            tokio::spawn(serve_redis(stream, held.clone()));
+        }
+    });
+
+    let dir = tempfile::tempdir().unwrap();
+    let (sig_pk, sig_sk) = sig::dilithium3_keypair().unwrap();
+    let client_pub_path = dir.path().join("client.pub");
+    std::fs::write(&client_pub_path, &sig_pk).unwrap();
+
+    let mut replicas = Vec::new();
+    for _ in 0..2 {
+        let mut config = ServerConfig {
+            bind: "127.0.0.1:0".to_string(),
+            ..Default::default()
+        };
+        config.replay.redis_url = Some(redis_url.clone());
+        config.client_keys.keys.push(ClientKeySeed {
+            tenant_id: hex::encode([0xABu8; 16]),
+            kid: "client-1".to_string(),
+            public_key_file: client_pub_path.clone(),
+        });
+        replicas.push(benteng_edge_api::run_server(config).await.unwrap());
+    }
+
+    let (kem_pk, _) = kem::kyber768_keypair().unwrap();
+    let mut envelope = EnvelopeOps::encrypt_and_sign(
+        b"replayed payload", &[0xABu8; 16], &[0x12u8; 8], "/replay", &kem_pk, &sig_sk, false,
+    ).unwrap();
+    EnvelopeOps::sign(&mut envelope, "client-1", &sig_sk).unwrap();
+    let body = envelope.to_cbor().unwrap();
+
+    let client = reqwest::Client::new();
+    let verify = |replica: usize| {
+        let url = format!("http://{}/pqc/verify", replicas[replica].local_addr());
+        let request = client.post(url).body(body.clone()).header("Content-Type", "application/cbor");
+        async move { request.send().await.unwrap().status() }
+    };
+
+    // Once one replica has accepted the envelope, neither accepts it again
+    assert_eq!(verify(0).await, 200);
+    assert_eq!(verify(1).await, 409);
+    assert_eq!(verify(0).await, 409);
+
+    // The key names the route, tenant and nonce and expires with the default window
+    let key = format!("benteng:replay:verify:{}:{}", hex::encode([0xABu8; 16]), hex::encode(&envelope.nonce));
+    let expires = keys.lock().unwrap()[key.as_bytes()];
+    let ttl = expires.saturating_duration_since(Instant::now());
+    assert!(ttl > Duration::from_secs(290) && ttl <= Duration::from_secs(300), "{:?}", ttl);
+
+    for replica in replicas {
+        replica.shutdown().await.unwrap();
+    }
+}
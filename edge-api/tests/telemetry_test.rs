@@ -0,0 +1,135 @@
+use axum::{body::Bytes, extract::State, routing::post, Router};
+use benteng_edge_api::client_keys::ClientKeyRegistry;
+use benteng_edge_api::config::{ClientKeySeed, ClientKeySettings, TelemetrySettings};
+use benteng_edge_api::kms_backend::KmsBackend;
+use benteng_edge_api::telemetry::{id_hash, Telemetry};
+use benteng_edge_api::{app, AppState};
+use benteng_sdk_core::{
+    crypto::{key_catalog::KeyOptions, kms::{DualControlConfig, DualControlKms, KmsGate}, sig},
+    envelope::operations::EnvelopeOps,
+};
+use opentelemetry_proto::tonic::collector::trace::v1::{ExportTraceServiceRequest, ExportTraceServiceResponse};
+use opentelemetry_proto::tonic::common::v1::any_value::Value as AnyValue;
+use opentelemetry_proto::tonic::trace::v1::Span;
+use prost::Message;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::prelude::*;
+
+type Collected = Arc<Mutex<Vec<Span>>>;
+
+/// OTLP/HTTP collector that keeps every span it is sent
+async fn collect(State(spans): State<Collected>, body: Bytes) -> Vec<u8> {
+    let request = ExportTraceServiceRequest::decode(body).unwrap();
+    let mut spans = spans.lock().unwrap();
+    for resource in request.resource_spans {
+        for scope in resource.scope_spans {
+            spans.extend(scope.spans);
+        }
+    }
+    ExportTraceServiceResponse::default().encode_to_vec()
+}
+
+fn attribute(span: &Span, key: &str) -> Option<String> {
+    span.attributes.iter()
+        .find(|kv| kv.key == key)
+        .and_then(|kv| kv.value.as_ref()?.value.as_ref())
+        .map(|value| match value {
+            AnyValue::StringValue(s) => s.clone(),
+            other => format!("{:?}", other),
+        })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_stage_spans_are_exported_in_the_callers_trace() {
+    let collected = Collected::default();
+    let collector = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let collector_addr = collector.local_addr().unwrap();
+    let routes = Router::new().route("/v1/traces", post(collect)).with_state(collected.clone());
+    tokio::spawn(async move { axum::serve(collector, routes).await.unwrap() });
+
+    let telemetry = Telemetry::init(&TelemetrySettings {
+        otlp_endpoint: Some(format!("http://{}/v1/traces", collector_addr)),
+        ..Default::default()
+    }).unwrap().unwrap();
+    tracing_subscriber::registry().with(telemetry.layer()).init();
+
+    // A KMS the test can also encrypt to
+    let tenant = [0xABu8; 16];
+    let policy = [0x12u8; 8];
+    let kms = DualControlKms::connect(DualControlConfig {
+        require_quorum: false,
+        ..Default::default()
+    }).unwrap();
+    kms.generate_key(&tenant, &policy, KeyOptions::default()).await.unwrap();
+    let wrapped = kms.generate_dek(None, &policy, &tenant, "/otel").await.unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let (sig_pk, sig_sk) = sig::dilithium3_keypair().unwrap();
+    let client_pub_path = dir.path().join("client.pub");
+    std::fs::write(&client_pub_path, &sig_pk).unwrap();
+    let registry = ClientKeyRegistry::open(&ClientKeySettings {
+        store_path: None,
+        keys: vec![ClientKeySeed {
+            tenant_id: hex::encode(tenant),
+            kid: "client-1".to_string(),
+            public_key_file: client_pub_path,
+        }],
+    }).unwrap();
+    let state = AppState::new(Arc::new(KmsBackend::Local(Box::new(kms))))
+        .with_client_keys(Arc::new(registry));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app(state)).await.unwrap() });
+
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let parent_id = "00f067aa0ba902b7";
+    let mut envelope = EnvelopeOps::encrypt_and_sign_with_dek(
+        b"traced payload", &tenant, &policy, "/otel", &wrapped, &sig_sk, false,
+    ).unwrap();
+    EnvelopeOps::sign(&mut envelope, "client-1", &sig_sk).unwrap();
+    let client = reqwest::Client::new();
+    for endpoint in ["verify", "decrypt"] {
+        let response = client.post(format!("{}/pqc/{}", base, endpoint))
+            .header("traceparent", format!("00-{}-{}-01", trace_id, parent_id))
+            .body(envelope.to_cbor().unwrap())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200, "{}", response.text().await.unwrap());
+    }
+
+    tokio::task::spawn_blocking(move || telemetry.force_flush()).await.unwrap().unwrap();
+    let spans = collected.lock().unwrap().clone();
+
+    // Every stage is exported, all within the caller's trace
+    for name in [
+        "request", "cbor_decode", "decision", "rate_limit", "signature", "replay_check",
+        "policy", "log_append", "kms_decrypt", "kms.k1", "kms.k2",
+    ] {
+        let span = spans.iter().find(|span| span.name == name)
+            .unwrap_or_else(|| panic!("no {} span", name));
+        assert_eq!(hex::encode(&span.trace_id), trace_id, "{} span in another trace", name);
+    }
+    let requests: Vec<&Span> = spans.iter().filter(|span| span.name == "request").collect();
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|span| hex::encode(&span.parent_span_id) == parent_id));
+
+    // Tenants and policies appear as hashes only
+    let decisions: Vec<&Span> = spans.iter().filter(|span| span.name == "decision").collect();
+    assert_eq!(decisions.len(), 2);
+    for span in decisions {
+        assert_eq!(attribute(span, "tenant.hash"), Some(id_hash(&tenant)));
+        assert_eq!(attribute(span, "policy.hash"), Some(id_hash(&policy)));
+        assert_eq!(attribute(span, "decision").as_deref(), Some("ok"));
+    }
+
+    // No key material in any attribute
+    let secrets = [hex::encode(&wrapped.dek[..]), hex::encode(&sig_pk[..32]), hex::encode(tenant)];
+    for span in &spans {
+        for kv in &span.attributes {
+            let value = format!("{:?}", kv.value);
+            assert!(secrets.iter().all(|secret| !value.contains(secret)), "{} leaks {}", span.name, kv.key);
+        }
+    }
+}
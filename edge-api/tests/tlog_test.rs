@@ -0,0 +1,138 @@
+// These envelopes are only verified, never decrypted through the KMS
+#![allow(deprecated)]
+
+use benteng_edge_api::config::{ClientKeySeed, ServerConfig};
+use benteng_edge_api::tlog::{CheckpointView, ConsistencyProof, EntryResponse, InclusionProof, LogReceipt};
+use benteng_sdk_core::{
+    envelope::operations::EnvelopeOps,
+    crypto::{kem, sig},
+};
+use benteng_transparency::{leaf_hash, verify_consistency, verify_inclusion, Checkpoint};
+use serde_json::Value;
+
+fn hash(hex_hash: &str) -> [u8; 32] {
+    hex::decode(hex_hash).unwrap().try_into().unwrap()
+}
+
+fn hashes(hex_hashes: &[String]) -> Vec<[u8; 32]> {
+    hex_hashes.iter().map(|h| hash(h)).collect()
+}
+
+#[tokio::test]
+async fn test_receipts_prove_inclusion() {
+    let dir = tempfile::tempdir().unwrap();
+    let (log_pk, log_sk) = sig::dilithium3_keypair().unwrap();
+    let log_key_path = dir.path().join("tlog.key");
+    std::fs::write(&log_key_path, &log_sk).unwrap();
+
+    let (sig_pk, sig_sk) = sig::dilithium3_keypair().unwrap();
+    let client_pub_path = dir.path().join("client.pub");
+    std::fs::write(&client_pub_path, &sig_pk).unwrap();
+
+    let mut config = ServerConfig {
+        bind: "127.0.0.1:0".to_string(),
+        ..Default::default()
+    };
+    config.transparency.signing_key_file = Some(log_key_path);
+    config.transparency.key_id = Some("tlog-test".to_string());
+    config.transparency.checkpoint_interval_ms = 60_000;
+    config.transparency.checkpoint_batch = 2;
+    config.client_keys.keys.push(ClientKeySeed {
+        tenant_id: hex::encode([0xABu8; 16]),
+        kid: "client-1".to_string(),
+        public_key_file: client_pub_path,
+    });
+    let server = benteng_edge_api::run_server(config).await.unwrap();
+    let base = format!("http://{}", server.local_addr());
+    let client = reqwest::Client::new();
+
+    let (kem_pk, _) = kem::kyber768_keypair().unwrap();
+    let verify = || async {
+        let mut envelope = EnvelopeOps::encrypt_and_sign(
+            b"logged payload", &[0xABu8; 16], &[0x12u8; 8], "/tlog", &kem_pk, &sig_sk, false,
+        ).unwrap();
+        EnvelopeOps::sign(&mut envelope, "client-1", &sig_sk).unwrap();
+        let response = client.post(format!("{}/pqc/verify", base))
+            .body(envelope.to_cbor().unwrap())
+            .header("Content-Type", "application/cbor")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let json: Value = response.json().await.unwrap();
+        serde_json::from_value::<LogReceipt>(json["receipt"].clone()).unwrap()
+    };
+
+    let checkpoint = || async {
+        let view: CheckpointView = client.get(format!("{}/tlog/checkpoint", base))
+            .send().await.unwrap()
+            .json().await.unwrap();
+        (Checkpoint::try_from(&view).unwrap(), view)
+    };
+
+    // Checkpoints are batched, so the receipt promises one
+    let first = verify().await;
+    assert!(first.checkpoint.is_none());
+    assert!(first.checkpoint_due_ms.unwrap() > chrono::Utc::now().timestamp_millis() as u64);
+
+    // Asking for the checkpoint before any is due signs one
+    let (first_checkpoint, _) = checkpoint().await;
+    assert_eq!(first_checkpoint.kid, "tlog-test");
+    assert!(first_checkpoint.verify(&log_pk));
+    assert!(first_checkpoint.tree_size > first.leaf_index);
+
+    // The logged entry hashes to the receipt's leaf
+    let entry: EntryResponse = client.get(format!("{}/tlog/entry/{}", base, first.leaf_index))
+        .send().await.unwrap()
+        .json().await.unwrap();
+    assert_eq!(hex::encode(leaf_hash(&entry.entry)), first.leaf_hash);
+    assert_eq!(entry.entry.ten, vec![0xABu8; 16]);
+
+    // And is included under the signed root
+    let proof: InclusionProof = client.get(format!(
+        "{}/tlog/proof/inclusion?index={}&size={}", base, first.leaf_index, first_checkpoint.tree_size,
+    )).send().await.unwrap().json().await.unwrap();
+    assert!(verify_inclusion(
+        &hash(&first.leaf_hash),
+        first.leaf_index,
+        first_checkpoint.tree_size,
+        &hashes(&proof.hashes),
+        &first_checkpoint.root_hash,
+    ));
+
+    // A full batch is checkpointed without waiting for the interval
+    verify().await;
+    let second = verify().await;
+    assert!(second.leaf_index > first.leaf_index);
+    let mut second_checkpoint = checkpoint().await;
+    for _ in 0..100 {
+        if second_checkpoint.0.tree_size > second.leaf_index {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        second_checkpoint = checkpoint().await;
+    }
+    let (second_checkpoint, _) = second_checkpoint;
+    assert_eq!(second_checkpoint.tree_size, second.leaf_index + 1);
+    assert!(second_checkpoint.verify(&log_pk));
+
+    // Later checkpoints extend earlier ones
+    let proof: ConsistencyProof = client.get(format!(
+        "{}/tlog/proof/consistency?from={}&to={}", base, first_checkpoint.tree_size, second_checkpoint.tree_size,
+    )).send().await.unwrap().json().await.unwrap();
+    assert!(verify_consistency(
+        first_checkpoint.tree_size,
+        second_checkpoint.tree_size,
+        &first_checkpoint.root_hash,
+        &second_checkpoint.root_hash,
+        &hashes(&proof.hashes),
+    ));
+
+    // Proofs beyond the log are refused
+    let response = client.get(format!("{}/tlog/proof/inclusion?index=0&size=1000", base)).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client.get(format!("{}/tlog/entry/1000", base)).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,169 @@
+// These envelopes are only verified, never decrypted through the KMS
+#![allow(deprecated)]
+
+use benteng_edge_api::config::{ClientCertTenant, ClientKeySeed, ServerConfig, TlsSettings};
+use benteng_edge_api::tls::{crypto_provider, fingerprint};
+use benteng_sdk_core::{
+    envelope::operations::EnvelopeOps,
+    crypto::{kem, sig},
+};
+use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, RootCertStore};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_rustls::TlsConnector;
+
+struct Response {
+    status: u16,
+    body: String,
+    kx_group: String,
+    server_cert: Vec<u8>,
+}
+
+/// One HTTP/1.1 request over a fresh TLS connection
+async fn request(addr: SocketAddr, config: Arc<ClientConfig>, method: &str, path: &str, body: &[u8]) -> Response {
+    let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let server_name = ServerName::try_from("localhost").unwrap();
+    let mut stream = TlsConnector::from(config).connect(server_name, tcp).await.unwrap();
+
+    let head = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/cbor\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        method, path, body.len(),
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(body).await.unwrap();
+
+    let mut raw = Vec::new();
+    // The server may close without close_notify once the response is sent
+    let _ = stream.read_to_end(&mut raw).await;
+    let raw = String::from_utf8_lossy(&raw).into_owned();
+    let (_, connection) = stream.get_ref();
+
+    Response {
+        status: raw.split(' ').nth(1).unwrap().parse().unwrap(),
+        body: raw.split_once("\r\n\r\n").unwrap().1.to_string(),
+        kx_group: format!("{:?}", connection.negotiated_key_exchange_group().unwrap().name()),
+        server_cert: connection.peer_certificates().unwrap()[0].to_vec(),
+    }
+}
+
+fn client_config(roots: &RootCertStore, client: Option<(&rcgen::Certificate, &KeyPair)>) -> Arc<ClientConfig> {
+    // Offer nothing but the hybrid group, so a handshake proves it was used
+    let builder = ClientConfig::builder_with_provider(Arc::new(crypto_provider(true)))
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_root_certificates(roots.clone());
+    let config = match client {
+        Some((cert, key)) => builder.with_client_auth_cert(
+            vec![cert.der().clone()],
+            PrivateKeyDer::try_from(key.serialize_der()).unwrap(),
+        ).unwrap(),
+        None => builder.with_no_client_auth(),
+    };
+    Arc::new(config)
+}
+
+fn server_cert(ca: &rcgen::Certificate, ca_key: &KeyPair) -> (rcgen::Certificate, KeyPair) {
+    let key = KeyPair::generate().unwrap();
+    let cert = CertificateParams::new(vec!["localhost".to_string()]).unwrap()
+        .signed_by(&key, ca, ca_key)
+        .unwrap();
+    (cert, key)
+}
+
+#[tokio::test]
+async fn test_hybrid_tls_with_client_certificates() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+
+    let (server, server_key) = server_cert(&ca, &ca_key);
+    let client_key = KeyPair::generate().unwrap();
+    let mut client_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+    let client = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+    let cert_path = dir.path().join("cert.pem");
+    let key_path = dir.path().join("key.pem");
+    let ca_path = dir.path().join("ca.pem");
+    std::fs::write(&cert_path, server.pem()).unwrap();
+    std::fs::write(&key_path, server_key.serialize_pem()).unwrap();
+    std::fs::write(&ca_path, ca.pem()).unwrap();
+
+    let (sig_pk, sig_sk) = sig::dilithium3_keypair().unwrap();
+    let client_pub_path = dir.path().join("client.pub");
+    std::fs::write(&client_pub_path, &sig_pk).unwrap();
+
+    let tenant = [0xABu8; 16];
+    let mut config = ServerConfig {
+        bind: "127.0.0.1:0".to_string(),
+        tls: Some(TlsSettings {
+            cert_path: cert_path.clone(),
+            key_path: key_path.clone(),
+            hybrid_only: false,
+            client_ca_path: Some(ca_path),
+            require_client_cert: false,
+            client_certs: vec![ClientCertTenant {
+                fingerprint: hex::encode(fingerprint(client.der())),
+                tenant_id: hex::encode(tenant),
+            }],
+        }),
+        ..Default::default()
+    };
+    for tenant_id in [tenant, [0xCDu8; 16]] {
+        config.client_keys.keys.push(ClientKeySeed {
+            tenant_id: hex::encode(tenant_id),
+            kid: format!("client-{}", hex::encode(&tenant_id[..1])),
+            public_key_file: client_pub_path.clone(),
+        });
+    }
+    let handle = benteng_edge_api::run_server(config).await.unwrap();
+    let addr = handle.local_addr();
+
+    let mut roots = RootCertStore::empty();
+    roots.add(CertificateDer::from(ca.der().to_vec())).unwrap();
+    let with_cert = client_config(&roots, Some((&client, &client_key)));
+
+    // The handshake settles on the hybrid group
+    let response = request(addr, with_cert.clone(), "GET", "/health", b"").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.kx_group, "X25519MLKEM768");
+
+    // A client certificate may act for its own tenant only
+    let (kem_pk, _) = kem::kyber768_keypair().unwrap();
+    let envelope_for = |tenant_id: [u8; 16]| {
+        let mut envelope = EnvelopeOps::encrypt_and_sign(
+            b"tls payload", &tenant_id, &[0x12u8; 8], "/tls", &kem_pk, &sig_sk, false,
+        ).unwrap();
+        EnvelopeOps::sign(&mut envelope, &format!("client-{}", hex::encode(&tenant_id[..1])), &sig_sk).unwrap();
+        envelope.to_cbor().unwrap()
+    };
+    let response = request(addr, with_cert.clone(), "POST", "/pqc/verify", &envelope_for(tenant)).await;
+    assert_eq!(response.status, 200, "{}", response.body);
+    let other_tenant = envelope_for([0xCDu8; 16]);
+    let response = request(addr, with_cert.clone(), "POST", "/pqc/verify", &other_tenant).await;
+    assert_eq!(response.status, 403);
+    assert!(response.body.contains("Client certificate not valid for tenant"));
+
+    // mTLS is optional; without a certificate only the envelope signature counts
+    let without_cert = client_config(&roots, None);
+    let response = request(addr, without_cert, "POST", "/pqc/verify", &other_tenant).await;
+    assert_eq!(response.status, 200, "{}", response.body);
+
+    // A new certificate is picked up on reload without a restart
+    let (renewed, renewed_key) = server_cert(&ca, &ca_key);
+    std::fs::write(&cert_path, renewed.pem()).unwrap();
+    std::fs::write(&key_path, renewed_key.serialize_pem()).unwrap();
+    assert_eq!(request(addr, with_cert.clone(), "GET", "/health", b"").await.server_cert, server.der().to_vec());
+    handle.reload_tls().unwrap();
+    let response = request(addr, with_cert, "GET", "/health", b"").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.server_cert, renewed.der().to_vec());
+
+    handle.shutdown().await.unwrap();
+}
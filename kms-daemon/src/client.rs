@@ -0,0 +1,229 @@
+//! `KmsGate` backed by a remote KMS daemon
+
+use crate::protocol::{
+    from_cbor, to_cbor, Identity, KmsRequest, KmsResponse, PeerKey, SealedResponse, SealedShare,
+    SignedRequest, CONTENT_TYPE, KMS_PATH,
+};
+
+use benteng_sdk_core::crypto::approval::{PendingRequest, RequestStatus, RequestSubmission};
+use benteng_sdk_core::crypto::kms::{DualControlConfig, KmsGate, RewrapRequest, RewrappedDek, WrappedDek};
+use benteng_sdk_core::crypto::quorum::{QuorumApproval, QuorumDenial};
+use benteng_sdk_core::crypto::shamir::CustodianShare;
+use benteng_sdk_core::BentengError;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+type Result<T> = std::result::Result<T, BentengError>;
+
+/// Talks to a KMS daemon, authenticating as `identity` and accepting only
+/// responses signed by `server`
+pub struct RemoteKms {
+    url: String,
+    identity: Identity,
+    server: PeerKey,
+    client: reqwest::Client,
+}
+
+impl RemoteKms {
+    /// Client for the daemon at `endpoint` (e.g. `http://10.0.0.5:7443`).
+    /// Each call is bounded by `config.timeout_ms`.
+    pub fn new(
+        endpoint: &str,
+        config: &DualControlConfig,
+        identity: Identity,
+        server: PeerKey,
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .map_err(|e| BentengError::KmsError(format!("KMS client: {}", e)))?;
+
+        Ok(Self {
+            url: format!("{}{}", endpoint.trim_end_matches('/'), KMS_PATH),
+            identity,
+            server,
+            client,
+        })
+    }
+
+    async fn call(&self, request: KmsRequest) -> Result<KmsResponse> {
+        let ts_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let (signed, reply_kem_sk) = SignedRequest::sign(&request, &self.identity, ts_ms)?;
+
+        let response = self.client.post(&self.url)
+            .headers(crate::trace::current_context())
+            .header(reqwest::header::CONTENT_TYPE, CONTENT_TYPE)
+            .body(to_cbor(&signed)?)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| BentengError::KmsUnavailable(format!("KMS daemon: {}", e)))?;
+        let body = response.bytes()
+            .await
+            .map_err(|e| BentengError::KmsUnavailable(format!("KMS daemon: {}", e)))?;
+
+        let sealed: SealedResponse = from_cbor(&body)?;
+        match sealed.open(&signed, &reply_kem_sk, &self.server)? {
+            KmsResponse::Error { message, code: Some(code) } => Err(BentengError::from_code(code, message)),
+            KmsResponse::Error { message, code: None } => Err(BentengError::KmsError(message)),
+            response => Ok(response),
+        }
+    }
+
+    /// KID and public key of a specific key, or of the active key for a
+    /// tenant and policy
+    pub async fn public_key(
+        &self,
+        tenant_id: &[u8],
+        policy_id: &[u8],
+        kid: Option<&str>,
+    ) -> Result<(String, Vec<u8>)> {
+        match self.call(KmsRequest::PublicKey {
+            tenant_id: tenant_id.to_vec(),
+            policy_id: policy_id.to_vec(),
+            kid: kid.map(str::to_string),
+        }).await? {
+            KmsResponse::PublicKey { kid, public_key } => Ok((kid, public_key)),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Turn DEK caching on or off for a tenant's policy in the daemon
+    pub async fn set_policy_caching(&self, tenant_id: &[u8], policy_id: &[u8], enabled: bool) -> Result<()> {
+        let request = KmsRequest::PolicyCaching {
+            tenant_id: tenant_id.to_vec(),
+            policy_id: policy_id.to_vec(),
+            enabled,
+        };
+        match self.call(request).await? {
+            KmsResponse::Done => Ok(()),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Record a signed approval. Needs an admin identity.
+    pub async fn approve(&self, approval: QuorumApproval, reason: &str) -> Result<PendingRequest> {
+        match self.call(KmsRequest::Approve { approval, reason: reason.to_string() }).await? {
+            KmsResponse::Request { request } => Ok(request),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Deny a request. Needs an admin identity.
+    pub async fn deny(&self, denial: QuorumDenial) -> Result<PendingRequest> {
+        match self.call(KmsRequest::Deny { denial }).await? {
+            KmsResponse::Request { request } => Ok(request),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Submitted requests, optionally only those with a status. Needs an
+    /// admin identity.
+    pub async fn list_requests(&self, status: Option<RequestStatus>) -> Result<Vec<PendingRequest>> {
+        match self.call(KmsRequest::ListRequests { status }).await? {
+            KmsResponse::Requests { requests } => Ok(requests),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Send a custodian's share towards unsealing HSM-B, returning how many
+    /// shares it holds. The share is sealed to the daemon's share key. Needs
+    /// an admin identity.
+    pub async fn submit_share(&self, share: &CustodianShare) -> Result<usize> {
+        let KmsResponse::ShareKey { public_key } = self.call(KmsRequest::ShareKey).await? else {
+            return Err(unexpected());
+        };
+        match self.call(KmsRequest::SubmitShare { share: SealedShare::seal(share, &public_key)? }).await? {
+            KmsResponse::Shares { held } => Ok(held),
+            _ => Err(unexpected()),
+        }
+    }
+}
+
+fn unexpected() -> BentengError {
+    BentengError::KmsError("Unexpected KMS response".into())
+}
+
+impl KmsGate for RemoteKms {
+    async fn dual_decrypt(
+        &self,
+        kem_ciphertext: &[u8],
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+    ) -> Result<[u8; 32]> {
+        self.dual_decrypt_with_caching(kem_ciphertext, kid, policy_id, tenant_id, path, true).await
+    }
+
+    async fn dual_decrypt_with_caching(
+        &self,
+        kem_ciphertext: &[u8],
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+        cache_dek: bool,
+    ) -> Result<[u8; 32]> {
+        match self.call(KmsRequest::DualDecrypt {
+            kem_ciphertext: kem_ciphertext.to_vec(),
+            kid: kid.map(str::to_string),
+            policy_id: policy_id.to_vec(),
+            tenant_id: tenant_id.to_vec(),
+            path: path.to_string(),
+            cache_dek,
+        }).await? {
+            KmsResponse::Dek { dek } => dek.as_slice().try_into().map_err(|_| unexpected()),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn generate_dek(
+        &self,
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+    ) -> Result<WrappedDek> {
+        match self.call(KmsRequest::GenerateDek {
+            kid: kid.map(str::to_string),
+            policy_id: policy_id.to_vec(),
+            tenant_id: tenant_id.to_vec(),
+            path: path.to_string(),
+        }).await? {
+            KmsResponse::WrappedDek { kid, kem_ciphertext, dek } => {
+                let dek = Zeroizing::new(dek);
+                Ok(WrappedDek {
+                    kid,
+                    kem_ciphertext,
+                    dek: Zeroizing::new(dek.as_slice().try_into().map_err(|_| unexpected())?),
+                })
+            }
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn rewrap(&self, request: RewrapRequest) -> Result<RewrappedDek> {
+        match self.call(KmsRequest::Rewrap { request }).await? {
+            KmsResponse::Rewrapped { rewrapped } => Ok(rewrapped),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn check_quorum(&self, request_id: &[u8]) -> Result<bool> {
+        match self.call(KmsRequest::CheckQuorum { request_id: request_id.to_vec() }).await? {
+            KmsResponse::Quorum { reached } => Ok(reached),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn submit_request(&self, submission: RequestSubmission) -> Result<()> {
+        match self.call(KmsRequest::SubmitRequest { submission }).await? {
+            KmsResponse::Done => Ok(()),
+            _ => Err(unexpected()),
+        }
+    }
+}
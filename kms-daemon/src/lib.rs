@@ -0,0 +1,14 @@
+//! Benteng KMS daemon and client
+//! Runs `DualControlKms` as a separate process so key material stays out of
+//! the internet-facing edge. See `protocol` for the wire format.
+
+pub mod client;
+pub mod notify;
+pub mod protocol;
+pub mod server;
+mod trace;
+
+pub use client::RemoteKms;
+pub use notify::{NotifierSettings, WebhookNotifier};
+pub use protocol::{Identity, PeerKey};
+pub use server::{router, DaemonConfig, TenantScope};
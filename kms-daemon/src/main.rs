@@ -0,0 +1,136 @@
+use anyhow::{bail, Context, Result};
+use benteng_kms_daemon::{router, DaemonConfig, Identity, NotifierSettings, PeerKey, TenantScope};
+use benteng_sdk_core::crypto::kms::{DualControlConfig, DualControlKms};
+use benteng_sdk_core::crypto::quorum::Approver;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing_subscriber::prelude::*;
+use zeroize::Zeroizing;
+
+fn env_or(name: &str, default: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+/// Parse `id=public_key_file,...`
+fn peer_keys(name: &str) -> Result<Vec<PeerKey>> {
+    let Ok(value) = std::env::var(name) else {
+        return Ok(vec![]);
+    };
+    value.split(',')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let Some((id, path)) = entry.split_once('=') else {
+                bail!("{} entries must be id=public_key_file: {}", name, entry);
+            };
+            Ok(PeerKey {
+                id: id.to_string(),
+                public_key: std::fs::read(path).with_context(|| format!("reading {}", path))?,
+            })
+        })
+        .collect()
+}
+
+/// Parse `id=*` or `id=hex_tenant|hex_tenant,...`
+fn client_tenants(name: &str) -> Result<HashMap<String, TenantScope>> {
+    let Ok(value) = std::env::var(name) else {
+        return Ok(HashMap::new());
+    };
+    value.split(',')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let Some((id, tenants)) = entry.split_once('=') else {
+                bail!("{} entries must be id=* or id=hex_tenant|...: {}", name, entry);
+            };
+            let scope = match tenants {
+                "*" => TenantScope::Any,
+                tenants => TenantScope::Tenants(tenants.split('|')
+                    .map(|tenant| hex::decode(tenant).with_context(|| format!("tenant {} in {}", tenant, name)))
+                    .collect::<Result<_>>()?),
+            };
+            Ok((id.to_string(), scope))
+        })
+        .collect()
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Spans join the edge's trace through `traceparent` and go to the same
+    // OTLP/HTTP collector, e.g. `http://collector:4318/v1/traces`
+    let provider = match std::env::var("BENTENG_KMSD_OTLP_ENDPOINT") {
+        Ok(endpoint) => Some(SdkTracerProvider::builder()
+            .with_batch_exporter(SpanExporter::builder().with_http().with_endpoint(endpoint).build()?)
+            .with_resource(Resource::builder().with_service_name("benteng-kmsd").build())
+            .build()),
+        Err(_) => None,
+    };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(tracing_subscriber::EnvFilter::from_default_env()))
+        .with(provider.as_ref().map(|provider| {
+            tracing_opentelemetry::layer()
+                .with_tracer(provider.tracer("benteng-kmsd"))
+                .with_filter(tracing_subscriber::filter::LevelFilter::INFO)
+        }))
+        .init();
+
+    let signing_key_path = std::env::var("BENTENG_KMSD_SIGNING_KEY")
+        .context("BENTENG_KMSD_SIGNING_KEY must name the daemon's ML-DSA secret key")?;
+    let identity = Identity {
+        id: env_or("BENTENG_KMSD_ID", "kmsd"),
+        signing_key: Zeroizing::new(std::fs::read(&signing_key_path)
+            .with_context(|| format!("reading {}", signing_key_path))?),
+    };
+
+    let clients = peer_keys("BENTENG_KMSD_CLIENTS")?;
+    if clients.is_empty() {
+        bail!("BENTENG_KMSD_CLIENTS must list at least one client as id=public_key_file");
+    }
+
+    // Tenants each client may act for; clients without an entry get none
+    let client_tenants = client_tenants("BENTENG_KMSD_CLIENT_TENANTS")?;
+    for client in &clients {
+        if !client_tenants.contains_key(&client.id) {
+            tracing::warn!(client = %client.id, "Client has no BENTENG_KMSD_CLIENT_TENANTS entry and is refused every tenant");
+        }
+    }
+
+    // Approvers' operators, e.g. an approval console; kept apart from the edges
+    let admins = peer_keys("BENTENG_KMSD_ADMINS")?;
+
+    let kms_config = DualControlConfig {
+        hsm_a_endpoint: env_or("BENTENG_KMS_HSM_A", "mock://hsm-a"),
+        hsm_b_endpoint: env_or("BENTENG_KMS_HSM_B", "mock://hsm-b"),
+        require_quorum: env_or("BENTENG_KMS_REQUIRE_QUORUM", "true") == "true",
+        approval_store_path: std::env::var("BENTENG_KMS_APPROVAL_STORE").ok().map(Into::into),
+        key_catalog_path: std::env::var("BENTENG_KMS_KEY_CATALOG").ok().map(Into::into),
+        ..Default::default()
+    };
+    let approvers = peer_keys("BENTENG_KMS_APPROVERS")?
+        .into_iter()
+        .map(|peer| Approver { id: peer.id, public_key: peer.public_key })
+        .collect();
+    let kms = NotifierSettings::from_env()
+        .apply(DualControlKms::connect(kms_config)?.with_approvers(approvers)?)?;
+
+    let config = DaemonConfig {
+        identity,
+        clients,
+        client_tenants,
+        admins,
+        max_skew: Duration::from_millis(env_or("BENTENG_KMSD_MAX_SKEW_MS", "30000").parse()?),
+    };
+
+    let bind = env_or("BENTENG_KMSD_BIND", "127.0.0.1:7443");
+    let listener = tokio::net::TcpListener::bind(&bind).await?;
+    tracing::info!("Benteng KMS daemon listening on {}", bind);
+    axum::serve(listener, router(Arc::new(kms), config)?).await?;
+    if let Some(provider) = provider {
+        // Exporting blocks on the collector
+        tokio::task::spawn_blocking(move || provider.shutdown()).await??;
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! Approval workflow notifiers the KMS is built with, in the daemon and in
+//! an edge running the KMS in-process
+
+use benteng_sdk_core::crypto::approval::{ApprovalEvent, ApprovalNotifier, FileNotifier};
+use benteng_sdk_core::crypto::kms::DualControlKms;
+use benteng_sdk_core::BentengError;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Where approval workflow events go
+#[derive(Debug, Clone, Default)]
+pub struct NotifierSettings {
+    /// `BENTENG_APPROVAL_WEBHOOK_URL`
+    pub webhook_url: Option<String>,
+    /// `BENTENG_APPROVAL_EVENTS_FILE`
+    pub events_file: Option<PathBuf>,
+}
+
+impl NotifierSettings {
+    pub fn from_env() -> Self {
+        Self {
+            webhook_url: std::env::var("BENTENG_APPROVAL_WEBHOOK_URL").ok(),
+            events_file: std::env::var("BENTENG_APPROVAL_EVENTS_FILE").ok().map(Into::into),
+        }
+    }
+
+    /// `kms` with a notifier for each configured destination
+    pub fn apply(&self, mut kms: DualControlKms) -> Result<DualControlKms, reqwest::Error> {
+        if let Some(url) = &self.webhook_url {
+            kms = kms.with_notifier(Arc::new(WebhookNotifier::new(url.clone(), Duration::from_secs(10))?));
+        }
+        if let Some(path) = &self.events_file {
+            kms = kms.with_notifier(Arc::new(FileNotifier::new(path)));
+        }
+        Ok(kms)
+    }
+}
+
+/// Posts approval workflow events as JSON to a webhook. Delivery happens in
+/// the background so a slow receiver never holds up the KMS.
+pub struct WebhookNotifier {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String, timeout: Duration) -> Result<Self, reqwest::Error> {
+        Ok(Self {
+            url,
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+        })
+    }
+}
+
+impl ApprovalNotifier for WebhookNotifier {
+    fn notify(&self, event: &ApprovalEvent) -> Result<(), BentengError> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|_| BentengError::KmsError("Webhook notifier needs a Tokio runtime".into()))?;
+
+        let body = serde_json::to_vec(event).map_err(|_| BentengError::InternalError)?;
+        let request = self.client.post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body);
+        let url = self.url.clone();
+        runtime.spawn(async move {
+            if let Err(e) = request.send().await.and_then(|r| r.error_for_status()) {
+                tracing::warn!(%url, "Approval webhook delivery failed: {}", e);
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+    use benteng_sdk_core::crypto::approval::{PendingRequest, RequestContext};
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_webhook_receives_events() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                tx.send(body).unwrap();
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let notifier = WebhookNotifier::new(format!("http://{}/hook", addr), Duration::from_secs(5)).unwrap();
+        let request = PendingRequest::new(&[1u8; 32], RequestContext {
+            tenant_id: "tenant1".into(),
+            policy_id: "policy1".into(),
+            path: "/test".into(),
+            requester: "client-1".into(),
+            envelope_hash: "00".into(),
+        });
+        notifier.notify(&ApprovalEvent::Requested { request }).unwrap();
+
+        let body = rx.recv().await.unwrap();
+        assert_eq!(body["event"], "requested");
+        assert_eq!(body["request"]["context"]["tenant_id"], "tenant1");
+    }
+}
@@ -0,0 +1,376 @@
+//! Wire protocol between the edge and the KMS daemon
+//!
+//! Transport is HTTP with CBOR bodies: a single endpoint, `POST /v1/kms`,
+//! with `Content-Type: application/cbor`.
+//!
+//! Both sides authenticate with pinned ML-DSA (Dilithium3) keys:
+//!
+//! * The client sends a [`SignedRequest`]. `sig` signs
+//!   `"benteng/kms-request/v1"` followed by the length-prefixed `client_id`,
+//!   `ts_ms` (big-endian u64), `nonce`, `reply_kem_pk` and `body`. `body`
+//!   is a CBOR [`KmsRequest`]. The daemon rejects unknown client IDs, bad
+//!   signatures, timestamps outside its skew window and repeated nonces,
+//!   answering `401` with no body. Approval operations are only served to
+//!   the admin identities, whose keys are pinned separately; other clients
+//!   get an `admin_required` error.
+//! * Custodian shares of the HSM-B master secret travel as a [`SealedShare`]
+//!   (admin only): the client fetches the daemon's share key, a Kyber768
+//!   key pair generated when the daemon starts, and encapsulates to it. The
+//!   share key is HKDF-SHA256 over the shared secret with salt
+//!   `"benteng/kms-share/v1"`; the share is sealed with AES-256-GCM.
+//! * The daemon answers `200` with a [`SealedResponse`]. It encapsulates to
+//!   the request's one-time ML-KEM (Kyber768) `reply_kem_pk`. The response
+//!   key is HKDF-SHA256 over the shared secret, with salt
+//!   `"benteng/kms-response/v1"` and info set to the request digest (the
+//!   SHA-256 of the request `sig`). The CBOR [`KmsResponse`] is sealed with
+//!   AES-256-GCM under that key, with the request digest as AAD. `sig` signs
+//!   `"benteng/kms-response/v1"` followed by the length-prefixed `server_id`,
+//!   request digest, `kem_ct`, `nonce` and `ct`.
+//!
+//! Binding the response to the request digest stops a response from being
+//! replayed against another request. DEKs and shares only cross the wire
+//! encrypted.
+//! Malformed bodies get `400`.
+
+use benteng_sdk_core::crypto::approval::{PendingRequest, RequestStatus, RequestSubmission};
+use benteng_sdk_core::crypto::kms::{RewrapRequest, RewrappedDek};
+use benteng_sdk_core::crypto::quorum::{QuorumApproval, QuorumDenial};
+use benteng_sdk_core::crypto::shamir::CustodianShare;
+use benteng_sdk_core::crypto::{aead, generate_nonce, kdf, kem, sig};
+use benteng_sdk_core::{BentengError, ErrorCode};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+type Result<T> = std::result::Result<T, BentengError>;
+
+pub const PROTOCOL_VERSION: u8 = 1;
+pub const KMS_PATH: &str = "/v1/kms";
+pub const CONTENT_TYPE: &str = "application/cbor";
+
+const REQUEST_DOMAIN: &[u8] = b"benteng/kms-request/v1";
+const RESPONSE_DOMAIN: &[u8] = b"benteng/kms-response/v1";
+const SHARE_DOMAIN: &[u8] = b"benteng/kms-share/v1";
+
+/// Operations the daemon serves
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "op")]
+pub enum KmsRequest {
+    DualDecrypt {
+        kem_ciphertext: Vec<u8>,
+        kid: Option<String>,
+        policy_id: Vec<u8>,
+        tenant_id: Vec<u8>,
+        path: String,
+        /// Whether the policy lets the daemon cache the DEK
+        #[serde(default = "cache_dek_default")]
+        cache_dek: bool,
+    },
+    /// New DEK for an envelope, see `KmsGate::generate_dek`
+    GenerateDek {
+        kid: Option<String>,
+        policy_id: Vec<u8>,
+        tenant_id: Vec<u8>,
+        path: String,
+    },
+    /// Move an envelope to another key, see `KmsGate::rewrap`
+    Rewrap {
+        request: RewrapRequest,
+    },
+    CheckQuorum {
+        request_id: Vec<u8>,
+    },
+    /// The daemon derives the request ID from the submission and records
+    /// the calling client as the requester
+    SubmitRequest {
+        submission: RequestSubmission,
+    },
+    /// Active key for a tenant and policy, or a specific KID
+    PublicKey {
+        tenant_id: Vec<u8>,
+        policy_id: Vec<u8>,
+        kid: Option<String>,
+    },
+    /// Turn DEK caching on or off for a tenant's policy in the daemon
+    PolicyCaching {
+        tenant_id: Vec<u8>,
+        policy_id: Vec<u8>,
+        enabled: bool,
+    },
+    /// Admin: record a signed approval for a submitted request
+    Approve {
+        approval: QuorumApproval,
+        reason: String,
+    },
+    /// Admin: deny a submitted request
+    Deny {
+        denial: QuorumDenial,
+    },
+    /// Admin: submitted requests, optionally only those with a status
+    ListRequests {
+        status: Option<RequestStatus>,
+    },
+    /// Admin: public key to seal custodian shares to
+    ShareKey,
+    /// Admin: a custodian's share towards unsealing HSM-B
+    SubmitShare {
+        share: SealedShare,
+    },
+}
+
+fn cache_dek_default() -> bool {
+    true
+}
+
+impl KmsRequest {
+    /// Whether only admin identities may send this operation
+    pub fn is_admin(&self) -> bool {
+        matches!(
+            self,
+            Self::Approve { .. } | Self::Deny { .. } | Self::ListRequests { .. }
+                | Self::ShareKey | Self::SubmitShare { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "result")]
+pub enum KmsResponse {
+    Dek { dek: Vec<u8> },
+    WrappedDek { kid: String, kem_ciphertext: Vec<u8>, dek: Vec<u8> },
+    Rewrapped { rewrapped: RewrappedDek },
+    Quorum { reached: bool },
+    PublicKey { kid: String, public_key: Vec<u8> },
+    Request { request: PendingRequest },
+    Requests { requests: Vec<PendingRequest> },
+    ShareKey { public_key: Vec<u8> },
+    /// Shares HSM-B holds after a submission
+    Shares { held: usize },
+    Done,
+    /// `code` is absent from daemons that predate the error catalogue
+    Error {
+        message: String,
+        #[serde(default)]
+        code: Option<ErrorCode>,
+    },
+}
+
+/// A signed request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedRequest {
+    pub v: u8,
+    pub client_id: String,
+    pub ts_ms: u64,
+    pub nonce: Vec<u8>,
+    /// One-time ML-KEM public key the response is sealed to
+    pub reply_kem_pk: Vec<u8>,
+    pub body: Vec<u8>,
+    pub sig: Vec<u8>,
+}
+
+/// A signed, encrypted response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedResponse {
+    pub v: u8,
+    pub server_id: String,
+    pub kem_ct: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ct: Vec<u8>,
+    pub sig: Vec<u8>,
+}
+
+/// A custodian share encrypted to a Kyber768 key: the daemon's share key
+/// on the wire, or the custodian's own key in a share file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedShare {
+    pub kem_ct: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ct: Vec<u8>,
+}
+
+/// A peer's identity and pinned public key
+#[derive(Debug, Clone)]
+pub struct PeerKey {
+    pub id: String,
+    pub public_key: Vec<u8>,
+}
+
+/// Our identity and signing key
+#[derive(Clone)]
+pub struct Identity {
+    pub id: String,
+    pub signing_key: Zeroizing<Vec<u8>>,
+}
+
+/// Length-prefixed fields under a domain separator
+fn signing_message(domain: &[u8], fields: &[&[u8]]) -> Vec<u8> {
+    let mut msg = domain.to_vec();
+    for field in fields {
+        msg.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        msg.extend_from_slice(field);
+    }
+    msg
+}
+
+pub fn to_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    ciborium::into_writer(value, &mut data).map_err(|_| BentengError::InternalError)?;
+    Ok(data)
+}
+
+pub fn from_cbor<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T> {
+    ciborium::from_reader(data)
+        .map_err(|_| BentengError::KmsError("Malformed KMS message".into()))
+}
+
+impl SignedRequest {
+    /// Sign a request, returning it with the secret key its response will
+    /// be sealed to
+    pub fn sign(
+        request: &KmsRequest,
+        identity: &Identity,
+        ts_ms: u64,
+    ) -> Result<(Self, Zeroizing<Vec<u8>>)> {
+        let (reply_kem_pk, reply_kem_sk) = kem::kyber768_keypair()?;
+        let mut nonce = vec![0u8; 16];
+        benteng_sdk_core::crypto::secure_random(&mut nonce)?;
+
+        let mut signed = Self {
+            v: PROTOCOL_VERSION,
+            client_id: identity.id.clone(),
+            ts_ms,
+            nonce,
+            reply_kem_pk,
+            body: to_cbor(request)?,
+            sig: vec![],
+        };
+        signed.sig = sig::dilithium3_sign(&identity.signing_key, &signed.signing_message())?;
+        Ok((signed, reply_kem_sk))
+    }
+
+    fn signing_message(&self) -> Vec<u8> {
+        signing_message(REQUEST_DOMAIN, &[
+            self.client_id.as_bytes(),
+            &self.ts_ms.to_be_bytes(),
+            &self.nonce,
+            &self.reply_kem_pk,
+            &self.body,
+        ])
+    }
+
+    /// Check the signature against the pinned key of the named client and
+    /// decode the body
+    pub fn verify(&self, clients: &[PeerKey]) -> Result<KmsRequest> {
+        if self.v != PROTOCOL_VERSION {
+            return Err(BentengError::KmsError("Unsupported protocol version".into()));
+        }
+        let client = clients.iter()
+            .find(|c| c.id == self.client_id)
+            .ok_or_else(|| BentengError::KmsError(format!("Unknown client {}", self.client_id)))?;
+        if !sig::dilithium3_verify(&client.public_key, &self.signing_message(), &self.sig)? {
+            return Err(BentengError::InvalidSignature);
+        }
+        from_cbor(&self.body)
+    }
+
+    /// Digest responses are bound to
+    pub fn digest(&self) -> [u8; 32] {
+        Sha256::digest(&self.sig).into()
+    }
+}
+
+fn response_key(shared_secret: &[u8], request_digest: &[u8; 32]) -> Result<[u8; 32]> {
+    let derived = kdf::hkdf_sha256_derive(shared_secret, Some(RESPONSE_DOMAIN), request_digest, 32)?;
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&derived);
+    Ok(key)
+}
+
+impl SealedResponse {
+    /// Seal and sign a response to `request`
+    pub fn seal(response: &KmsResponse, request: &SignedRequest, identity: &Identity) -> Result<Self> {
+        let request_digest = request.digest();
+        let (kem_ct, shared_secret) = kem::kyber768_encapsulate(&request.reply_kem_pk)?;
+        let key = Zeroizing::new(response_key(&*shared_secret, &request_digest)?);
+        let nonce = generate_nonce()?;
+        let body = Zeroizing::new(to_cbor(response)?);
+
+        let mut sealed = Self {
+            v: PROTOCOL_VERSION,
+            server_id: identity.id.clone(),
+            kem_ct,
+            nonce: nonce.to_vec(),
+            ct: aead::aes_256_gcm_encrypt(&key, &nonce, &body, &request_digest)?,
+            sig: vec![],
+        };
+        sealed.sig = sig::dilithium3_sign(&identity.signing_key, &sealed.signing_message(&request_digest))?;
+        Ok(sealed)
+    }
+
+    fn signing_message(&self, request_digest: &[u8; 32]) -> Vec<u8> {
+        signing_message(RESPONSE_DOMAIN, &[
+            self.server_id.as_bytes(),
+            request_digest,
+            &self.kem_ct,
+            &self.nonce,
+            &self.ct,
+        ])
+    }
+
+    /// Check the daemon's signature and binding to `request`, then decrypt
+    pub fn open(
+        &self,
+        request: &SignedRequest,
+        reply_kem_sk: &[u8],
+        server: &PeerKey,
+    ) -> Result<KmsResponse> {
+        let request_digest = request.digest();
+        if self.v != PROTOCOL_VERSION || self.server_id != server.id {
+            return Err(BentengError::KmsError("Response from unexpected KMS".into()));
+        }
+        if !sig::dilithium3_verify(&server.public_key, &self.signing_message(&request_digest), &self.sig)? {
+            return Err(BentengError::InvalidSignature);
+        }
+
+        let shared_secret = kem::kyber768_decapsulate(reply_kem_sk, &self.kem_ct)?;
+        let key = Zeroizing::new(response_key(&*shared_secret, &request_digest)?);
+        let nonce: [u8; 12] = self.nonce.as_slice()
+            .try_into()
+            .map_err(|_| BentengError::AeadFailure)?;
+        let body = aead::aes_256_gcm_decrypt(&key, &nonce, &self.ct, &request_digest)?;
+        from_cbor(&body)
+    }
+}
+
+fn share_key(shared_secret: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+    let derived = kdf::hkdf_sha256_derive(shared_secret, Some(SHARE_DOMAIN), b"", 32)?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&derived);
+    Ok(key)
+}
+
+impl SealedShare {
+    /// Seal `share` to a share key
+    pub fn seal(share: &CustodianShare, public_key: &[u8]) -> Result<Self> {
+        let (kem_ct, shared_secret) = kem::kyber768_encapsulate(public_key)?;
+        let key = share_key(&*shared_secret)?;
+        let nonce = generate_nonce()?;
+        let body = Zeroizing::new(to_cbor(share)?);
+        Ok(Self {
+            kem_ct,
+            nonce: nonce.to_vec(),
+            ct: aead::aes_256_gcm_encrypt(&key, &nonce, &body, SHARE_DOMAIN)?,
+        })
+    }
+
+    pub fn open(&self, secret_key: &[u8]) -> Result<CustodianShare> {
+        let shared_secret = kem::kyber768_decapsulate(secret_key, &self.kem_ct)?;
+        let key = share_key(&*shared_secret)?;
+        let nonce: [u8; 12] = self.nonce.as_slice()
+            .try_into()
+            .map_err(|_| BentengError::AeadFailure)?;
+        let body = Zeroizing::new(aead::aes_256_gcm_decrypt(&key, &nonce, &self.ct, SHARE_DOMAIN)?);
+        from_cbor(&body)
+    }
+}
@@ -0,0 +1,288 @@
+//! KMS daemon: serves the wire protocol on top of `DualControlKms`
+
+use crate::protocol::{
+    from_cbor, to_cbor, Identity, KmsRequest, KmsResponse, PeerKey, SealedResponse, SignedRequest,
+    CONTENT_TYPE, KMS_PATH,
+};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use benteng_sdk_core::crypto::kem;
+use benteng_sdk_core::crypto::kms::{DualControlKms, KmsGate};
+use benteng_sdk_core::{BentengError, ErrorCode};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::Instrument;
+use zeroize::Zeroizing;
+
+/// Tenants a client may use keys of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TenantScope {
+    Any,
+    Tenants(Vec<Vec<u8>>),
+}
+
+impl TenantScope {
+    pub fn allows(&self, tenant_id: &[u8]) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tenants(tenants) => tenants.iter().any(|t| t == tenant_id),
+        }
+    }
+}
+
+/// Daemon identity and the clients allowed to call it
+#[derive(Clone)]
+pub struct DaemonConfig {
+    pub identity: Identity,
+    pub clients: Vec<PeerKey>,
+    /// Scope of each client by ID. Clients without one are refused every
+    /// tenant-scoped operation.
+    pub client_tenants: HashMap<String, TenantScope>,
+    /// Identities allowed to approve, deny and list requests
+    pub admins: Vec<PeerKey>,
+    /// Largest accepted difference between request and daemon clocks
+    pub max_skew: Duration,
+}
+
+#[derive(Clone)]
+struct DaemonState {
+    kms: Arc<DualControlKms>,
+    config: Arc<DaemonConfig>,
+    /// Request nonces seen within the skew window, with their expiry
+    seen_nonces: Arc<Mutex<HashMap<Vec<u8>, u64>>>,
+    /// Key pair custodian shares are sealed to, fresh for each process
+    share_public_key: Arc<Vec<u8>>,
+    share_secret_key: Arc<Zeroizing<Vec<u8>>>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+pub fn router(kms: Arc<DualControlKms>, config: DaemonConfig) -> Result<Router, BentengError> {
+    Ok(Router::new()
+        .route(KMS_PATH, post(handle))
+        .with_state(DaemonState::new(kms, config)?))
+}
+
+impl DaemonState {
+    fn new(kms: Arc<DualControlKms>, config: DaemonConfig) -> Result<Self, BentengError> {
+        let (share_public_key, share_secret_key) = kem::kyber768_keypair()?;
+        Ok(Self {
+            kms,
+            config: Arc::new(config),
+            seen_nonces: Arc::new(Mutex::new(HashMap::new())),
+            share_public_key: Arc::new(share_public_key),
+            share_secret_key: Arc::new(share_secret_key),
+        })
+    }
+
+    /// Verify the request and record its nonce. Also returns whether the
+    /// caller is an admin.
+    fn authenticate(&self, request: &SignedRequest) -> Result<(KmsRequest, bool), BentengError> {
+        let admin = self.config.admins.iter().any(|peer| peer.id == request.client_id);
+        let peers = if admin { &self.config.admins } else { &self.config.clients };
+        let operation = request.verify(peers)?;
+
+        let now = now_ms();
+        let skew = self.config.max_skew.as_millis() as u64;
+        if request.ts_ms.abs_diff(now) > skew {
+            return Err(BentengError::KmsError("Request timestamp outside the allowed skew".into()));
+        }
+
+        let mut seen = self.seen_nonces.lock().map_err(|_| BentengError::InternalError)?;
+        seen.retain(|_, expires_at| *expires_at > now);
+        if seen.insert(request.nonce.clone(), now + 2 * skew).is_some() {
+            return Err(BentengError::KmsError("Replayed request".into()));
+        }
+        Ok((operation, admin))
+    }
+
+    /// Whether the caller's tenant scope covers the operation. Approval and
+    /// unsealing operations are not tenant scoped.
+    fn in_scope(&self, client_id: &str, operation: &KmsRequest) -> Result<bool, BentengError> {
+        let scope = self.config.client_tenants.get(client_id);
+        let allows = |tenant_id: &[u8]| scope.is_some_and(|scope| scope.allows(tenant_id));
+        Ok(match operation {
+            KmsRequest::DualDecrypt { tenant_id, .. }
+            | KmsRequest::GenerateDek { tenant_id, .. }
+            | KmsRequest::PublicKey { tenant_id, kid: None, .. }
+            | KmsRequest::PolicyCaching { tenant_id, .. } => allows(tenant_id),
+            KmsRequest::Rewrap { request } => allows(&request.tenant_id),
+            KmsRequest::SubmitRequest { submission } => allows(&submission.tenant_id),
+            KmsRequest::PublicKey { kid: Some(kid), .. } => match self.kms.key_catalog().get(kid)? {
+                Some(record) => allows(&hex::decode(&record.tenant_id).map_err(|_| BentengError::InternalError)?),
+                // Reported as an unknown key
+                None => true,
+            },
+            KmsRequest::CheckQuorum { .. }
+            | KmsRequest::Approve { .. }
+            | KmsRequest::Deny { .. }
+            | KmsRequest::ListRequests { .. }
+            | KmsRequest::ShareKey
+            | KmsRequest::SubmitShare { .. } => true,
+        })
+    }
+
+    async fn execute(&self, client_id: &str, operation: KmsRequest) -> Result<KmsResponse, BentengError> {
+        Ok(match operation {
+            KmsRequest::DualDecrypt { kem_ciphertext, kid, policy_id, tenant_id, path, cache_dek } => {
+                let dek = self.kms
+                    .dual_decrypt_with_caching(&kem_ciphertext, kid.as_deref(), &policy_id, &tenant_id, &path, cache_dek)
+                    .await?;
+                KmsResponse::Dek { dek: dek.to_vec() }
+            }
+            KmsRequest::GenerateDek { kid, policy_id, tenant_id, path } => {
+                let wrapped = self.kms
+                    .generate_dek(kid.as_deref(), &policy_id, &tenant_id, &path)
+                    .await?;
+                KmsResponse::WrappedDek {
+                    kid: wrapped.kid,
+                    kem_ciphertext: wrapped.kem_ciphertext,
+                    dek: wrapped.dek.to_vec(),
+                }
+            }
+            KmsRequest::Rewrap { request } => KmsResponse::Rewrapped {
+                rewrapped: self.kms.rewrap(request).await?,
+            },
+            KmsRequest::CheckQuorum { request_id } => KmsResponse::Quorum {
+                reached: self.kms.check_quorum(&request_id).await?,
+            },
+            KmsRequest::SubmitRequest { mut submission } => {
+                submission.requester = client_id.to_string();
+                self.kms.submit_request(submission).await?;
+                KmsResponse::Done
+            }
+            KmsRequest::PublicKey { tenant_id, policy_id, kid } => {
+                let (kid, public_key) = match kid {
+                    Some(kid) => {
+                        let public_key = self.kms.get_public_key(&kid).await?;
+                        (kid, public_key)
+                    }
+                    None => self.kms.active_public_key(&tenant_id, &policy_id).await?,
+                };
+                KmsResponse::PublicKey { kid, public_key }
+            }
+            KmsRequest::PolicyCaching { tenant_id, policy_id, enabled } => {
+                self.kms.set_policy_caching(&tenant_id, &policy_id, enabled)?;
+                KmsResponse::Done
+            }
+            KmsRequest::Approve { approval, reason } => KmsResponse::Request {
+                request: self.kms.approve(approval, &reason).await?,
+            },
+            KmsRequest::Deny { denial } => KmsResponse::Request {
+                request: self.kms.deny(denial).await?,
+            },
+            KmsRequest::ListRequests { status } => KmsResponse::Requests {
+                requests: self.kms.list_requests(status).await?,
+            },
+            KmsRequest::ShareKey => KmsResponse::ShareKey {
+                public_key: self.share_public_key.to_vec(),
+            },
+            KmsRequest::SubmitShare { share } => {
+                let share = share.open(&self.share_secret_key)?;
+                let custodian = share.custodian.clone();
+                let held = self.kms.submit_share(share).await?;
+                tracing::info!(%custodian, held, "Accepted custodian share");
+                KmsResponse::Shares { held }
+            }
+        })
+    }
+}
+
+async fn handle(State(state): State<DaemonState>, headers: HeaderMap, body: Bytes) -> Response {
+    let span = crate::trace::request_span(&headers);
+    respond(state, body).instrument(span).await
+}
+
+async fn respond(state: DaemonState, body: Bytes) -> Response {
+    let request: SignedRequest = match from_cbor(&body) {
+        Ok(request) => request,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let (operation, admin) = match state.authenticate(&request) {
+        Ok(authenticated) => authenticated,
+        Err(e) => {
+            tracing::warn!(client = %request.client_id, "Rejected KMS request: {}", e);
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    };
+    tracing::Span::current().record("kms.client", request.client_id.as_str());
+
+    let response = if operation.is_admin() && !admin {
+        tracing::warn!(client = %request.client_id, "Refused admin operation");
+        KmsResponse::Error {
+            message: "Operation requires an admin identity".into(),
+            code: Some(ErrorCode::AdminRequired),
+        }
+    } else {
+        match state.in_scope(&request.client_id, &operation) {
+            Ok(true) => state.execute(&request.client_id, operation).await,
+            Ok(false) => {
+                tracing::warn!(client = %request.client_id, "Refused operation outside the client's tenants");
+                Ok(KmsResponse::Error {
+                    message: "Tenant outside the client's scope".into(),
+                    code: Some(ErrorCode::TenantForbidden),
+                })
+            }
+            Err(e) => Err(e),
+        }
+        .unwrap_or_else(|e| KmsResponse::Error { message: e.to_string(), code: Some(e.code()) })
+    };
+
+    match SealedResponse::seal(&response, &request, &state.config.identity).and_then(|sealed| to_cbor(&sealed)) {
+        Ok(data) => ([(header::CONTENT_TYPE, CONTENT_TYPE)], data).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to seal KMS response: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use benteng_sdk_core::crypto::kms::DualControlConfig;
+    use benteng_sdk_core::crypto::sig;
+
+    #[test]
+    fn test_replayed_and_stale_requests_are_refused() {
+        let (client_pk, client_sk) = sig::dilithium3_keypair().unwrap();
+        let (_, server_sk) = sig::dilithium3_keypair().unwrap();
+        let client = Identity { id: "edge".into(), signing_key: Zeroizing::new(client_sk) };
+        let state = DaemonState::new(
+            Arc::new(DualControlKms::new(DualControlConfig::default()).unwrap()),
+            DaemonConfig {
+                identity: Identity { id: "kmsd".into(), signing_key: Zeroizing::new(server_sk) },
+                clients: vec![PeerKey { id: "edge".into(), public_key: client_pk }],
+                client_tenants: HashMap::new(),
+                admins: vec![],
+                max_skew: Duration::from_secs(30),
+            },
+        ).unwrap();
+        let operation = KmsRequest::CheckQuorum { request_id: vec![0; 32] };
+
+        let (request, _) = SignedRequest::sign(&operation, &client, now_ms()).unwrap();
+        assert_eq!(state.authenticate(&request).unwrap(), (operation.clone(), false));
+        assert!(state.authenticate(&request).is_err());
+
+        let (stale, _) = SignedRequest::sign(&operation, &client, now_ms() - 60_000).unwrap();
+        assert!(state.authenticate(&stale).is_err());
+
+        let (mut tampered, _) = SignedRequest::sign(&operation, &client, now_ms()).unwrap();
+        tampered.body = to_cbor(&KmsRequest::CheckQuorum { request_id: vec![1; 32] }).unwrap();
+        assert!(state.authenticate(&tampered).is_err());
+    }
+}
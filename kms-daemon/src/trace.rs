@@ -0,0 +1,49 @@
+//! W3C trace context on KMS calls
+//! The client sends its current span as `traceparent` so daemon spans join
+//! the edge's trace. Only the trace and span IDs cross the wire.
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(key.as_bytes()), HeaderValue::from_str(&value)) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Headers carrying the current span's trace context. Empty when no
+/// OpenTelemetry layer is installed.
+pub(crate) fn current_context() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let context = tracing::Span::current().context();
+    TraceContextPropagator::new().inject_context(&context, &mut HeaderInjector(&mut headers));
+    headers
+}
+
+/// Span for one KMS request, joined to the caller's trace when it sent a
+/// `traceparent`
+pub(crate) fn request_span(headers: &HeaderMap) -> tracing::Span {
+    let span = tracing::info_span!("kms_request", kms.client = tracing::field::Empty);
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(headers));
+    // Only fails when no OpenTelemetry layer is installed
+    let _ = span.set_parent(parent);
+    span
+}
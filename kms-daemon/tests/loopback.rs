@@ -0,0 +1,343 @@
+use benteng_kms_daemon::{router, DaemonConfig, Identity, NotifierSettings, PeerKey, RemoteKms, TenantScope};
+use benteng_sdk_core::crypto::approval::{RequestStatus, RequestSubmission};
+use benteng_sdk_core::crypto::key_catalog::KeyOptions;
+use benteng_sdk_core::crypto::kms::{unwrap_dek, DualControlConfig, DualControlKms, KmsGate, RewrapRequest};
+use benteng_sdk_core::crypto::quorum::{Approver, QuorumApproval, QuorumDenial};
+use benteng_sdk_core::crypto::{kem, shamir, sig};
+use benteng_sdk_core::ErrorCode;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+fn identity(id: &str) -> (Identity, PeerKey) {
+    let (public_key, secret_key) = sig::dilithium3_keypair().unwrap();
+    (
+        Identity { id: id.to_string(), signing_key: Zeroizing::new(secret_key) },
+        PeerKey { id: id.to_string(), public_key },
+    )
+}
+
+#[tokio::test]
+async fn test_remote_kms_over_loopback() {
+    let config = DualControlConfig {
+        require_quorum: false,
+        timeout_ms: 2000,
+        ..Default::default()
+    };
+    let kms = Arc::new(DualControlKms::new(config.clone()).unwrap());
+    let key = kms.generate_key(b"tenant", b"policy", KeyOptions::default()).await.unwrap();
+
+    let (server_identity, server_key) = identity("kmsd");
+    let (edge_identity, edge_key) = identity("edge-1");
+    let (rogue_identity, _) = identity("edge-1");
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let app = router(kms.clone(), DaemonConfig {
+        identity: server_identity,
+        clients: vec![edge_key],
+        client_tenants: HashMap::from([("edge-1".to_string(), TenantScope::Any)]),
+        admins: vec![],
+        max_skew: Duration::from_secs(30),
+    }).unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let remote = RemoteKms::new(&endpoint, &config, edge_identity, server_key.clone()).unwrap();
+
+    // Encrypt to the active key fetched through the daemon
+    let (kid, public_key) = remote.public_key(b"tenant", b"policy", None).await.unwrap();
+    assert_eq!(kid, key.kid);
+    let (ciphertext, _) = kem::kyber768_encapsulate(&public_key).unwrap();
+
+    let dek = remote.dual_decrypt(&ciphertext, Some(&kid), b"policy", b"tenant", "/").await.unwrap();
+    assert_eq!(dek, kms.dual_decrypt(&ciphertext, Some(&kid), b"policy", b"tenant", "/").await.unwrap());
+    assert!(!remote.check_quorum(&[0u8; 32]).await.unwrap());
+
+    // A policy's no-cache setting travels with the request
+    let entries = kms.dek_cache_stats().entries;
+    remote.dual_decrypt_with_caching(&ciphertext, Some(&kid), b"policy", b"tenant", "/private", false).await.unwrap();
+    assert_eq!(kms.dek_cache_stats().entries, entries);
+
+    // DEKs issued through the daemon decrypt through it too
+    let wrapped = remote.generate_dek(None, b"policy", b"tenant", "/").await.unwrap();
+    assert_eq!(wrapped.kid, key.kid);
+    let dek = remote.dual_decrypt(&wrapped.kem_ciphertext, Some(&wrapped.kid), b"policy", b"tenant", "/").await.unwrap();
+    assert_eq!(dek, *wrapped.dek);
+
+    // Rewraps run in the daemon and only return wrapped key material
+    let next = kms.rotate_key(b"tenant", b"policy").await.unwrap();
+    let rewrapped = remote.rewrap(RewrapRequest {
+        kem_ciphertext: wrapped.kem_ciphertext.clone(),
+        wrapped_dek: None,
+        kid: Some(wrapped.kid.clone()),
+        new_kid: None,
+        tenant_id: b"tenant".to_vec(),
+        policy_id: b"policy".to_vec(),
+        path: "/".into(),
+    }).await.unwrap();
+    assert_eq!(rewrapped.kid, next.kid);
+    let kek = remote.dual_decrypt(&rewrapped.kem_ciphertext, Some(&next.kid), b"policy", b"tenant", "/").await.unwrap();
+    assert_eq!(*unwrap_dek(&kek, &next.kid, &rewrapped.wrapped_dek).unwrap(), *wrapped.dek);
+
+    // KMS errors come back as errors with their code, not transport failures
+    let err = remote.dual_decrypt(&ciphertext, Some("missing"), b"policy", b"tenant", "/").await.unwrap_err();
+    assert!(err.to_string().contains("Unknown key missing"));
+    assert_eq!(err.code(), ErrorCode::KeyNotFound);
+
+    // A client with the right ID but the wrong key is refused
+    let rogue = RemoteKms::new(&endpoint, &config, rogue_identity, server_key).unwrap();
+    assert!(rogue.check_quorum(&[0u8; 32]).await.is_err());
+
+    // So is a daemon whose key the client did not pin
+    let (_, other_server) = identity("kmsd");
+    let (edge_identity, _) = identity("edge-1");
+    let misdirected = RemoteKms::new(&endpoint, &config, edge_identity, other_server).unwrap();
+    assert!(misdirected.check_quorum(&[0u8; 32]).await.is_err());
+
+    // Calls are bounded by timeout_ms
+    let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let silent_endpoint = format!("http://{}", silent.local_addr().unwrap());
+    let short = DualControlConfig { timeout_ms: 200, ..config };
+    let (edge_identity, _) = identity("edge-1");
+    let (_, server_key) = identity("kmsd");
+    let stalled = RemoteKms::new(&silent_endpoint, &short, edge_identity, server_key).unwrap();
+    let started = std::time::Instant::now();
+    let err = stalled.check_quorum(&[0u8; 32]).await.unwrap_err();
+    assert_eq!(err.code(), ErrorCode::KmsUnavailable);
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn test_approvals_over_loopback() {
+    let config = DualControlConfig { timeout_ms: 2000, ..Default::default() };
+    let (pk1, sk1) = sig::dilithium3_keypair().unwrap();
+    let (pk2, sk2) = sig::dilithium3_keypair().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let events = dir.path().join("events.jsonl");
+    let notifiers = NotifierSettings { webhook_url: None, events_file: Some(events.clone()) };
+    let kms = Arc::new(notifiers.apply(DualControlKms::new(config.clone()).unwrap()
+        .with_approvers(vec![
+            Approver { id: "approver1".into(), public_key: pk1 },
+            Approver { id: "approver2".into(), public_key: pk2 },
+        ])
+        .unwrap()).unwrap());
+    let key = kms.generate_key(b"tenant", b"policy", KeyOptions::default()).await.unwrap();
+
+    let (server_identity, server_key) = identity("kmsd");
+    let (edge_identity, edge_key) = identity("edge-1");
+    let (admin_identity, admin_key) = identity("console");
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let app = router(kms, DaemonConfig {
+        identity: server_identity,
+        clients: vec![edge_key],
+        client_tenants: HashMap::from([("edge-1".to_string(), TenantScope::Any)]),
+        admins: vec![admin_key],
+        max_skew: Duration::from_secs(30),
+    }).unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let edge = RemoteKms::new(&endpoint, &config, edge_identity, server_key.clone()).unwrap();
+    let admin = RemoteKms::new(&endpoint, &config, admin_identity, server_key).unwrap();
+
+    let (_, public_key) = edge.public_key(b"tenant", b"policy", None).await.unwrap();
+    let (ciphertext, _) = kem::kyber768_encapsulate(&public_key).unwrap();
+    let request_id = DualControlKms::request_id(&ciphertext, b"policy", b"tenant", "/").unwrap();
+    edge.submit_request(RequestSubmission {
+        kem_ciphertext: ciphertext.clone(),
+        tenant_id: b"tenant".to_vec(),
+        policy_id: b"policy".to_vec(),
+        path: "/".into(),
+        requester: "client-1".into(),
+        envelope_hash: "ab".repeat(32),
+    }).await.unwrap();
+    let logged = std::fs::read_to_string(&events).unwrap();
+    assert_eq!(logged.lines().count(), 1);
+    assert!(logged.contains(r#""event":"requested""#));
+    let decrypt = || edge.dual_decrypt(&ciphertext, Some(&key.kid), b"policy", b"tenant", "/");
+    assert_eq!(decrypt().await.unwrap_err().code(), ErrorCode::QuorumNotMet);
+
+    // Edges cannot review requests; admins can
+    let err = edge.list_requests(None).await.unwrap_err();
+    assert!(err.to_string().contains("requires an admin identity"));
+    let pending = admin.list_requests(Some(RequestStatus::Pending)).await.unwrap();
+    assert_eq!(pending.len(), 1);
+    // Named after the authenticated client, not what it claimed
+    assert_eq!(pending[0].request_id, hex::encode(request_id));
+    assert_eq!(pending[0].context.requester, "edge-1");
+
+    for (approver, sk) in [("approver1", &sk1), ("approver2", &sk2)] {
+        let approval = QuorumApproval::sign(&request_id, b"policy", approver, 60, sk).unwrap();
+        admin.approve(approval, "expected batch job").await.unwrap();
+    }
+    assert!(decrypt().await.is_ok());
+
+    let denial = QuorumDenial::sign(&request_id, "approver1", "revoked", &sk1).unwrap();
+    assert_eq!(admin.deny(denial).await.unwrap().status, RequestStatus::Denied);
+    assert_eq!(decrypt().await.unwrap_err().code(), ErrorCode::RequestDenied);
+}
+
+#[tokio::test]
+async fn test_custodians_unseal_hsm_b_over_loopback() {
+    let custodians: Vec<String> = ["alice", "bob", "carol"].iter().map(|c| c.to_string()).collect();
+    let secret = shamir::generate_secret().unwrap();
+    let shares = shamir::split(&secret, 2, &custodians).unwrap();
+    let config = DualControlConfig {
+        hsm_b_endpoint: format!("threshold://{}?threshold=2", shamir::secret_id(&secret).unwrap()),
+        require_quorum: false,
+        timeout_ms: 2000,
+        ..Default::default()
+    };
+    let kms = Arc::new(DualControlKms::connect(config.clone()).unwrap());
+    let key = kms.generate_key(b"tenant", b"policy", KeyOptions::default()).await.unwrap();
+
+    let (server_identity, server_key) = identity("kmsd");
+    let (edge_identity, edge_key) = identity("edge-1");
+    let (admin_identity, admin_key) = identity("console");
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let app = router(kms, DaemonConfig {
+        identity: server_identity,
+        clients: vec![edge_key],
+        client_tenants: HashMap::from([("edge-1".to_string(), TenantScope::Any)]),
+        admins: vec![admin_key],
+        max_skew: Duration::from_secs(30),
+    }).unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let edge = RemoteKms::new(&endpoint, &config, edge_identity, server_key.clone()).unwrap();
+    let admin = RemoteKms::new(&endpoint, &config, admin_identity, server_key).unwrap();
+
+    let (_, public_key) = edge.public_key(b"tenant", b"policy", None).await.unwrap();
+    let (ciphertext, _) = kem::kyber768_encapsulate(&public_key).unwrap();
+    let decrypt = || edge.dual_decrypt(&ciphertext, Some(&key.kid), b"policy", b"tenant", "/");
+
+    // Sealed until enough custodians have submitted their shares
+    assert_eq!(decrypt().await.unwrap_err().code(), ErrorCode::KmsUnavailable);
+    let err = edge.submit_share(&shares[0]).await.unwrap_err();
+    assert!(err.to_string().contains("requires an admin identity"));
+    assert_eq!(admin.submit_share(&shares[0]).await.unwrap(), 1);
+    assert_eq!(decrypt().await.unwrap_err().code(), ErrorCode::KmsUnavailable);
+
+    assert_eq!(admin.submit_share(&shares[2]).await.unwrap(), 2);
+    assert!(decrypt().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_clients_are_limited_to_their_tenants() {
+    let config = DualControlConfig {
+        require_quorum: false,
+        timeout_ms: 2000,
+        ..Default::default()
+    };
+    let kms = Arc::new(DualControlKms::new(config.clone()).unwrap());
+    kms.generate_key(b"tenant", b"policy", KeyOptions::default()).await.unwrap();
+    let other = kms.generate_key(b"other", b"policy", KeyOptions::default()).await.unwrap();
+
+    let (server_identity, server_key) = identity("kmsd");
+    let (edge_identity, edge_key) = identity("edge-1");
+    let (unscoped_identity, unscoped_key) = identity("edge-2");
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let app = router(kms.clone(), DaemonConfig {
+        identity: server_identity,
+        clients: vec![edge_key, unscoped_key],
+        client_tenants: HashMap::from([("edge-1".to_string(), TenantScope::Tenants(vec![b"tenant".to_vec()]))]),
+        admins: vec![],
+        max_skew: Duration::from_secs(30),
+    }).unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let edge = RemoteKms::new(&endpoint, &config, edge_identity, server_key.clone()).unwrap();
+    let unscoped = RemoteKms::new(&endpoint, &config, unscoped_identity, server_key).unwrap();
+    let refused = |err: benteng_sdk_core::BentengError| err.to_string().contains("outside the client's scope");
+
+    // Its own tenant works
+    let wrapped = edge.generate_dek(None, b"policy", b"tenant", "/").await.unwrap();
+    edge.dual_decrypt(&wrapped.kem_ciphertext, Some(&wrapped.kid), b"policy", b"tenant", "/").await.unwrap();
+
+    // Other tenants' keys are out of reach
+    let foreign = kms.generate_dek(None, b"policy", b"other", "/").await.unwrap();
+    assert!(refused(edge.public_key(b"other", b"policy", None).await.unwrap_err()));
+    assert!(refused(edge.public_key(b"tenant", b"policy", Some(&other.kid)).await.unwrap_err()));
+    assert!(refused(edge.generate_dek(None, b"policy", b"other", "/").await.unwrap_err()));
+    let err = edge.dual_decrypt(&foreign.kem_ciphertext, Some(&other.kid), b"policy", b"other", "/").await.unwrap_err();
+    assert!(refused(err));
+    let err = edge.rewrap(RewrapRequest {
+        kem_ciphertext: foreign.kem_ciphertext.clone(),
+        wrapped_dek: None,
+        kid: Some(other.kid.clone()),
+        new_kid: None,
+        tenant_id: b"other".to_vec(),
+        policy_id: b"policy".to_vec(),
+        path: "/".into(),
+    }).await.unwrap_err();
+    assert!(refused(err));
+
+    // Nor can it describe their requests to approvers
+    let err = edge.submit_request(RequestSubmission {
+        kem_ciphertext: foreign.kem_ciphertext.clone(),
+        tenant_id: b"other".to_vec(),
+        policy_id: b"policy".to_vec(),
+        path: "/".into(),
+        requester: "edge-1".into(),
+        envelope_hash: "ab".repeat(32),
+    }).await.unwrap_err();
+    assert!(refused(err));
+
+    // Caching settings only for its own tenant
+    edge.set_policy_caching(b"tenant", b"policy", false).await.unwrap();
+    assert!(refused(edge.set_policy_caching(b"other", b"policy", false).await.unwrap_err()));
+
+    // A client without a scope gets no tenant at all
+    assert!(refused(unscoped.public_key(b"tenant", b"policy", None).await.unwrap_err()));
+}
+
+#[tokio::test]
+async fn test_daemon_spans_join_the_callers_trace() {
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    use tracing::Instrument;
+    use tracing_subscriber::prelude::*;
+
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("loopback")));
+    // The daemon runs on the test's thread, so it sees the same subscriber
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let config = DualControlConfig { require_quorum: false, timeout_ms: 2000, ..Default::default() };
+    let kms = Arc::new(DualControlKms::new(config.clone()).unwrap());
+    kms.generate_key(b"tenant", b"policy", KeyOptions::default()).await.unwrap();
+
+    let (server_identity, server_key) = identity("kmsd");
+    let (edge_identity, edge_key) = identity("edge-1");
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let app = router(kms, DaemonConfig {
+        identity: server_identity,
+        clients: vec![edge_key],
+        client_tenants: HashMap::from([("edge-1".to_string(), TenantScope::Any)]),
+        admins: vec![],
+        max_skew: Duration::from_secs(30),
+    }).unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    let remote = RemoteKms::new(&endpoint, &config, edge_identity, server_key).unwrap();
+
+    remote.public_key(b"tenant", b"policy", None)
+        .instrument(tracing::info_span!("edge"))
+        .await
+        .unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let edge = spans.iter().find(|span| span.name == "edge").unwrap();
+    let daemon = spans.iter().find(|span| span.name == "kms_request").unwrap();
+    assert_eq!(daemon.span_context.trace_id(), edge.span_context.trace_id());
+    assert_eq!(daemon.parent_span_id, edge.span_context.span_id());
+    assert!(daemon.attributes.iter().any(|kv| kv.key.as_str() == "kms.client" && kv.value.as_str() == "edge-1"));
+}
@@ -1,3 +1,6 @@
+// These envelopes are only verified, never decrypted through the KMS
+#![allow(deprecated)]
+
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
 use benteng_sdk_core::{
     envelope::{Envelope, operations::EnvelopeOps},
@@ -20,6 +20,7 @@ pub struct Aad {
 
 impl Aad {
     /// Build AAD from envelope components
+    #[allow(clippy::too_many_arguments)]
     pub fn build(
         ver: u8,
         tenant_id: &[u8],
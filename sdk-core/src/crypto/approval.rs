@@ -0,0 +1,178 @@
+//! Pending-request registry and approver notifications for HSM-B
+//! Every request that needs a quorum is recorded with its context so
+//! approvers can see what they are approving or denying.
+
+use crate::error::BentengError;
+use crate::crypto::kms::DualControlKms;
+use crate::envelope::Envelope;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+type Result<T> = std::result::Result<T, BentengError>;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// What a request asks HSM-B to release a K2 for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestContext {
+    pub tenant_id: String,
+    pub policy_id: String,
+    pub path: String,
+    pub requester: String,
+    /// Hex SHA-256 of the CBOR envelope
+    pub envelope_hash: String,
+}
+
+/// A decryption submitted for review. The KMS derives the request ID and
+/// the context approvers see from these inputs, so a submission cannot
+/// describe another request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestSubmission {
+    pub kem_ciphertext: Vec<u8>,
+    pub tenant_id: Vec<u8>,
+    pub policy_id: Vec<u8>,
+    pub path: String,
+    /// Replaced by the authenticated client ID when submitted to a daemon
+    pub requester: String,
+    /// Hex SHA-256 of the CBOR envelope, as reported by the requester
+    pub envelope_hash: String,
+}
+
+impl RequestSubmission {
+    pub fn from_envelope(envelope: &Envelope, requester: &str) -> Result<Self> {
+        Ok(Self {
+            kem_ciphertext: envelope.kem_ct.clone(),
+            tenant_id: envelope.tenant_id.clone(),
+            policy_id: envelope.policy_id.clone(),
+            path: envelope.path.clone(),
+            requester: requester.to_string(),
+            envelope_hash: hex::encode(Sha256::digest(envelope.to_cbor()?)),
+        })
+    }
+
+    /// Quorum request ID approvers sign over
+    pub fn request_id(&self) -> Result<[u8; 32]> {
+        DualControlKms::request_id(&self.kem_ciphertext, &self.policy_id, &self.tenant_id, &self.path)
+    }
+
+    pub fn context(&self) -> RequestContext {
+        RequestContext {
+            tenant_id: String::from_utf8_lossy(&self.tenant_id).into_owned(),
+            policy_id: String::from_utf8_lossy(&self.policy_id).into_owned(),
+            path: self.path.clone(),
+            requester: self.requester.clone(),
+            envelope_hash: self.envelope_hash.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestStatus {
+    Pending,
+    /// The quorum has been reached
+    Approved,
+    /// An approver denied the request; it can no longer be approved
+    Denied,
+}
+
+/// One approver's approval or denial
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decision {
+    pub approver: String,
+    pub approved: bool,
+    pub reason: String,
+    pub decided_at: u64,
+}
+
+/// A request waiting for, or decided by, the quorum
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingRequest {
+    /// Hex request ID that approvals are signed over
+    pub request_id: String,
+    pub context: RequestContext,
+    pub created_at: u64,
+    pub status: RequestStatus,
+    pub decisions: Vec<Decision>,
+}
+
+impl PendingRequest {
+    pub fn new(request_id: &[u8], context: RequestContext) -> Self {
+        Self {
+            request_id: hex::encode(request_id),
+            context,
+            created_at: now_secs(),
+            status: RequestStatus::Pending,
+            decisions: vec![],
+        }
+    }
+
+    pub(crate) fn record(&mut self, approver: &str, approved: bool, reason: &str) {
+        self.decisions.push(Decision {
+            approver: approver.to_string(),
+            approved,
+            reason: reason.to_string(),
+            decided_at: now_secs(),
+        });
+    }
+
+    /// The first denial, if any
+    pub fn denial(&self) -> Option<&Decision> {
+        self.decisions.iter().find(|d| !d.approved)
+    }
+}
+
+/// Workflow event sent to notifiers
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case", tag = "event")]
+pub enum ApprovalEvent {
+    Requested { request: PendingRequest },
+    Approved { request: PendingRequest, approver: String },
+    Denied { request: PendingRequest, approver: String },
+    QuorumReached { request: PendingRequest },
+}
+
+/// Receives approval workflow events, e.g. to page approvers
+pub trait ApprovalNotifier: Send + Sync {
+    fn notify(&self, event: &ApprovalEvent) -> Result<()>;
+}
+
+/// Appends events to a local file as JSON lines
+pub struct FileNotifier {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileNotifier {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl ApprovalNotifier for FileNotifier {
+    fn notify(&self, event: &ApprovalEvent) -> Result<()> {
+        let mut line = serde_json::to_vec(event).map_err(|_| BentengError::InternalError)?;
+        line.push(b'\n');
+
+        let _guard = self.lock.lock().map_err(|_| BentengError::InternalError)?;
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(&line))
+            .map_err(|e| BentengError::KmsError(format!("Approval event sink: {}", e)))
+    }
+}
@@ -0,0 +1,289 @@
+//! Bounded LRU cache of derived DEKs
+//! Entries are keyed by a hash of the decryption inputs, expire after a TTL
+//! and are zeroized on eviction. Entries can be dropped by tenant, KID or
+//! quorum request, and caching can be switched off for a tenant's policy.
+
+use crate::error::BentengError;
+
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+type Result<T> = std::result::Result<T, BentengError>;
+
+/// Inputs a cached DEK was derived from
+#[derive(Debug, Clone, Copy)]
+pub struct DekCacheKey<'a> {
+    pub kid: &'a str,
+    pub kem_ciphertext: &'a [u8],
+    pub tenant_id: &'a [u8],
+    pub policy_id: &'a [u8],
+    pub path: &'a str,
+    /// Quorum request the DEK was released for. Derived from the other
+    /// inputs, so it is not part of the digest.
+    pub request_id: &'a [u8],
+}
+
+impl DekCacheKey<'_> {
+    /// SHA-256 over the length-prefixed inputs
+    fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for field in [
+            self.kid.as_bytes(),
+            self.kem_ciphertext,
+            self.tenant_id,
+            self.policy_id,
+            self.path.as_bytes(),
+        ] {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field);
+        }
+        hasher.finalize().into()
+    }
+}
+
+struct Entry {
+    tenant_id: Vec<u8>,
+    policy_id: Vec<u8>,
+    kid: String,
+    request_id: Vec<u8>,
+    expires_at: Instant,
+    dek: Zeroizing<[u8; 32]>,
+}
+
+/// Cache counters since creation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DekCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped for capacity or expiry
+    pub evictions: u64,
+    /// Entries dropped by explicit invalidation
+    pub invalidations: u64,
+    pub entries: usize,
+}
+
+/// LRU/TTL cache of DEKs. A capacity of zero disables caching.
+pub struct DekCache {
+    entries: Option<Mutex<LruCache<[u8; 32], Entry>>>,
+    ttl: Duration,
+    /// Tenant and policy IDs whose DEKs are never cached
+    uncached_policies: RwLock<HashSet<(Vec<u8>, Vec<u8>)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl DekCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: NonZeroUsize::new(capacity).map(|capacity| Mutex::new(LruCache::new(capacity))),
+            ttl,
+            uncached_policies: RwLock::new(HashSet::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    fn policy_cached(&self, tenant_id: &[u8], policy_id: &[u8]) -> bool {
+        self.uncached_policies.read().is_ok_and(|uncached| {
+            !uncached.contains(&(tenant_id.to_vec(), policy_id.to_vec()))
+        })
+    }
+
+    /// Turn caching on or off for a tenant's policy. Disabling it drops the
+    /// policy's cached DEKs for that tenant.
+    pub fn set_policy_caching(&self, tenant_id: &[u8], policy_id: &[u8], enabled: bool) -> Result<()> {
+        let scope = (tenant_id.to_vec(), policy_id.to_vec());
+        let mut uncached = self.uncached_policies.write().map_err(|_| BentengError::InternalError)?;
+        if enabled {
+            uncached.remove(&scope);
+            return Ok(());
+        }
+        if uncached.insert(scope) {
+            drop(uncached);
+            self.invalidate(|entry| entry.tenant_id == tenant_id && entry.policy_id == policy_id)?;
+        }
+        Ok(())
+    }
+
+    /// Cached DEK, if present and unexpired
+    pub fn get(&self, key: &DekCacheKey<'_>) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        let Some(entries) = &self.entries else {
+            return Ok(None);
+        };
+        if !self.policy_cached(key.tenant_id, key.policy_id) {
+            return Ok(None);
+        }
+
+        let digest = key.digest();
+        let mut entries = entries.lock().map_err(|_| BentengError::InternalError)?;
+        let found = match entries.get(&digest) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.dek.clone()),
+            Some(_) => {
+                entries.pop(&digest);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => None,
+        };
+
+        let counter = if found.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        Ok(found)
+    }
+
+    /// Cache a DEK, evicting the least recently used entry when full
+    pub fn insert(&self, key: &DekCacheKey<'_>, dek: &[u8; 32]) -> Result<()> {
+        let Some(entries) = &self.entries else {
+            return Ok(());
+        };
+        if !self.policy_cached(key.tenant_id, key.policy_id) {
+            return Ok(());
+        }
+
+        let digest = key.digest();
+        let entry = Entry {
+            tenant_id: key.tenant_id.to_vec(),
+            policy_id: key.policy_id.to_vec(),
+            kid: key.kid.to_string(),
+            request_id: key.request_id.to_vec(),
+            expires_at: Instant::now() + self.ttl,
+            dek: Zeroizing::new(*dek),
+        };
+
+        let mut entries = entries.lock().map_err(|_| BentengError::InternalError)?;
+        if let Some((evicted, _)) = entries.push(digest, entry) {
+            if evicted != digest {
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    fn invalidate(&self, matches: impl Fn(&Entry) -> bool) -> Result<usize> {
+        let Some(entries) = &self.entries else {
+            return Ok(0);
+        };
+
+        let mut entries = entries.lock().map_err(|_| BentengError::InternalError)?;
+        let doomed: Vec<[u8; 32]> = entries.iter()
+            .filter(|(_, entry)| matches(entry))
+            .map(|(digest, _)| *digest)
+            .collect();
+        for digest in &doomed {
+            entries.pop(digest);
+        }
+
+        self.invalidations.fetch_add(doomed.len() as u64, Ordering::Relaxed);
+        Ok(doomed.len())
+    }
+
+    /// Drop every DEK of a tenant, returning how many were dropped
+    pub fn invalidate_tenant(&self, tenant_id: &[u8]) -> Result<usize> {
+        self.invalidate(|entry| entry.tenant_id == tenant_id)
+    }
+
+    /// Drop every DEK unwrapped with a key
+    pub fn invalidate_kid(&self, kid: &str) -> Result<usize> {
+        self.invalidate(|entry| entry.kid == kid)
+    }
+
+    /// Drop the DEK released for a quorum request, e.g. once it is denied
+    pub fn invalidate_request(&self, request_id: &[u8]) -> Result<usize> {
+        self.invalidate(|entry| entry.request_id == request_id)
+    }
+
+    pub fn clear(&self) -> Result<usize> {
+        self.invalidate(|_| true)
+    }
+
+    pub fn stats(&self) -> DekCacheStats {
+        DekCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            entries: self.entries.as_ref()
+                .and_then(|entries| entries.lock().ok().map(|entries| entries.len()))
+                .unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key<'a>(kid: &'a str, tenant_id: &'a [u8], policy_id: &'a [u8]) -> DekCacheKey<'a> {
+        DekCacheKey {
+            kid,
+            kem_ciphertext: b"ct",
+            tenant_id,
+            policy_id,
+            path: "/",
+            request_id: tenant_id,
+        }
+    }
+
+    #[test]
+    fn test_lru_eviction_and_invalidation() {
+        let cache = DekCache::new(2, Duration::from_secs(60));
+        let (a, b, c) = (key("k1", b"t1", b"p"), key("k1", b"t2", b"p"), key("k2", b"t1", b"p"));
+
+        assert!(cache.get(&a).unwrap().is_none());
+        cache.insert(&a, &[1; 32]).unwrap();
+        cache.insert(&b, &[2; 32]).unwrap();
+        assert_eq!(*cache.get(&a).unwrap().unwrap(), [1; 32]);
+
+        // b is least recently used
+        cache.insert(&c, &[3; 32]).unwrap();
+        assert!(cache.get(&b).unwrap().is_none());
+        assert_eq!(cache.stats(), DekCacheStats { hits: 1, misses: 2, evictions: 1, invalidations: 0, entries: 2 });
+
+        assert_eq!(cache.invalidate_kid("k2").unwrap(), 1);
+        cache.insert(&b, &[2; 32]).unwrap();
+        assert_eq!(cache.invalidate_tenant(b"t1").unwrap(), 1);
+        assert!(cache.get(&a).unwrap().is_none());
+        assert!(cache.get(&b).unwrap().is_some());
+        assert_eq!(cache.invalidate_request(b"t2").unwrap(), 1);
+        assert!(cache.get(&b).unwrap().is_none());
+        cache.insert(&b, &[2; 32]).unwrap();
+
+        // Only t2 stops caching p
+        cache.insert(&a, &[1; 32]).unwrap();
+        cache.set_policy_caching(b"t2", b"p", false).unwrap();
+        assert_eq!(cache.stats().entries, 1);
+        cache.insert(&b, &[2; 32]).unwrap();
+        assert!(cache.get(&b).unwrap().is_none());
+        assert!(cache.get(&a).unwrap().is_some());
+        cache.set_policy_caching(b"t1", b"p", false).unwrap();
+        assert_eq!(cache.stats().entries, 0);
+        cache.insert(&a, &[1; 32]).unwrap();
+        assert!(cache.get(&a).unwrap().is_none());
+        cache.set_policy_caching(b"t1", b"p", true).unwrap();
+        cache.insert(&a, &[1; 32]).unwrap();
+        assert!(cache.get(&a).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_expired_entries_are_evicted() {
+        let cache = DekCache::new(4, Duration::ZERO);
+        let a = key("k1", b"t1", b"p");
+        cache.insert(&a, &[1; 32]).unwrap();
+        assert!(cache.get(&a).unwrap().is_none());
+        assert_eq!(cache.stats().evictions, 1);
+
+        let disabled = DekCache::new(0, Duration::from_secs(60));
+        disabled.insert(&a, &[1; 32]).unwrap();
+        assert!(disabled.get(&a).unwrap().is_none());
+    }
+}
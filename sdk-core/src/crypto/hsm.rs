@@ -0,0 +1,443 @@
+//! HSM backends for the dual-control KMS
+//! Secret keys stay behind `HsmBackend`; the KMS only sees public keys and
+//! the derived K1/K2 halves.
+
+use crate::error::BentengError;
+use crate::crypto::kdf::hkdf_sha256_derive;
+use crate::crypto::kem::{kyber768_keypair, kyber768_decapsulate, kyber768_encapsulate};
+use crate::crypto::shamir::{self, CustodianShare};
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::{Zeroize, Zeroizing};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+type Result<T> = std::result::Result<T, BentengError>;
+
+/// Endpoint scheme of the in-process mock HSM
+pub const MOCK_SCHEME: &str = "mock://";
+
+/// Endpoint scheme of a `ThresholdHsm`:
+/// `threshold://<secret_id>?threshold=<n>[&idle_secs=<s>]`
+pub const THRESHOLD_SCHEME: &str = "threshold://";
+
+/// Key operations performed inside an HSM. A hardware or PKCS#11 token
+/// implements these with non-extractable keys; nothing here returns secret
+/// key material.
+pub trait HsmBackend: Send + Sync {
+    /// Public half of the KEM key pair for a KID
+    fn public_key(&self, kid: &str) -> Result<Vec<u8>>;
+
+    /// HSM-A: Kyber decapsulation followed by HKDF1
+    fn derive_k1(&self, kid: &str, kem_ciphertext: &[u8]) -> Result<[u8; 32]>;
+
+    /// HSM-A: Kyber encapsulation to `kid` followed by HKDF1, returning the
+    /// KEM ciphertext and the K1 that `derive_k1` will recover from it
+    fn encapsulate_k1(&self, kid: &str) -> Result<(Vec<u8>, [u8; 32])> {
+        let (kem_ciphertext, shared_secret) = kyber768_encapsulate(&self.public_key(kid)?)?;
+        Ok((kem_ciphertext, k1_from_shared_secret(&*shared_secret)?))
+    }
+
+    /// HSM-B: HKDF2 over the request context
+    fn derive_k2(&self, context: &[u8]) -> Result<[u8; 32]>;
+
+    /// Generate a KEM key pair under `kid`, returning the public key
+    fn generate_key(&self, kid: &str) -> Result<Vec<u8>> {
+        let _ = kid;
+        Err(BentengError::KmsError("Key generation not supported by this HSM".into()))
+    }
+
+    /// Destroy the key pair under `kid`
+    fn destroy_key(&self, kid: &str) -> Result<()> {
+        let _ = kid;
+        Err(BentengError::KmsError("Key destruction not supported by this HSM".into()))
+    }
+
+    /// Accept a custodian's share of the master secret, returning how many
+    /// are held
+    fn submit_share(&self, share: CustodianShare) -> Result<usize> {
+        let _ = share;
+        Err(BentengError::KmsError("This HSM takes no custodian shares".into()))
+    }
+}
+
+/// Backend for an HSM endpoint: `mock://`, `threshold://` or, with the
+/// `pkcs11` feature, a `pkcs11:` token URI
+pub fn open(endpoint: &str) -> Result<Arc<dyn HsmBackend>> {
+    if endpoint.starts_with(MOCK_SCHEME) {
+        return Ok(Arc::new(MockHsm::new()));
+    }
+    if let Some(rest) = endpoint.strip_prefix(THRESHOLD_SCHEME) {
+        let parsed = rest.split_once('?')
+            .and_then(|(secret_id, query)| {
+                let (mut threshold, mut idle_timeout) = (None, DEFAULT_IDLE_SEAL);
+                for pair in query.split('&') {
+                    match pair.split_once('=')? {
+                        ("threshold", n) => threshold = Some(n.parse::<u8>().ok()?),
+                        ("idle_secs", secs) => idle_timeout = Duration::from_secs(secs.parse().ok()?),
+                        _ => return None,
+                    }
+                }
+                Some((secret_id, threshold?, idle_timeout))
+            })
+            .filter(|(secret_id, _, _)| !secret_id.is_empty());
+        let Some((secret_id, threshold, idle_timeout)) = parsed else {
+            return Err(BentengError::KmsError(format!(
+                "Threshold HSM endpoints are {}<secret_id>?threshold=<n>[&idle_secs=<s>]: {}", THRESHOLD_SCHEME, endpoint
+            )));
+        };
+        return Ok(Arc::new(ThresholdHsm::new(secret_id, threshold).with_idle_timeout(idle_timeout)));
+    }
+    #[cfg(feature = "pkcs11")]
+    if endpoint.starts_with(crate::crypto::pkcs11::PKCS11_SCHEME) {
+        return Ok(Arc::new(crate::crypto::pkcs11::Pkcs11Hsm::open(endpoint)?));
+    }
+    Err(BentengError::KmsError(format!("Unsupported HSM endpoint: {}", endpoint)))
+}
+
+/// Backend for the HSM-A endpoint. Same schemes as `open`, but a `pkcs11:`
+/// token is refused unless its endpoint opts in to software Kyber.
+pub fn open_hsm_a(endpoint: &str) -> Result<Arc<dyn HsmBackend>> {
+    #[cfg(feature = "pkcs11")]
+    if endpoint.starts_with(crate::crypto::pkcs11::PKCS11_SCHEME) {
+        return Ok(Arc::new(crate::crypto::pkcs11::Pkcs11Hsm::open_hsm_a(endpoint)?));
+    }
+    open(endpoint)
+}
+
+fn to_key(derived: Zeroizing<Vec<u8>>) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&derived);
+    key
+}
+
+/// HKDF1 with HSM-A specific domain separation
+pub(crate) fn k1_from_shared_secret(shared_secret: &[u8]) -> Result<[u8; 32]> {
+    Ok(to_key(hkdf_sha256_derive(
+        shared_secret,
+        Some(b"benteng/hsm-a/k1/v1"),
+        b"",
+        32
+    )?))
+}
+
+/// Mock HSM key storage
+struct HsmKeyPair {
+    public_key: Vec<u8>,
+    secret_key: Zeroizing<Vec<u8>>,
+}
+
+/// In-process HSM for tests and development. Keys live in process memory,
+/// and K2 is derived from the request context alone, so anyone can compute it.
+#[derive(Default)]
+pub struct MockHsm {
+    keys: RwLock<HashMap<String, HsmKeyPair>>,
+}
+
+impl MockHsm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generate a Kyber768 key pair under `kid`
+    pub fn generate(&self, kid: &str) -> Result<()> {
+        let (public_key, secret_key) = kyber768_keypair()?;
+        let mut keys = self.keys.write().map_err(|_| BentengError::InternalError)?;
+        keys.insert(kid.to_string(), HsmKeyPair {
+            public_key,
+            secret_key,
+        });
+        Ok(())
+    }
+}
+
+impl HsmBackend for MockHsm {
+    fn public_key(&self, kid: &str) -> Result<Vec<u8>> {
+        let keys = self.keys.read().map_err(|_| BentengError::InternalError)?;
+        let pair = keys.get(kid)
+            .ok_or_else(|| BentengError::KeyNotFound("Key not found".into()))?;
+        Ok(pair.public_key.clone())
+    }
+
+    fn derive_k1(&self, kid: &str, kem_ciphertext: &[u8]) -> Result<[u8; 32]> {
+        let keys = self.keys.read().map_err(|_| BentengError::InternalError)?;
+        let pair = keys.get(kid)
+            .ok_or_else(|| BentengError::KeyNotFound("KEM key not found in HSM-A".into()))?;
+
+        // Decapsulate to get shared secret
+        let shared_secret = kyber768_decapsulate(&pair.secret_key, kem_ciphertext)?;
+
+        k1_from_shared_secret(&*shared_secret)
+    }
+
+    fn generate_key(&self, kid: &str) -> Result<Vec<u8>> {
+        self.generate(kid)?;
+        self.public_key(kid)
+    }
+
+    fn destroy_key(&self, kid: &str) -> Result<()> {
+        let mut keys = self.keys.write().map_err(|_| BentengError::InternalError)?;
+        keys.remove(kid)
+            .map(|_| ())
+            .ok_or_else(|| BentengError::KeyNotFound("Key not found".into()))
+    }
+
+    fn derive_k2(&self, context: &[u8]) -> Result<[u8; 32]> {
+        Ok(to_key(hkdf_sha256_derive(
+            context,
+            Some(b"benteng/hsm-b/k2/v1"),
+            b"",
+            32
+        )?))
+    }
+}
+
+/// Idle time after which an unsealed `ThresholdHsm` seals itself
+pub const DEFAULT_IDLE_SEAL: Duration = Duration::from_secs(900);
+
+const K2_SALT: &[u8] = b"benteng/hsm-b/k2/v2";
+
+enum Seal {
+    /// Shares submitted so far, below the threshold
+    Sealed(Vec<CustodianShare>),
+    /// HKDF2 pseudorandom key extracted from the reconstructed secret
+    Unsealed {
+        prk: Zeroizing<[u8; 32]>,
+        last_used: Instant,
+        epoch: u64,
+    },
+}
+
+/// HSM-B whose K2 derivation needs a master secret split across custodians.
+/// Custodians submit their shares to unseal it. Once the threshold is
+/// reached the secret is reconstructed once, reduced to the HKDF2 key K2s
+/// are expanded from, and it and the shares are zeroized. The HSM seals
+/// itself again after `idle_timeout` without a derivation.
+pub struct ThresholdHsm {
+    secret_id: String,
+    threshold: u8,
+    idle_timeout: Duration,
+    state: Arc<Mutex<Seal>>,
+    unseals: AtomicU64,
+}
+
+impl ThresholdHsm {
+    /// Sealed HSM for the share set identified by `secret_id`
+    pub fn new(secret_id: impl Into<String>, threshold: u8) -> Self {
+        Self {
+            secret_id: secret_id.into(),
+            threshold,
+            idle_timeout: DEFAULT_IDLE_SEAL,
+            state: Arc::new(Mutex::new(Seal::Sealed(Vec::new()))),
+            unseals: AtomicU64::new(0),
+        }
+    }
+
+    /// Seal after `idle_timeout` without a derivation instead of
+    /// `DEFAULT_IDLE_SEAL`
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Accept a custodian's share, returning how many are held. The share
+    /// that reaches the threshold unseals the HSM; if the shares do not
+    /// reconstruct the secret, all are discarded. Shares submitted while
+    /// unsealed are dropped.
+    pub fn submit_share(&self, share: CustodianShare) -> Result<usize> {
+        if share.secret_id != self.secret_id || share.threshold != self.threshold {
+            return Err(BentengError::KmsError(format!("Share from {} belongs to another secret", share.custodian)));
+        }
+
+        let mut state = self.state.lock().map_err(|_| BentengError::InternalError)?;
+        self.seal_if_idle(&mut state);
+        let Seal::Sealed(shares) = &mut *state else {
+            return Ok(self.threshold as usize);
+        };
+        shares.retain(|s| s.custodian != share.custodian);
+        shares.push(share);
+        if shares.len() < self.threshold as usize {
+            return Ok(shares.len());
+        }
+
+        let secret = shamir::combine(shares);
+        shares.clear();
+        let (mut extracted, _) = Hkdf::<Sha256>::extract(Some(K2_SALT), &*secret?);
+        let mut prk = Zeroizing::new([0u8; 32]);
+        prk.copy_from_slice(&extracted);
+        extracted.as_mut_slice().zeroize();
+
+        let epoch = self.unseals.fetch_add(1, Ordering::Relaxed) + 1;
+        *state = Seal::Unsealed {
+            prk,
+            last_used: Instant::now(),
+            epoch,
+        };
+        self.watch_idle(epoch);
+        Ok(self.threshold as usize)
+    }
+
+    /// Seal once the HSM has idled for `idle_timeout`, even if nothing
+    /// calls it again
+    fn watch_idle(&self, epoch: u64) {
+        let state = Arc::downgrade(&self.state);
+        let idle_timeout = self.idle_timeout;
+        std::thread::spawn(move || loop {
+            let wait = {
+                let Some(state) = state.upgrade() else { return };
+                let Ok(mut state) = state.lock() else { return };
+                match &*state {
+                    Seal::Unsealed { last_used, epoch: current, .. } if *current == epoch => {
+                        let idle = last_used.elapsed();
+                        if idle >= idle_timeout {
+                            *state = Seal::Sealed(Vec::new());
+                            tracing::info!("Threshold HSM-B sealed after {:?} idle", idle_timeout);
+                            return;
+                        }
+                        idle_timeout - idle
+                    }
+                    // Sealed, or unsealed again with its own watcher
+                    _ => return,
+                }
+            };
+            std::thread::sleep(wait);
+        });
+    }
+
+    fn seal_if_idle(&self, state: &mut Seal) {
+        if let Seal::Unsealed { last_used, .. } = state {
+            if last_used.elapsed() >= self.idle_timeout {
+                *state = Seal::Sealed(Vec::new());
+            }
+        }
+    }
+
+    pub fn is_unsealed(&self) -> bool {
+        self.state.lock().is_ok_and(|mut state| {
+            self.seal_if_idle(&mut state);
+            matches!(*state, Seal::Unsealed { .. })
+        })
+    }
+
+    /// Drop the derived key and any submitted shares
+    pub fn seal(&self) -> Result<()> {
+        *self.state.lock().map_err(|_| BentengError::InternalError)? = Seal::Sealed(Vec::new());
+        Ok(())
+    }
+}
+
+impl HsmBackend for ThresholdHsm {
+    fn public_key(&self, _kid: &str) -> Result<Vec<u8>> {
+        Err(BentengError::KmsError("Threshold HSM-B holds no KEM keys".into()))
+    }
+
+    fn derive_k1(&self, _kid: &str, _kem_ciphertext: &[u8]) -> Result<[u8; 32]> {
+        Err(BentengError::KmsError("Threshold HSM-B holds no KEM keys".into()))
+    }
+
+    fn submit_share(&self, share: CustodianShare) -> Result<usize> {
+        // The inherent method
+        ThresholdHsm::submit_share(self, share)
+    }
+
+    fn derive_k2(&self, context: &[u8]) -> Result<[u8; 32]> {
+        let mut state = self.state.lock().map_err(|_| BentengError::InternalError)?;
+        self.seal_if_idle(&mut state);
+        match &mut *state {
+            Seal::Sealed(shares) => Err(BentengError::KmsUnavailable(format!(
+                "HSM-B is sealed: {} of {} shares", shares.len(), self.threshold
+            ))),
+            Seal::Unsealed { prk, last_used, .. } => {
+                *last_used = Instant::now();
+                let hkdf = Hkdf::<Sha256>::from_prk(&**prk).map_err(|_| BentengError::InternalError)?;
+                let mut k2 = [0u8; 32];
+                hkdf.expand(context, &mut k2).map_err(|_| BentengError::InternalError)?;
+                Ok(k2)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_hsm_k1_matches_encapsulation() {
+        let hsm = MockHsm::new();
+        hsm.generate("kid-1").unwrap();
+
+        let public_key = hsm.public_key("kid-1").unwrap();
+        let (ciphertext, shared_secret) = crate::crypto::kem::kyber768_encapsulate(&public_key).unwrap();
+        let expected = hkdf_sha256_derive(&*shared_secret, Some(b"benteng/hsm-a/k1/v1"), b"", 32).unwrap();
+
+        assert_eq!(hsm.derive_k1("kid-1", &ciphertext).unwrap().to_vec(), *expected);
+        assert!(hsm.derive_k1("kid-2", &ciphertext).is_err());
+
+        let (ciphertext, k1) = hsm.encapsulate_k1("kid-1").unwrap();
+        assert_eq!(hsm.derive_k1("kid-1", &ciphertext).unwrap(), k1);
+    }
+
+    #[test]
+    fn test_threshold_hsm_needs_quorum_of_shares() {
+        let custodians: Vec<String> = ["alice", "bob", "carol"].iter().map(|c| c.to_string()).collect();
+        let secret = shamir::generate_secret().unwrap();
+        let shares = shamir::split(&secret, 2, &custodians).unwrap();
+
+        let hsm = ThresholdHsm::new(shamir::secret_id(&secret).unwrap(), 2);
+        assert!(hsm.derive_k2(b"context").is_err());
+        assert_eq!(hsm.submit_share(shares[0].clone()).unwrap(), 1);
+        assert_eq!(hsm.submit_share(shares[0].clone()).unwrap(), 1);
+        assert!(!hsm.is_unsealed());
+        assert_eq!(hsm.submit_share(shares[2].clone()).unwrap(), 2);
+
+        let expected = hkdf_sha256_derive(&*secret, Some(b"benteng/hsm-b/k2/v2"), b"context", 32).unwrap();
+        assert_eq!(hsm.derive_k2(b"context").unwrap().to_vec(), *expected);
+        assert_ne!(hsm.derive_k2(b"context").unwrap(), MockHsm::new().derive_k2(b"context").unwrap());
+
+        hsm.seal().unwrap();
+        assert!(matches!(hsm.derive_k2(b"context"), Err(BentengError::KmsUnavailable(_))));
+
+        // Shares of another secret are refused
+        let other = shamir::split(&shamir::generate_secret().unwrap(), 2, &custodians).unwrap();
+        assert!(hsm.submit_share(other[0].clone()).is_err());
+
+        // Built from an endpoint, shares go in through the backend
+        let endpoint = format!("threshold://{}?threshold=2", shamir::secret_id(&secret).unwrap());
+        let backend = open(&endpoint).unwrap();
+        assert_eq!(backend.submit_share(shares[1].clone()).unwrap(), 1);
+        assert_eq!(backend.submit_share(shares[2].clone()).unwrap(), 2);
+        assert_eq!(backend.derive_k2(b"context").unwrap().to_vec(), *expected);
+        assert!(open("threshold://abc").is_err());
+        assert!(open(&format!("{}&idle_secs=60", endpoint)).is_ok());
+        assert!(open(&format!("{}&idle=60", endpoint)).is_err());
+        assert!(MockHsm::new().submit_share(shares[0].clone()).is_err());
+    }
+
+    #[test]
+    fn test_threshold_hsm_drops_shares_and_seals_when_idle() {
+        let custodians: Vec<String> = ["alice", "bob"].iter().map(|c| c.to_string()).collect();
+        let secret = shamir::generate_secret().unwrap();
+        let shares = shamir::split(&secret, 2, &custodians).unwrap();
+
+        let hsm = ThresholdHsm::new(shamir::secret_id(&secret).unwrap(), 2)
+            .with_idle_timeout(Duration::from_millis(200));
+        hsm.submit_share(shares[0].clone()).unwrap();
+        hsm.submit_share(shares[1].clone()).unwrap();
+        assert!(matches!(*hsm.state.lock().unwrap(), Seal::Unsealed { .. }));
+
+        // Derivations keep it unsealed
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_millis(100));
+            hsm.derive_k2(b"context").unwrap();
+        }
+
+        // The watcher seals it without another call
+        std::thread::sleep(Duration::from_millis(600));
+        assert!(matches!(&*hsm.state.lock().unwrap(), Seal::Sealed(shares) if shares.is_empty()));
+        assert!(matches!(hsm.derive_k2(b"context"), Err(BentengError::KmsUnavailable(_))));
+    }
+}
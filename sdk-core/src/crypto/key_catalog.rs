@@ -0,0 +1,404 @@
+//! Catalog of KMS key pairs and their lifecycle
+//! Each key has an explicit KID and is scoped to one tenant and policy.
+//! Keys move pending -> active -> decrypt-only -> destroyed; at most one key
+//! per scope is active, and only active keys are used for new encryptions.
+//! The active KID of each scope is indexed, so finding it does not read the
+//! whole catalog.
+
+use crate::error::BentengError;
+
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+type Result<T> = std::result::Result<T, BentengError>;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyState {
+    /// Created, waiting for its activation time
+    Pending,
+    /// Used for encryption and decryption
+    Active,
+    /// Superseded; kept to decrypt existing data until it is re-encrypted
+    DecryptOnly,
+    /// Key material destroyed in the HSM
+    Destroyed,
+}
+
+/// Catalog entry for one key. Holds no key material.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRecord {
+    pub kid: String,
+    /// Hex tenant ID
+    pub tenant_id: String,
+    /// Hex policy ID
+    pub policy_id: String,
+    pub state: KeyState,
+    pub created_at: u64,
+    /// When the key becomes active
+    pub activate_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activated_at: Option<u64>,
+    /// Rotate this long after activation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation_period_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retired_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destroyed_at: Option<u64>,
+}
+
+impl KeyRecord {
+    /// Whether the key may wrap new data
+    pub fn can_encrypt(&self) -> bool {
+        self.state == KeyState::Active
+    }
+
+    /// Whether the key may unwrap existing data
+    pub fn can_decrypt(&self) -> bool {
+        matches!(self.state, KeyState::Active | KeyState::DecryptOnly)
+    }
+
+    /// Whether rotation is due at `now`
+    pub fn rotation_due(&self, now: u64) -> bool {
+        match (self.state, self.activated_at, self.rotation_period_secs) {
+            (KeyState::Active, Some(activated_at), Some(period)) => activated_at.saturating_add(period) <= now,
+            _ => false,
+        }
+    }
+}
+
+/// Options for a new key
+#[derive(Debug, Clone, Default)]
+pub struct KeyOptions {
+    /// Explicit KID; a random one is assigned when unset
+    pub kid: Option<String>,
+    /// Activation time; the key activates immediately when unset
+    pub activate_at: Option<u64>,
+    pub rotation_period_secs: Option<u64>,
+}
+
+fn storage_error(e: sled::Error) -> BentengError {
+    BentengError::KmsError(format!("Key catalog: {}", e))
+}
+
+/// sled-backed key catalog
+pub struct KeyCatalog {
+    db: Db,
+    /// Serializes state transitions that touch several records
+    lock: Mutex<()>,
+}
+
+impl KeyCatalog {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let db = sled::open(path).map_err(storage_error)?;
+        Self::open(db)
+    }
+
+    /// Catalog that lives only as long as the process
+    pub fn temporary() -> Result<Self> {
+        let db = sled::Config::new().temporary(true).open().map_err(storage_error)?;
+        Self::open(db)
+    }
+
+    /// Rebuild the active-key index from the records, so catalogs written
+    /// before it existed are indexed too
+    fn open(db: Db) -> Result<Self> {
+        let catalog = Self { db, lock: Mutex::new(()) };
+        let mut batch = sled::Batch::default();
+        for item in catalog.db.scan_prefix(b"active:") {
+            let (key, _) = item.map_err(storage_error)?;
+            batch.remove(key);
+        }
+        for record in catalog.list()? {
+            if record.state == KeyState::Active {
+                batch.insert(Self::active_index(&record.tenant_id, &record.policy_id).as_bytes(), record.kid.as_bytes());
+            }
+        }
+        catalog.db.apply_batch(batch).map_err(storage_error)?;
+        Ok(catalog)
+    }
+
+    fn key(kid: &str) -> String {
+        format!("key:{}", kid)
+    }
+
+    /// Index entry holding the active KID of a scope, from hex IDs
+    fn active_index(tenant_id: &str, policy_id: &str) -> String {
+        format!("active:{}:{}", tenant_id, policy_id)
+    }
+
+    fn put(&self, record: &KeyRecord) -> Result<()> {
+        self.put_all(&[record])
+    }
+
+    /// Write records and their index entries in one batch
+    fn put_all(&self, records: &[&KeyRecord]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+        let mut activated = Vec::new();
+        for record in records {
+            let value = serde_json::to_vec(record).map_err(|_| BentengError::InternalError)?;
+            batch.insert(Self::key(&record.kid).as_bytes(), value);
+
+            let index = Self::active_index(&record.tenant_id, &record.policy_id);
+            if record.state == KeyState::Active {
+                batch.insert(index.as_bytes(), record.kid.as_bytes());
+                activated.push(index);
+            } else if !activated.contains(&index)
+                && self.db.get(index.as_bytes()).map_err(storage_error)?.is_some_and(|kid| kid == record.kid.as_bytes())
+            {
+                batch.remove(index.as_bytes());
+            }
+        }
+        self.db.apply_batch(batch).map_err(storage_error)?;
+        self.db.flush().map_err(storage_error)?;
+        Ok(())
+    }
+
+    fn active_in(&self, tenant_id: &str, policy_id: &str) -> Result<Option<KeyRecord>> {
+        let Some(kid) = self.db.get(Self::active_index(tenant_id, policy_id).as_bytes()).map_err(storage_error)? else {
+            return Ok(None);
+        };
+        let kid = std::str::from_utf8(&kid).map_err(|_| BentengError::InternalError)?;
+        Ok(self.get(kid)?.filter(|r| r.state == KeyState::Active))
+    }
+
+    pub fn get(&self, kid: &str) -> Result<Option<KeyRecord>> {
+        self.db.get(Self::key(kid).as_bytes())
+            .map_err(storage_error)?
+            .map(|value| serde_json::from_slice(&value).map_err(|_| BentengError::InternalError))
+            .transpose()
+    }
+
+    fn require(&self, kid: &str) -> Result<KeyRecord> {
+        self.get(kid)?
+            .ok_or_else(|| BentengError::KeyNotFound(format!("Unknown key {}", kid)))
+    }
+
+    /// All keys, ordered by KID
+    pub fn list(&self) -> Result<Vec<KeyRecord>> {
+        let mut records = Vec::new();
+        for item in self.db.scan_prefix(b"key:") {
+            let (_, value) = item.map_err(storage_error)?;
+            records.push(serde_json::from_slice(&value).map_err(|_| BentengError::InternalError)?);
+        }
+        Ok(records)
+    }
+
+    /// Record a new pending key
+    pub fn create(&self, tenant_id: &[u8], policy_id: &[u8], options: KeyOptions) -> Result<KeyRecord> {
+        let _guard = self.lock.lock().map_err(|_| BentengError::InternalError)?;
+        let now = now_secs();
+
+        let kid = options.kid.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        if kid.is_empty() {
+            return Err(BentengError::KmsError("KID must not be empty".into()));
+        }
+        if self.get(&kid)?.is_some() {
+            return Err(BentengError::KeyUnavailable(format!("Key {} already exists", kid)));
+        }
+
+        let record = KeyRecord {
+            kid,
+            tenant_id: hex::encode(tenant_id),
+            policy_id: hex::encode(policy_id),
+            state: KeyState::Pending,
+            created_at: now,
+            activate_at: options.activate_at.unwrap_or(now),
+            activated_at: None,
+            rotation_period_secs: options.rotation_period_secs,
+            retired_at: None,
+            destroyed_at: None,
+        };
+        self.put(&record)?;
+        Ok(record)
+    }
+
+    /// The active key for a tenant and policy
+    pub fn active_key(&self, tenant_id: &[u8], policy_id: &[u8]) -> Result<Option<KeyRecord>> {
+        self.active_in(&hex::encode(tenant_id), &hex::encode(policy_id))
+    }
+
+    /// Activate a pending key now, moving the scope's current active key to
+    /// decrypt-only
+    pub fn activate(&self, kid: &str) -> Result<KeyRecord> {
+        let _guard = self.lock.lock().map_err(|_| BentengError::InternalError)?;
+        self.activate_locked(kid, now_secs())
+    }
+
+    fn activate_locked(&self, kid: &str, now: u64) -> Result<KeyRecord> {
+        let mut record = self.require(kid)?;
+        if record.state != KeyState::Pending {
+            return Err(BentengError::KeyUnavailable(format!("Key {} is not pending", kid)));
+        }
+
+        // The old and new key change state together
+        let current = self.active_in(&record.tenant_id, &record.policy_id)?.map(|mut current| {
+            current.state = KeyState::DecryptOnly;
+            current.retired_at = Some(now);
+            current
+        });
+        record.state = KeyState::Active;
+        record.activated_at = Some(now);
+        self.put_all(&current.iter().chain([&record]).collect::<Vec<_>>())?;
+        Ok(record)
+    }
+
+    /// Activate every pending key whose activation time has passed, oldest
+    /// first, returning the keys activated
+    pub fn apply_schedule(&self, now: u64) -> Result<Vec<KeyRecord>> {
+        let _guard = self.lock.lock().map_err(|_| BentengError::InternalError)?;
+
+        let mut due: Vec<KeyRecord> = self.list()?
+            .into_iter()
+            .filter(|r| r.state == KeyState::Pending && r.activate_at <= now)
+            .collect();
+        due.sort_by_key(|r| r.activate_at);
+
+        due.iter().map(|r| self.activate_locked(&r.kid, now)).collect()
+    }
+
+    /// Active keys whose rotation period has elapsed
+    pub fn due_for_rotation(&self, now: u64) -> Result<Vec<KeyRecord>> {
+        Ok(self.list()?.into_iter().filter(|r| r.rotation_due(now)).collect())
+    }
+
+    /// Stop using an active key for new data
+    pub fn retire(&self, kid: &str) -> Result<KeyRecord> {
+        let _guard = self.lock.lock().map_err(|_| BentengError::InternalError)?;
+        let mut record = self.require(kid)?;
+        if record.state != KeyState::Active {
+            return Err(BentengError::KeyUnavailable(format!("Key {} is not active", kid)));
+        }
+        record.state = KeyState::DecryptOnly;
+        record.retired_at = Some(now_secs());
+        self.put(&record)?;
+        Ok(record)
+    }
+
+    /// Mark a pending or decrypt-only key destroyed. Active keys must be
+    /// retired first.
+    pub fn destroy(&self, kid: &str) -> Result<KeyRecord> {
+        let _guard = self.lock.lock().map_err(|_| BentengError::InternalError)?;
+        let mut record = self.require(kid)?;
+        if !matches!(record.state, KeyState::Pending | KeyState::DecryptOnly) {
+            return Err(BentengError::KeyUnavailable(format!("Key {} cannot be destroyed while {:?}", kid, record.state)));
+        }
+        record.state = KeyState::Destroyed;
+        record.destroyed_at = Some(now_secs());
+        self.put(&record)?;
+        Ok(record)
+    }
+
+    /// Key that data under `kid` should be re-encrypted to, if it is
+    /// decrypt-only and its scope has an active key
+    pub fn reencryption_target(&self, kid: &str) -> Result<Option<KeyRecord>> {
+        let record = self.require(kid)?;
+        if record.state != KeyState::DecryptOnly {
+            return Ok(None);
+        }
+        self.active_in(&record.tenant_id, &record.policy_id)
+    }
+
+    /// Decrypt-only KIDs mapped to the active KID their data should move to
+    pub fn reencryption_plan(&self) -> Result<BTreeMap<String, String>> {
+        let records = self.list()?;
+        Ok(records.iter()
+            .filter(|r| r.state == KeyState::DecryptOnly)
+            .filter_map(|old| {
+                records.iter()
+                    .find(|r| {
+                        r.state == KeyState::Active
+                            && r.tenant_id == old.tenant_id
+                            && r.policy_id == old.policy_id
+                    })
+                    .map(|new| (old.kid.clone(), new.kid.clone()))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_key_lifecycle() {
+        let dir = tempdir().unwrap();
+        let now = now_secs();
+
+        {
+            let catalog = KeyCatalog::new(dir.path()).unwrap();
+            let k1 = catalog.create(b"t", b"p", KeyOptions {
+                kid: Some("k1".into()),
+                rotation_period_secs: Some(60),
+                ..Default::default()
+            }).unwrap();
+            assert_eq!(k1.state, KeyState::Pending);
+            let err = catalog.create(b"t", b"p", KeyOptions { kid: Some("k1".into()), ..Default::default() }).unwrap_err();
+            assert_eq!(err, BentengError::KeyUnavailable("Key k1 already exists".into()));
+
+            catalog.create(b"t", b"p", KeyOptions {
+                kid: Some("k2".into()),
+                activate_at: Some(now + 3600),
+                ..Default::default()
+            }).unwrap();
+            // Same-prefix tenants no longer share keys
+            catalog.create(b"tenant-a", b"p", KeyOptions { kid: Some("k3".into()), ..Default::default() }).unwrap();
+
+            let activated = catalog.apply_schedule(now).unwrap();
+            assert_eq!(activated.iter().map(|r| r.kid.as_str()).collect::<Vec<_>>(), ["k1", "k3"]);
+        }
+
+        let catalog = KeyCatalog::new(dir.path()).unwrap();
+        assert_eq!(catalog.active_key(b"t", b"p").unwrap().unwrap().kid, "k1");
+        assert!(catalog.due_for_rotation(now).unwrap().is_empty());
+        assert_eq!(catalog.due_for_rotation(now + 60).unwrap()[0].kid, "k1");
+
+        // The scheduled key replaces k1, which stays usable for decryption
+        catalog.apply_schedule(now + 3600).unwrap();
+        let k1 = catalog.get("k1").unwrap().unwrap();
+        assert_eq!(k1.state, KeyState::DecryptOnly);
+        assert!(k1.can_decrypt() && !k1.can_encrypt());
+        assert_eq!(catalog.active_key(b"t", b"p").unwrap().unwrap().kid, "k2");
+        assert_eq!(catalog.reencryption_target("k1").unwrap().unwrap().kid, "k2");
+        assert_eq!(catalog.reencryption_plan().unwrap().get("k1").map(String::as_str), Some("k2"));
+
+        assert_eq!(catalog.destroy("k2").unwrap_err().code(), ErrorCode::KeyUnavailable);
+        assert_eq!(catalog.retire("k1").unwrap_err().code(), ErrorCode::KeyUnavailable);
+        let k1 = catalog.destroy("k1").unwrap();
+        assert!(!k1.can_decrypt());
+        assert!(catalog.reencryption_plan().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_active_key_index() {
+        let dir = tempdir().unwrap();
+        {
+            let catalog = KeyCatalog::new(dir.path()).unwrap();
+            catalog.create(b"t", b"p", KeyOptions { kid: Some("k1".into()), ..Default::default() }).unwrap();
+            catalog.activate("k1").unwrap();
+            // As written before the index existed
+            catalog.db.remove(KeyCatalog::active_index(&hex::encode(b"t"), &hex::encode(b"p"))).unwrap();
+        }
+
+        let catalog = KeyCatalog::new(dir.path()).unwrap();
+        assert_eq!(catalog.active_key(b"t", b"p").unwrap().unwrap().kid, "k1");
+        assert!(catalog.active_key(b"t", b"q").unwrap().is_none());
+        catalog.retire("k1").unwrap();
+        assert!(catalog.active_key(b"t", b"p").unwrap().is_none());
+    }
+}
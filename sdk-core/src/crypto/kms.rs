@@ -2,16 +2,28 @@
 //! K1 from HSM-A (Kyber decapsulation + HKDF1)
 //! K2 from HSM-B (Quorum approval + HKDF2)
 //! Final DEK = HKDF(K1 || K2)
+//! Encryption runs the same derivation with HSM-A encapsulating instead of
+//! decapsulating, so envelopes made from a `WrappedDek` decrypt through the gate.
+//! A rewrap moves an envelope to a new key: the derived key then only wraps
+//! the original DEK, so the payload ciphertext never changes.
 
 use crate::error::BentengError;
 use crate::crypto::kdf::hkdf_sha256_derive;
-use crate::crypto::kem::{kyber768_keypair, kyber768_decapsulate};
+use crate::crypto::{aead, generate_nonce};
+use crate::crypto::hsm::{self, HsmBackend, MockHsm, MOCK_SCHEME};
+use crate::crypto::quorum::{Approver, ApproverRegistry, QuorumApproval, QuorumDenial};
+use crate::crypto::approval::{ApprovalEvent, ApprovalNotifier, PendingRequest, RequestContext, RequestStatus, RequestSubmission};
+use crate::crypto::kms_storage::{ApprovalStore, QuorumStorage};
+use crate::crypto::key_catalog::{KeyCatalog, KeyOptions, KeyRecord};
+use crate::crypto::dek_cache::{DekCache, DekCacheKey, DekCacheStats};
+use crate::crypto::shamir::CustodianShare;
+use tracing::Instrument;
 
 
-use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use std::collections::HashMap;
+use std::path::PathBuf;
 use std::time::{SystemTime, Duration};
 
 /// Result type for KMS operations
@@ -24,6 +36,12 @@ pub struct DualControlConfig {
     pub hsm_b_endpoint: String,
     pub require_quorum: bool,
     pub quorum_threshold: usize,
+    /// Longest lifetime an approval may claim
+    pub approval_ttl_secs: u64,
+    /// sled directory for quorum approvals; a temporary store when unset
+    pub approval_store_path: Option<PathBuf>,
+    /// sled directory for the key catalog; a temporary catalog when unset
+    pub key_catalog_path: Option<PathBuf>,
     pub timeout_ms: u64,
     pub max_cache_entries: usize,
     pub cache_ttl_secs: u64,
@@ -36,6 +54,9 @@ impl Default for DualControlConfig {
             hsm_b_endpoint: "mock://hsm-b".to_string(),
             require_quorum: true,
             quorum_threshold: 2,
+            approval_ttl_secs: 900,
+            approval_store_path: None,
+            key_catalog_path: None,
             timeout_ms: 5000,
             max_cache_entries: 100,
             cache_ttl_secs: 300,
@@ -43,168 +64,337 @@ impl Default for DualControlConfig {
     }
 }
 
-/// Cached key material with expiry
-#[derive(ZeroizeOnDrop)]
-struct CachedKey {
-    #[zeroize(skip)]
-    expires_at: SystemTime,
-    key_material: Vec<u8>,
+/// A fresh DEK and the KEM ciphertext that lets the KMS derive it again
+pub struct WrappedDek {
+    pub kid: String,
+    pub kem_ciphertext: Vec<u8>,
+    pub dek: Zeroizing<[u8; 32]>,
 }
 
-/// Mock HSM key storage
-struct HsmKeyPair {
-    public_key: Vec<u8>,
-    secret_key: Zeroizing<Vec<u8>>,
+impl std::fmt::Debug for WrappedDek {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WrappedDek")
+            .field("kid", &self.kid)
+            .field("kem_ciphertext", &hex::encode(&self.kem_ciphertext))
+            .field("dek", &"<redacted>")
+            .finish()
+    }
+}
+
+/// An envelope's key material, to be moved to another key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewrapRequest {
+    pub kem_ciphertext: Vec<u8>,
+    /// Wrapped DEK from an earlier rewrap
+    pub wrapped_dek: Option<Vec<u8>>,
+    /// Key the envelope is currently encapsulated to
+    pub kid: Option<String>,
+    /// Target key; the active key for the tenant and policy when unset
+    pub new_kid: Option<String>,
+    pub tenant_id: Vec<u8>,
+    pub policy_id: Vec<u8>,
+    pub path: String,
+}
+
+/// Key material of a rewrapped envelope
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewrappedDek {
+    pub kid: String,
+    pub kem_ciphertext: Vec<u8>,
+    pub wrapped_dek: Vec<u8>,
+}
+
+const DEK_WRAP_DOMAIN: &[u8] = b"benteng/dek-wrap/v1";
+
+/// Wrap a DEK under the key derived for `kid`; returns nonce || ciphertext
+pub fn wrap_dek(kek: &[u8; 32], kid: &str, dek: &[u8; 32]) -> Result<Vec<u8>> {
+    let nonce = generate_nonce()?;
+    let ciphertext = aead::aes_256_gcm_encrypt(kek, &nonce, dek, &[DEK_WRAP_DOMAIN, kid.as_bytes()].concat())?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+/// Recover a DEK wrapped by `wrap_dek`
+pub fn unwrap_dek(kek: &[u8; 32], kid: &str, wrapped: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+    if wrapped.len() < 12 {
+        return Err(BentengError::AeadFailure);
+    }
+    let (nonce, ciphertext) = wrapped.split_at(12);
+    let nonce: [u8; 12] = nonce.try_into().map_err(|_| BentengError::AeadFailure)?;
+    let plaintext = aead::aes_256_gcm_decrypt(kek, &nonce, ciphertext, &[DEK_WRAP_DOMAIN, kid.as_bytes()].concat())?;
+    
+    let mut dek = Zeroizing::new([0u8; 32]);
+    if plaintext.len() != dek.len() {
+        return Err(BentengError::AeadFailure);
+    }
+    dek.copy_from_slice(&plaintext);
+    Ok(dek)
 }
 
 /// KMS gate trait for dual-control operations
 pub trait KmsGate: Send + Sync {
-    /// Perform dual-control decryption to derive DEK
+    /// Create a DEK for a new envelope, encapsulated to `kid` or to the
+    /// active key for the tenant and policy. Encryption needs no quorum.
+    fn generate_dek(
+        &self,
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+    ) -> impl std::future::Future<Output = Result<WrappedDek>> + Send;
+    
+
+    /// Perform dual-control decryption to derive DEK. Without a KID the
+    /// active key for the tenant and policy is used.
     fn dual_decrypt(
         &self,
         kem_ciphertext: &[u8],
+        kid: Option<&str>,
         policy_id: &[u8],
         tenant_id: &[u8],
         path: &str,
     ) -> impl std::future::Future<Output = Result<[u8; 32]>> + Send;
     
+    /// `dual_decrypt` under a policy that allows or forbids caching the
+    /// DEK. Gates without a DEK cache ignore `cache_dek`.
+    fn dual_decrypt_with_caching(
+        &self,
+        kem_ciphertext: &[u8],
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+        cache_dek: bool,
+    ) -> impl std::future::Future<Output = Result<[u8; 32]>> + Send {
+        let _ = cache_dek;
+        self.dual_decrypt(kem_ciphertext, kid, policy_id, tenant_id, path)
+    }
+    
+    /// Move an envelope to another key without releasing its DEK: the old
+    /// key unwraps it and the new key wraps it again. Unwrapping needs the
+    /// same authorization as decrypting, so the request must not be denied
+    /// and must have its quorum where one is required.
+    fn rewrap(&self, request: RewrapRequest) -> impl std::future::Future<Output = Result<RewrappedDek>> + Send;
+    
     /// Get quorum approval status
     fn check_quorum(&self, request_id: &[u8]) -> impl std::future::Future<Output = Result<bool>> + Send;
+    
+    /// Record what a request is for, so approvers can review it before a
+    /// decryption needing the quorum. Gates without a quorum ignore it.
+    fn submit_request(
+        &self,
+        submission: RequestSubmission,
+    ) -> impl std::future::Future<Output = Result<()>> + Send {
+        let _ = submission;
+        async { Ok(()) }
+    }
 }
 
 /// Production dual-control KMS implementation
 pub struct DualControlKms {
     config: DualControlConfig,
-    cache: Arc<RwLock<HashMap<Vec<u8>, CachedKey>>>,
-    hsm_a_keys: Arc<RwLock<HashMap<String, HsmKeyPair>>>, // Mock HSM-A storage
-    quorum_approvals: Arc<RwLock<HashMap<Vec<u8>, Vec<String>>>>, // Mock quorum tracking
+    dek_cache: DekCache,
+    hsm_a: Arc<dyn HsmBackend>,
+    hsm_b: Arc<dyn HsmBackend>,
+    mock_hsm: Option<Arc<MockHsm>>,
+    approvers: ApproverRegistry,
+    approval_store: Arc<dyn ApprovalStore>,
+    notifiers: Vec<Arc<dyn ApprovalNotifier>>,
+    key_catalog: Arc<KeyCatalog>,
 }
 
 impl DualControlKms {
-    pub fn new(config: DualControlConfig) -> Self {
-        Self {
+    /// KMS backed by a single in-process mock HSM acting as HSM-A and HSM-B,
+    /// with a temporary approval store and key catalog
+    pub fn new(config: DualControlConfig) -> Result<Self> {
+        let mock = Arc::new(MockHsm::new());
+        let mut kms = Self::with_backends(config, mock.clone(), mock.clone())?;
+        kms.mock_hsm = Some(mock);
+        Ok(kms)
+    }
+    
+    /// KMS using the given HSM-A and HSM-B backends
+    pub fn with_backends(
+        config: DualControlConfig,
+        hsm_a: Arc<dyn HsmBackend>,
+        hsm_b: Arc<dyn HsmBackend>,
+    ) -> Result<Self> {
+        let approval_store = QuorumStorage::temporary()?;
+        let key_catalog = KeyCatalog::temporary()?;
+        let dek_cache = DekCache::new(
+            config.max_cache_entries,
+            Duration::from_secs(config.cache_ttl_secs),
+        );
+        
+        Ok(Self {
             config,
-            cache: Arc::new(RwLock::new(HashMap::new())),
-            hsm_a_keys: Arc::new(RwLock::new(HashMap::new())),
-            quorum_approvals: Arc::new(RwLock::new(HashMap::new())),
+            dek_cache,
+            hsm_a,
+            hsm_b,
+            mock_hsm: None,
+            approvers: ApproverRegistry::default(),
+            approval_store: Arc::new(approval_store),
+            notifiers: Vec::new(),
+            key_catalog: Arc::new(key_catalog),
+        })
+    }
+    
+    /// KMS for the configured endpoints, with approvals and the key catalog
+    /// persisted under the configured paths. `mock://` endpoints on both
+    /// sides share one mock HSM; see `hsm::open` for the other schemes and
+    /// `hsm::open_hsm_a` for what HSM-A accepts.
+    pub fn connect(config: DualControlConfig) -> Result<Self> {
+        let mocked = [&config.hsm_a_endpoint, &config.hsm_b_endpoint]
+            .iter()
+            .all(|endpoint| endpoint.starts_with(MOCK_SCHEME));
+        let backends = if mocked {
+            None
+        } else {
+            Some((hsm::open_hsm_a(&config.hsm_a_endpoint)?, hsm::open(&config.hsm_b_endpoint)?))
+        };
+        
+        let store = config.approval_store_path.as_ref().map(QuorumStorage::new).transpose()?;
+        let catalog = config.key_catalog_path.as_ref().map(KeyCatalog::new).transpose()?;
+        let mut kms = match backends {
+            Some((hsm_a, hsm_b)) => Self::with_backends(config, hsm_a, hsm_b)?,
+            None => Self::new(config)?,
+        };
+        if let Some(store) = store {
+            kms = kms.with_approval_store(Arc::new(store));
+        }
+        if let Some(catalog) = catalog {
+            kms = kms.with_key_catalog(Arc::new(catalog));
         }
+        Ok(kms)
     }
     
-    /// Initialize with a mock HSM key for testing
-    pub async fn init_mock_hsm(&self, kid: &str) -> Result<()> {
-        let (public_key, secret_key) = kyber768_keypair()?;
-        let mut keys = self.hsm_a_keys.write().await;
-        keys.insert(kid.to_string(), HsmKeyPair {
-            public_key,
-            secret_key,
-        });
-        Ok(())
+    /// Replace the approval store
+    pub fn with_approval_store(mut self, store: Arc<dyn ApprovalStore>) -> Self {
+        self.approval_store = store;
+        self
     }
     
-    /// Get public key for a KID
-    pub async fn get_public_key(&self, kid: &str) -> Result<Vec<u8>> {
-        let keys = self.hsm_a_keys.read().await;
-        let pair = keys.get(kid)
-            .ok_or_else(|| BentengError::KmsError("Key not found".into()))?;
-        Ok(pair.public_key.clone())
+    /// Replace the key catalog
+    pub fn with_key_catalog(mut self, catalog: Arc<KeyCatalog>) -> Self {
+        self.key_catalog = catalog;
+        self
     }
     
-    /// Get K1 from HSM-A via Kyber decapsulation
-    async fn get_k1(&self, kem_ciphertext: &[u8], kid: &str) -> Result<[u8; 32]> {
-        // In production, this would call actual HSM API
-        // For now, use mock HSM storage
-        let keys = self.hsm_a_keys.read().await;
-        let pair = keys.get(kid)
-            .ok_or_else(|| BentengError::KmsError("KEM key not found in HSM-A".into()))?;
-        
-        // Decapsulate to get shared secret
-        let shared_secret = kyber768_decapsulate(&pair.secret_key, kem_ciphertext)?;
-        
-        // Apply HKDF1 with HSM-A specific domain separation
-        let k1_vec = hkdf_sha256_derive(
-            &*shared_secret,
-            Some(b"benteng/hsm-a/k1/v1"),
-            b"",
-            32
-        )?;
-        
-        let mut k1 = [0u8; 32];
-        k1.copy_from_slice(&k1_vec);
-        
-        Ok(k1)
+    pub fn key_catalog(&self) -> Arc<KeyCatalog> {
+        self.key_catalog.clone()
     }
     
-    /// Get K2 from HSM-B via quorum approval
-    async fn get_k2(&self, request_id: &[u8], policy_id: &[u8]) -> Result<[u8; 32]> {
-        // Check quorum approval
-        if self.config.require_quorum {
-            let approvals = self.quorum_approvals.read().await;
-            let approval_list = approvals.get(request_id);
-            
-            if approval_list.map_or(0, |list| list.len()) < self.config.quorum_threshold {
-                return Err(BentengError::KmsError("Insufficient quorum approvals".into()));
+    /// Send approval workflow events to a notifier, in addition to any
+    /// already registered
+    pub fn with_notifier(mut self, notifier: Arc<dyn ApprovalNotifier>) -> Self {
+        self.notifiers.push(notifier);
+        self
+    }
+    
+    fn notify(&self, event: ApprovalEvent) {
+        for notifier in &self.notifiers {
+            if let Err(e) = notifier.notify(&event) {
+                tracing::warn!("Approval notifier failed: {}", e);
             }
         }
+    }
+    
+    fn load_request(&self, request_id: &[u8]) -> Result<PendingRequest> {
+        self.approval_store.get_request(request_id)?
+            .ok_or_else(|| BentengError::RequestNotFound("Unknown request".into()))
+    }
+    
+    /// Add a request to the pending registry and notify approvers. A request
+    /// that is already registered is returned unchanged.
+    pub async fn register_request(
+        &self,
+        request_id: &[u8],
+        context: RequestContext,
+    ) -> Result<PendingRequest> {
+        if let Some(existing) = self.approval_store.get_request(request_id)? {
+            return Ok(existing);
+        }
         
-        // In production, this would call HSM-B API with quorum proof
-        // For now, derive K2 from request_id and policy_id
-        let mut context = Vec::new();
-        context.extend_from_slice(request_id);
-        context.extend_from_slice(policy_id);
+        let request = PendingRequest::new(request_id, context);
+        self.approval_store.put_request(&request)?;
+        self.notify(ApprovalEvent::Requested { request: request.clone() });
+        Ok(request)
+    }
+    
+    /// Registered requests, optionally only those with the given status
+    pub async fn list_requests(&self, status: Option<RequestStatus>) -> Result<Vec<PendingRequest>> {
+        let mut requests = self.approval_store.list_requests()?;
+        requests.retain(|r| status.is_none_or(|s| r.status == s));
+        requests.sort_by_key(|r| r.created_at);
+        Ok(requests)
+    }
+    
+    pub async fn get_request(&self, request_id: &[u8]) -> Result<Option<PendingRequest>> {
+        self.approval_store.get_request(request_id)
+    }
+    
+    /// Approve a registered request with a signed approval. The request is
+    /// marked approved once the quorum is reached; denied requests stay denied.
+    pub async fn approve(&self, approval: QuorumApproval, reason: &str) -> Result<PendingRequest> {
+        let mut request = self.load_request(&approval.request_id)?;
+        if request.status == RequestStatus::Denied {
+            return Err(BentengError::RequestDenied("Request already denied".into()));
+        }
         
-        let k2_vec = hkdf_sha256_derive(
-            &context,
-            Some(b"benteng/hsm-b/k2/v1"),
-            b"",
-            32
-        )?;
+        self.add_approval(approval.clone()).await?;
+        request.record(&approval.approver, true, reason);
         
-        let mut k2 = [0u8; 32];
-        k2.copy_from_slice(&k2_vec);
+        let approvals = self.approval_store.get_approvals(&approval.request_id)?;
+        let reached = request.status == RequestStatus::Pending
+            && self.approvers.count_valid(&approvals, &approval.request_id, None) >= self.config.quorum_threshold;
+        if reached {
+            request.status = RequestStatus::Approved;
+        }
+        self.approval_store.put_request(&request)?;
         
-        Ok(k2)
+        self.notify(ApprovalEvent::Approved {
+            request: request.clone(),
+            approver: approval.approver,
+        });
+        if reached {
+            self.notify(ApprovalEvent::QuorumReached { request: request.clone() });
+        }
+        Ok(request)
     }
     
-    /// Add quorum approval (for testing)
-    pub async fn add_approval(&self, request_id: &[u8], approver: &str) -> Result<()> {
-        let mut approvals = self.quorum_approvals.write().await;
-        approvals.entry(request_id.to_vec())
-            .or_insert_with(Vec::new)
-            .push(approver.to_string());
-        Ok(())
+    /// Deny a registered request with a signed denial. Decryption of a
+    /// denied request fails regardless of approvals, and a DEK already
+    /// cached for it is dropped.
+    pub async fn deny(&self, denial: QuorumDenial) -> Result<PendingRequest> {
+        self.approvers.verify_denial(&denial)?;
+        
+        let mut request = self.load_request(&denial.request_id)?;
+        request.status = RequestStatus::Denied;
+        request.record(&denial.approver, false, &denial.reason);
+        self.approval_store.put_request(&request)?;
+        self.dek_cache.invalidate_request(&denial.request_id)?;
+        
+        self.notify(ApprovalEvent::Denied {
+            request: request.clone(),
+            approver: denial.approver,
+        });
+        Ok(request)
     }
-}
-
-impl KmsGate for DualControlKms {
-    async fn dual_decrypt(
-        &self,
+    
+    /// Register the approvers whose signed approvals count towards the quorum
+    pub fn with_approvers(mut self, approvers: Vec<Approver>) -> Result<Self> {
+        self.approvers = ApproverRegistry::new(approvers, self.config.approval_ttl_secs)?;
+        Ok(self)
+    }
+    
+    /// Quorum request ID for a decryption. Approvers sign over this value.
+    pub fn request_id(
         kem_ciphertext: &[u8],
         policy_id: &[u8],
         tenant_id: &[u8],
         path: &str,
     ) -> Result<[u8; 32]> {
-        // Generate cache key
-        let mut cache_key = Vec::new();
-        cache_key.extend_from_slice(kem_ciphertext);
-        cache_key.extend_from_slice(policy_id);
-        cache_key.extend_from_slice(tenant_id);
-        cache_key.extend_from_slice(path.as_bytes());
-        
-        // Check cache
-        {
-            let cache = self.cache.read().await;
-            if let Some(cached) = cache.get(&cache_key) {
-                if cached.expires_at > SystemTime::now() {
-                    let mut dek = [0u8; 32];
-                    dek.copy_from_slice(&cached.key_material);
-                    return Ok(dek);
-                }
-            }
-        }
-        
-        // Generate request ID for quorum tracking
         let request_id_vec = hkdf_sha256_derive(
-            &cache_key,
+            &[kem_ciphertext, policy_id, tenant_id, path.as_bytes()].concat(),
             Some(b"benteng/request-id/v1"),
             b"",
             32
@@ -212,22 +402,200 @@ impl KmsGate for DualControlKms {
         
         let mut request_id = [0u8; 32];
         request_id.copy_from_slice(&request_id_vec);
+        Ok(request_id)
+    }
+    
+    /// Generate a key pair in HSM-A for a tenant and policy and add it to
+    /// the catalog. It becomes active at `options.activate_at`, or now.
+    pub async fn generate_key(
+        &self,
+        tenant_id: &[u8],
+        policy_id: &[u8],
+        options: KeyOptions,
+    ) -> Result<KeyRecord> {
+        let record = self.key_catalog.create(tenant_id, policy_id, options)?;
+        if let Err(e) = self.hsm_a.generate_key(&record.kid) {
+            self.key_catalog.destroy(&record.kid)?;
+            return Err(e);
+        }
         
-        // Get K1 from HSM-A (Kyber decapsulation + HKDF1)
-        let kid = format!("{}-{}", 
-            hex::encode(&tenant_id[..4]), 
-            hex::encode(&policy_id[..4])
-        );
-        let k1 = self.get_k1(kem_ciphertext, &kid).await?;
+        self.key_catalog.apply_schedule(Self::now_secs())?;
+        self.key_catalog.get(&record.kid)?.ok_or(BentengError::InternalError)
+    }
+    
+    /// Replace the active key of a tenant and policy with a new one. The
+    /// old key becomes decrypt-only.
+    pub async fn rotate_key(&self, tenant_id: &[u8], policy_id: &[u8]) -> Result<KeyRecord> {
+        let rotation_period_secs = self.key_catalog.active_key(tenant_id, policy_id)?
+            .and_then(|current| current.rotation_period_secs);
+        self.generate_key(tenant_id, policy_id, KeyOptions {
+            rotation_period_secs,
+            ..Default::default()
+        }).await
+    }
+    
+    /// Activate scheduled keys and rotate active keys whose rotation period
+    /// has elapsed, returning the keys that became active
+    pub async fn rotate_due_keys(&self) -> Result<Vec<KeyRecord>> {
+        let now = Self::now_secs();
+        let mut activated = self.key_catalog.apply_schedule(now)?;
         
-        // Get K2 from HSM-B (quorum approval + HKDF2)
-        let k2 = self.get_k2(&request_id, policy_id).await?;
+        for due in self.key_catalog.due_for_rotation(now)? {
+            let tenant_id = hex::decode(&due.tenant_id).map_err(|_| BentengError::InternalError)?;
+            let policy_id = hex::decode(&due.policy_id).map_err(|_| BentengError::InternalError)?;
+            activated.push(self.rotate_key(&tenant_id, &policy_id).await?);
+        }
+        Ok(activated)
+    }
+    
+    /// Stop using a key for new data; it can still decrypt
+    pub async fn retire_key(&self, kid: &str) -> Result<KeyRecord> {
+        self.key_catalog.retire(kid)
+    }
+    
+    /// Destroy a pending or decrypt-only key in HSM-A. Data still wrapped to
+    /// it can no longer be decrypted.
+    pub async fn destroy_key(&self, kid: &str) -> Result<KeyRecord> {
+        let record = self.key_catalog.destroy(kid)?;
+        self.dek_cache.invalidate_kid(kid)?;
+        self.hsm_a.destroy_key(kid)?;
+        Ok(record)
+    }
+    
+    /// Drop a tenant's cached DEKs, e.g. during incident response
+    pub async fn invalidate_tenant(&self, tenant_id: &[u8]) -> Result<usize> {
+        self.dek_cache.invalidate_tenant(tenant_id)
+    }
+    
+    /// Drop cached DEKs unwrapped with a key
+    pub async fn invalidate_kid(&self, kid: &str) -> Result<usize> {
+        self.dek_cache.invalidate_kid(kid)
+    }
+    
+    /// Hand a custodian's share of the master secret to HSM-B, returning how
+    /// many it holds. HSM-B refuses K2 derivations until it holds enough.
+    pub async fn submit_share(&self, share: CustodianShare) -> Result<usize> {
+        self.hsm_b.submit_share(share)
+    }
+    
+    /// Turn DEK caching on or off for a tenant's policy
+    pub fn set_policy_caching(&self, tenant_id: &[u8], policy_id: &[u8], enabled: bool) -> Result<()> {
+        self.dek_cache.set_policy_caching(tenant_id, policy_id, enabled)
+    }
+    
+    pub fn dek_cache_stats(&self) -> DekCacheStats {
+        self.dek_cache.stats()
+    }
+    
+    /// Get public key for a KID
+    pub async fn get_public_key(&self, kid: &str) -> Result<Vec<u8>> {
+        self.hsm_a.public_key(kid)
+    }
+    
+    /// KID and public key of the active key for a tenant and policy
+    pub async fn active_public_key(&self, tenant_id: &[u8], policy_id: &[u8]) -> Result<(String, Vec<u8>)> {
+        let record = self.key_catalog.active_key(tenant_id, policy_id)?
+            .ok_or_else(|| BentengError::KeyNotFound("No active key for tenant and policy".into()))?;
+        let public_key = self.hsm_a.public_key(&record.kid)?;
+        Ok((record.kid, public_key))
+    }
+    
+    /// Catalog entry of the key a decryption should use
+    fn decryption_key(&self, kid: Option<&str>, tenant_id: &[u8], policy_id: &[u8]) -> Result<KeyRecord> {
+        let record = self.scoped_key(kid, tenant_id, policy_id)?;
+        if !record.can_decrypt() {
+            return Err(BentengError::KeyUnavailable(format!("Key {} is {:?}", record.kid, record.state)));
+        }
+        Ok(record)
+    }
+    
+    /// Catalog entry of the key a new envelope should be encrypted to
+    fn encryption_key(&self, kid: Option<&str>, tenant_id: &[u8], policy_id: &[u8]) -> Result<KeyRecord> {
+        let record = self.scoped_key(kid, tenant_id, policy_id)?;
+        if !record.can_encrypt() {
+            return Err(BentengError::KeyUnavailable(format!("Key {} is {:?}", record.kid, record.state)));
+        }
+        Ok(record)
+    }
+    
+    /// `kid` if it belongs to the tenant and policy, else their active key
+    fn scoped_key(&self, kid: Option<&str>, tenant_id: &[u8], policy_id: &[u8]) -> Result<KeyRecord> {
+        Ok(match kid {
+            Some(kid) => self.key_catalog.get(kid)?
+                .filter(|r| r.tenant_id == hex::encode(tenant_id) && r.policy_id == hex::encode(policy_id))
+                .ok_or_else(|| BentengError::KeyNotFound(format!("Unknown key {} for tenant and policy", kid)))?,
+            None => self.key_catalog.active_key(tenant_id, policy_id)?
+                .ok_or_else(|| BentengError::KeyNotFound("No active key for tenant and policy".into()))?,
+        })
+    }
+    
+    fn now_secs() -> u64 {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+    
+    /// Get K1 from HSM-A via Kyber decapsulation
+    async fn get_k1(&self, kem_ciphertext: &[u8], kid: &str) -> Result<[u8; 32]> {
+        self.hsm_a.derive_k1(kid, kem_ciphertext)
+    }
+    
+    /// Check that a decryption request was submitted, is not denied and has
+    /// the quorum. Passes when the quorum is not required.
+    fn authorize(&self, request_id: &[u8], policy_id: &[u8]) -> Result<()> {
+        if !self.config.require_quorum {
+            return Ok(());
+        }
+        
+        let request = self.approval_store.get_request(request_id)?
+            .ok_or_else(|| BentengError::QuorumNotMet("Request not submitted for approval".into()))?;
+        if let Some(denial) = request.denial() {
+            return Err(BentengError::RequestDenied(format!(
+                "Request denied by {}: {}", denial.approver, denial.reason
+            )));
+        }
+        
+        let approvals = self.approval_store.get_approvals(request_id)?;
+        let valid = self.approvers.count_valid(&approvals, request_id, Some(policy_id));
+        if valid < self.config.quorum_threshold {
+            return Err(BentengError::QuorumNotMet("Insufficient quorum approvals".into()));
+        }
+        Ok(())
+    }
+    
+    /// K2 from HSM-B; callers check the quorum first
+    fn hsm_b_k2(&self, request_id: &[u8], policy_id: &[u8]) -> Result<[u8; 32]> {
+        let mut context = Vec::new();
+        context.extend_from_slice(request_id);
+        context.extend_from_slice(policy_id);
         
-        // Combine K1 and K2 using HKDF to derive final DEK
-        let mut combined = Vec::with_capacity(64);
+        self.hsm_b.derive_k2(&context)
+    }
+    
+    /// Record a signed approval. It must verify against a registered
+    /// approver; a newer approval from the same approver replaces the old one.
+    pub async fn add_approval(&self, approval: QuorumApproval) -> Result<()> {
+        self.approvers.verify(&approval)?;
+        self.approval_store.add_approval(&approval)
+    }
+    
+    /// Combine K1 and K2 using HKDF to derive the final DEK
+    fn combine_dek(
+        mut k1: [u8; 32],
+        mut k2: [u8; 32],
+        tenant_id: &[u8],
+        policy_id: &[u8],
+        path: &str,
+    ) -> Result<Zeroizing<[u8; 32]>> {
+        let mut combined = Zeroizing::new(Vec::with_capacity(64));
         combined.extend_from_slice(&k1);
         combined.extend_from_slice(&k2);
         
+        // Zeroize intermediate keys
+        k1.zeroize();
+        k2.zeroize();
+        
         let dek_vec = hkdf_sha256_derive(
             &combined,
             Some(b"benteng/dek/v1"),
@@ -235,46 +603,138 @@ impl KmsGate for DualControlKms {
             32
         )?;
         
-        let mut dek = [0u8; 32];
+        let mut dek = Zeroizing::new([0u8; 32]);
         dek.copy_from_slice(&dek_vec);
+        Ok(dek)
+    }
+    
+    /// Remove expired approvals from the store
+    pub async fn cleanup_expired_approvals(&self) -> Result<usize> {
+        self.approval_store.cleanup_expired()
+    }
+}
+
+impl KmsGate for DualControlKms {
+    async fn dual_decrypt(
+        &self,
+        kem_ciphertext: &[u8],
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+    ) -> Result<[u8; 32]> {
+        self.dual_decrypt_with_caching(kem_ciphertext, kid, policy_id, tenant_id, path, true).await
+    }
+    
+    /// The DEK is cached only if both `cache_dek` and the caching setting
+    /// of the policy allow it
+    async fn dual_decrypt_with_caching(
+        &self,
+        kem_ciphertext: &[u8],
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+        cache_dek: bool,
+    ) -> Result<[u8; 32]> {
+        let key = self.decryption_key(kid, tenant_id, policy_id)?;
         
-        // Zeroize intermediate keys
-        let mut k1 = k1;
-        let mut k2 = k2;
-        k1.zeroize();
-        k2.zeroize();
-        combined.zeroize();
+        // The request must have been submitted with its context. A denial
+        // or a lapsed quorum applies before any cached DEK is served.
+        let request_id = Self::request_id(kem_ciphertext, policy_id, tenant_id, path)?;
+        tracing::info_span!("kms.quorum", quorum = self.config.require_quorum)
+            .in_scope(|| self.authorize(&request_id, policy_id))?;
         
-        // Cache the result
-        {
-            let mut cache = self.cache.write().await;
-            let cached = CachedKey {
-                expires_at: SystemTime::now() + Duration::from_secs(self.config.cache_ttl_secs),
-                key_material: dek.to_vec(),
-            };
-            
-            // Evict old entries if cache is full
-            if cache.len() >= self.config.max_cache_entries {
-                // Remove expired entries
-                cache.retain(|_, v| v.expires_at > SystemTime::now());
-                
-                // If still full, remove oldest
-                if cache.len() >= self.config.max_cache_entries {
-                    if let Some(oldest_key) = cache.keys().next().cloned() {
-                        cache.remove(&oldest_key);
-                    }
-                }
+        let cache_key = DekCacheKey {
+            kid: &key.kid,
+            kem_ciphertext,
+            tenant_id,
+            policy_id,
+            path,
+            request_id: &request_id,
+        };
+        if cache_dek {
+            if let Some(dek) = self.dek_cache.get(&cache_key)? {
+                return Ok(*dek);
             }
-            
-            cache.insert(cache_key, cached);
         }
         
-        Ok(dek)
+        // Get K1 from HSM-A (Kyber decapsulation + HKDF1)
+        let k1 = self.get_k1(kem_ciphertext, &key.kid)
+            .instrument(tracing::info_span!("kms.k1", kid = %key.kid))
+            .await?;
+        
+        // Get K2 from HSM-B (HKDF2)
+        let k2 = tracing::info_span!("kms.k2")
+            .in_scope(|| self.hsm_b_k2(&request_id, policy_id))?;
+        
+        let dek = Self::combine_dek(k1, k2, tenant_id, policy_id, path)?;
+        
+        if cache_dek {
+            self.dek_cache.insert(&cache_key, &dek)?;
+        }
+        
+        Ok(*dek)
+    }
+    
+    async fn generate_dek(
+        &self,
+        kid: Option<&str>,
+        policy_id: &[u8],
+        tenant_id: &[u8],
+        path: &str,
+    ) -> Result<WrappedDek> {
+        let key = self.encryption_key(kid, tenant_id, policy_id)?;
+        
+        // K1 from HSM-A by encapsulating to the key
+        let (kem_ciphertext, k1) = self.hsm_a.encapsulate_k1(&key.kid)?;
+        
+        // K2 from HSM-B for the request the decryption will make
+        let request_id = Self::request_id(&kem_ciphertext, policy_id, tenant_id, path)?;
+        let k2 = self.hsm_b_k2(&request_id, policy_id)?;
+        
+        let dek = Self::combine_dek(k1, k2, tenant_id, policy_id, path)?;
+        
+        Ok(WrappedDek { kid: key.kid, kem_ciphertext, dek })
+    }
+    
+    async fn rewrap(&self, request: RewrapRequest) -> Result<RewrappedDek> {
+        let RewrapRequest { kem_ciphertext, wrapped_dek, kid, new_kid, tenant_id, policy_id, path } = request;
+        
+        // Recovering the DEK under the old key is a decryption, so a denied
+        // or unapproved request gets no K2
+        let old = self.decryption_key(kid.as_deref(), &tenant_id, &policy_id)?;
+        let request_id = Self::request_id(&kem_ciphertext, &policy_id, &tenant_id, &path)?;
+        self.authorize(&request_id, &policy_id)?;
+        let k1 = self.get_k1(&kem_ciphertext, &old.kid).await?;
+        let k2 = self.hsm_b_k2(&request_id, &policy_id)?;
+        let kek = Self::combine_dek(k1, k2, &tenant_id, &policy_id, &path)?;
+        let dek = match &wrapped_dek {
+            Some(wrapped) => unwrap_dek(&kek, &old.kid, wrapped)?,
+            None => kek,
+        };
+        
+        // Wrap it under a fresh encapsulation to the new key
+        let new = self.encryption_key(new_kid.as_deref(), &tenant_id, &policy_id)?;
+        let (kem_ciphertext, k1) = self.hsm_a.encapsulate_k1(&new.kid)?;
+        let request_id = Self::request_id(&kem_ciphertext, &policy_id, &tenant_id, &path)?;
+        let k2 = self.hsm_b_k2(&request_id, &policy_id)?;
+        let kek = Self::combine_dek(k1, k2, &tenant_id, &policy_id, &path)?;
+        let wrapped_dek = wrap_dek(&kek, &new.kid, &dek)?;
+        
+        Ok(RewrappedDek { kid: new.kid, kem_ciphertext, wrapped_dek })
+    }
+    
+    async fn submit_request(&self, submission: RequestSubmission) -> Result<()> {
+        if self.config.require_quorum {
+            self.register_request(&submission.request_id()?, submission.context()).await?;
+        }
+        Ok(())
     }
     
     async fn check_quorum(&self, request_id: &[u8]) -> Result<bool> {
-        let approvals = self.quorum_approvals.read().await;
-        let count = approvals.get(request_id).map_or(0, |list| list.len());
+        let approvals = self.approval_store.get_approvals(request_id)?;
+        let count = self.approvers.count_valid(&approvals, request_id, None);
         Ok(count >= self.config.quorum_threshold)
     }
 }
@@ -290,19 +750,19 @@ mod tests {
             ..Default::default()
         };
         
-        let kms = DualControlKms::new(config);
+        let kms = DualControlKms::new(config).unwrap();
         
-        // Initialize mock HSM key
-        let kid = format!("{}-{}", hex::encode(&[1u8; 4]), hex::encode(&[2u8; 4]));
-        kms.init_mock_hsm(&kid).await.unwrap();
+        // Create the tenant's key
+        let key = kms.generate_key(&[1u8; 16], &[2u8; 8], KeyOptions::default()).await.unwrap();
         
         // Generate test KEM ciphertext
-        let public_key = kms.get_public_key(&kid).await.unwrap();
+        let public_key = kms.get_public_key(&key.kid).await.unwrap();
         let (ciphertext, _) = crate::crypto::kem::kyber768_encapsulate(&public_key).unwrap();
         
         // Test dual decrypt
         let dek = kms.dual_decrypt(
             &ciphertext,
+            Some(&key.kid),
             &[2u8; 8],
             &[1u8; 16],
             "/test/path"
@@ -319,50 +779,71 @@ mod tests {
             ..Default::default()
         };
         
-        let kms = DualControlKms::new(config);
+        let (pk1, sk1) = crate::crypto::sig::dilithium3_keypair().unwrap();
+        let (pk2, sk2) = crate::crypto::sig::dilithium3_keypair().unwrap();
+        let (_, rogue_sk) = crate::crypto::sig::dilithium3_keypair().unwrap();
+        let kms = DualControlKms::new(config).unwrap()
+            .with_approvers(vec![
+                Approver { id: "approver1".into(), public_key: pk1 },
+                Approver { id: "approver2".into(), public_key: pk2 },
+            ])
+            .unwrap();
         
-        // Initialize mock HSM key
-        let kid = format!("{}-{}", hex::encode(&[1u8; 4]), hex::encode(&[2u8; 4]));
-        kms.init_mock_hsm(&kid).await.unwrap();
+        // Create the tenant's key
+        let key = kms.generate_key(&[1u8; 16], &[2u8; 8], KeyOptions::default()).await.unwrap();
         
         // Generate test data
-        let public_key = kms.get_public_key(&kid).await.unwrap();
+        let public_key = kms.get_public_key(&key.kid).await.unwrap();
         let (ciphertext, _) = crate::crypto::kem::kyber768_encapsulate(&public_key).unwrap();
+        let policy_id = [2u8; 8];
+        let request_id = DualControlKms::request_id(&ciphertext, &policy_id, &[1u8; 16], "/test/path").unwrap();
         
-        // Generate request ID
-        let mut request_data = Vec::new();
-        request_data.extend_from_slice(&ciphertext);
-        request_data.extend_from_slice(&[2u8; 8]);
-        request_data.extend_from_slice(&[1u8; 16]);
-        request_data.extend_from_slice(b"/test/path");
-        
-        let request_id_vec = hkdf_sha256_derive(
-            &request_data,
-            Some(b"benteng/request-id/v1"),
-            b"",
-            32
-        ).unwrap();
-        
-        let mut request_id = [0u8; 32];
-        request_id.copy_from_slice(&request_id_vec);
+        // Requests must be submitted before approvals count
+        let result = kms.dual_decrypt(&ciphertext, None, &policy_id, &[1u8; 16], "/test/path").await;
+        assert_eq!(result, Err(BentengError::QuorumNotMet("Request not submitted for approval".into())));
+        kms.submit_request(RequestSubmission {
+            kem_ciphertext: ciphertext.clone(),
+            tenant_id: vec![1u8; 16],
+            policy_id: policy_id.to_vec(),
+            path: "/test/path".into(),
+            requester: "client-1".into(),
+            envelope_hash: "ab".repeat(32),
+        }).await.unwrap();
         
         // Should fail without quorum
         let result = kms.dual_decrypt(
             &ciphertext,
-            &[2u8; 8],
+            Some(&key.kid),
+            &policy_id,
             &[1u8; 16],
             "/test/path"
         ).await;
         assert!(result.is_err());
         
-        // Add approvals
-        kms.add_approval(&request_id, "approver1").await.unwrap();
-        kms.add_approval(&request_id, "approver2").await.unwrap();
+        // Unregistered or forged approvals are refused
+        let forged = QuorumApproval::sign(&request_id, &policy_id, "approver2", 60, &rogue_sk).unwrap();
+        assert!(kms.add_approval(forged).await.is_err());
+        let unknown = QuorumApproval::sign(&request_id, &policy_id, "mallory", 60, &rogue_sk).unwrap();
+        assert!(kms.add_approval(unknown).await.is_err());
+        
+        // The same approver twice does not make a quorum
+        for _ in 0..2 {
+            kms.add_approval(QuorumApproval::sign(&request_id, &policy_id, "approver1", 60, &sk1).unwrap())
+                .await
+                .unwrap();
+        }
+        assert!(!kms.check_quorum(&request_id).await.unwrap());
+        assert!(kms.dual_decrypt(&ciphertext, None, &policy_id, &[1u8; 16], "/test/path").await.is_err());
+        
+        kms.add_approval(QuorumApproval::sign(&request_id, &policy_id, "approver2", 60, &sk2).unwrap())
+            .await
+            .unwrap();
         
         // Should succeed with quorum
         let dek = kms.dual_decrypt(
             &ciphertext,
-            &[2u8; 8],
+            Some(&key.kid),
+            &policy_id,
             &[1u8; 16],
             "/test/path"
         ).await.unwrap();
@@ -372,4 +853,261 @@ mod tests {
         // Check quorum status
         assert!(kms.check_quorum(&request_id).await.unwrap());
     }
+    
+    #[tokio::test]
+    async fn test_key_rotation() {
+        let config = DualControlConfig {
+            require_quorum: false,
+            ..Default::default()
+        };
+        let kms = DualControlKms::new(config).unwrap();
+        
+        // Short IDs are fine and don't share keys with similar ones
+        let old = kms.generate_key(b"t", b"p", KeyOptions {
+            kid: Some("tenant-t-2024".into()),
+            rotation_period_secs: Some(0),
+            ..Default::default()
+        }).await.unwrap();
+        kms.generate_key(b"t2", b"p", KeyOptions::default()).await.unwrap();
+        assert!(kms.generate_key(b"t", b"p", KeyOptions {
+            kid: Some("tenant-t-2024".into()),
+            ..Default::default()
+        }).await.is_err());
+        
+        let (ciphertext, _) = crate::crypto::kem::kyber768_encapsulate(
+            &kms.get_public_key(&old.kid).await.unwrap()
+        ).unwrap();
+        let dek = kms.dual_decrypt(&ciphertext, None, b"p", b"t", "/").await.unwrap();
+        
+        let rotated = kms.rotate_due_keys().await.unwrap();
+        assert_eq!(rotated.len(), 1);
+        let (kid, _) = kms.active_public_key(b"t", b"p").await.unwrap();
+        assert_eq!(kid, rotated[0].kid);
+        assert_eq!(rotated[0].rotation_period_secs, Some(0));
+        
+        // The old key still decrypts when named, but is no longer the default
+        assert_eq!(kms.dual_decrypt(&ciphertext, Some(&old.kid), b"p", b"t", "/").await.unwrap(), dek);
+        assert_ne!(kms.dual_decrypt(&ciphertext, None, b"p", b"t", "/").await.unwrap(), dek);
+        assert!(kms.dual_decrypt(&ciphertext, Some(&old.kid), b"p", b"t2", "/").await.is_err());
+        assert_eq!(
+            kms.key_catalog().reencryption_target(&old.kid).unwrap().unwrap().kid,
+            rotated[0].kid
+        );
+        
+        kms.destroy_key(&old.kid).await.unwrap();
+        assert!(kms.get_public_key(&old.kid).await.is_err());
+        assert!(kms.dual_decrypt(&ciphertext, Some(&old.kid), b"p", b"t", "/").await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_dek_cache() {
+        let config = DualControlConfig {
+            require_quorum: false,
+            ..Default::default()
+        };
+        let kms = DualControlKms::new(config).unwrap();
+        let key = kms.generate_key(b"tenant", b"policy", KeyOptions::default()).await.unwrap();
+        let (ciphertext, _) = crate::crypto::kem::kyber768_encapsulate(
+            &kms.get_public_key(&key.kid).await.unwrap()
+        ).unwrap();
+        
+        let dek = kms.dual_decrypt(&ciphertext, None, b"policy", b"tenant", "/").await.unwrap();
+        assert_eq!(kms.dual_decrypt(&ciphertext, None, b"policy", b"tenant", "/").await.unwrap(), dek);
+        let stats = kms.dek_cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+        
+        assert_eq!(kms.invalidate_tenant(b"other").await.unwrap(), 0);
+        assert_eq!(kms.invalidate_tenant(b"tenant").await.unwrap(), 1);
+        kms.dual_decrypt(&ciphertext, None, b"policy", b"tenant", "/").await.unwrap();
+        assert_eq!(kms.invalidate_kid(&key.kid).await.unwrap(), 1);
+        
+        // A request made under a no-cache policy neither reads nor fills it
+        kms.dual_decrypt(&ciphertext, None, b"policy", b"tenant", "/").await.unwrap();
+        let hits = kms.dek_cache_stats().hits;
+        kms.dual_decrypt_with_caching(&ciphertext, None, b"policy", b"tenant", "/other", false).await.unwrap();
+        kms.dual_decrypt_with_caching(&ciphertext, None, b"policy", b"tenant", "/", false).await.unwrap();
+        assert_eq!((kms.dek_cache_stats().hits, kms.dek_cache_stats().entries), (hits, 1));
+        assert_eq!(kms.invalidate_kid(&key.kid).await.unwrap(), 1);
+        
+        // High-sensitivity policies bypass the cache
+        kms.set_policy_caching(b"tenant", b"policy", false).unwrap();
+        kms.dual_decrypt(&ciphertext, None, b"policy", b"tenant", "/").await.unwrap();
+        assert_eq!(kms.dek_cache_stats().entries, 0);
+    }
+    
+    #[tokio::test]
+    async fn test_rewrap() {
+        let config = DualControlConfig {
+            require_quorum: false,
+            ..Default::default()
+        };
+        let kms = DualControlKms::new(config).unwrap();
+        let old = kms.generate_key(b"tenant", b"policy", KeyOptions::default()).await.unwrap();
+        let wrapped = kms.generate_dek(None, b"policy", b"tenant", "/").await.unwrap();
+        let new = kms.rotate_key(b"tenant", b"policy").await.unwrap();
+        
+        let request = RewrapRequest {
+            kem_ciphertext: wrapped.kem_ciphertext.clone(),
+            wrapped_dek: None,
+            kid: Some(old.kid.clone()),
+            new_kid: None,
+            tenant_id: b"tenant".to_vec(),
+            policy_id: b"policy".to_vec(),
+            path: "/".into(),
+        };
+        let rewrapped = kms.rewrap(request.clone()).await.unwrap();
+        assert_eq!(rewrapped.kid, new.kid);
+        
+        // The old key can go; the new key recovers the same DEK
+        kms.destroy_key(&old.kid).await.unwrap();
+        assert!(kms.rewrap(request).await.is_err());
+        let kek = kms.dual_decrypt(&rewrapped.kem_ciphertext, Some(&new.kid), b"policy", b"tenant", "/").await.unwrap();
+        assert_eq!(*unwrap_dek(&kek, &new.kid, &rewrapped.wrapped_dek).unwrap(), *wrapped.dek);
+        assert!(unwrap_dek(&kek, &old.kid, &rewrapped.wrapped_dek).is_err());
+        
+        // Rewrapping again carries the wrapped DEK forward
+        let newest = kms.rotate_key(b"tenant", b"policy").await.unwrap();
+        let again = kms.rewrap(RewrapRequest {
+            kem_ciphertext: rewrapped.kem_ciphertext,
+            wrapped_dek: Some(rewrapped.wrapped_dek),
+            kid: Some(new.kid),
+            new_kid: Some(newest.kid.clone()),
+            tenant_id: b"tenant".to_vec(),
+            policy_id: b"policy".to_vec(),
+            path: "/".into(),
+        }).await.unwrap();
+        let kek = kms.dual_decrypt(&again.kem_ciphertext, Some(&newest.kid), b"policy", b"tenant", "/").await.unwrap();
+        assert_eq!(*unwrap_dek(&kek, &newest.kid, &again.wrapped_dek).unwrap(), *wrapped.dek);
+    }
+    
+    #[tokio::test]
+    async fn test_approvals_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DualControlConfig {
+            quorum_threshold: 2,
+            approval_store_path: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        
+        let (pk1, sk1) = crate::crypto::sig::dilithium3_keypair().unwrap();
+        let (pk2, sk2) = crate::crypto::sig::dilithium3_keypair().unwrap();
+        let approvers = vec![
+            Approver { id: "approver1".into(), public_key: pk1 },
+            Approver { id: "approver2".into(), public_key: pk2 },
+        ];
+        let request_id = [7u8; 32];
+        
+        {
+            let kms = DualControlKms::connect(config.clone()).unwrap()
+                .with_approvers(approvers.clone())
+                .unwrap();
+            kms.add_approval(QuorumApproval::sign(&request_id, b"policy", "approver1", 60, &sk1).unwrap())
+                .await
+                .unwrap();
+        }
+        
+        let kms = DualControlKms::connect(config).unwrap()
+            .with_approvers(approvers)
+            .unwrap();
+        assert!(!kms.check_quorum(&request_id).await.unwrap());
+        kms.add_approval(QuorumApproval::sign(&request_id, b"policy", "approver2", 60, &sk2).unwrap())
+            .await
+            .unwrap();
+        assert!(kms.check_quorum(&request_id).await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_approval_workflow() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = dir.path().join("events.jsonl");
+        
+        let (pk1, sk1) = crate::crypto::sig::dilithium3_keypair().unwrap();
+        let (pk2, sk2) = crate::crypto::sig::dilithium3_keypair().unwrap();
+        let kms = DualControlKms::new(DualControlConfig::default()).unwrap()
+            .with_approvers(vec![
+                Approver { id: "approver1".into(), public_key: pk1 },
+                Approver { id: "approver2".into(), public_key: pk2 },
+            ])
+            .unwrap()
+            .with_notifier(Arc::new(crate::crypto::approval::FileNotifier::new(&events)));
+        
+        let key = kms.generate_key(&[1u8; 16], &[2u8; 8], KeyOptions::default()).await.unwrap();
+        let public_key = kms.get_public_key(&key.kid).await.unwrap();
+        
+        let submission = |kem_ciphertext: &[u8]| RequestSubmission {
+            kem_ciphertext: kem_ciphertext.to_vec(),
+            tenant_id: vec![1u8; 16],
+            policy_id: vec![2u8; 8],
+            path: "/test/path".into(),
+            requester: "client-1".into(),
+            envelope_hash: "ab".repeat(32),
+        };
+        
+        // Approved request
+        let (ciphertext, _) = crate::crypto::kem::kyber768_encapsulate(&public_key).unwrap();
+        let request_id = DualControlKms::request_id(&ciphertext, &[2u8; 8], &[1u8; 16], "/test/path").unwrap();
+        kms.submit_request(submission(&ciphertext)).await.unwrap();
+        assert_eq!(kms.list_requests(Some(RequestStatus::Pending)).await.unwrap().len(), 1);
+        
+        let request = kms.approve(
+            QuorumApproval::sign(&request_id, &[2u8; 8], "approver1", 60, &sk1).unwrap(),
+            "expected batch job",
+        ).await.unwrap();
+        assert_eq!(request.status, RequestStatus::Pending);
+        let request = kms.approve(
+            QuorumApproval::sign(&request_id, &[2u8; 8], "approver2", 60, &sk2).unwrap(),
+            "confirmed with requester",
+        ).await.unwrap();
+        assert_eq!(request.status, RequestStatus::Approved);
+        assert_eq!(request.context.requester, "client-1");
+        assert!(kms.dual_decrypt(&ciphertext, None, &[2u8; 8], &[1u8; 16], "/test/path").await.is_ok());
+        assert_eq!(kms.dek_cache_stats().entries, 1);
+        
+        // A late denial overrides the quorum and drops the cached DEK
+        kms.deny(QuorumDenial::sign(&request_id, "approver1", "compromised requester", &sk1).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(kms.dek_cache_stats().entries, 0);
+        let err = kms.dual_decrypt(&ciphertext, None, &[2u8; 8], &[1u8; 16], "/test/path").await.unwrap_err();
+        assert!(matches!(err, BentengError::RequestDenied(_)));
+        
+        // Denied request: one denial overrides any approvals
+        let (ciphertext, _) = crate::crypto::kem::kyber768_encapsulate(&public_key).unwrap();
+        let request_id = DualControlKms::request_id(&ciphertext, &[2u8; 8], &[1u8; 16], "/test/path").unwrap();
+        kms.submit_request(submission(&ciphertext)).await.unwrap();
+        kms.approve(
+            QuorumApproval::sign(&request_id, &[2u8; 8], "approver1", 60, &sk1).unwrap(),
+            "looks fine",
+        ).await.unwrap();
+        
+        let forged = QuorumDenial::sign(&request_id, "approver2", "forged", &sk1).unwrap();
+        assert!(kms.deny(forged).await.is_err());
+        let request = kms.deny(QuorumDenial::sign(&request_id, "approver2", "unknown requester", &sk2).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(request.status, RequestStatus::Denied);
+        
+        let err = kms.approve(
+            QuorumApproval::sign(&request_id, &[2u8; 8], "approver2", 60, &sk2).unwrap(),
+            "changed my mind",
+        ).await.unwrap_err();
+        assert_eq!(err, BentengError::RequestDenied("Request already denied".into()));
+        let err = kms.approve(
+            QuorumApproval::sign(b"no such request", &[2u8; 8], "approver1", 60, &sk1).unwrap(),
+            "",
+        ).await.unwrap_err();
+        assert_eq!(err, BentengError::RequestNotFound("Unknown request".into()));
+        let err = kms.dual_decrypt(&ciphertext, None, &[2u8; 8], &[1u8; 16], "/test/path").await.unwrap_err();
+        assert_eq!(err, BentengError::RequestDenied("Request denied by approver2: unknown requester".into()));
+        
+        let events: Vec<serde_json::Value> = std::fs::read_to_string(&events).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let kinds: Vec<&str> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+        assert_eq!(kinds, [
+            "requested", "approved", "approved", "quorum_reached", "denied",
+            "requested", "approved", "denied",
+        ]);
+    }
 }
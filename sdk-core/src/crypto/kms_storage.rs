@@ -1,106 +1,119 @@
-use sled::{Db, IVec};
+use crate::error::BentengError;
+use crate::crypto::approval::PendingRequest;
+use crate::crypto::quorum::QuorumApproval;
+
+use sled::Db;
 use std::path::Path;
-use serde::{Serialize, Deserialize};
-use std::time::{SystemTime, UNIX_EPOCH};
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PersistedApproval {
-    pub request_id: Vec<u8>,
-    pub approver: String,
-    pub timestamp: u64,
-    pub signature: Vec<u8>,
+
+type Result<T> = std::result::Result<T, BentengError>;
+
+/// Durable storage for signed quorum approvals and the requests they
+/// approve. Approvals are keyed by request and approver, so storing a second
+/// approval from the same approver replaces the first.
+pub trait ApprovalStore: Send + Sync {
+    fn add_approval(&self, approval: &QuorumApproval) -> Result<()>;
+    fn get_approvals(&self, request_id: &[u8]) -> Result<Vec<QuorumApproval>>;
+    /// Drop expired approvals, returning how many were removed
+    fn cleanup_expired(&self) -> Result<usize>;
+    fn put_request(&self, request: &PendingRequest) -> Result<()>;
+    fn get_request(&self, request_id: &[u8]) -> Result<Option<PendingRequest>>;
+    fn list_requests(&self) -> Result<Vec<PendingRequest>>;
 }
 
+fn storage_error(e: sled::Error) -> BentengError {
+    BentengError::KmsError(format!("Approval store: {}", e))
+}
+
+/// sled-backed approval store
 pub struct QuorumStorage {
     db: Db,
 }
 
 impl QuorumStorage {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, sled::Error> {
-        let db = sled::open(path)?;
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let db = sled::open(path).map_err(storage_error)?;
         Ok(Self { db })
     }
-    
-    pub fn add_approval(&self, approval: PersistedApproval) -> Result<(), sled::Error> {
-        let key = format!("approval:{}:{}", 
-            hex::encode(&approval.request_id),
-            approval.approver
-        );
-        
-        let value = serde_json::to_vec(&approval).unwrap();
-        self.db.insert(key.as_bytes(), value)?;
-        
-        // Update approval count
-        self.increment_approval_count(&approval.request_id)?;
-        
+
+    /// Store that lives only as long as the process, for tests and the mock HSM
+    pub fn temporary() -> Result<Self> {
+        let db = sled::Config::new().temporary(true).open().map_err(storage_error)?;
+        Ok(Self { db })
+    }
+
+    fn key(request_id: &[u8], approver: &str) -> String {
+        format!("approval:{}:{}", hex::encode(request_id), approver)
+    }
+}
+
+impl ApprovalStore for QuorumStorage {
+    fn add_approval(&self, approval: &QuorumApproval) -> Result<()> {
+        let key = Self::key(&approval.request_id, &approval.approver);
+        let value = serde_json::to_vec(approval).map_err(|_| BentengError::InternalError)?;
+
+        self.db.insert(key.as_bytes(), value).map_err(storage_error)?;
+        self.db.flush().map_err(storage_error)?;
         Ok(())
     }
-    
-    pub fn get_approvals(&self, request_id: &[u8]) -> Result<Vec<PersistedApproval>, sled::Error> {
+
+    fn get_approvals(&self, request_id: &[u8]) -> Result<Vec<QuorumApproval>> {
         let prefix = format!("approval:{}:", hex::encode(request_id));
-        
+
         let mut approvals = Vec::new();
         for item in self.db.scan_prefix(prefix.as_bytes()) {
-            let (_, value) = item?;
+            let (_, value) = item.map_err(storage_error)?;
             if let Ok(approval) = serde_json::from_slice(&value) {
                 approvals.push(approval);
             }
         }
-        
+
         Ok(approvals)
     }
-    
-    pub fn get_approval_count(&self, request_id: &[u8]) -> Result<usize, sled::Error> {
-        let key = format!("count:{}", hex::encode(request_id));
-        
-        match self.db.get(key.as_bytes())? {
-            Some(value) => {
-                let count = u64::from_le_bytes(
-                    value.as_ref().try_into().unwrap_or([0; 8])
-                );
-                Ok(count as usize)
+
+    fn cleanup_expired(&self) -> Result<usize> {
+        let mut removed = 0;
+
+        for item in self.db.scan_prefix(b"approval:") {
+            let (key, value) = item.map_err(storage_error)?;
+
+            let expired = serde_json::from_slice::<QuorumApproval>(&value)
+                .map_or(true, |approval| approval.is_expired());
+            if expired {
+                self.db.remove(key).map_err(storage_error)?;
+                removed += 1;
             }
-            None => Ok(0)
         }
+
+        Ok(removed)
     }
-    
-    fn increment_approval_count(&self, request_id: &[u8]) -> Result<(), sled::Error> {
-        let key = format!("count:{}", hex::encode(request_id));
-        
-        self.db.update_and_fetch(key.as_bytes(), |old| {
-            let count = match old {
-                Some(bytes) => {
-                    u64::from_le_bytes(bytes.try_into().unwrap_or([0; 8])) + 1
-                }
-                None => 1
-            };
-            Some(IVec::from(&count.to_le_bytes()))
-        })?;
-        
+
+    fn put_request(&self, request: &PendingRequest) -> Result<()> {
+        let key = format!("request:{}", request.request_id);
+        let value = serde_json::to_vec(request).map_err(|_| BentengError::InternalError)?;
+
+        self.db.insert(key.as_bytes(), value).map_err(storage_error)?;
+        self.db.flush().map_err(storage_error)?;
         Ok(())
     }
-    
-    pub fn cleanup_old_approvals(&self, max_age_secs: u64) -> Result<usize, sled::Error> {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        
-        let cutoff = now - max_age_secs;
-        let mut removed = 0;
-        
-        for item in self.db.scan_prefix(b"approval:") {
-            let (key, value) = item?;
-            
-            if let Ok(approval) = serde_json::from_slice::<PersistedApproval>(&value) {
-                if approval.timestamp < cutoff {
-                    self.db.remove(key)?;
-                    removed += 1;
-                }
+
+    fn get_request(&self, request_id: &[u8]) -> Result<Option<PendingRequest>> {
+        let key = format!("request:{}", hex::encode(request_id));
+
+        self.db.get(key.as_bytes())
+            .map_err(storage_error)?
+            .map(|value| serde_json::from_slice(&value).map_err(|_| BentengError::InternalError))
+            .transpose()
+    }
+
+    fn list_requests(&self) -> Result<Vec<PendingRequest>> {
+        let mut requests = Vec::new();
+        for item in self.db.scan_prefix(b"request:") {
+            let (_, value) = item.map_err(storage_error)?;
+            if let Ok(request) = serde_json::from_slice(&value) {
+                requests.push(request);
             }
         }
-        
-        Ok(removed)
+        Ok(requests)
     }
 }
 
@@ -108,26 +121,36 @@ impl QuorumStorage {
 mod tests {
     use super::*;
     use tempfile::tempdir;
-    
+
+    fn approval(approver: &str, expires_at: u64) -> QuorumApproval {
+        QuorumApproval {
+            request_id: vec![1, 2, 3, 4],
+            policy_id: b"policy".to_vec(),
+            approver: approver.to_string(),
+            expires_at,
+            signature: vec![5, 6, 7, 8],
+        }
+    }
+
     #[test]
     fn test_quorum_persistence() {
         let dir = tempdir().unwrap();
+
+        {
+            let storage = QuorumStorage::new(dir.path()).unwrap();
+            storage.add_approval(&approval("approver1", u64::MAX)).unwrap();
+            storage.add_approval(&approval("approver1", u64::MAX - 1)).unwrap();
+            storage.add_approval(&approval("approver2", 0)).unwrap();
+        }
+
+        // Approvals survive a reopen, one per approver
         let storage = QuorumStorage::new(dir.path()).unwrap();
-        
-        let approval = PersistedApproval {
-            request_id: vec![1, 2, 3, 4],
-            approver: "approver1".to_string(),
-            timestamp: 1234567890,
-            signature: vec![5, 6, 7, 8],
-        };
-        
-        storage.add_approval(approval.clone()).unwrap();
-        
         let approvals = storage.get_approvals(&[1, 2, 3, 4]).unwrap();
-        assert_eq!(approvals.len(), 1);
+        assert_eq!(approvals.len(), 2);
         assert_eq!(approvals[0].approver, "approver1");
-        
-        let count = storage.get_approval_count(&[1, 2, 3, 4]).unwrap();
-        assert_eq!(count, 1);
+        assert_eq!(approvals[0].expires_at, u64::MAX - 1);
+
+        assert_eq!(storage.cleanup_expired().unwrap(), 1);
+        assert_eq!(storage.get_approvals(&[1, 2, 3, 4]).unwrap().len(), 1);
     }
 }
@@ -2,10 +2,19 @@
 
 pub mod aad;
 pub mod aead;
+pub mod dek_cache;
+pub mod approval;
 pub mod kdf;
 pub mod kem;
+pub mod hsm;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
 pub mod sig;
 pub mod kms;
+pub mod key_catalog;
+pub mod kms_storage;
+pub mod quorum;
+pub mod shamir;
 
 use crate::error::{BentengError, Result};
 use rand::RngCore;
@@ -0,0 +1,462 @@
+//! PKCS#11 HSM backend
+//! Endpoints follow RFC 7512: `pkcs11:token=<label>?module-path=<library>`
+//! with the user PIN from `pin-source=<file>` or `pin-value=<pin>`.
+//!
+//! HSM-B derives K2 with an in-token HMAC key that never leaves the token.
+//!
+//! Tokens implement FIPS ML-KEM rather than the round-3 Kyber768 that
+//! envelopes use, so a token cannot decapsulate for HSM-A. A token serves
+//! HSM-A only when its endpoint opts in with `x-software-kem=insecure`. Each
+//! Kyber secret key is then kept sealed under a non-extractable AES-256 key
+//! of the token and decrypted into process memory for every decapsulation,
+//! so the key is exposed to the process and K1 is not token-protected.
+//! Without the opt-in, `open_hsm_a` refuses the endpoint and the KEM
+//! operations fail.
+
+use crate::error::BentengError;
+use crate::crypto::hsm::{k1_from_shared_secret, HsmBackend};
+use crate::crypto::kem::{kyber768_decapsulate, kyber768_keypair};
+use crate::crypto::generate_nonce;
+
+use cryptoki::context::{CInitializeArgs, CInitializeFlags, Pkcs11};
+use cryptoki::error::{Error as Pkcs11Error, RvError};
+use cryptoki::mechanism::aead::GcmParams;
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, AttributeType, KeyType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::types::AuthPin;
+use zeroize::Zeroizing;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+type Result<T> = std::result::Result<T, BentengError>;
+
+/// Endpoint scheme of PKCS#11 tokens
+pub const PKCS11_SCHEME: &str = "pkcs11:";
+
+const WRAP_KEY_LABEL: &str = "benteng/hsm-a/wrap";
+const K2_KEY_LABEL: &str = "benteng/hsm-b/k2";
+const KEM_PUBLIC_APP: &str = "benteng/hsm-a/kem-public";
+const KEM_SECRET_APP: &str = "benteng/hsm-a/kem-secret";
+
+fn hsm_error(e: Pkcs11Error) -> BentengError {
+    BentengError::KmsUnavailable(format!("PKCS#11: {}", e))
+}
+
+fn no_token_kem() -> BentengError {
+    BentengError::KmsError(
+        "PKCS#11 tokens cannot decapsulate Kyber768; HSM-A needs x-software-kem=insecure to run it in software".into()
+    )
+}
+
+/// Token, library and PIN named by a `pkcs11:` endpoint
+#[derive(Clone)]
+pub struct Pkcs11Uri {
+    pub token: String,
+    pub module_path: PathBuf,
+    /// Run Kyber in software with secret keys sealed by the token;
+    /// `x-software-kem=insecure`
+    pub software_kem: bool,
+    pin: Zeroizing<String>,
+}
+
+impl std::fmt::Debug for Pkcs11Uri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pkcs11Uri")
+            .field("token", &self.token)
+            .field("module_path", &self.module_path)
+            .field("software_kem", &self.software_kem)
+            .field("pin", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Pkcs11Uri {
+    pub fn parse(endpoint: &str) -> Result<Self> {
+        let invalid = |reason: &str| BentengError::KmsError(format!("Invalid PKCS#11 endpoint: {}", reason));
+        let rest = endpoint.strip_prefix(PKCS11_SCHEME).ok_or_else(|| invalid("not a pkcs11: URI"))?;
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        let mut token = None;
+        for attribute in path.split(';').filter(|a| !a.is_empty()) {
+            if let Some(("token", value)) = attribute.split_once('=') {
+                token = Some(percent_decode(value).ok_or_else(|| invalid("bad escape in token"))?);
+            }
+        }
+
+        let mut module_path = None;
+        let mut software_kem = false;
+        let mut pin = None;
+        for attribute in query.split('&').filter(|a| !a.is_empty()) {
+            let (name, value) = attribute.split_once('=').ok_or_else(|| invalid(attribute))?;
+            let value = percent_decode(value).ok_or_else(|| invalid("bad escape"))?;
+            match name {
+                "module-path" => module_path = Some(PathBuf::from(value)),
+                "x-software-kem" if value == "insecure" => software_kem = true,
+                "x-software-kem" => return Err(invalid("x-software-kem only takes insecure")),
+                "pin-value" => pin = Some(Zeroizing::new(value)),
+                "pin-source" => {
+                    let file = value.strip_prefix("file:").unwrap_or(&value);
+                    let contents = Zeroizing::new(std::fs::read_to_string(file)
+                        .map_err(|e| BentengError::KmsError(format!("PKCS#11 PIN source {}: {}", file, e)))?);
+                    pin = Some(Zeroizing::new(contents.trim_end_matches(['\r', '\n']).to_string()));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            token: token.ok_or_else(|| invalid("missing token"))?,
+            module_path: module_path.ok_or_else(|| invalid("missing module-path"))?,
+            software_kem,
+            pin: pin.ok_or_else(|| invalid("missing pin-source or pin-value"))?,
+        })
+    }
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// One context per library: a second `C_Initialize` is refused and the
+/// first `C_Finalize` would end every session of the process
+fn context(module_path: &Path) -> Result<Pkcs11> {
+    static CONTEXTS: OnceLock<Mutex<HashMap<PathBuf, Pkcs11>>> = OnceLock::new();
+    let mut contexts = CONTEXTS.get_or_init(Default::default)
+        .lock()
+        .map_err(|_| BentengError::InternalError)?;
+    if let Some(context) = contexts.get(module_path) {
+        return Ok(context.clone());
+    }
+
+    let context = Pkcs11::new(module_path).map_err(hsm_error)?;
+    match context.initialize(CInitializeArgs::new(CInitializeFlags::OS_LOCKING_OK)) {
+        Ok(()) | Err(Pkcs11Error::Pkcs11(RvError::CryptokiAlreadyInitialized, _)) => {}
+        Err(e) => return Err(hsm_error(e)),
+    }
+    contexts.insert(module_path.to_path_buf(), context.clone());
+    Ok(context)
+}
+
+/// HSM on a PKCS#11 token. One logged-in session serves all operations.
+pub struct Pkcs11Hsm {
+    token: String,
+    software_kem: bool,
+    session: Mutex<Session>,
+}
+
+impl Pkcs11Hsm {
+    /// Log in to the token named by a `pkcs11:` endpoint
+    pub fn open(endpoint: &str) -> Result<Self> {
+        Self::open_uri(Pkcs11Uri::parse(endpoint)?)
+    }
+
+    /// Log in to a token serving as HSM-A. Refused unless the endpoint opts
+    /// in to software Kyber with `x-software-kem=insecure`.
+    pub fn open_hsm_a(endpoint: &str) -> Result<Self> {
+        let uri = Pkcs11Uri::parse(endpoint)?;
+        if !uri.software_kem {
+            return Err(no_token_kem());
+        }
+        tracing::warn!(token = %uri.token, "HSM-A decapsulates in software; Kyber secret keys pass through process memory");
+        Self::open_uri(uri)
+    }
+
+    fn open_uri(uri: Pkcs11Uri) -> Result<Self> {
+        let context = context(&uri.module_path)?;
+
+        let mut slot = None;
+        for candidate in context.get_slots_with_token().map_err(hsm_error)? {
+            let info = context.get_token_info(candidate).map_err(hsm_error)?;
+            if info.label().trim_end() == uri.token {
+                slot = Some(candidate);
+                break;
+            }
+        }
+        let slot = slot.ok_or_else(|| BentengError::KmsUnavailable(format!("PKCS#11 token {} not found", uri.token)))?;
+
+        let session = context.open_rw_session(slot).map_err(hsm_error)?;
+        let pin = AuthPin::from(uri.pin.as_str());
+        match session.login(UserType::User, Some(&pin)) {
+            // Login state is shared by all sessions of the token
+            Ok(()) | Err(Pkcs11Error::Pkcs11(RvError::UserAlreadyLoggedIn, _)) => {}
+            Err(e) => return Err(hsm_error(e)),
+        }
+
+        Ok(Self {
+            token: uri.token,
+            software_kem: uri.software_kem,
+            session: Mutex::new(session),
+        })
+    }
+
+    /// KEM operations run in software, so only when the endpoint opted in
+    fn check_software_kem(&self) -> Result<()> {
+        if !self.software_kem {
+            return Err(no_token_kem());
+        }
+        Ok(())
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    fn session(&self) -> Result<std::sync::MutexGuard<'_, Session>> {
+        self.session.lock().map_err(|_| BentengError::InternalError)
+    }
+
+    fn find(session: &Session, template: &[Attribute]) -> Result<Option<ObjectHandle>> {
+        Ok(session.find_objects(template).map_err(hsm_error)?.into_iter().next())
+    }
+
+    fn find_secret_key(session: &Session, label: &str) -> Result<Option<ObjectHandle>> {
+        Self::find(session, &[
+            Attribute::Class(ObjectClass::SECRET_KEY),
+            Attribute::Label(label.as_bytes().to_vec()),
+        ])
+    }
+
+    fn find_data(session: &Session, application: &str, kid: &str) -> Result<Option<ObjectHandle>> {
+        Self::find(session, &[
+            Attribute::Class(ObjectClass::DATA),
+            Attribute::Application(application.as_bytes().to_vec()),
+            Attribute::Label(kid.as_bytes().to_vec()),
+        ])
+    }
+
+    fn data_value(session: &Session, object: ObjectHandle) -> Result<Vec<u8>> {
+        let attributes = session.get_attributes(object, &[AttributeType::Value]).map_err(hsm_error)?;
+        match attributes.into_iter().next() {
+            Some(Attribute::Value(value)) => Ok(value),
+            _ => Err(BentengError::KmsError("PKCS#11 data object has no value".into())),
+        }
+    }
+
+    /// Token key sealing the Kyber secret keys, created on first use
+    fn wrap_key(session: &Session) -> Result<ObjectHandle> {
+        if let Some(key) = Self::find_secret_key(session, WRAP_KEY_LABEL)? {
+            return Ok(key);
+        }
+        session.generate_key(&Mechanism::AesKeyGen, &[
+            Attribute::Token(true),
+            Attribute::Private(true),
+            Attribute::Sensitive(true),
+            Attribute::Extractable(false),
+            Attribute::KeyType(KeyType::AES),
+            Attribute::ValueLen(32.into()),
+            Attribute::Encrypt(true),
+            Attribute::Decrypt(true),
+            Attribute::Label(WRAP_KEY_LABEL.as_bytes().to_vec()),
+        ]).map_err(hsm_error)
+    }
+
+    /// Token HMAC key for K2, created on first use
+    fn k2_key(session: &Session) -> Result<ObjectHandle> {
+        if let Some(key) = Self::find_secret_key(session, K2_KEY_LABEL)? {
+            return Ok(key);
+        }
+        session.generate_key(&Mechanism::GenericSecretKeyGen, &[
+            Attribute::Token(true),
+            Attribute::Private(true),
+            Attribute::Sensitive(true),
+            Attribute::Extractable(false),
+            Attribute::KeyType(KeyType::GENERIC_SECRET),
+            Attribute::ValueLen(32.into()),
+            Attribute::Sign(true),
+            Attribute::Label(K2_KEY_LABEL.as_bytes().to_vec()),
+        ]).map_err(hsm_error)
+    }
+}
+
+impl HsmBackend for Pkcs11Hsm {
+    fn public_key(&self, kid: &str) -> Result<Vec<u8>> {
+        let session = self.session()?;
+        let object = Self::find_data(&session, KEM_PUBLIC_APP, kid)?
+            .ok_or_else(|| BentengError::KeyNotFound("Key not found".into()))?;
+        Self::data_value(&session, object)
+    }
+
+    fn derive_k1(&self, kid: &str, kem_ciphertext: &[u8]) -> Result<[u8; 32]> {
+        self.check_software_kem()?;
+        let session = self.session()?;
+        let object = Self::find_data(&session, KEM_SECRET_APP, kid)?
+            .ok_or_else(|| BentengError::KeyNotFound("KEM key not found in HSM-A".into()))?;
+        let sealed = Self::data_value(&session, object)?;
+        if sealed.len() < 12 {
+            return Err(BentengError::KmsError("Sealed KEM key is truncated".into()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(12);
+
+        let mut nonce = nonce.to_vec();
+        let params = GcmParams::new(&mut nonce, kid.as_bytes(), 128.into()).map_err(hsm_error)?;
+        let secret_key = Zeroizing::new(session
+            .decrypt(&Mechanism::AesGcm(params), Self::wrap_key(&session)?, ciphertext)
+            .map_err(hsm_error)?);
+        drop(session);
+
+        let shared_secret = kyber768_decapsulate(&secret_key, kem_ciphertext)?;
+        k1_from_shared_secret(&*shared_secret)
+    }
+
+    fn derive_k2(&self, context: &[u8]) -> Result<[u8; 32]> {
+        let session = self.session()?;
+        let key = Self::k2_key(&session)?;
+        let message = [b"benteng/hsm-b/k2/pkcs11".as_slice(), context].concat();
+        let mac = session.sign(&Mechanism::Sha256Hmac, key, &message).map_err(hsm_error)?;
+        mac.try_into().map_err(|_| BentengError::KmsError("PKCS#11 HMAC has the wrong length".into()))
+    }
+
+    fn generate_key(&self, kid: &str) -> Result<Vec<u8>> {
+        self.check_software_kem()?;
+        let session = self.session()?;
+        if Self::find_data(&session, KEM_PUBLIC_APP, kid)?.is_some() {
+            return Err(BentengError::KeyUnavailable(format!("Key {} already exists", kid)));
+        }
+
+        let (public_key, secret_key) = kyber768_keypair()?;
+        let nonce = generate_nonce()?;
+        let mut iv = nonce.to_vec();
+        let params = GcmParams::new(&mut iv, kid.as_bytes(), 128.into()).map_err(hsm_error)?;
+        let ciphertext = session
+            .encrypt(&Mechanism::AesGcm(params), Self::wrap_key(&session)?, &secret_key)
+            .map_err(hsm_error)?;
+
+        let data = |application: &str, private: bool, value: Vec<u8>| vec![
+            Attribute::Class(ObjectClass::DATA),
+            Attribute::Token(true),
+            Attribute::Private(private),
+            Attribute::Application(application.as_bytes().to_vec()),
+            Attribute::Label(kid.as_bytes().to_vec()),
+            Attribute::Value(value),
+        ];
+        session.create_object(&data(KEM_SECRET_APP, true, [nonce.as_slice(), &ciphertext].concat()))
+            .map_err(hsm_error)?;
+        session.create_object(&data(KEM_PUBLIC_APP, false, public_key.clone()))
+            .map_err(hsm_error)?;
+        Ok(public_key)
+    }
+
+    fn destroy_key(&self, kid: &str) -> Result<()> {
+        let session = self.session()?;
+        let mut found = false;
+        for application in [KEM_SECRET_APP, KEM_PUBLIC_APP] {
+            if let Some(object) = Self::find_data(&session, application, kid)? {
+                session.destroy_object(object).map_err(hsm_error)?;
+                found = true;
+            }
+        }
+        if !found {
+            return Err(BentengError::KeyNotFound("Key not found".into()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::kms::{DualControlConfig, DualControlKms, KmsGate};
+
+    #[test]
+    fn test_parse_endpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let pin_file = dir.path().join("pin");
+        std::fs::write(&pin_file, "1234\n").unwrap();
+
+        let uri = Pkcs11Uri::parse(&format!(
+            "pkcs11:token=Benteng%20A?module-path=/usr/lib/softhsm/libsofthsm2.so&pin-source=file:{}",
+            pin_file.display()
+        )).unwrap();
+        assert_eq!(uri.token, "Benteng A");
+        assert_eq!(uri.module_path, PathBuf::from("/usr/lib/softhsm/libsofthsm2.so"));
+        assert_eq!(uri.pin.as_str(), "1234");
+        assert!(!format!("{:?}", uri).contains("1234"));
+        assert!(!uri.software_kem);
+
+        let uri = Pkcs11Uri::parse("pkcs11:token=a?module-path=/lib.so&pin-value=1&x-software-kem=insecure").unwrap();
+        assert!(uri.software_kem);
+        assert!(Pkcs11Uri::parse("pkcs11:token=a?module-path=/lib.so&pin-value=1&x-software-kem=yes").is_err());
+
+        assert!(Pkcs11Uri::parse("pkcs11:token=a?pin-value=1").is_err());
+        assert!(Pkcs11Uri::parse("pkcs11:?module-path=/lib.so&pin-value=1").is_err());
+        assert!(Pkcs11Uri::parse("mock://hsm-a").is_err());
+    }
+
+    #[test]
+    fn test_hsm_a_requires_software_kem_opt_in() {
+        // Refused before the library is loaded
+        let endpoint = "pkcs11:token=a?module-path=/nonexistent/libpkcs11.so&pin-value=1";
+        let err = DualControlKms::connect(DualControlConfig {
+            hsm_a_endpoint: endpoint.to_string(),
+            hsm_b_endpoint: "mock://hsm-b".to_string(),
+            ..Default::default()
+        }).err().unwrap();
+        assert!(matches!(err, BentengError::KmsError(ref reason) if reason.contains("x-software-kem")), "{:?}", err);
+    }
+
+    /// Runs against SoftHSMv2 when `SOFTHSM2_MODULE` names its library
+    #[tokio::test]
+    async fn test_softhsm_dual_control() {
+        let Ok(module) = std::env::var("SOFTHSM2_MODULE") else {
+            eprintln!("SOFTHSM2_MODULE not set; skipping");
+            return;
+        };
+
+        // A private token directory with one initialized token per HSM
+        let dir = tempfile::tempdir().unwrap();
+        let tokens = dir.path().join("tokens");
+        std::fs::create_dir(&tokens).unwrap();
+        let conf = dir.path().join("softhsm2.conf");
+        std::fs::write(&conf, format!("directories.tokendir = {}\nobjectstore.backend = file\n", tokens.display())).unwrap();
+        std::env::set_var("SOFTHSM2_CONF", &conf);
+
+        let pkcs11 = context(Path::new(&module)).unwrap();
+        for label in ["benteng-a", "benteng-b"] {
+            let slot = pkcs11.get_slots_with_token().unwrap().into_iter()
+                .find(|slot| !pkcs11.get_token_info(*slot).unwrap().token_initialized())
+                .unwrap();
+            pkcs11.init_token(slot, &AuthPin::from("so-pin"), label).unwrap();
+            let session = pkcs11.open_rw_session(slot).unwrap();
+            session.login(UserType::So, Some(&AuthPin::from("so-pin"))).unwrap();
+            session.init_pin(&AuthPin::from("1234")).unwrap();
+        }
+
+        let endpoint = |label: &str| format!("pkcs11:token={}?module-path={}&pin-value=1234", label, module);
+        let hsm_a = format!("{}&x-software-kem=insecure", endpoint("benteng-a"));
+        let kms = DualControlKms::connect(DualControlConfig {
+            hsm_a_endpoint: hsm_a.clone(),
+            hsm_b_endpoint: endpoint("benteng-b"),
+            require_quorum: false,
+            ..Default::default()
+        }).unwrap();
+        let key = kms.generate_key(b"tenant", b"policy", Default::default()).await.unwrap();
+
+        let wrapped = kms.generate_dek(None, b"policy", b"tenant", "/v1/data").await.unwrap();
+        assert_eq!(wrapped.kid, key.kid);
+        let dek = kms.dual_decrypt(&wrapped.kem_ciphertext, Some(&key.kid), b"policy", b"tenant", "/v1/data").await.unwrap();
+        assert_eq!(dek, *wrapped.dek);
+
+        // Keys persist in the token across sessions
+        let hsm = Pkcs11Hsm::open_hsm_a(&hsm_a).unwrap();
+        assert!(matches!(hsm.generate_key(&key.kid), Err(BentengError::KeyUnavailable(_))));
+        let (ciphertext, k1) = hsm.encapsulate_k1(&key.kid).unwrap();
+        assert_eq!(hsm.derive_k1(&key.kid, &ciphertext).unwrap(), k1);
+        hsm.destroy_key(&key.kid).unwrap();
+        assert!(matches!(hsm.public_key(&key.kid), Err(BentengError::KeyNotFound(_))));
+    }
+}
@@ -0,0 +1,217 @@
+//! Signed quorum approvals for HSM-B
+//! An approval is an ML-DSA (Dilithium3) signature by a registered approver
+//! over the request ID, the policy and an expiry time.
+
+use crate::error::BentengError;
+use crate::crypto::sig;
+
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+type Result<T> = std::result::Result<T, BentengError>;
+
+const APPROVAL_DOMAIN: &[u8] = b"benteng/quorum-approval/v1";
+const DENIAL_DOMAIN: &[u8] = b"benteng/quorum-denial/v1";
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Person or system allowed to approve HSM-B requests
+#[derive(Debug, Clone)]
+pub struct Approver {
+    pub id: String,
+    pub public_key: Vec<u8>,
+}
+
+/// One approver's signed approval of a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuorumApproval {
+    pub request_id: Vec<u8>,
+    pub policy_id: Vec<u8>,
+    pub approver: String,
+    pub expires_at: u64,
+    pub signature: Vec<u8>,
+}
+
+impl QuorumApproval {
+    /// Sign an approval valid for `ttl_secs`
+    pub fn sign(
+        request_id: &[u8],
+        policy_id: &[u8],
+        approver: &str,
+        ttl_secs: u64,
+        signing_key: &[u8],
+    ) -> Result<Self> {
+        let mut approval = Self {
+            request_id: request_id.to_vec(),
+            policy_id: policy_id.to_vec(),
+            approver: approver.to_string(),
+            expires_at: now_secs() + ttl_secs,
+            signature: vec![],
+        };
+        approval.signature = sig::dilithium3_sign(signing_key, &approval.signing_message())?;
+        Ok(approval)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        now_secs() >= self.expires_at
+    }
+
+    fn signing_message(&self) -> Vec<u8> {
+        let mut msg = signing_message(
+            APPROVAL_DOMAIN,
+            &[&self.request_id, &self.policy_id, self.approver.as_bytes()],
+        );
+        msg.extend_from_slice(&self.expires_at.to_be_bytes());
+        msg
+    }
+}
+
+/// One approver's signed denial of a request, with the reason given
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuorumDenial {
+    pub request_id: Vec<u8>,
+    pub approver: String,
+    pub reason: String,
+    pub signature: Vec<u8>,
+}
+
+impl QuorumDenial {
+    pub fn sign(request_id: &[u8], approver: &str, reason: &str, signing_key: &[u8]) -> Result<Self> {
+        let mut denial = Self {
+            request_id: request_id.to_vec(),
+            approver: approver.to_string(),
+            reason: reason.to_string(),
+            signature: vec![],
+        };
+        denial.signature = sig::dilithium3_sign(signing_key, &denial.signing_message())?;
+        Ok(denial)
+    }
+
+    fn signing_message(&self) -> Vec<u8> {
+        signing_message(
+            DENIAL_DOMAIN,
+            &[&self.request_id, self.approver.as_bytes(), self.reason.as_bytes()],
+        )
+    }
+}
+
+/// Length-prefixed fields under a domain separator
+fn signing_message(domain: &[u8], fields: &[&[u8]]) -> Vec<u8> {
+    let mut msg = domain.to_vec();
+    for field in fields {
+        msg.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        msg.extend_from_slice(field);
+    }
+    msg
+}
+
+/// Registered approvers and the longest approval lifetime accepted
+#[derive(Debug, Clone, Default)]
+pub struct ApproverRegistry {
+    approvers: Vec<Approver>,
+    max_ttl_secs: u64,
+}
+
+impl ApproverRegistry {
+    /// Approver IDs and public keys must be unique so one key cannot be
+    /// counted twice
+    pub fn new(approvers: Vec<Approver>, max_ttl_secs: u64) -> Result<Self> {
+        for (i, approver) in approvers.iter().enumerate() {
+            if approvers[..i].iter().any(|a| a.id == approver.id || a.public_key == approver.public_key) {
+                return Err(BentengError::KmsError(format!("Duplicate approver {}", approver.id)));
+            }
+        }
+        Ok(Self { approvers, max_ttl_secs })
+    }
+
+    fn verify_signature(&self, approver: &str, msg: &[u8], signature: &[u8]) -> Result<()> {
+        let approver = self.approvers.iter()
+            .find(|a| a.id == approver)
+            .ok_or_else(|| BentengError::InvalidApproval(format!("Unknown approver {}", approver)))?;
+
+        if !sig::dilithium3_verify(&approver.public_key, msg, signature)? {
+            return Err(BentengError::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    /// Check a denial is signed by a registered approver
+    pub fn verify_denial(&self, denial: &QuorumDenial) -> Result<()> {
+        self.verify_signature(&denial.approver, &denial.signing_message(), &denial.signature)
+    }
+
+    /// Check an approval is signed by a registered approver, unexpired and
+    /// no longer-lived than the registry allows
+    pub fn verify(&self, approval: &QuorumApproval) -> Result<()> {
+        self.verify_signature(&approval.approver, &approval.signing_message(), &approval.signature)?;
+        if approval.is_expired() {
+            return Err(BentengError::InvalidApproval("Approval expired".into()));
+        }
+        if approval.expires_at > now_secs() + self.max_ttl_secs {
+            return Err(BentengError::InvalidApproval("Approval lifetime exceeds the allowed TTL".into()));
+        }
+        Ok(())
+    }
+
+    /// Number of distinct approvers with a valid approval of this request,
+    /// optionally restricted to one policy
+    pub fn count_valid(
+        &self,
+        approvals: &[QuorumApproval],
+        request_id: &[u8],
+        policy_id: Option<&[u8]>,
+    ) -> usize {
+        let mut approvers: Vec<&str> = approvals.iter()
+            .filter(|a| a.request_id == request_id && policy_id.is_none_or(|p| a.policy_id == p))
+            .filter(|a| self.verify(a).is_ok())
+            .map(|a| a.approver.as_str())
+            .collect();
+        approvers.sort_unstable();
+        approvers.dedup();
+        approvers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+
+    #[test]
+    fn test_approvals_are_signed_and_deduplicated() {
+        let (pk1, sk1) = sig::dilithium3_keypair().unwrap();
+        let (pk2, sk2) = sig::dilithium3_keypair().unwrap();
+        let registry = ApproverRegistry::new(vec![
+            Approver { id: "alice".into(), public_key: pk1 },
+            Approver { id: "bob".into(), public_key: pk2 },
+        ], 600).unwrap();
+
+        let a1 = QuorumApproval::sign(b"req", b"policy", "alice", 60, &sk1).unwrap();
+        let a2 = QuorumApproval::sign(b"req", b"policy", "alice", 120, &sk1).unwrap();
+        assert_eq!(registry.count_valid(&[a1.clone(), a2], b"req", Some(&b"policy"[..])), 1);
+
+        // Bob's key cannot sign for Alice, and approvals do not transfer
+        let forged = QuorumApproval::sign(b"req", b"policy", "alice", 60, &sk2).unwrap();
+        assert_eq!(registry.verify(&forged), Err(BentengError::InvalidSignature));
+        assert_eq!(registry.count_valid(std::slice::from_ref(&a1), b"req", Some(&b"other"[..])), 0);
+
+        let mut tampered = a1.clone();
+        tampered.expires_at += 60;
+        assert!(registry.verify(&tampered).is_err());
+
+        let expired = QuorumApproval::sign(b"req", b"policy", "bob", 0, &sk2).unwrap();
+        assert_eq!(registry.verify(&expired), Err(BentengError::InvalidApproval("Approval expired".into())));
+        let too_long = QuorumApproval::sign(b"req", b"policy", "bob", 3600, &sk2).unwrap();
+        assert_eq!(registry.verify(&too_long).unwrap_err().code(), ErrorCode::InvalidApproval);
+        let unknown = QuorumApproval::sign(b"req", b"policy", "mallory", 60, &sk2).unwrap();
+        assert_eq!(registry.verify(&unknown).unwrap_err().code(), ErrorCode::InvalidApproval);
+
+        let bob = QuorumApproval::sign(b"req", b"policy", "bob", 60, &sk2).unwrap();
+        assert_eq!(registry.count_valid(&[a1, bob], b"req", Some(&b"policy"[..])), 2);
+    }
+}
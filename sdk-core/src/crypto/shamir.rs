@@ -0,0 +1,163 @@
+//! Shamir secret sharing of the HSM-B master secret
+//! The 32-byte secret behind K2 is split across custodians so that no single
+//! party can derive K2. A share set is identified by a secret ID derived from
+//! the secret, which lets a reconstruction detect bad or mismatched shares.
+
+use crate::error::BentengError;
+use crate::crypto::kdf::hkdf_sha256_derive;
+use crate::crypto::secure_random;
+
+use serde::{Deserialize, Serialize};
+use sharks::{Share, Sharks};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+type Result<T> = std::result::Result<T, BentengError>;
+
+/// Length of the HSM-B master secret
+pub const SECRET_LEN: usize = 32;
+
+/// One custodian's share of the HSM-B master secret
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct CustodianShare {
+    pub custodian: String,
+    /// Shares needed to reconstruct the secret
+    pub threshold: u8,
+    /// Hex ID of the secret this share belongs to
+    pub secret_id: String,
+    /// Hex-encoded share
+    pub share: String,
+}
+
+impl std::fmt::Debug for CustodianShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustodianShare")
+            .field("custodian", &self.custodian)
+            .field("threshold", &self.threshold)
+            .field("secret_id", &self.secret_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Generate a fresh master secret
+pub fn generate_secret() -> Result<Zeroizing<[u8; SECRET_LEN]>> {
+    let mut secret = Zeroizing::new([0u8; SECRET_LEN]);
+    secure_random(&mut *secret)?;
+    Ok(secret)
+}
+
+/// Public identifier of a master secret
+pub fn secret_id(secret: &[u8; SECRET_LEN]) -> Result<String> {
+    let id = hkdf_sha256_derive(secret, Some(b"benteng/hsm-b/secret-id/v1"), b"", 16)?;
+    Ok(hex::encode(&*id))
+}
+
+/// Split `secret` into one share per custodian, any `threshold` of which
+/// reconstruct it
+pub fn split(secret: &[u8; SECRET_LEN], threshold: u8, custodians: &[String]) -> Result<Vec<CustodianShare>> {
+    if threshold < 2 || threshold as usize > custodians.len() {
+        return Err(BentengError::KmsError(format!(
+            "Threshold must be between 2 and {} custodians", custodians.len()
+        )));
+    }
+    if custodians.len() > u8::MAX as usize {
+        return Err(BentengError::KmsError("Too many custodians".into()));
+    }
+    for (i, custodian) in custodians.iter().enumerate() {
+        if custodians[..i].contains(custodian) {
+            return Err(BentengError::KmsError(format!("Duplicate custodian {}", custodian)));
+        }
+    }
+
+    let secret_id = secret_id(secret)?;
+    Ok(Sharks(threshold)
+        .dealer(secret)
+        .zip(custodians)
+        .map(|(share, custodian)| {
+            let bytes = Zeroizing::new(Vec::from(&share));
+            CustodianShare {
+                custodian: custodian.clone(),
+                threshold,
+                secret_id: secret_id.clone(),
+                share: hex::encode(&*bytes),
+            }
+        })
+        .collect())
+}
+
+/// Reconstruct the master secret from at least `threshold` shares of the
+/// same secret. The result is zeroized when dropped.
+pub fn combine(shares: &[CustodianShare]) -> Result<Zeroizing<[u8; SECRET_LEN]>> {
+    let first = shares.first()
+        .ok_or_else(|| BentengError::KmsError("No shares supplied".into()))?;
+
+    let mut decoded = Vec::with_capacity(shares.len());
+    for (i, share) in shares.iter().enumerate() {
+        if share.secret_id != first.secret_id || share.threshold != first.threshold {
+            return Err(BentengError::KmsError(format!("Share from {} belongs to another secret", share.custodian)));
+        }
+        if shares[..i].iter().any(|s| s.custodian == share.custodian) {
+            return Err(BentengError::KmsError(format!("Duplicate share from {}", share.custodian)));
+        }
+        let bytes = Zeroizing::new(hex::decode(&share.share)
+            .map_err(|_| BentengError::KmsError(format!("Malformed share from {}", share.custodian)))?);
+        decoded.push(Share::try_from(bytes.as_slice())
+            .map_err(|e| BentengError::KmsError(format!("Malformed share from {}: {}", share.custodian, e)))?);
+    }
+
+    let recovered = Zeroizing::new(Sharks(first.threshold)
+        .recover(&decoded)
+        .map_err(|e| BentengError::KmsError(e.to_string()))?);
+    if recovered.len() != SECRET_LEN {
+        return Err(BentengError::KmsError("Shares do not reconstruct a valid secret".into()));
+    }
+
+    let mut secret = Zeroizing::new([0u8; SECRET_LEN]);
+    secret.copy_from_slice(&recovered);
+    if secret_id(&secret)? != first.secret_id {
+        return Err(BentengError::KmsError("Shares do not reconstruct the expected secret".into()));
+    }
+    Ok(secret)
+}
+
+/// Re-share an existing secret to a new custodian set and threshold. The
+/// secret, and so every K2, is unchanged; the old shares can no longer be
+/// combined with the new ones.
+pub fn reshare(shares: &[CustodianShare], threshold: u8, custodians: &[String]) -> Result<Vec<CustodianShare>> {
+    let secret = combine(shares)?;
+    split(&secret, threshold, custodians)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_split_combine_reshare() {
+        let secret = generate_secret().unwrap();
+        let shares = split(&secret, 2, &names(&["alice", "bob", "carol"])).unwrap();
+        assert_eq!(shares.len(), 3);
+
+        assert_eq!(*combine(&shares[1..]).unwrap(), *secret);
+        assert!(combine(&shares[..1]).is_err());
+        assert!(combine(&[shares[0].clone(), shares[0].clone()]).is_err());
+
+        let mut tampered = shares[1].clone();
+        let mut bytes = hex::decode(&tampered.share).unwrap();
+        bytes[1] ^= 1;
+        tampered.share = hex::encode(bytes);
+        assert!(combine(&[shares[0].clone(), tampered]).is_err());
+
+        // Re-sharing keeps the secret but old and new shares don't mix
+        let new_shares = reshare(&shares[..2], 3, &names(&["dave", "erin", "frank", "grace"])).unwrap();
+        assert_eq!(*combine(&new_shares[..3]).unwrap(), *secret);
+        assert!(combine(&[shares[0].clone(), new_shares[0].clone(), new_shares[1].clone()]).is_err());
+
+        assert!(split(&secret, 1, &names(&["alice", "bob"])).is_err());
+        assert!(split(&secret, 3, &names(&["alice", "bob"])).is_err());
+        assert!(split(&secret, 2, &names(&["alice", "alice"])).is_err());
+    }
+}
@@ -20,6 +20,13 @@ pub fn dilithium3_sign(secret_key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
     Ok(sig.as_bytes().to_vec())
 }
 
+/// Check that bytes decode as a Dilithium3 public key
+pub fn dilithium3_check_public_key(public_key: &[u8]) -> Result<()> {
+    dilithium3::PublicKey::from_bytes(public_key)
+        .map(|_| ())
+        .map_err(|_| BentengError::InvalidSignature)
+}
+
 /// Dilithium3 verify
 pub fn dilithium3_verify(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<bool> {
     let pk = dilithium3::PublicKey::from_bytes(public_key)
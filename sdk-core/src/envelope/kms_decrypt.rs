@@ -1,26 +1,79 @@
-//! KMS-based decrypt operations for envelopes
+//! KMS-based encrypt and decrypt operations for envelopes
 
 use crate::error::BentengError;
-use crate::envelope::Envelope;
-use crate::crypto::kms::KmsGate;
+use crate::envelope::{operations::EnvelopeOps, Envelope};
+use crate::crypto::kms::{unwrap_dek, KmsGate};
+use crate::crypto::approval::RequestSubmission;
 use crate::crypto::aad::Aad;
 use crate::crypto::aead;
+use zeroize::Zeroizing;
 
-/// Decrypt an envelope using dual-control KMS
+/// Encrypt and sign a payload under a fresh DEK from the KMS, so that
+/// `decrypt_with_kms` can open it again
+#[allow(clippy::too_many_arguments)]
+pub async fn encrypt_with_kms<K: KmsGate>(
+    payload: &[u8],
+    tenant_id: &[u8],
+    policy_id: &[u8],
+    path: &str,
+    kms: &K,
+    kid: Option<&str>,
+    client_sig_sk: &[u8],
+    hybrid: bool,
+) -> Result<Envelope, BentengError> {
+    let wrapped = kms.generate_dek(kid, policy_id, tenant_id, path).await?;
+    EnvelopeOps::encrypt_and_sign_with_dek(payload, tenant_id, policy_id, path, &wrapped, client_sig_sk, hybrid)
+}
+
+/// Decrypt an envelope using dual-control KMS. The request and its
+/// requester are submitted for quorum review first.
 pub async fn decrypt_with_kms<K: KmsGate>(
     envelope: &Envelope,
     kms: &K,
+    requester: &str,
+) -> Result<Vec<u8>, BentengError> {
+    decrypt(envelope, kms, requester, None).await
+}
+
+/// `decrypt_with_kms` under a policy that allows or forbids the KMS caching
+/// the DEK
+pub async fn decrypt_with_kms_caching<K: KmsGate>(
+    envelope: &Envelope,
+    kms: &K,
+    requester: &str,
+    cache_dek: bool,
+) -> Result<Vec<u8>, BentengError> {
+    decrypt(envelope, kms, requester, Some(cache_dek)).await
+}
+
+async fn decrypt<K: KmsGate>(
+    envelope: &Envelope,
+    kms: &K,
+    requester: &str,
+    cache_dek: Option<bool>,
 ) -> Result<Vec<u8>, BentengError> {
     // Extract KEM ciphertext from envelope
     let kem_ct = &envelope.kem_ct;
     
+    kms.submit_request(RequestSubmission::from_envelope(envelope, requester)?).await?;
+    
     // Get DEK from dual-control KMS
-    let dek = kms.dual_decrypt(
-        kem_ct,
-        &envelope.policy_id,
-        &envelope.tenant_id,
-        &envelope.path,
-    ).await?;
+    let kid = envelope.kid.as_deref();
+    let dek = match cache_dek {
+        Some(cache_dek) => kms.dual_decrypt_with_caching(
+            kem_ct, kid, &envelope.policy_id, &envelope.tenant_id, &envelope.path, cache_dek,
+        ).await?,
+        None => kms.dual_decrypt(kem_ct, kid, &envelope.policy_id, &envelope.tenant_id, &envelope.path).await?,
+    };
+    
+    // A rewrapped envelope's derived key only wraps the DEK
+    let dek = match &envelope.wrapped_dek {
+        Some(wrapped) => {
+            let kid = envelope.kid.as_deref().ok_or(BentengError::AeadFailure)?;
+            unwrap_dek(&dek, kid, wrapped)?
+        }
+        None => Zeroizing::new(dek),
+    };
     
     // Extract fields from envelope
     let required_algs = envelope.aad_ext.required_algs.as_str();
@@ -65,6 +118,7 @@ pub async fn decrypt_with_kms<K: KmsGate>(
 mod tests {
     use super::*;
     use crate::crypto::kms::{DualControlConfig, DualControlKms};
+    use crate::crypto::key_catalog::KeyOptions;
     
     #[tokio::test]
     async fn test_kms_decrypt() {
@@ -73,17 +127,60 @@ mod tests {
             require_quorum: false,
             ..Default::default()
         };
-        let kms = DualControlKms::new(config);
+        let kms = DualControlKms::new(config).unwrap();
+        
+        // Create the tenant's key
+        let key = kms.generate_key(&[0xABu8; 4], &[0x12u8; 4], KeyOptions::default()).await.unwrap();
+        assert!(!kms.check_quorum(&[0u8; 32]).await.unwrap());
+        
+        // Encrypt toward the KMS-held key
+        let (client_sig_pk, client_sig_sk) = crate::crypto::sig::dilithium3_keypair().unwrap();
+        let wrapped = kms.generate_dek(None, &[0x12u8; 4], &[0xABu8; 4], "/payments").await.unwrap();
+        assert_eq!(wrapped.kid, key.kid);
+        let envelope = EnvelopeOps::encrypt_and_sign_with_dek(
+            b"Secret message",
+            &[0xABu8; 4],
+            &[0x12u8; 4],
+            "/payments",
+            &wrapped,
+            &client_sig_sk,
+            true,
+        ).unwrap();
+        assert_eq!(envelope.kid.as_deref(), Some(key.kid.as_str()));
+        
+        // Survives the wire and decrypts through the gate
+        let envelope = Envelope::from_cbor(&envelope.to_cbor().unwrap()).unwrap();
+        EnvelopeOps::verify(&envelope, &client_sig_pk).unwrap();
+        let plaintext = decrypt_with_kms(&envelope, &kms, "test").await.unwrap();
+        assert_eq!(plaintext, b"Secret message");
+        
+        // The DEK is bound to the path
+        let mut moved = envelope.clone();
+        moved.path = "/other".into();
+        assert!(decrypt_with_kms(&moved, &kms, "test").await.is_err());
+        
+        // A rotated-out key no longer encrypts but still decrypts
+        let next = kms.rotate_key(&[0xABu8; 4], &[0x12u8; 4]).await.unwrap();
+        assert_eq!(kms.generate_dek(None, &[0x12u8; 4], &[0xABu8; 4], "/payments").await.unwrap().kid, next.kid);
+        assert!(kms.generate_dek(Some(&key.kid), &[0x12u8; 4], &[0xABu8; 4], "/payments").await.is_err());
+        assert_eq!(decrypt_with_kms(&envelope, &kms, "test").await.unwrap(), b"Secret message");
+    }
+    
+    #[tokio::test]
+    async fn test_encrypt_with_kms() {
+        let kms = DualControlKms::new(DualControlConfig { require_quorum: false, ..Default::default() }).unwrap();
+        kms.generate_key(&[0xCDu8; 4], &[0x34u8; 4], KeyOptions::default()).await.unwrap();
+        let (client_sig_pk, client_sig_sk) = crate::crypto::sig::dilithium3_keypair().unwrap();
         
-        // Initialize mock HSM
-        let kid = format!("{}-{}", 
-            hex::encode(&[0xABu8; 4]), 
-            hex::encode(&[0x12u8; 4])
-        );
-        kms.init_mock_hsm(&kid).await.unwrap();
+        let envelope = encrypt_with_kms(
+            b"Round trip", &[0xCDu8; 4], &[0x34u8; 4], "/ledger", &kms, None, &client_sig_sk, false,
+        ).await.unwrap();
+        EnvelopeOps::verify(&envelope, &client_sig_pk).unwrap();
+        assert_eq!(decrypt_with_kms(&envelope, &kms, "test").await.unwrap(), b"Round trip");
         
-        // For now, just test that KMS can be initialized
-        // Full integration test would require fixing all the imports
-        assert!(kms.check_quorum(&[0u8; 32]).await.unwrap() == false);
+        // A tenant without a key gets no DEK
+        assert!(encrypt_with_kms(
+            b"Round trip", &[0xEFu8; 4], &[0x34u8; 4], "/ledger", &kms, None, &client_sig_sk, false,
+        ).await.is_err());
     }
 }
@@ -59,6 +59,16 @@ pub struct Envelope {
     pub sig: Vec<u8>,
     #[serde(rename = "12")]
     pub ct: Vec<u8>,
+    /// KMS key the KEM ciphertext is wrapped to
+    #[serde(rename = "13", default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    /// DEK wrapped under the key derived from `kem_ct`, set by a rewrap
+    #[serde(rename = "14", default, skip_serializing_if = "Option::is_none")]
+    pub wrapped_dek: Option<Vec<u8>>,
+    /// ID of the signing key: the client's registered key, or the service
+    /// key after a rewrap
+    #[serde(rename = "15", default, skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
 }
 
 impl Envelope {
@@ -79,6 +89,9 @@ impl Envelope {
             kem_ct: vec![],
             sig: vec![],
             ct: vec![],
+            kid: None,
+            wrapped_dek: None,
+            signer: None,
         }
     }
     
@@ -1,7 +1,7 @@
 //! High-level envelope operations
 
 use crate::{
-    crypto::{self, aad::Aad, aead, kdf, kem, sig},
+    crypto::{self, aad::Aad, aead, kdf, kem, kms::WrappedDek, sig},
     envelope::Envelope,
     error::{BentengError, Result},
 };
@@ -11,7 +11,9 @@ use zeroize::Zeroizing;
 pub struct EnvelopeOps;
 
 impl EnvelopeOps {
-    /// Encrypt and sign a payload
+    /// Encrypt and sign a payload under a DEK derived from the server's KEM
+    /// key. The KMS cannot derive this DEK, so only `decrypt` opens it.
+    #[deprecated(note = "the KMS cannot decrypt these envelopes; use `encrypt_and_sign_with_dek` or `kms_decrypt::encrypt_with_kms`")]
     pub fn encrypt_and_sign(
         payload: &[u8],
         tenant_id: &[u8],
@@ -27,6 +29,53 @@ impl EnvelopeOps {
             path.to_string(),
         );
         
+        // Generate DEK
+        let (kem_ct, shared_secret) = kem::kyber768_encapsulate(server_kem_pk)?;
+        envelope.kem_ct = kem_ct;
+        
+        // Derive DEK from shared secret
+        let dek = kdf::hkdf_sha256_derive(
+            &shared_secret[..],
+            Some(tenant_id),
+            policy_id,
+            32,
+        )?;
+        let mut dek_array = Zeroizing::new([0u8; 32]);
+        dek_array.copy_from_slice(&dek);
+        
+        Self::seal(envelope, payload, &dek_array, client_sig_sk, hybrid)
+    }
+    
+    /// Encrypt and sign a payload under a DEK from `KmsGate::generate_dek`.
+    /// The envelope records the KID so `decrypt_with_kms` can unwrap it.
+    pub fn encrypt_and_sign_with_dek(
+        payload: &[u8],
+        tenant_id: &[u8],
+        policy_id: &[u8],
+        path: &str,
+        wrapped: &WrappedDek,
+        client_sig_sk: &[u8],
+        hybrid: bool,
+    ) -> Result<Envelope> {
+        let mut envelope = Envelope::new(
+            tenant_id.to_vec(),
+            policy_id.to_vec(),
+            path.to_string(),
+        );
+        envelope.kid = Some(wrapped.kid.clone());
+        envelope.kem_ct = wrapped.kem_ciphertext.clone();
+        
+        Self::seal(envelope, payload, &wrapped.dek, client_sig_sk, hybrid)
+    }
+    
+    /// Encrypt the payload into an envelope whose KEM fields are set, then sign it
+    fn seal(
+        mut envelope: Envelope,
+        payload: &[u8],
+        dek: &[u8; 32],
+        client_sig_sk: &[u8],
+        hybrid: bool,
+    ) -> Result<Envelope> {
         // Set hybrid flag
         envelope.algs.hybrid = hybrid;
         
@@ -41,9 +90,9 @@ impl EnvelopeOps {
         // Build AAD
         let aad = Aad::build(
             envelope.ver,
-            tenant_id,
-            policy_id,
-            path,
+            &envelope.tenant_id,
+            &envelope.policy_id,
+            &envelope.path,
             ts_epoch_ms,
             &envelope.aad_ext.required_algs,
             hybrid,
@@ -51,23 +100,9 @@ impl EnvelopeOps {
         );
         let aad_bytes = aad.to_cbor()?;
         
-        // Generate DEK
-        let (kem_ct, shared_secret) = kem::kyber768_encapsulate(server_kem_pk)?;
-        envelope.kem_ct = kem_ct;
-        
-        // Derive DEK from shared secret
-        let dek = kdf::hkdf_sha256_derive(
-            &shared_secret[..],
-            Some(tenant_id),
-            policy_id,
-            32,
-        )?;
-        
         // Encrypt payload
-        let mut dek_array = [0u8; 32];
-        dek_array.copy_from_slice(&dek);
         let ciphertext = aead::aes_256_gcm_encrypt(
-            &dek_array,
+            dek,
             &nonce,
             payload,
             &aad_bytes,
@@ -82,6 +117,32 @@ impl EnvelopeOps {
         Ok(envelope)
     }
     
+    /// Sign, or re-sign, the envelope as the key `signer`: a client key the
+    /// edge resolves from its registry, or a service key after a rewrap
+    pub fn sign(
+        envelope: &mut Envelope,
+        signer: &str,
+        sig_sk: &[u8],
+    ) -> Result<()> {
+        envelope.signer = Some(signer.to_string());
+        
+        let aad = Aad::build(
+            envelope.ver,
+            &envelope.tenant_id,
+            &envelope.policy_id,
+            &envelope.path,
+            envelope.ts_epoch_ms,
+            &envelope.aad_ext.required_algs,
+            envelope.algs.hybrid,
+            envelope.aad_ext.device_attest_hash.clone(),
+        );
+        let aad_bytes = aad.to_cbor()?;
+        
+        let sig_msg = Self::build_signature_message(envelope, &aad_bytes)?;
+        envelope.sig = sig::dilithium3_sign(sig_sk, &sig_msg)?;
+        Ok(())
+    }
+    
     /// Verify envelope signature and policy
     pub fn verify(
         envelope: &Envelope,
@@ -186,6 +247,7 @@ mod tests {
     use super::*;
     
     #[test]
+    #[allow(deprecated)]
     fn test_encrypt_verify_decrypt() {
         // Generate keys
         let (server_kem_pk, server_kem_sk) = kem::kyber768_keypair().unwrap();
@@ -1,5 +1,9 @@
 //! Benteng error types
+//! Every error the SDK and the edge API report carries an [`ErrorCode`].
+//! Codes are stable across releases, so clients can branch on them instead of
+//! on messages.
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
@@ -18,8 +22,236 @@ pub enum BentengError {
     #[error("KMS error: {0}")]
     KmsError(String),
 
+    #[error("{0}")]
+    QuorumNotMet(String),
+
+    #[error("{0}")]
+    RequestDenied(String),
+
+    #[error("{0}")]
+    RequestNotFound(String),
+
+    #[error("{0}")]
+    InvalidApproval(String),
+
+    #[error("{0}")]
+    KeyNotFound(String),
+
+    #[error("{0}")]
+    KeyUnavailable(String),
+
+    #[error("{0}")]
+    KmsUnavailable(String),
+
+    #[error("Policy bundle rejected: {0}")]
+    PolicyBundleRejected(String),
+
     #[error("Internal error")]
     InternalError,
 }
 
+impl BentengError {
+    /// Catalogue entry for this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::PolicyMismatch => ErrorCode::PolicyMismatch,
+            Self::InvalidSignature => ErrorCode::InvalidSignature,
+            Self::AeadFailure => ErrorCode::AeadFailure,
+            Self::EntropyUnavailable => ErrorCode::EntropyUnavailable,
+            Self::KmsError(_) => ErrorCode::KmsError,
+            Self::QuorumNotMet(_) => ErrorCode::QuorumNotMet,
+            Self::RequestDenied(_) => ErrorCode::RequestDenied,
+            Self::RequestNotFound(_) => ErrorCode::RequestNotFound,
+            Self::InvalidApproval(_) => ErrorCode::InvalidApproval,
+            Self::KeyNotFound(_) => ErrorCode::KeyNotFound,
+            Self::KeyUnavailable(_) => ErrorCode::KeyUnavailable,
+            Self::KmsUnavailable(_) => ErrorCode::KmsUnavailable,
+            Self::PolicyBundleRejected(_) => ErrorCode::PolicyBundleRejected,
+            Self::InternalError => ErrorCode::InternalError,
+        }
+    }
+
+    /// Error for `code` as reported by a remote KMS, keeping its message.
+    /// Codes the SDK has no variant for become `KmsError`.
+    pub fn from_code(code: ErrorCode, message: String) -> Self {
+        match code {
+            ErrorCode::PolicyMismatch => Self::PolicyMismatch,
+            ErrorCode::InvalidSignature => Self::InvalidSignature,
+            ErrorCode::AeadFailure => Self::AeadFailure,
+            ErrorCode::EntropyUnavailable => Self::EntropyUnavailable,
+            ErrorCode::QuorumNotMet => Self::QuorumNotMet(message),
+            ErrorCode::RequestDenied => Self::RequestDenied(message),
+            ErrorCode::RequestNotFound => Self::RequestNotFound(message),
+            ErrorCode::InvalidApproval => Self::InvalidApproval(message),
+            ErrorCode::KeyNotFound => Self::KeyNotFound(message),
+            ErrorCode::KeyUnavailable => Self::KeyUnavailable(message),
+            ErrorCode::KmsUnavailable => Self::KmsUnavailable(message),
+            ErrorCode::PolicyBundleRejected => Self::PolicyBundleRejected(message),
+            ErrorCode::InternalError => Self::InternalError,
+            _ => Self::KmsError(message),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, BentengError>;
+
+/// Declares [`ErrorCode`] from one table of code, HTTP status and title, so
+/// the variants, [`ErrorCode::ALL`] and the lookups cannot drift apart
+macro_rules! error_codes {
+    ($($variant:ident => ($code:literal, $status:literal, $title:literal),)*) => {
+        /// Stable, machine-readable error codes
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ErrorCode {
+            $($variant,)*
+        }
+
+        impl ErrorCode {
+            /// The whole catalogue
+            pub const ALL: &'static [ErrorCode] = &[$(Self::$variant,)*];
+
+            fn entry(&self) -> (&'static str, u16, &'static str) {
+                match self {
+                    $(Self::$variant => ($code, $status, $title),)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    // Requests
+    InvalidEnvelope => ("invalid_envelope", 400, "Invalid envelope format"),
+    EnvelopeExpired => ("envelope_expired", 400, "Envelope too old"),
+    InvalidRequest => ("invalid_request", 400, "Invalid request"),
+    NotFound => ("not_found", 404, "Not found"),
+    RateLimited => ("rate_limited", 429, "Rate limit exceeded"),
+    ReplayDetected => ("replay_detected", 409, "Replay detected"),
+
+    // Authentication and authorization
+    UnknownClientKey => ("unknown_client_key", 401, "Unknown client key"),
+    InvalidSignature => ("invalid_signature", 401, "Invalid signature"),
+    AdminRequired => ("admin_required", 401, "Admin credential required"),
+    TenantForbidden => ("tenant_forbidden", 403, "Client certificate not valid for tenant"),
+
+    // Policy
+    PolicyViolation => ("policy_violation", 403, "Policy violation"),
+    UnknownPolicy => ("unknown_policy", 403, "Unknown policy"),
+    PolicyMismatch => ("policy_mismatch", 403, "Policy mismatch"),
+    PolicyExpired => ("policy_expired", 503, "Policy state expired"),
+    PolicyUnavailable => ("policy_unavailable", 503, "No policy available"),
+    PolicyBundleRejected => ("policy_bundle_rejected", 422, "Policy bundle rejected"),
+
+    // Cryptography and the KMS
+    AeadFailure => ("aead_failure", 422, "AEAD failure"),
+    EntropyUnavailable => ("entropy_unavailable", 503, "Entropy unavailable"),
+    KmsError => ("kms_error", 500, "KMS error"),
+    QuorumNotMet => ("quorum_not_met", 403, "KMS quorum not met"),
+    RequestDenied => ("request_denied", 403, "Request denied"),
+    RequestNotFound => ("request_not_found", 404, "Request not found"),
+    InvalidApproval => ("invalid_approval", 403, "Invalid approval"),
+    KeyNotFound => ("key_not_found", 404, "Key not found"),
+    KeyUnavailable => ("key_unavailable", 409, "Key unavailable"),
+    KmsUnavailable => ("kms_unavailable", 503, "KMS unavailable"),
+
+    // Client key registry
+    ClientKeyNotFound => ("client_key_not_found", 404, "Client key not found"),
+    ClientKeyConflict => ("client_key_conflict", 409, "Client key conflict"),
+    ClientKeyRevoked => ("client_key_revoked", 409, "Client key revoked"),
+    InvalidClientKey => ("invalid_client_key", 400, "Invalid client key"),
+    AttestationFailed => ("attestation_failed", 403, "Attestation failed"),
+
+    // Transparency log
+    LogEmpty => ("log_empty", 404, "Log is empty"),
+    LogEntryNotFound => ("log_entry_not_found", 404, "No such entry"),
+    InvalidProofRequest => ("invalid_proof_request", 400, "Invalid proof request"),
+
+    // Dependencies
+    ClientKeyRegistryUnavailable => ("client_key_registry_unavailable", 503, "Client key registry unavailable"),
+    ReplayStoreUnavailable => ("replay_store_unavailable", 503, "Replay store unavailable"),
+    TransparencyLogUnavailable => ("transparency_log_unavailable", 503, "Transparency log unavailable"),
+    InternalError => ("internal_error", 500, "Internal error"),
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        self.entry().0
+    }
+
+    /// HTTP status the edge API answers with
+    pub fn http_status(&self) -> u16 {
+        self.entry().1
+    }
+
+    /// Short summary that does not vary between occurrences
+    pub fn title(&self) -> &'static str {
+        self.entry().2
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ErrorCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL.iter()
+            .find(|code| code.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown error code {}", s))
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        code.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_catalogue() {
+        let codes: HashSet<&str> = ErrorCode::ALL.iter().map(ErrorCode::as_str).collect();
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+
+        for code in ErrorCode::ALL {
+            assert_eq!(code.as_str().parse::<ErrorCode>(), Ok(*code));
+            assert!((400..600).contains(&code.http_status()), "{}", code);
+            let json = serde_json::to_string(code).unwrap();
+            assert_eq!(serde_json::from_str::<ErrorCode>(&json).unwrap(), *code);
+        }
+        assert!("no_such_code".parse::<ErrorCode>().is_err());
+    }
+
+    #[test]
+    fn test_errors_round_trip_through_codes() {
+        // As a remote KMS reports them: code and message
+        let errors = [
+            BentengError::QuorumNotMet("Insufficient quorum approvals".into()),
+            BentengError::KeyNotFound("Unknown key k1".into()),
+            BentengError::KmsUnavailable("KMS daemon: connection refused".into()),
+            BentengError::RequestNotFound("Unknown request".into()),
+            BentengError::InvalidApproval("Approval expired".into()),
+            BentengError::InvalidSignature,
+        ];
+        for error in errors {
+            assert_eq!(BentengError::from_code(error.code(), error.to_string()), error);
+        }
+        assert_eq!(BentengError::QuorumNotMet(String::new()).code().http_status(), 403);
+        assert_eq!(BentengError::RequestNotFound(String::new()).code().as_str(), "request_not_found");
+    }
+}
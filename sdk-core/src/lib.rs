@@ -4,10 +4,13 @@ pub mod crypto;
 pub mod envelope;
 pub mod error;
 pub mod policy;
+pub mod policy_bundle;
+pub mod policy_delta;
+pub mod policy_freshness;
 
 // Re-exports
 pub use envelope::{AadExtensions, AlgorithmSet, Envelope};
-pub use error::{BentengError, Result};
+pub use error::{BentengError, ErrorCode, Result};
 pub use policy::Policy;
 
 /// Library version
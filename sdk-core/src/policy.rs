@@ -1,10 +1,11 @@
 //! Policy management and validation
 
+use crate::envelope::Envelope;
 use crate::error::{BentengError, Result};
 use serde::{Deserialize, Serialize};
 
 /// Policy configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Policy {
     pub tenant_id: String,
     pub policy_id: String,
@@ -15,11 +16,100 @@ pub struct Policy {
     pub require_device_attest: bool,
     pub hybrid_allowed: bool,
     pub replay_ttl_ms: u64,
+    /// Whether the KMS may cache DEKs for this policy; off for
+    /// high-sensitivity paths
+    #[serde(default = "default_cache_dek")]
+    pub cache_dek: bool,
     pub version: u32,
 }
 
+fn default_cache_dek() -> bool {
+    true
+}
+
+/// Individual policy rule, used to attribute a rejection to a specific check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyRule {
+    Tenant,
+    PolicyId,
+    Path,
+    RequiredAlgs,
+    MaxAge,
+    MaxBodyBytes,
+    DeviceAttest,
+    Hybrid,
+}
+
+/// Request attributes a policy is evaluated against
+#[derive(Debug, Clone)]
+pub struct PolicyRequest<'a> {
+    pub tenant_id: &'a [u8],
+    pub policy_id: &'a [u8],
+    pub path: &'a str,
+    pub ts_epoch_ms: u64,
+    pub required_algs: &'a str,
+    pub body_bytes: usize,
+    pub hybrid: bool,
+    pub device_attested: bool,
+}
+
+impl<'a> PolicyRequest<'a> {
+    /// Build a request from a decoded envelope and the size of its encoded
+    /// body. `device_attested` comes from the signing key's enrollment, not
+    /// from the envelope, whose attestation hash the client chooses.
+    pub fn from_envelope(envelope: &'a Envelope, body_bytes: usize, device_attested: bool) -> Self {
+        Self {
+            tenant_id: &envelope.tenant_id,
+            policy_id: &envelope.policy_id,
+            path: &envelope.path,
+            ts_epoch_ms: envelope.ts_epoch_ms,
+            required_algs: &envelope.aad_ext.required_algs,
+            body_bytes,
+            hybrid: envelope.algs.hybrid,
+            device_attested,
+        }
+    }
+}
+
 impl Policy {
-    /// Validate envelope against policy
+    /// Evaluate every rule and return the ones the request violates
+    pub fn evaluate(&self, request: &PolicyRequest<'_>) -> Vec<PolicyRule> {
+        let mut violations = Vec::new();
+
+        if request.tenant_id != self.tenant_id.as_bytes() {
+            violations.push(PolicyRule::Tenant);
+        }
+        if request.policy_id != self.policy_id.as_bytes() {
+            violations.push(PolicyRule::PolicyId);
+        }
+        if request.path != self.path {
+            violations.push(PolicyRule::Path);
+        }
+        if request.required_algs != self.required_algs {
+            violations.push(PolicyRule::RequiredAlgs);
+        }
+
+        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+        if now_ms > request.ts_epoch_ms && (now_ms - request.ts_epoch_ms) > self.max_age_ms {
+            violations.push(PolicyRule::MaxAge);
+        }
+
+        if request.body_bytes > self.max_body_bytes {
+            violations.push(PolicyRule::MaxBodyBytes);
+        }
+        if self.require_device_attest && !request.device_attested {
+            violations.push(PolicyRule::DeviceAttest);
+        }
+        if request.hybrid && !self.hybrid_allowed {
+            violations.push(PolicyRule::Hybrid);
+        }
+
+        violations
+    }
+
+    /// Validate envelope against policy. Only the rules these fields
+    /// decide are checked; see [`Policy::evaluate`] for the rest.
     pub fn validate_envelope(
         &self,
         tenant_id: &[u8],
@@ -28,32 +118,26 @@ impl Policy {
         ts_epoch_ms: u64,
         required_algs: &str,
     ) -> Result<()> {
-        // Check tenant match
-        if tenant_id != self.tenant_id.as_bytes() {
-            return Err(BentengError::PolicyMismatch);
-        }
-
-        // Check policy match
-        if policy_id != self.policy_id.as_bytes() {
-            return Err(BentengError::PolicyMismatch);
-        }
-
-        // Check path match
-        if path != self.path {
-            return Err(BentengError::PolicyMismatch);
-        }
-
-        // Check algorithms
-        if required_algs != self.required_algs {
-            return Err(BentengError::PolicyMismatch);
-        }
-
-        // Check age
-        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
-        if now_ms > ts_epoch_ms && (now_ms - ts_epoch_ms) > self.max_age_ms {
+        const CHECKED: [PolicyRule; 5] = [
+            PolicyRule::Tenant,
+            PolicyRule::PolicyId,
+            PolicyRule::Path,
+            PolicyRule::RequiredAlgs,
+            PolicyRule::MaxAge,
+        ];
+        let request = PolicyRequest {
+            tenant_id,
+            policy_id,
+            path,
+            ts_epoch_ms,
+            required_algs,
+            body_bytes: 0,
+            hybrid: false,
+            device_attested: false,
+        };
+        if self.evaluate(&request).iter().any(|rule| CHECKED.contains(rule)) {
             return Err(BentengError::PolicyMismatch);
         }
-
         Ok(())
     }
 }
@@ -74,6 +158,7 @@ mod tests {
             require_device_attest: false,
             hybrid_allowed: true,
             replay_ttl_ms: 30000,
+            cache_dek: true,
             version: 1,
         };
 
@@ -100,5 +185,61 @@ mod tests {
                 "kyber+dilithium",
             )
             .is_err());
+
+        // Should fail - too old
+        assert!(policy
+            .validate_envelope(
+                b"tenant123",
+                b"policy456",
+                "/payments/transfer",
+                now - 60000,
+                "kyber+dilithium",
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_policy_evaluate_reports_rules() {
+        let policy = Policy {
+            tenant_id: "tenant123".to_string(),
+            policy_id: "policy456".to_string(),
+            path: "/payments/transfer".to_string(),
+            required_algs: "kyber+dilithium".to_string(),
+            max_age_ms: 30000,
+            max_body_bytes: 1024,
+            require_device_attest: true,
+            hybrid_allowed: false,
+            replay_ttl_ms: 30000,
+            cache_dek: true,
+            version: 1,
+        };
+
+        let request = PolicyRequest {
+            tenant_id: b"tenant123",
+            policy_id: b"policy456",
+            path: "/payments/transfer",
+            ts_epoch_ms: chrono::Utc::now().timestamp_millis() as u64,
+            required_algs: "kyber+dilithium",
+            body_bytes: 4096,
+            hybrid: true,
+            device_attested: false,
+        };
+
+        assert_eq!(
+            policy.evaluate(&request),
+            vec![
+                PolicyRule::MaxBodyBytes,
+                PolicyRule::DeviceAttest,
+                PolicyRule::Hybrid,
+            ]
+        );
+
+        let request = PolicyRequest {
+            body_bytes: 512,
+            hybrid: false,
+            device_attested: true,
+            ..request
+        };
+        assert!(policy.evaluate(&request).is_empty());
     }
 }
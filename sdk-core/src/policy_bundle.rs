@@ -1,19 +1,39 @@
-use crate::policy::Policy;
+use crate::policy::{Policy, PolicyRequest, PolicyRule};
 use crate::crypto::sig;
+use crate::error::BentengError;
+use crate::policy_delta::{policy_state_hash, PolicyState, SignedPolicyDelta};
+use crate::policy_freshness::{
+    BundleTimestamp, ExpiryBehaviour, Freshness, HighWaterMark, HighWaterMarkStore,
+};
 use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::time::SystemTime;
 
+/// Number of individual divergences retained by a `ShadowReport`
+const SHADOW_RECENT_LIMIT: usize = 100;
+
+/// One signer's signature over a policy bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleSignature {
+    pub signer_kid: String,
+    pub signature: Vec<u8>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignedPolicyBundle {
+    /// Tenant this bundle is a shard of; `None` for a bundle covering all tenants
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
     pub policies: Vec<Policy>,
     pub version: u64,
     pub created_at: u64,
     pub not_after: u64,
-    pub signer_kid: String,
-    pub signature: Vec<u8>,
+    pub signatures: Vec<BundleSignature>,
 }
 
 impl SignedPolicyBundle {
+    /// Create a bundle with a single signature
     pub fn create(
         policies: Vec<Policy>,
         version: u64,
@@ -21,32 +41,69 @@ impl SignedPolicyBundle {
         signer_kid: String,
         signing_key: &[u8],
     ) -> Result<Self, crate::error::BentengError> {
+        let mut bundle = Self::unsigned(policies, version, ttl_secs);
+        bundle.add_signature(signer_kid, signing_key)?;
+        Ok(bundle)
+    }
+    
+    /// Create a bundle with no signatures, to be passed between signers
+    pub fn unsigned(policies: Vec<Policy>, version: u64, ttl_secs: u64) -> Self {
         let now = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap()
             .as_secs();
         
-        let bundle = Self {
+        Self {
+            tenant_id: None,
             policies,
             version,
             created_at: now,
             not_after: now + ttl_secs,
-            signer_kid,
-            signature: vec![], // Will be filled after signing
-        };
-        
-        // Serialize for signing (without signature field)
-        let msg = Self::serialize_for_signing(&bundle)?;
-        
-        // Sign with Dilithium3
+            signatures: vec![],
+        }
+    }
+    
+    /// Unsigned shard holding only `tenant_id`'s policies
+    pub fn shard(tenant_id: &str, policies: &[Policy], version: u64, ttl_secs: u64) -> Self {
+        let policies = policies.iter().filter(|p| p.tenant_id == tenant_id).cloned().collect();
+        Self {
+            tenant_id: Some(tenant_id.to_string()),
+            ..Self::unsigned(policies, version, ttl_secs)
+        }
+    }
+    
+    /// Add (or replace) this signer's Dilithium3 signature
+    pub fn add_signature(
+        &mut self,
+        signer_kid: String,
+        signing_key: &[u8],
+    ) -> Result<(), BentengError> {
+        let msg = Self::serialize_for_signing(self)?;
         let signature = sig::dilithium3_sign(signing_key, &msg)?;
         
-        Ok(Self { signature, ..bundle })
+        self.signatures.retain(|s| s.signer_kid != signer_kid);
+        self.signatures.push(BundleSignature { signer_kid, signature });
+        Ok(())
+    }
+    
+    /// Parse a bundle from its JSON distribution format
+    pub fn from_json(data: &[u8]) -> Result<Self, BentengError> {
+        serde_json::from_slice(data)
+            .map_err(|e| BentengError::PolicyBundleRejected(format!("malformed bundle: {}", e)))
+    }
+    
+    /// Serialize a bundle to its JSON distribution format
+    pub fn to_json(&self) -> Result<Vec<u8>, BentengError> {
+        serde_json::to_vec_pretty(self)
+            .map_err(|_| BentengError::InternalError)
     }
     
+    /// True if any signature on the bundle verifies under `public_key`
     pub fn verify(&self, public_key: &[u8]) -> Result<bool, crate::error::BentengError> {
         let msg = Self::serialize_for_signing(self)?;
-        sig::dilithium3_verify(public_key, &msg, &self.signature)
+        Ok(self.signatures.iter().any(|s| {
+            sig::dilithium3_verify(public_key, &msg, &s.signature).unwrap_or(false)
+        }))
     }
     
     pub fn is_valid(&self) -> bool {
@@ -58,47 +115,676 @@ impl SignedPolicyBundle {
         now >= self.created_at && now < self.not_after
     }
     
+    /// SHA-256 of the signed content. Independent of which signatures are attached.
+    pub fn content_hash(&self) -> Result<[u8; 32], BentengError> {
+        let msg = Self::serialize_for_signing(self)?;
+        Ok(Sha256::digest(&msg).into())
+    }
+    
+    /// Order-independent hash of the policies, comparable with the state an
+    /// edge reconstructs from deltas
+    pub fn state_hash(&self) -> Result<[u8; 32], BentengError> {
+        policy_state_hash(self.tenant_id.as_deref(), &self.policies)
+    }
+    
     fn serialize_for_signing(bundle: &Self) -> Result<Vec<u8>, crate::error::BentengError> {
         let mut to_sign = bundle.clone();
-        to_sign.signature = vec![]; // Clear signature for deterministic serialization
+        to_sign.signatures = vec![]; // Clear signatures for deterministic serialization
         
         serde_json::to_vec(&to_sign)
             .map_err(|_| crate::error::BentengError::InternalError)
     }
 }
 
+/// Key a policy bundle must be signed with to be accepted
+#[derive(Debug, Clone)]
+pub struct TrustAnchor {
+    pub kid: String,
+    pub public_key: Vec<u8>,
+}
+
+/// Registered bundle signers and the number of distinct signatures required
+#[derive(Debug, Clone)]
+pub struct SignerSet {
+    signers: Vec<TrustAnchor>,
+    threshold: usize,
+}
+
+impl SignerSet {
+    /// M-of-N signer set. Key IDs and public keys must be unique so one key
+    /// cannot be counted twice.
+    pub fn new(signers: Vec<TrustAnchor>, threshold: usize) -> Result<Self, BentengError> {
+        if threshold == 0 || threshold > signers.len() {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "threshold {} invalid for {} signers", threshold, signers.len()
+            )));
+        }
+        
+        for (i, signer) in signers.iter().enumerate() {
+            if signers[..i].iter().any(|s| s.kid == signer.kid || s.public_key == signer.public_key) {
+                return Err(BentengError::PolicyBundleRejected(format!(
+                    "duplicate signer {}", signer.kid
+                )));
+            }
+        }
+        
+        Ok(Self { signers, threshold })
+    }
+    
+    /// 1-of-1 signer set
+    pub fn single(anchor: TrustAnchor) -> Self {
+        Self {
+            signers: vec![anchor],
+            threshold: 1,
+        }
+    }
+    
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+    
+    /// Key IDs of the registered signers whose signatures on the bundle are valid
+    pub fn valid_signers(&self, bundle: &SignedPolicyBundle) -> Result<Vec<String>, BentengError> {
+        let msg = SignedPolicyBundle::serialize_for_signing(bundle)?;
+        Ok(self.valid_signers_for(&msg, &bundle.signatures))
+    }
+    
+    /// Require at least `threshold` distinct registered signers
+    pub fn verify(&self, bundle: &SignedPolicyBundle) -> Result<(), BentengError> {
+        let msg = SignedPolicyBundle::serialize_for_signing(bundle)?;
+        self.verify_message(&msg, &bundle.signatures)
+    }
+    
+    pub(crate) fn valid_signers_for(&self, msg: &[u8], signatures: &[BundleSignature]) -> Vec<String> {
+        let mut valid: Vec<String> = Vec::new();
+        for signature in signatures {
+            if valid.contains(&signature.signer_kid) {
+                continue;
+            }
+            let Some(signer) = self.signers.iter().find(|s| s.kid == signature.signer_kid) else {
+                continue;
+            };
+            if sig::dilithium3_verify(&signer.public_key, msg, &signature.signature).unwrap_or(false) {
+                valid.push(signer.kid.clone());
+            }
+        }
+        valid
+    }
+    
+    pub(crate) fn verify_message(
+        &self,
+        msg: &[u8],
+        signatures: &[BundleSignature],
+    ) -> Result<(), BentengError> {
+        let valid = self.valid_signers_for(msg, signatures);
+        if valid.is_empty() {
+            return Err(BentengError::InvalidSignature);
+        }
+        if valid.len() < self.threshold {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "{} of {} required signatures", valid.len(), self.threshold
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Result of evaluating one request against the live policy and the
+/// candidate policy staged in the next bundle
+#[derive(Debug, Clone, Serialize)]
+pub struct ShadowEvaluation {
+    pub candidate_version: u64,
+    pub live: Vec<PolicyRule>,
+    pub candidate: Vec<PolicyRule>,
+}
+
+impl ShadowEvaluation {
+    /// True when the candidate would have reached a different decision or
+    /// rejected for different reasons
+    pub fn is_divergent(&self) -> bool {
+        self.live != self.candidate
+    }
+
+    /// Rules the candidate enforces that the live policy did not trip
+    pub fn newly_blocked(&self) -> Vec<PolicyRule> {
+        self.candidate
+            .iter()
+            .filter(|rule| !self.live.contains(rule))
+            .copied()
+            .collect()
+    }
+}
+
+/// A single divergent decision recorded in shadow mode
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub ts: u64,
+    pub tenant_id: String,
+    pub policy_id: String,
+    pub path: String,
+    pub live: Vec<PolicyRule>,
+    pub candidate: Vec<PolicyRule>,
+}
+
+/// Aggregated shadow-mode results for the current candidate bundle
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ShadowReport {
+    pub candidate_version: Option<u64>,
+    pub evaluated: u64,
+    pub divergent: u64,
+    /// Requests the live policy allowed but the candidate would block
+    pub would_block: u64,
+    /// Requests the live policy blocked but the candidate would allow
+    pub would_allow: u64,
+    /// Requests each candidate rule would have blocked on its own
+    pub blocked_by_rule: BTreeMap<PolicyRule, u64>,
+    pub recent: VecDeque<Divergence>,
+}
+
+impl ShadowReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a shadow evaluation, resetting the report when a different
+    /// candidate version is being evaluated
+    pub fn record(&mut self, request: &PolicyRequest<'_>, evaluation: &ShadowEvaluation) {
+        if self.candidate_version != Some(evaluation.candidate_version) {
+            *self = Self {
+                candidate_version: Some(evaluation.candidate_version),
+                ..Self::default()
+            };
+        }
+
+        self.evaluated += 1;
+        if !evaluation.is_divergent() {
+            return;
+        }
+
+        self.divergent += 1;
+        match (evaluation.live.is_empty(), evaluation.candidate.is_empty()) {
+            (true, false) => self.would_block += 1,
+            (false, true) => self.would_allow += 1,
+            _ => {}
+        }
+        for rule in evaluation.newly_blocked() {
+            *self.blocked_by_rule.entry(rule).or_insert(0) += 1;
+        }
+
+        if self.recent.len() >= SHADOW_RECENT_LIMIT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(Divergence {
+            ts: chrono::Utc::now().timestamp_millis() as u64,
+            tenant_id: String::from_utf8_lossy(request.tenant_id).into_owned(),
+            policy_id: String::from_utf8_lossy(request.policy_id).into_owned(),
+            path: request.path.to_string(),
+            live: evaluation.live.clone(),
+            candidate: evaluation.candidate.clone(),
+        });
+    }
+}
+
+/// Any signed document a policy source can publish
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PolicyDocument {
+    Bundle(SignedPolicyBundle),
+    Delta(SignedPolicyDelta),
+    Timestamp(BundleTimestamp),
+}
+
+impl PolicyDocument {
+    /// Parse a single document or a JSON array of documents
+    pub fn parse_all(data: &[u8]) -> Result<Vec<Self>, BentengError> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            Many(Vec<PolicyDocument>),
+            One(PolicyDocument),
+        }
+        
+        match serde_json::from_slice(data) {
+            Ok(OneOrMany::Many(documents)) => Ok(documents),
+            Ok(OneOrMany::One(document)) => Ok(vec![document]),
+            Err(_) => Err(BentengError::PolicyBundleRejected(
+                "not a policy bundle, delta or timestamp".into()
+            )),
+        }
+    }
+}
+
+/// Active and staged policy state for one scope: the all-tenant bundle, or
+/// a single tenant shard
+#[derive(Default)]
+struct Lane {
+    current: Option<PolicyState>,
+    next: Option<PolicyState>,
+    high_water_mark: Option<HighWaterMark>,
+    latest_timestamp: Option<BundleTimestamp>,
+}
+
+impl Lane {
+    /// Staged state if any, otherwise the active one
+    fn latest(&self) -> Option<&PolicyState> {
+        self.next.as_ref().or(self.current.as_ref())
+    }
+    
+    fn current_version(&self) -> u64 {
+        self.current.as_ref().map_or(0, |s| s.version)
+    }
+    
+    /// Whether freshness is tracked for this scope
+    fn is_tracked(&self) -> bool {
+        self.current.is_some() || self.next.is_some() || self.latest_timestamp.is_some()
+    }
+}
+
+#[derive(Default)]
 pub struct PolicyDistributor {
-    current_bundle: Option<SignedPolicyBundle>,
-    next_bundle: Option<SignedPolicyBundle>,
+    signer_set: Option<SignerSet>,
+    timestamp_signers: Option<SignerSet>,
+    high_water_mark_store: Option<Box<dyn HighWaterMarkStore>>,
+    expiry_behaviour: ExpiryBehaviour,
+    subscriptions: Option<BTreeSet<String>>,
+    lanes: BTreeMap<Option<String>, Lane>,
 }
 
 impl PolicyDistributor {
+    /// Distributor without signers; rejects every bundle
     pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Distributor accepting bundles signed by a single trust anchor
+    pub fn with_trust_anchor(trust_anchor: TrustAnchor) -> Self {
+        Self::with_signer_set(SignerSet::single(trust_anchor))
+    }
+    
+    /// Distributor requiring M-of-N signatures from a signer set
+    pub fn with_signer_set(signer_set: SignerSet) -> Self {
         Self {
-            current_bundle: None,
-            next_bundle: None,
+            signer_set: Some(signer_set),
+            ..Self::new()
+        }
+    }
+    
+    /// Persist activations and refuse bundles below the stored high-water mark
+    pub fn with_high_water_mark_store(
+        mut self,
+        store: Box<dyn HighWaterMarkStore>,
+    ) -> Result<Self, BentengError> {
+        self.lanes.entry(None).or_default().high_water_mark = store.load(None)?;
+        self.high_water_mark_store = Some(store);
+        Ok(self)
+    }
+    
+    /// Require signed timestamps from these signers to consider the policy state fresh
+    pub fn with_timestamp_signers(mut self, signers: SignerSet) -> Self {
+        self.timestamp_signers = Some(signers);
+        self
+    }
+    
+    pub fn with_expiry_behaviour(mut self, behaviour: ExpiryBehaviour) -> Self {
+        self.expiry_behaviour = behaviour;
+        self
+    }
+    
+    /// Serve only these tenants. Shards for other tenants are refused and
+    /// their policies in all-tenant bundles are ignored.
+    pub fn with_subscriptions<I: IntoIterator<Item = String>>(mut self, tenants: I) -> Self {
+        self.subscriptions = Some(tenants.into_iter().collect());
+        self
+    }
+    
+    /// Whether this edge serves `tenant_id`; the all-tenant scope is always served
+    pub fn serves(&self, tenant_id: Option<&str>) -> bool {
+        match (tenant_id, &self.subscriptions) {
+            (Some(tenant_id), Some(subscriptions)) => subscriptions.contains(tenant_id),
+            _ => true,
+        }
+    }
+    
+    fn check_scope(&self, tenant_id: Option<&str>) -> Result<(), BentengError> {
+        if !self.serves(tenant_id) {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "not subscribed to tenant {}", tenant_id.unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Lane for a scope, loading its high-water mark on first use
+    fn lane_mut(&mut self, tenant_id: Option<&str>) -> Result<&mut Lane, BentengError> {
+        let key = tenant_id.map(str::to_string);
+        if !self.lanes.contains_key(&key) {
+            let high_water_mark = match &self.high_water_mark_store {
+                Some(store) => store.load(tenant_id)?,
+                None => None,
+            };
+            self.lanes.insert(key.clone(), Lane {
+                high_water_mark,
+                ..Lane::default()
+            });
+        }
+        Ok(self.lanes.get_mut(&key).expect("lane inserted above"))
+    }
+    
+    /// Verify a full bundle or shard against the signer set and stage it for
+    /// activation. The bundle must be within its validity window, newer than
+    /// both the active and the staged state of its scope, and not below that
+    /// scope's high-water mark. The state at the high-water mark itself is
+    /// accepted again so an edge can reload it after a restart.
+    pub fn update_bundle(&mut self, bundle: SignedPolicyBundle) -> Result<(), BentengError> {
+        let signer_set = self.signer_set.as_ref().ok_or_else(|| {
+            BentengError::PolicyBundleRejected("no trust anchor configured".into())
+        })?;
+        
+        signer_set.verify(&bundle)?;
+        self.check_scope(bundle.tenant_id.as_deref())?;
+        
+        if !bundle.is_valid() {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "bundle v{} is outside its validity window", bundle.version
+            )));
+        }
+        
+        self.stage(PolicyState::from_bundle(bundle)?)
+    }
+    
+    /// Verify a delta and stage the state it produces. The delta must apply
+    /// to the latest staged or active state of its scope, and the result
+    /// must match the state hash it was signed with.
+    pub fn update_delta(&mut self, delta: SignedPolicyDelta) -> Result<(), BentengError> {
+        let signer_set = self.signer_set.as_ref().ok_or_else(|| {
+            BentengError::PolicyBundleRejected("no trust anchor configured".into())
+        })?;
+        
+        signer_set.verify_message(&delta.serialize_for_signing()?, &delta.signatures)?;
+        self.check_scope(delta.tenant_id.as_deref())?;
+        
+        if !delta.is_valid() {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "delta v{} is outside its validity window", delta.version
+            )));
+        }
+        
+        let lane = self.lane_mut(delta.tenant_id.as_deref())?;
+        let base = lane.latest().ok_or_else(|| {
+            BentengError::PolicyBundleRejected(format!(
+                "no base state for delta v{}", delta.version
+            ))
+        })?;
+        let state = base.apply(&delta)?;
+        
+        self.stage(state)
+    }
+    
+    fn stage(&mut self, state: PolicyState) -> Result<(), BentengError> {
+        let lane = self.lane_mut(state.tenant_id.as_deref())?;
+        
+        if let Some(mark) = &lane.high_water_mark {
+            if state.version < mark.version
+                || (state.version == mark.version && state.state_hash != mark.state_hash)
+            {
+                return Err(BentengError::PolicyBundleRejected(format!(
+                    "bundle v{} is below the high-water mark v{}", state.version, mark.version
+                )));
+            }
+        }
+        
+        let latest = lane.latest().map_or(0, |s| s.version);
+        if state.version <= latest {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "bundle v{} is not newer than v{}", state.version, latest
+            )));
+        }
+        
+        lane.next = Some(state);
+        Ok(())
+    }
+    
+    /// Promote the staged state of every scope. Callers hold the distributor
+    /// behind a lock, so lookups observe either the old or the new state,
+    /// never a mix. The high-water marks of all promoted scopes are persisted
+    /// in one write before any scope swaps, so a failed write leaves every
+    /// scope as it was.
+    pub fn activate_next(&mut self) -> Result<(), BentengError> {
+        self.activate(false)
+    }
+    
+    /// Promote staged state only for scopes with nothing active yet, leaving
+    /// other candidates staged for shadow evaluation
+    pub fn activate_initial(&mut self) -> Result<(), BentengError> {
+        self.activate(true)
+    }
+    
+    fn activate(&mut self, initial_only: bool) -> Result<(), BentengError> {
+        let accepted_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let marks: Vec<(Option<String>, HighWaterMark)> = self.lanes.iter()
+            .filter(|(_, lane)| !(initial_only && lane.current.is_some()))
+            .filter_map(|(tenant_id, lane)| {
+                let next = lane.next.as_ref()?;
+                Some((tenant_id.clone(), HighWaterMark {
+                    version: next.version,
+                    state_hash: next.state_hash,
+                    accepted_at,
+                }))
+            })
+            .collect();
+        
+        if let Some(store) = self.high_water_mark_store.as_ref().filter(|_| !marks.is_empty()) {
+            let batch: Vec<(Option<&str>, &HighWaterMark)> = marks.iter()
+                .map(|(tenant_id, mark)| (tenant_id.as_deref(), mark))
+                .collect();
+            store.store_all(&batch)?;
         }
+        
+        for (tenant_id, mark) in marks {
+            let lane = self.lanes.get_mut(&tenant_id).expect("lane of a collected mark");
+            lane.current = lane.next.take();
+            lane.high_water_mark = Some(mark);
+        }
+        Ok(())
     }
     
-    pub fn update_bundle(&mut self, bundle: SignedPolicyBundle) {
-        if bundle.version > self.current_version() {
-            self.next_bundle = Some(bundle);
+    /// Verify and record a timestamp for its scope. Timestamps must not go
+    /// backwards in time, must not announce a version below what is already
+    /// active, and must not announce a version this edge already holds with
+    /// a different state hash.
+    pub fn update_timestamp(&mut self, timestamp: BundleTimestamp) -> Result<(), BentengError> {
+        let signers = self.timestamp_signers.as_ref().ok_or_else(|| {
+            BentengError::PolicyBundleRejected("no timestamp signers configured".into())
+        })?;
+        
+        signers.verify_message(&timestamp.serialize_for_signing()?, &timestamp.signatures)?;
+        self.check_scope(timestamp.tenant_id.as_deref())?;
+        
+        if timestamp.is_expired() {
+            return Err(BentengError::PolicyBundleRejected("timestamp expired".into()));
+        }
+        
+        let lane = self.lane_mut(timestamp.tenant_id.as_deref())?;
+        if let Some(latest) = &lane.latest_timestamp {
+            if timestamp.issued_at < latest.issued_at {
+                return Err(BentengError::PolicyBundleRejected(
+                    "timestamp older than the last one accepted".into()
+                ));
+            }
+        }
+        let floor = lane.high_water_mark.as_ref().map_or(0, |m| m.version);
+        if timestamp.bundle_version < floor {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "timestamp announces v{} below the high-water mark v{}",
+                timestamp.bundle_version, floor
+            )));
         }
+        // The same version with another hash is a fork, not this edge's state
+        let mut held = lane.current.iter().chain(&lane.next)
+            .map(|s| (s.version, s.state_hash))
+            .chain(lane.high_water_mark.as_ref().map(|m| (m.version, m.state_hash)));
+        if held.any(|(version, state_hash)| version == timestamp.bundle_version && state_hash != timestamp.state_hash) {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "timestamp announces v{} with a different state hash", timestamp.bundle_version
+            )));
+        }
+        
+        lane.latest_timestamp = Some(timestamp);
+        Ok(())
     }
     
-    pub fn activate_next(&mut self) {
-        if let Some(next) = self.next_bundle.take() {
-            self.current_bundle = Some(next);
+    /// Current freshness of the active policy state, taking the worst scope
+    pub fn freshness(&self) -> Freshness {
+        if self.lanes.values().any(|l| l.current.as_ref().is_some_and(|s| !s.is_valid())) {
+            return Freshness::Expired;
         }
+        
+        if self.timestamp_signers.is_some() {
+            let mut tracked = self.lanes.values().filter(|l| l.is_tracked()).peekable();
+            if tracked.peek().is_none() {
+                return Freshness::Stale;
+            }
+            
+            let mut behind = None;
+            for lane in tracked {
+                match &lane.latest_timestamp {
+                    None => return Freshness::Stale,
+                    Some(ts) if ts.is_expired() => return Freshness::Stale,
+                    Some(ts) if ts.bundle_version > lane.current_version()
+                        || (ts.bundle_version == lane.current_version()
+                            && lane.current.as_ref().is_some_and(|s| s.state_hash != ts.state_hash)) => {
+                        behind = behind.max(Some(ts.bundle_version));
+                    }
+                    Some(_) => {}
+                }
+            }
+            if let Some(advertised) = behind {
+                return Freshness::Behind { advertised };
+            }
+        }
+        
+        Freshness::Fresh
+    }
+    
+    /// Apply the expiry behaviour: an error when policy state is degraded and
+    /// the distributor fails closed, otherwise the current freshness
+    pub fn check_freshness(&self) -> Result<Freshness, BentengError> {
+        let freshness = self.freshness();
+        if freshness.is_degraded() && self.expiry_behaviour == ExpiryBehaviour::FailClosed {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "policy state is {:?}", freshness
+            )));
+        }
+        Ok(freshness)
+    }
+    
+    /// Last activated version and hash of the all-tenant bundle
+    pub fn high_water_mark(&self) -> Option<&HighWaterMark> {
+        self.lanes.get(&None)?.high_water_mark.as_ref()
+    }
+    
+    /// True when signers are configured, so policy comes from bundles and
+    /// never from a default
+    pub fn expects_bundles(&self) -> bool {
+        self.signer_set.is_some()
     }
     
+    /// True once a bundle or shard has been activated
+    pub fn has_active_bundle(&self) -> bool {
+        self.lanes.values().any(|l| l.current.is_some())
+    }
+    
+    /// True when any scope has a staged bundle, shard or delta
+    pub fn has_staged(&self) -> bool {
+        self.lanes.values().any(|l| l.next.is_some())
+    }
+    
+    /// Version of the staged all-tenant bundle, if any
+    pub fn candidate_version(&self) -> Option<u64> {
+        self.lanes.get(&None)?.next.as_ref().map(|s| s.version)
+    }
+    
+    /// Version of the active all-tenant bundle
+    pub fn current_version(&self) -> u64 {
+        self.lanes.get(&None).map_or(0, Lane::current_version)
+    }
+    
+    /// Latest staged or active version for a scope, which a new bundle or
+    /// delta must exceed
+    pub fn latest_version(&self, tenant_id: Option<&str>) -> u64 {
+        self.lanes.get(&tenant_id.map(str::to_string))
+            .and_then(Lane::latest)
+            .map_or(0, |s| s.version)
+    }
+    
+    /// Active version of each tenant shard
+    pub fn shard_versions(&self) -> BTreeMap<String, u64> {
+        self.lanes.iter()
+            .filter_map(|(tenant_id, lane)| Some((tenant_id.clone()?, lane.current.as_ref()?.version)))
+            .collect()
+    }
+    
+    /// Active policy, from the tenant's shard if it has one, otherwise from
+    /// the all-tenant bundle. An active shard is authoritative: a policy it
+    /// drops is not served from the all-tenant bundle.
     pub fn get_policy(&self, tenant_id: &str, policy_id: &str) -> Option<&Policy> {
-        self.current_bundle.as_ref()?.policies.iter()
-            .find(|p| p.tenant_id == tenant_id && p.policy_id == policy_id)
+        self.lookup(tenant_id, policy_id, |lane| lane.current.as_ref())
+            .map(|(_, policy)| policy)
     }
     
-    fn current_version(&self) -> u64 {
-        self.current_bundle.as_ref().map(|b| b.version).unwrap_or(0)
+    /// Policy that would be active once the staged state is promoted
+    pub fn get_candidate_policy(&self, tenant_id: &str, policy_id: &str) -> Option<&Policy> {
+        self.lookup(tenant_id, policy_id, Lane::latest)
+            .map(|(_, policy)| policy)
+    }
+    
+    fn lookup<'a>(
+        &'a self,
+        tenant_id: &str,
+        policy_id: &str,
+        state: impl Fn(&'a Lane) -> Option<&'a PolicyState>,
+    ) -> Option<(&'a PolicyState, &'a Policy)> {
+        if !self.serves(Some(tenant_id)) {
+            return None;
+        }
+        
+        let s = [Some(tenant_id.to_string()), None].iter()
+            .find_map(|key| state(self.lanes.get(key)?))?;
+        Some((s, s.get(tenant_id, policy_id)?))
+    }
+    
+    /// Evaluate a request against both the live policy and its counterpart
+    /// in the staged state that would replace it: the tenant's staged shard,
+    /// or the staged all-tenant bundle when the tenant has no active shard.
+    /// The result never affects the live decision.
+    pub fn shadow_evaluate(
+        &self,
+        live: &Policy,
+        request: &PolicyRequest<'_>,
+    ) -> Option<ShadowEvaluation> {
+        let tenant_id = String::from_utf8_lossy(request.tenant_id);
+        let policy_id = String::from_utf8_lossy(request.policy_id);
+        if !self.serves(Some(&tenant_id)) {
+            return None;
+        }
+
+        let shard = self.lanes.get(&Some(tenant_id.to_string()));
+        let staged = match shard {
+            Some(lane) if lane.next.is_some() => lane.next.as_ref(),
+            // An active shard stays authoritative after the global promotion
+            Some(lane) if lane.current.is_some() => None,
+            _ => self.lanes.get(&None)?.next.as_ref(),
+        }?;
+        let candidate = staged.get(&tenant_id, &policy_id)?;
+
+        Some(ShadowEvaluation {
+            candidate_version: staged.version,
+            live: live.evaluate(request),
+            candidate: candidate.evaluate(request),
+        })
     }
 }
 
@@ -121,6 +807,7 @@ mod tests {
                 require_device_attest: false,
                 hybrid_allowed: true,
                 replay_ttl_ms: 30000,
+                cache_dek: true,
                 version: 1,
             }
         ];
@@ -136,4 +823,428 @@ mod tests {
         assert!(bundle.verify(&pk).unwrap());
         assert!(bundle.is_valid());
     }
+    
+    #[test]
+    fn test_shadow_evaluation_reports_divergence() {
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        
+        let live = Policy {
+            tenant_id: "tenant1".to_string(),
+            policy_id: "policy1".to_string(),
+            path: "/test".to_string(),
+            required_algs: "kyber+dilithium".to_string(),
+            max_age_ms: 30000,
+            max_body_bytes: 65536,
+            require_device_attest: false,
+            hybrid_allowed: true,
+            replay_ttl_ms: 30000,
+            cache_dek: true,
+            version: 1,
+        };
+        let stricter = Policy {
+            max_body_bytes: 128,
+            require_device_attest: true,
+            version: 2,
+            ..live.clone()
+        };
+        
+        let mut distributor = PolicyDistributor::with_trust_anchor(TrustAnchor {
+            kid: "btk/policy-signer/v1".to_string(),
+            public_key: pk,
+        });
+        let request = PolicyRequest {
+            tenant_id: b"tenant1",
+            policy_id: b"policy1",
+            path: "/test",
+            ts_epoch_ms: chrono::Utc::now().timestamp_millis() as u64,
+            required_algs: "kyber+dilithium",
+            body_bytes: 1024,
+            hybrid: false,
+            device_attested: false,
+        };
+        
+        // Nothing staged, nothing to compare against
+        assert!(distributor.shadow_evaluate(&live, &request).is_none());
+        
+        let bundle = SignedPolicyBundle::create(
+            vec![stricter],
+            2,
+            3600,
+            "btk/policy-signer/v1".to_string(),
+            &sk,
+        ).unwrap();
+        distributor.update_bundle(bundle).unwrap();
+        
+        let evaluation = distributor.shadow_evaluate(&live, &request).unwrap();
+        assert!(evaluation.is_divergent());
+        assert!(evaluation.live.is_empty());
+        assert_eq!(
+            evaluation.newly_blocked(),
+            vec![PolicyRule::MaxBodyBytes, PolicyRule::DeviceAttest]
+        );
+        
+        let mut report = ShadowReport::new();
+        report.record(&request, &evaluation);
+        report.record(&request, &evaluation);
+        assert_eq!(report.candidate_version, Some(2));
+        assert_eq!(report.evaluated, 2);
+        assert_eq!(report.would_block, 2);
+        assert_eq!(report.blocked_by_rule[&PolicyRule::DeviceAttest], 2);
+        assert_eq!(report.recent.len(), 2);
+        
+        // Live policy is unaffected until the bundle is activated
+        assert!(distributor.get_policy("tenant1", "policy1").is_none());
+    }
+    
+    fn test_policy() -> Policy {
+        Policy {
+            tenant_id: "tenant1".to_string(),
+            policy_id: "policy1".to_string(),
+            path: "/test".to_string(),
+            required_algs: "kyber+dilithium".to_string(),
+            max_age_ms: 30000,
+            max_body_bytes: 65536,
+            require_device_attest: false,
+            hybrid_allowed: true,
+            replay_ttl_ms: 30000,
+            cache_dek: true,
+            version: 1,
+        }
+    }
+    
+    #[test]
+    fn test_distributor_enforces_trust_anchor_and_versions() {
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        let (_, other_sk) = sig::dilithium3_keypair().unwrap();
+        let kid = "btk/policy-signer/v1".to_string();
+        
+        let mut distributor = PolicyDistributor::with_trust_anchor(TrustAnchor {
+            kid: kid.clone(),
+            public_key: pk,
+        });
+        
+        // Signed by a key other than the trust anchor
+        let forged = SignedPolicyBundle::create(
+            vec![test_policy()], 1, 3600, kid.clone(), &other_sk,
+        ).unwrap();
+        assert_eq!(distributor.update_bundle(forged), Err(BentengError::InvalidSignature));
+        
+        // Expired on arrival
+        let expired = SignedPolicyBundle::create(
+            vec![test_policy()], 1, 0, kid.clone(), &sk,
+        ).unwrap();
+        assert!(distributor.update_bundle(expired).is_err());
+        
+        let v2 = SignedPolicyBundle::create(
+            vec![test_policy()], 2, 3600, kid.clone(), &sk,
+        ).unwrap();
+        distributor.update_bundle(v2).unwrap();
+        assert_eq!(distributor.candidate_version(), Some(2));
+        distributor.activate_next().unwrap();
+        assert!(distributor.has_active_bundle());
+        assert!(distributor.get_policy("tenant1", "policy1").is_some());
+        
+        // Rollback to an older, validly signed bundle is refused
+        let v1 = SignedPolicyBundle::create(
+            vec![test_policy()], 1, 3600, kid, &sk,
+        ).unwrap();
+        assert!(distributor.update_bundle(v1).is_err());
+        assert_eq!(distributor.current_version(), 2);
+        
+        // Without a trust anchor nothing is accepted
+        let bundle = SignedPolicyBundle::create(
+            vec![test_policy()], 3, 3600, "btk/policy-signer/v1".to_string(), &sk,
+        ).unwrap();
+        assert!(PolicyDistributor::new().update_bundle(bundle).is_err());
+    }
+    
+    #[test]
+    fn test_threshold_signatures() {
+        let keys: Vec<_> = (0..3).map(|_| sig::dilithium3_keypair().unwrap()).collect();
+        let (_, outsider_sk) = sig::dilithium3_keypair().unwrap();
+        
+        let signers = keys.iter().enumerate()
+            .map(|(i, (pk, _))| TrustAnchor {
+                kid: format!("btk/policy-signer/{}", i),
+                public_key: pk.clone(),
+            })
+            .collect::<Vec<_>>();
+        let signer_set = SignerSet::new(signers.clone(), 2).unwrap();
+        
+        // Same key registered twice cannot satisfy the threshold on its own
+        let mut duplicate = signers.clone();
+        duplicate[1].public_key = duplicate[0].public_key.clone();
+        assert!(SignerSet::new(duplicate, 2).is_err());
+        assert!(SignerSet::new(signers, 4).is_err());
+        
+        let mut bundle = SignedPolicyBundle::unsigned(vec![test_policy()], 1, 3600);
+        bundle.add_signature("btk/policy-signer/0".to_string(), &keys[0].1).unwrap();
+        assert!(signer_set.verify(&bundle).is_err());
+        
+        // Re-signing with the same signer does not add a second vote
+        bundle.add_signature("btk/policy-signer/0".to_string(), &keys[0].1).unwrap();
+        assert_eq!(bundle.signatures.len(), 1);
+        
+        // Unknown signers are ignored, as are signatures claiming another kid
+        bundle.add_signature("btk/outsider".to_string(), &outsider_sk).unwrap();
+        bundle.add_signature("btk/policy-signer/1".to_string(), &outsider_sk).unwrap();
+        assert!(signer_set.verify(&bundle).is_err());
+        
+        // Partially signed bundles survive a round trip between signers
+        let mut bundle = SignedPolicyBundle::from_json(&bundle.to_json().unwrap()).unwrap();
+        bundle.add_signature("btk/policy-signer/2".to_string(), &keys[2].1).unwrap();
+        assert_eq!(
+            signer_set.valid_signers(&bundle).unwrap(),
+            vec!["btk/policy-signer/0".to_string(), "btk/policy-signer/2".to_string()]
+        );
+        
+        let mut distributor = PolicyDistributor::with_signer_set(signer_set);
+        distributor.update_bundle(bundle).unwrap();
+    }
+    
+    #[test]
+    fn test_high_water_mark_blocks_rollback_after_restart() {
+        use crate::policy_freshness::SledHighWaterMarkStore;
+        
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        let anchor = TrustAnchor { kid: "btk/policy-signer/v1".to_string(), public_key: pk };
+        let dir = tempfile::tempdir().unwrap();
+        let open = || {
+            PolicyDistributor::with_trust_anchor(anchor.clone())
+                .with_high_water_mark_store(Box::new(SledHighWaterMarkStore::open(dir.path()).unwrap()))
+                .unwrap()
+        };
+        
+        let v1 = SignedPolicyBundle::create(
+            vec![test_policy()], 1, 3600, anchor.kid.clone(), &sk,
+        ).unwrap();
+        let v2 = SignedPolicyBundle::create(
+            vec![test_policy()], 2, 3600, anchor.kid.clone(), &sk,
+        ).unwrap();
+        let v2_forked = SignedPolicyBundle::create(
+            vec![], 2, 3600, anchor.kid.clone(), &sk,
+        ).unwrap();
+        
+        {
+            let mut distributor = open();
+            distributor.update_bundle(v2.clone()).unwrap();
+            distributor.activate_next().unwrap();
+        }
+        
+        // Fresh process: in-memory state is gone but the mark is not
+        let mut distributor = open();
+        assert_eq!(distributor.high_water_mark().unwrap().version, 2);
+        assert!(distributor.update_bundle(v1).is_err());
+        assert!(distributor.update_bundle(v2_forked).is_err());
+        
+        // The bundle at the mark can be reloaded
+        distributor.update_bundle(v2).unwrap();
+        distributor.activate_next().unwrap();
+        assert_eq!(distributor.current_version(), 2);
+    }
+    
+    #[test]
+    fn test_activation_is_all_or_nothing() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        
+        struct FlakyStore(Arc<AtomicBool>);
+        
+        impl HighWaterMarkStore for FlakyStore {
+            fn load(&self, _tenant_id: Option<&str>) -> crate::error::Result<Option<HighWaterMark>> {
+                Ok(None)
+            }
+            
+            fn store_all(&self, _marks: &[(Option<&str>, &HighWaterMark)]) -> crate::error::Result<()> {
+                if self.0.load(Ordering::SeqCst) {
+                    return Err(BentengError::InternalError);
+                }
+                Ok(())
+            }
+        }
+        
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        let kid = "btk/policy-signer/v1".to_string();
+        let failing = Arc::new(AtomicBool::new(true));
+        let mut distributor = PolicyDistributor::with_trust_anchor(TrustAnchor {
+            kid: kid.clone(),
+            public_key: pk,
+        })
+        .with_high_water_mark_store(Box::new(FlakyStore(failing.clone())))
+        .unwrap();
+        
+        distributor.update_bundle(SignedPolicyBundle::create(
+            vec![test_policy()], 1, 3600, kid.clone(), &sk,
+        ).unwrap()).unwrap();
+        let mut shard = SignedPolicyBundle::shard("tenant1", &[test_policy()], 1, 3600);
+        shard.add_signature(kid, &sk).unwrap();
+        distributor.update_bundle(shard).unwrap();
+        
+        // A failed write leaves every scope staged and nothing active
+        assert!(distributor.activate_next().is_err());
+        assert!(!distributor.has_active_bundle());
+        assert_eq!(distributor.candidate_version(), Some(1));
+        assert_eq!(distributor.latest_version(Some("tenant1")), 1);
+        assert!(distributor.high_water_mark().is_none());
+        
+        failing.store(false, Ordering::SeqCst);
+        distributor.activate_next().unwrap();
+        assert_eq!(distributor.current_version(), 1);
+        assert_eq!(distributor.shard_versions().get("tenant1"), Some(&1));
+        assert!(!distributor.has_staged());
+    }
+    
+    #[test]
+    fn test_timestamps_detect_freeze() {
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        let (ts_pk, ts_sk) = sig::dilithium3_keypair().unwrap();
+        let ts_anchor = TrustAnchor { kid: "btk/timestamp/v1".to_string(), public_key: ts_pk };
+        
+        let mut distributor = PolicyDistributor::with_trust_anchor(TrustAnchor {
+            kid: "btk/policy-signer/v1".to_string(),
+            public_key: pk.clone(),
+        })
+        .with_timestamp_signers(SignerSet::single(ts_anchor.clone()));
+        
+        let v1 = SignedPolicyBundle::create(
+            vec![test_policy()], 1, 3600, "btk/policy-signer/v1".to_string(), &sk,
+        ).unwrap();
+        distributor.update_bundle(v1.clone()).unwrap();
+        distributor.activate_next().unwrap();
+        
+        // No timestamp yet: fail closed by default
+        assert_eq!(distributor.freshness(), Freshness::Stale);
+        assert!(distributor.check_freshness().is_err());
+        
+        let mut ts = BundleTimestamp::unsigned(None, 1, v1.state_hash().unwrap(), 300);
+        ts.add_signature(ts_anchor.kid.clone(), &ts_sk).unwrap();
+        distributor.update_timestamp(ts).unwrap();
+        assert_eq!(distributor.check_freshness().unwrap(), Freshness::Fresh);
+        
+        // A timestamp for the active version with another state hash points
+        // at a forked bundle
+        let mut forked = BundleTimestamp::unsigned(None, 1, [0u8; 32], 300);
+        forked.add_signature(ts_anchor.kid.clone(), &ts_sk).unwrap();
+        assert!(distributor.update_timestamp(forked).is_err());
+        assert_eq!(distributor.freshness(), Freshness::Fresh);
+        
+        // A newer bundle is announced but withheld
+        let mut ts = BundleTimestamp::unsigned(None, 2, [0u8; 32], 300);
+        ts.add_signature(ts_anchor.kid.clone(), &ts_sk).unwrap();
+        distributor.update_timestamp(ts).unwrap();
+        assert_eq!(distributor.freshness(), Freshness::Behind { advertised: 2 });
+        
+        // An edge that received a forked v1 before the timestamp is behind
+        let mut forked_edge = PolicyDistributor::with_trust_anchor(TrustAnchor {
+            kid: "btk/policy-signer/v1".to_string(),
+            public_key: pk,
+        })
+        .with_timestamp_signers(SignerSet::single(ts_anchor.clone()));
+        let mut ts = BundleTimestamp::unsigned(None, 1, [9u8; 32], 300);
+        ts.add_signature(ts_anchor.kid.clone(), &ts_sk).unwrap();
+        forked_edge.update_timestamp(ts).unwrap();
+        forked_edge.update_bundle(v1.clone()).unwrap();
+        forked_edge.activate_next().unwrap();
+        assert_eq!(forked_edge.freshness(), Freshness::Behind { advertised: 1 });
+        
+        // Expired timestamps are refused, and unsigned ones too
+        let mut expired = BundleTimestamp::unsigned(None, 2, [0u8; 32], 0);
+        expired.add_signature(ts_anchor.kid.clone(), &ts_sk).unwrap();
+        assert!(distributor.update_timestamp(expired).is_err());
+        assert!(distributor.update_timestamp(BundleTimestamp::unsigned(None, 3, [0u8; 32], 300)).is_err());
+        
+        // Fail-open keeps serving an expired bundle
+        let mut distributor = PolicyDistributor::with_trust_anchor(ts_anchor.clone())
+            .with_expiry_behaviour(ExpiryBehaviour::FailOpen);
+        let mut short = SignedPolicyBundle::unsigned(vec![test_policy()], 1, 3600);
+        short.add_signature(ts_anchor.kid.clone(), &ts_sk).unwrap();
+        distributor.update_bundle(short).unwrap();
+        distributor.activate_next().unwrap();
+        distributor.lanes.get_mut(&None).unwrap().current.as_mut().unwrap().not_after = 0;
+        assert_eq!(distributor.check_freshness().unwrap(), Freshness::Expired);
+    }
+    
+    #[test]
+    fn test_shards_and_deltas() {
+        let (pk, sk) = sig::dilithium3_keypair().unwrap();
+        let kid = "btk/policy-signer/v1".to_string();
+        let mut distributor = PolicyDistributor::with_trust_anchor(TrustAnchor {
+            kid: kid.clone(),
+            public_key: pk,
+        })
+        .with_subscriptions(["tenant1".to_string()]);
+        
+        let other = Policy { tenant_id: "tenant2".to_string(), ..test_policy() };
+        let all = vec![test_policy(), other.clone()];
+        
+        // Shards for tenants this edge does not serve are refused
+        let mut foreign = SignedPolicyBundle::shard("tenant2", &all, 1, 3600);
+        foreign.add_signature(kid.clone(), &sk).unwrap();
+        assert!(distributor.update_bundle(foreign).is_err());
+        
+        let mut v1 = SignedPolicyBundle::shard("tenant1", &all, 1, 3600);
+        v1.add_signature(kid.clone(), &sk).unwrap();
+        distributor.update_bundle(v1.clone()).unwrap();
+        distributor.activate_next().unwrap();
+        assert_eq!(distributor.shard_versions().get("tenant1"), Some(&1));
+        assert!(distributor.get_policy("tenant1", "policy1").is_some());
+        assert!(distributor.get_policy("tenant2", "policy1").is_none());
+        
+        // Deltas apply on top of the active shard
+        let target = SignedPolicyBundle::shard(
+            "tenant1",
+            &[Policy { max_age_ms: 5000, ..test_policy() }],
+            2,
+            3600,
+        );
+        let mut delta = SignedPolicyDelta::diff(&v1, &target, 3600).unwrap();
+        delta.add_signature(kid.clone(), &sk).unwrap();
+        distributor.update_delta(delta.clone()).unwrap();
+        distributor.activate_next().unwrap();
+        assert_eq!(distributor.latest_version(Some("tenant1")), 2);
+        assert_eq!(distributor.get_policy("tenant1", "policy1").unwrap().max_age_ms, 5000);
+        
+        // Replayed or unsigned deltas are refused
+        assert!(distributor.update_delta(delta.clone()).is_err());
+        let mut unsigned = SignedPolicyDelta::diff(&target, &SignedPolicyBundle::shard("tenant1", &[], 3, 3600), 3600).unwrap();
+        assert_eq!(distributor.update_delta(unsigned.clone()), Err(BentengError::InvalidSignature));
+        unsigned.add_signature(kid.clone(), &sk).unwrap();
+        distributor.update_delta(unsigned).unwrap();
+        distributor.activate_next().unwrap();
+        assert!(distributor.get_policy("tenant1", "policy1").is_none());
+        
+        // A policy the shard dropped is not served from the all-tenant bundle
+        let global = SignedPolicyBundle::create(all.clone(), 1, 3600, kid.clone(), &sk).unwrap();
+        distributor.update_bundle(global).unwrap();
+        distributor.activate_next().unwrap();
+        assert_eq!(distributor.current_version(), 1);
+        assert!(distributor.get_policy("tenant1", "policy1").is_none());
+        assert!(distributor.get_candidate_policy("tenant1", "policy1").is_none());
+
+        // Shadow evaluation compares against the staged state that would
+        // replace the live policy: a staged global bundle does not, while
+        // the tenant's shard is active
+        let live = test_policy();
+        let strict = Policy { max_body_bytes: 128, version: 2, ..test_policy() };
+        let request = PolicyRequest {
+            tenant_id: b"tenant1",
+            policy_id: b"policy1",
+            path: "/test",
+            ts_epoch_ms: chrono::Utc::now().timestamp_millis() as u64,
+            required_algs: "kyber+dilithium",
+            body_bytes: 1024,
+            hybrid: false,
+            device_attested: false,
+        };
+        let global = SignedPolicyBundle::create(vec![strict.clone()], 2, 3600, kid.clone(), &sk).unwrap();
+        distributor.update_bundle(global).unwrap();
+        assert!(distributor.shadow_evaluate(&live, &request).is_none());
+
+        let mut shard = SignedPolicyBundle::shard("tenant1", &[strict], 4, 3600);
+        shard.add_signature(kid, &sk).unwrap();
+        distributor.update_bundle(shard).unwrap();
+        let evaluation = distributor.shadow_evaluate(&live, &request).unwrap();
+        assert_eq!(evaluation.candidate_version, 4);
+        assert_eq!(evaluation.newly_blocked(), vec![PolicyRule::MaxBodyBytes]);
+    }
 }
@@ -0,0 +1,336 @@
+//! Incremental policy updates and per-tenant policy state
+
+use crate::crypto::sig;
+use crate::error::{BentengError, Result};
+use crate::policy::Policy;
+use crate::policy_bundle::{BundleSignature, SignedPolicyBundle};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::SystemTime;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Hash of a policy set, independent of the order policies are listed in.
+/// Bundles and deltas are both reduced to this hash, so an edge that
+/// reconstructs state from deltas can check it matches the publisher's.
+pub fn policy_state_hash(tenant_id: Option<&str>, policies: &[Policy]) -> Result<[u8; 32]> {
+    let mut sorted: Vec<&Policy> = policies.iter().collect();
+    sorted.sort_by(|a, b| (&a.tenant_id, &a.policy_id).cmp(&(&b.tenant_id, &b.policy_id)));
+
+    let data = serde_json::to_vec(&(tenant_id, sorted)).map_err(|_| BentengError::InternalError)?;
+    Ok(Sha256::digest(&data).into())
+}
+
+/// Shard policies must all belong to the shard's tenant, and no scope may
+/// hold two policies with the same tenant and policy ID
+fn check_policies(tenant_id: Option<&str>, policies: &[Policy]) -> Result<()> {
+    for (i, policy) in policies.iter().enumerate() {
+        if tenant_id.is_some_and(|t| t != policy.tenant_id) {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "policy {}/{} outside shard {}",
+                policy.tenant_id, policy.policy_id, tenant_id.unwrap_or_default()
+            )));
+        }
+        if policies[..i].iter().any(|p| p.tenant_id == policy.tenant_id && p.policy_id == policy.policy_id) {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "duplicate policy {}/{}", policy.tenant_id, policy.policy_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// One change carried by a delta
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PolicyChange {
+    /// Add a policy that does not exist in the base state
+    Add { policy: Policy },
+    /// Replace an existing policy
+    Modify { policy: Policy },
+    Remove { tenant_id: String, policy_id: String },
+}
+
+/// Signed set of changes against a base state. The delta names the base by
+/// version and state hash and commits to the state hash it produces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedPolicyDelta {
+    /// Shard the delta applies to; `None` for the all-tenant bundle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub base_version: u64,
+    pub base_hash: [u8; 32],
+    pub version: u64,
+    pub state_hash: [u8; 32],
+    pub changes: Vec<PolicyChange>,
+    pub created_at: u64,
+    pub not_after: u64,
+    pub signatures: Vec<BundleSignature>,
+}
+
+impl SignedPolicyDelta {
+    /// Unsigned delta turning `base` into `target`. Both bundles must cover
+    /// the same scope.
+    pub fn diff(base: &SignedPolicyBundle, target: &SignedPolicyBundle, ttl_secs: u64) -> Result<Self> {
+        if base.tenant_id != target.tenant_id {
+            return Err(BentengError::PolicyBundleRejected("bundles cover different scopes".into()));
+        }
+        if target.version <= base.version {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "target v{} is not newer than base v{}", target.version, base.version
+            )));
+        }
+
+        let find = |policies: &'_ [Policy], p: &Policy| {
+            policies.iter().find(|q| q.tenant_id == p.tenant_id && q.policy_id == p.policy_id).cloned()
+        };
+
+        let mut changes = Vec::new();
+        for policy in &base.policies {
+            if find(&target.policies, policy).is_none() {
+                changes.push(PolicyChange::Remove {
+                    tenant_id: policy.tenant_id.clone(),
+                    policy_id: policy.policy_id.clone(),
+                });
+            }
+        }
+        for policy in &target.policies {
+            match find(&base.policies, policy) {
+                None => changes.push(PolicyChange::Add { policy: policy.clone() }),
+                Some(old) if old != *policy => changes.push(PolicyChange::Modify { policy: policy.clone() }),
+                Some(_) => {}
+            }
+        }
+
+        let now = now_secs();
+        Ok(Self {
+            tenant_id: target.tenant_id.clone(),
+            base_version: base.version,
+            base_hash: base.state_hash()?,
+            version: target.version,
+            state_hash: target.state_hash()?,
+            changes,
+            created_at: now,
+            not_after: now + ttl_secs,
+            signatures: vec![],
+        })
+    }
+
+    /// Add (or replace) this signer's Dilithium3 signature
+    pub fn add_signature(&mut self, signer_kid: String, signing_key: &[u8]) -> Result<()> {
+        let signature = sig::dilithium3_sign(signing_key, &self.serialize_for_signing()?)?;
+
+        self.signatures.retain(|s| s.signer_kid != signer_kid);
+        self.signatures.push(BundleSignature { signer_kid, signature });
+        Ok(())
+    }
+
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data)
+            .map_err(|e| BentengError::PolicyBundleRejected(format!("malformed delta: {}", e)))
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).map_err(|_| BentengError::InternalError)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let now = now_secs();
+        now >= self.created_at && now < self.not_after
+    }
+
+    pub(crate) fn serialize_for_signing(&self) -> Result<Vec<u8>> {
+        let mut to_sign = self.clone();
+        to_sign.signatures = vec![];
+
+        serde_json::to_vec(&to_sign).map_err(|_| BentengError::InternalError)
+    }
+}
+
+/// Verified policy set for one scope, built from a bundle or from a base
+/// state plus deltas
+#[derive(Debug, Clone)]
+pub struct PolicyState {
+    pub tenant_id: Option<String>,
+    pub version: u64,
+    pub state_hash: [u8; 32],
+    pub created_at: u64,
+    pub not_after: u64,
+    pub policies: Vec<Policy>,
+}
+
+impl PolicyState {
+    /// State described by a full bundle. Signatures are checked by the caller.
+    pub fn from_bundle(bundle: SignedPolicyBundle) -> Result<Self> {
+        check_policies(bundle.tenant_id.as_deref(), &bundle.policies)?;
+
+        Ok(Self {
+            state_hash: bundle.state_hash()?,
+            tenant_id: bundle.tenant_id,
+            version: bundle.version,
+            created_at: bundle.created_at,
+            not_after: bundle.not_after,
+            policies: bundle.policies,
+        })
+    }
+
+    /// Apply a delta and check the result against the hash it commits to.
+    /// The delta's validity window replaces the base state's.
+    pub fn apply(&self, delta: &SignedPolicyDelta) -> Result<Self> {
+        if delta.tenant_id != self.tenant_id {
+            return Err(BentengError::PolicyBundleRejected("delta targets a different scope".into()));
+        }
+        if delta.base_version != self.version || delta.base_hash != self.state_hash {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "delta v{} is based on v{}, not the current v{}",
+                delta.version, delta.base_version, self.version
+            )));
+        }
+        if delta.version <= self.version {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "delta v{} is not newer than v{}", delta.version, self.version
+            )));
+        }
+
+        let mut policies = self.policies.clone();
+        for change in &delta.changes {
+            let position = |policies: &[Policy], tenant_id: &str, policy_id: &str| {
+                policies.iter().position(|p| p.tenant_id == tenant_id && p.policy_id == policy_id)
+            };
+
+            match change {
+                PolicyChange::Add { policy } => {
+                    if position(&policies, &policy.tenant_id, &policy.policy_id).is_some() {
+                        return Err(BentengError::PolicyBundleRejected(format!(
+                            "delta adds existing policy {}/{}", policy.tenant_id, policy.policy_id
+                        )));
+                    }
+                    policies.push(policy.clone());
+                }
+                PolicyChange::Modify { policy } => {
+                    let Some(i) = position(&policies, &policy.tenant_id, &policy.policy_id) else {
+                        return Err(BentengError::PolicyBundleRejected(format!(
+                            "delta modifies unknown policy {}/{}", policy.tenant_id, policy.policy_id
+                        )));
+                    };
+                    policies[i] = policy.clone();
+                }
+                PolicyChange::Remove { tenant_id, policy_id } => {
+                    let Some(i) = position(&policies, tenant_id, policy_id) else {
+                        return Err(BentengError::PolicyBundleRejected(format!(
+                            "delta removes unknown policy {}/{}", tenant_id, policy_id
+                        )));
+                    };
+                    policies.remove(i);
+                }
+            }
+        }
+
+        check_policies(self.tenant_id.as_deref(), &policies)?;
+        let state_hash = policy_state_hash(self.tenant_id.as_deref(), &policies)?;
+        if state_hash != delta.state_hash {
+            return Err(BentengError::PolicyBundleRejected(format!(
+                "state after delta v{} does not match its state hash", delta.version
+            )));
+        }
+
+        Ok(Self {
+            tenant_id: self.tenant_id.clone(),
+            version: delta.version,
+            state_hash,
+            created_at: delta.created_at,
+            not_after: delta.not_after,
+            policies,
+        })
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let now = now_secs();
+        now >= self.created_at && now < self.not_after
+    }
+
+    pub fn get(&self, tenant_id: &str, policy_id: &str) -> Option<&Policy> {
+        self.policies.iter().find(|p| p.tenant_id == tenant_id && p.policy_id == policy_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(tenant_id: &str, policy_id: &str, max_age_ms: u64) -> Policy {
+        Policy {
+            tenant_id: tenant_id.to_string(),
+            policy_id: policy_id.to_string(),
+            path: "/test".to_string(),
+            required_algs: "kyber+dilithium".to_string(),
+            max_age_ms,
+            max_body_bytes: 65536,
+            require_device_attest: false,
+            hybrid_allowed: true,
+            replay_ttl_ms: 30000,
+            cache_dek: true,
+            version: 1,
+        }
+    }
+
+    #[test]
+    fn test_delta_reconstructs_target_state() {
+        let base = SignedPolicyBundle::unsigned(
+            vec![policy("t1", "a", 30000), policy("t1", "b", 30000), policy("t2", "a", 30000)],
+            1,
+            3600,
+        );
+        let target = SignedPolicyBundle::unsigned(
+            vec![policy("t2", "a", 30000), policy("t1", "a", 5000), policy("t1", "c", 30000)],
+            2,
+            3600,
+        );
+
+        let delta = SignedPolicyDelta::diff(&base, &target, 3600).unwrap();
+        assert_eq!(delta.changes.len(), 3);
+
+        let state = PolicyState::from_bundle(base.clone()).unwrap().apply(&delta).unwrap();
+        assert_eq!(state.version, 2);
+        assert_eq!(state.state_hash, target.state_hash().unwrap());
+        assert_eq!(state.get("t1", "a").unwrap().max_age_ms, 5000);
+        assert!(state.get("t1", "b").is_none());
+
+        // Applying against anything but the named base is refused
+        assert!(state.apply(&delta).is_err());
+
+        // A delta whose changes do not produce the committed hash is refused
+        let mut tampered = delta.clone();
+        tampered.changes.pop();
+        assert!(PolicyState::from_bundle(base).unwrap().apply(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_shard_rejects_foreign_policies() {
+        let shard = SignedPolicyBundle::shard("t1", &[policy("t1", "a", 30000)], 1, 3600);
+        let state = PolicyState::from_bundle(shard.clone()).unwrap();
+
+        let mut foreign = shard.clone();
+        foreign.policies.push(policy("t2", "a", 30000));
+        assert!(PolicyState::from_bundle(foreign).is_err());
+
+        // Shards of different tenants never share a state hash
+        assert_ne!(
+            SignedPolicyBundle::shard("t1", &[], 1, 3600).state_hash().unwrap(),
+            SignedPolicyBundle::shard("t2", &[], 1, 3600).state_hash().unwrap(),
+        );
+
+        // Nor can a delta move another tenant's policy into the shard
+        let emptied = SignedPolicyBundle::shard("t1", &[], 2, 3600);
+        let delta = SignedPolicyDelta {
+            changes: vec![PolicyChange::Add { policy: policy("t2", "b", 30000) }],
+            ..SignedPolicyDelta::diff(&shard, &emptied, 3600).unwrap()
+        };
+        assert!(state.apply(&delta).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Rollback and freeze-attack protection for policy distribution
+
+use crate::crypto::sig;
+use crate::error::{BentengError, Result};
+use crate::policy_bundle::BundleSignature;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::SystemTime;
+
+const HIGH_WATER_MARK_KEY: &[u8] = b"policy:high-water-mark";
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Last policy state an edge activated for one scope. Bundles and deltas
+/// older than this are refused, even after a restart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighWaterMark {
+    pub version: u64,
+    pub state_hash: [u8; 32],
+    pub accepted_at: u64,
+}
+
+/// Durable storage for policy high-water marks, one per scope: `None` for
+/// the all-tenant bundle, `Some(tenant)` for a tenant shard
+pub trait HighWaterMarkStore: Send + Sync {
+    fn load(&self, tenant_id: Option<&str>) -> Result<Option<HighWaterMark>>;
+
+    /// Store marks for several scopes at once: all of them or none
+    fn store_all(&self, marks: &[(Option<&str>, &HighWaterMark)]) -> Result<()>;
+
+    fn store(&self, tenant_id: Option<&str>, mark: &HighWaterMark) -> Result<()> {
+        self.store_all(&[(tenant_id, mark)])
+    }
+}
+
+fn high_water_mark_key(tenant_id: Option<&str>) -> Vec<u8> {
+    match tenant_id {
+        None => HIGH_WATER_MARK_KEY.to_vec(),
+        Some(tenant_id) => [HIGH_WATER_MARK_KEY, b":tenant:", tenant_id.as_bytes()].concat(),
+    }
+}
+
+/// sled-backed high-water mark store
+pub struct SledHighWaterMarkStore {
+    db: sled::Db,
+}
+
+impl SledHighWaterMarkStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let db = sled::open(path)
+            .map_err(|e| BentengError::PolicyBundleRejected(format!("high-water mark store: {}", e)))?;
+        Ok(Self { db })
+    }
+}
+
+impl HighWaterMarkStore for SledHighWaterMarkStore {
+    fn load(&self, tenant_id: Option<&str>) -> Result<Option<HighWaterMark>> {
+        let value = self.db.get(high_water_mark_key(tenant_id))
+            .map_err(|_| BentengError::InternalError)?;
+
+        value
+            .map(|v| serde_json::from_slice(&v).map_err(|_| BentengError::InternalError))
+            .transpose()
+    }
+
+    fn store_all(&self, marks: &[(Option<&str>, &HighWaterMark)]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+        for (tenant_id, mark) in marks {
+            let value = serde_json::to_vec(mark).map_err(|_| BentengError::InternalError)?;
+            batch.insert(high_water_mark_key(*tenant_id), value);
+        }
+        self.db.apply_batch(batch).map_err(|_| BentengError::InternalError)?;
+        self.db.flush().map_err(|_| BentengError::InternalError)?;
+        Ok(())
+    }
+}
+
+/// Short-lived signed statement of the newest bundle, in the style of a TUF
+/// timestamp role. Publishers re-sign it frequently; an edge whose latest
+/// timestamp has expired is not receiving updates. Tenant shards each get
+/// their own timestamp.
+///
+/// There is no separate snapshot role. In TUF the snapshot pins the version
+/// of every targets file so that files from different releases cannot be
+/// mixed. Here each scope is a single signed state, and `state_hash` covers
+/// all of its policies, so the timestamp pins the whole state directly. A
+/// timestamp naming a version the edge holds under another hash is refused,
+/// or reported as `Behind` when it arrived first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleTimestamp {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub bundle_version: u64,
+    pub state_hash: [u8; 32],
+    pub issued_at: u64,
+    pub expires_at: u64,
+    pub signatures: Vec<BundleSignature>,
+}
+
+impl BundleTimestamp {
+    pub fn unsigned(
+        tenant_id: Option<String>,
+        bundle_version: u64,
+        state_hash: [u8; 32],
+        ttl_secs: u64,
+    ) -> Self {
+        let now = now_secs();
+        Self {
+            tenant_id,
+            bundle_version,
+            state_hash,
+            issued_at: now,
+            expires_at: now + ttl_secs,
+            signatures: vec![],
+        }
+    }
+
+    /// Add (or replace) this signer's Dilithium3 signature
+    pub fn add_signature(&mut self, signer_kid: String, signing_key: &[u8]) -> Result<()> {
+        let signature = sig::dilithium3_sign(signing_key, &self.serialize_for_signing()?)?;
+
+        self.signatures.retain(|s| s.signer_kid != signer_kid);
+        self.signatures.push(BundleSignature { signer_kid, signature });
+        Ok(())
+    }
+
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data)
+            .map_err(|e| BentengError::PolicyBundleRejected(format!("malformed timestamp: {}", e)))
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).map_err(|_| BentengError::InternalError)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        now_secs() >= self.expires_at
+    }
+
+    pub(crate) fn serialize_for_signing(&self) -> Result<Vec<u8>> {
+        let mut to_sign = self.clone();
+        to_sign.signatures = vec![];
+
+        serde_json::to_vec(&to_sign).map_err(|_| BentengError::InternalError)
+    }
+}
+
+/// What an edge does with requests once its policy state can no longer be
+/// trusted to be current
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryBehaviour {
+    /// Reject requests until a fresh bundle arrives
+    #[default]
+    FailClosed,
+    /// Keep enforcing the last bundle and report the condition
+    FailOpen,
+}
+
+/// Freshness of the active policy state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "state")]
+pub enum Freshness {
+    Fresh,
+    /// A newer bundle has been announced but not yet activated
+    Behind { advertised: u64 },
+    /// No unexpired timestamp has been received
+    Stale,
+    /// An active bundle or delta is past its `not_after`
+    Expired,
+}
+
+impl Freshness {
+    /// True when the expiry behaviour applies
+    pub fn is_degraded(&self) -> bool {
+        matches!(self, Freshness::Stale | Freshness::Expired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_high_water_mark_survives_reopen() {
+        let dir = tempdir().unwrap();
+        let mark = HighWaterMark {
+            version: 7,
+            state_hash: [3u8; 32],
+            accepted_at: 1234567890,
+        };
+
+        {
+            let store = SledHighWaterMarkStore::open(dir.path()).unwrap();
+            assert_eq!(store.load(None).unwrap(), None);
+            store.store(None, &mark).unwrap();
+        }
+
+        let store = SledHighWaterMarkStore::open(dir.path()).unwrap();
+        assert_eq!(store.load(None).unwrap(), Some(mark));
+        assert_eq!(store.load(Some("tenant1")).unwrap(), None);
+    }
+}
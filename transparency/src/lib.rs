@@ -1,7 +1,13 @@
 //! Benteng Transparency Log
 
+use benteng_sdk_core::crypto::sig;
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Arc;
+use zeroize::Zeroizing;
 
 /// Log entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,19 +23,24 @@ pub struct LogEntry {
     pub rc: u16,            // result code (0 = success)
 }
 
-/// Merkle tree node
-#[derive(Debug, Clone)]
-pub struct MerkleNode {
-    pub hash: [u8; 32],
-    pub left: Option<Box<MerkleNode>>,
-    pub right: Option<Box<MerkleNode>>,
+/// ML-DSA key checkpoints are signed with
+pub struct CheckpointSigner {
+    pub kid: String,
+    pub secret_key: Zeroizing<Vec<u8>>,
 }
 
 /// Transparency log
+/// An RFC 9162 Merkle tree: leaves are `SHA-256(0x00 || JSON entry)` and
+/// nodes `SHA-256(0x01 || left || right)`.
 pub struct TransparencyLog {
     entries: Vec<LogEntry>,
-    tree: Option<MerkleNode>,
+    /// Hashes of complete subtrees; `levels[k][i]` covers leaves
+    /// `i * 2^k .. (i + 1) * 2^k`
+    levels: Vec<Vec<[u8; 32]>>,
     checkpoints: Vec<Checkpoint>,
+    signer: Option<Arc<CheckpointSigner>>,
+    /// JSON-lines file entries are appended to
+    storage: Option<File>,
 }
 
 /// Signed checkpoint
@@ -39,26 +50,221 @@ pub struct Checkpoint {
     pub root_hash: [u8; 32],
     pub ts: u64,
     pub ver: u8,
+    /// Signing key ID; empty when unsigned
+    #[serde(default)]
+    pub kid: String,
     pub signature: Vec<u8>,
 }
 
+impl Checkpoint {
+    /// Bytes covered by the signature
+    pub fn signed_data(&self) -> Vec<u8> {
+        let mut data = b"benteng/tlog-checkpoint/v1\n".to_vec();
+        data.push(self.ver);
+        data.extend_from_slice(&(self.tree_size as u64).to_be_bytes());
+        data.extend_from_slice(&self.root_hash);
+        data.extend_from_slice(&self.ts.to_be_bytes());
+        data.extend_from_slice(self.kid.as_bytes());
+        data
+    }
+    
+    /// Sign with `signer`, recording its key ID
+    pub fn sign(&mut self, signer: &CheckpointSigner) -> Result<(), String> {
+        self.kid = signer.kid.clone();
+        self.signature = sig::dilithium3_sign(&signer.secret_key, &self.signed_data())
+            .map_err(|e| format!("Signing checkpoint: {}", e))?;
+        Ok(())
+    }
+    
+    /// Check the signature against the log's ML-DSA public key
+    pub fn verify(&self, public_key: &[u8]) -> bool {
+        !self.signature.is_empty()
+            && sig::dilithium3_verify(public_key, &self.signed_data(), &self.signature).unwrap_or(false)
+    }
+}
+
+/// Leaf hash of an entry
+pub fn leaf_hash(entry: &LogEntry) -> [u8; 32] {
+    let leaf_data = serde_json::to_vec(entry).expect("log entries serialize");
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]); // Leaf prefix
+    hasher.update(&leaf_data);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]); // Node prefix
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Largest power of two below `n`, for `n > 1`
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// Check that `leaf_hash` is entry `index` of the tree of `size` leaves with
+/// root `root` (RFC 9162 section 2.1.3.2)
+pub fn verify_inclusion(leaf_hash: &[u8; 32], index: usize, size: usize, proof: &[[u8; 32]], root: &[u8; 32]) -> bool {
+    if index >= size {
+        return false;
+    }
+    let (mut fn_, mut sn) = (index, size - 1);
+    let mut r = *leaf_hash;
+    for p in proof {
+        if sn == 0 {
+            return false;
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            r = node_hash(p, &r);
+            if fn_ & 1 == 0 {
+                while fn_ & 1 == 0 && fn_ != 0 {
+                    fn_ >>= 1;
+                    sn >>= 1;
+                }
+            }
+        } else {
+            r = node_hash(&r, p);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    sn == 0 && &r == root
+}
+
+/// Check that the tree of `to` leaves with root `to_root` extends the tree
+/// of `from` leaves with root `from_root` (RFC 9162 section 2.1.4.2)
+pub fn verify_consistency(from: usize, to: usize, from_root: &[u8; 32], to_root: &[u8; 32], proof: &[[u8; 32]]) -> bool {
+    if from == 0 || from > to {
+        return false;
+    }
+    if from == to {
+        return proof.is_empty() && from_root == to_root;
+    }
+
+    let mut proof = proof.to_vec();
+    if from.is_power_of_two() {
+        proof.insert(0, *from_root);
+    }
+    let (mut fn_, mut sn) = (from - 1, to - 1);
+    while fn_ & 1 == 1 {
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    let Some((first, rest)) = proof.split_first() else {
+        return false;
+    };
+    let (mut fr, mut sr) = (*first, *first);
+    for c in rest {
+        if sn == 0 {
+            return false;
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            fr = node_hash(c, &fr);
+            sr = node_hash(c, &sr);
+            if fn_ & 1 == 0 {
+                while fn_ & 1 == 0 && fn_ != 0 {
+                    fn_ >>= 1;
+                    sn >>= 1;
+                }
+            }
+        } else {
+            sr = node_hash(&sr, c);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    sn == 0 && &fr == from_root && &sr == to_root
+}
+
 impl TransparencyLog {
     pub fn new() -> Self {
         Self {
             entries: Vec::new(),
-            tree: None,
+            levels: Vec::new(),
             checkpoints: Vec::new(),
+            signer: None,
+            storage: None,
         }
     }
     
+    /// Sign checkpoints with `signer`
+    pub fn with_signer(mut self, signer: CheckpointSigner) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+    
+    /// Log persisted to a JSON-lines file, reloading any entries already in it
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let mut log = Self::new();
+        
+        if path.exists() {
+            let file = File::open(path).map_err(|e| format!("Opening {}: {}", path.display(), e))?;
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(|e| e.to_string())?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry = serde_json::from_str(&line).map_err(|e| format!("Corrupt log entry: {}", e))?;
+                log.push(entry);
+            }
+        }
+        
+        log.storage = Some(OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Opening {}: {}", path.display(), e))?);
+        Ok(log)
+    }
+    
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
     /// Append entry to log
     pub fn append(&mut self, entry: LogEntry) -> Result<usize, String> {
+        if let Some(storage) = &mut self.storage {
+            let mut line = serde_json::to_vec(&entry).map_err(|e| e.to_string())?;
+            line.push(b'\n');
+            storage.write_all(&line).map_err(|e| e.to_string())?;
+            storage.sync_data().map_err(|e| e.to_string())?;
+        }
+        
         let entry_id = self.entries.len();
-        self.entries.push(entry);
-        self.rebuild_tree();
+        self.push(entry);
         Ok(entry_id)
     }
     
+    /// Add an entry and the subtree hashes it completes
+    fn push(&mut self, entry: LogEntry) {
+        let mut hash = leaf_hash(&entry);
+        self.entries.push(entry);
+        
+        let mut level = 0;
+        loop {
+            if self.levels.len() == level {
+                self.levels.push(Vec::new());
+            }
+            let nodes = &mut self.levels[level];
+            nodes.push(hash);
+            if nodes.len() % 2 == 1 {
+                break;
+            }
+            hash = node_hash(&nodes[nodes.len() - 2], &nodes[nodes.len() - 1]);
+            level += 1;
+        }
+    }
+    
     /// Get entry by ID
     pub fn get_entry(&self, id: usize) -> Option<&LogEntry> {
         self.entries.get(id)
@@ -69,83 +275,134 @@ impl TransparencyLog {
         self.checkpoints.last()
     }
     
-    /// Create new checkpoint
-    pub fn create_checkpoint(&mut self) -> Result<Checkpoint, String> {
+    /// Key checkpoints are signed with, if any
+    pub fn signer(&self) -> Option<Arc<CheckpointSigner>> {
+        self.signer.clone()
+    }
+    
+    /// Entries the latest checkpoint does not cover yet
+    pub fn uncheckpointed(&self) -> usize {
+        self.entries.len() - self.checkpoints.last().map_or(0, |c| c.tree_size)
+    }
+    
+    /// Unsigned checkpoint over every entry so far, so it can be signed
+    /// without holding the log
+    pub fn draft_checkpoint(&self) -> Result<Checkpoint, String> {
         let root_hash = self.get_root_hash()
             .ok_or_else(|| "No entries in log".to_string())?;
         
-        let checkpoint = Checkpoint {
+        Ok(Checkpoint {
             tree_size: self.entries.len(),
             root_hash,
             ts: chrono::Utc::now().timestamp_millis() as u64,
             ver: 1,
-            signature: vec![], // TODO: Sign with checkpoint signer
-        };
+            kid: String::new(),
+            signature: vec![],
+        })
+    }
+    
+    /// Make `checkpoint` the latest unless a larger one already is. It must
+    /// match the log's root at its size.
+    pub fn install_checkpoint(&mut self, checkpoint: Checkpoint) -> Result<(), String> {
+        if self.root_at(checkpoint.tree_size) != Some(checkpoint.root_hash) {
+            return Err("Checkpoint does not match the log".into());
+        }
+        if self.checkpoints.last().is_some_and(|latest| latest.tree_size >= checkpoint.tree_size) {
+            return Ok(());
+        }
         
-        self.checkpoints.push(checkpoint.clone());
+        // Only the newest checkpoint is served; older sizes are reached
+        // through consistency proofs
+        self.checkpoints.clear();
+        self.checkpoints.push(checkpoint);
+        Ok(())
+    }
+    
+    /// Create new checkpoint over every entry so far, signed when the log
+    /// has a signer
+    pub fn create_checkpoint(&mut self) -> Result<Checkpoint, String> {
+        let mut checkpoint = self.draft_checkpoint()?;
+        if let Some(signer) = &self.signer {
+            checkpoint.sign(signer)?;
+        }
+        self.install_checkpoint(checkpoint.clone())?;
         Ok(checkpoint)
     }
     
-    /// Rebuild Merkle tree
-    fn rebuild_tree(&mut self) {
-        if self.entries.is_empty() {
-            self.tree = None;
-            return;
-        }
-        
-        // Create leaf nodes
-        let mut nodes: Vec<MerkleNode> = self.entries
-            .iter()
-            .map(|entry| {
-                let leaf_data = serde_json::to_vec(entry).unwrap();
-                let mut hasher = Sha256::new();
-                hasher.update(&[0x00]); // Leaf prefix
-                hasher.update(&leaf_data);
-                MerkleNode {
-                    hash: hasher.finalize().into(),
-                    left: None,
-                    right: None,
-                }
-            })
-            .collect();
-        
-        // Build tree bottom-up
-        while nodes.len() > 1 {
-            let mut next_level = Vec::new();
-            
-            for chunk in nodes.chunks(2) {
-                let node = if chunk.len() == 2 {
-                    let mut hasher = Sha256::new();
-                    hasher.update(&[0x01]); // Node prefix
-                    hasher.update(&chunk[0].hash);
-                    hasher.update(&chunk[1].hash);
-                    
-                    MerkleNode {
-                        hash: hasher.finalize().into(),
-                        left: Some(Box::new(chunk[0].clone())),
-                        right: Some(Box::new(chunk[1].clone())),
-                    }
-                } else {
-                    chunk[0].clone()
-                };
-                next_level.push(node);
-            }
-            
-            nodes = next_level;
+    /// Hash of leaves `start .. start + size`, for `size > 0`
+    fn subtree_hash(&self, start: usize, size: usize) -> [u8; 32] {
+        if size.is_power_of_two() && start.is_multiple_of(size) {
+            return self.levels[size.trailing_zeros() as usize][start / size];
         }
-        
-        self.tree = nodes.into_iter().next();
+        let k = split(size);
+        node_hash(&self.subtree_hash(start, k), &self.subtree_hash(start + k, size - k))
     }
     
     /// Get root hash
     pub fn get_root_hash(&self) -> Option<[u8; 32]> {
-        self.tree.as_ref().map(|node| node.hash)
+        self.root_at(self.entries.len())
     }
     
-    /// Get inclusion proof for entry
-    pub fn get_inclusion_proof(&self, _entry_id: usize) -> Option<Vec<[u8; 32]>> {
-        // TODO: Implement inclusion proof
-        None
+    /// Root hash of the tree as it was with `size` entries
+    pub fn root_at(&self, size: usize) -> Option<[u8; 32]> {
+        (size > 0 && size <= self.entries.len()).then(|| self.subtree_hash(0, size))
+    }
+    
+    /// Get inclusion proof for entry in the current tree
+    pub fn get_inclusion_proof(&self, entry_id: usize) -> Option<Vec<[u8; 32]>> {
+        self.inclusion_proof(entry_id, self.entries.len()).ok()
+    }
+    
+    /// Audit path of entry `index` in the tree of `size` entries
+    pub fn inclusion_proof(&self, index: usize, size: usize) -> Result<Vec<[u8; 32]>, String> {
+        if size > self.entries.len() || index >= size {
+            return Err(format!("No entry {} in a tree of {} entries", index, size));
+        }
+        
+        let mut proof = Vec::new();
+        let (mut index, mut start, mut size) = (index, 0, size);
+        while size > 1 {
+            let k = split(size);
+            if index < k {
+                proof.push(self.subtree_hash(start + k, size - k));
+                size = k;
+            } else {
+                proof.push(self.subtree_hash(start, k));
+                index -= k;
+                start += k;
+                size -= k;
+            }
+        }
+        proof.reverse();
+        Ok(proof)
+    }
+    
+    /// Proof that the tree of `to` entries extends the tree of `from`
+    pub fn consistency_proof(&self, from: usize, to: usize) -> Result<Vec<[u8; 32]>, String> {
+        if from == 0 || from > to || to > self.entries.len() {
+            return Err(format!("No consistency proof from {} to {} entries", from, to));
+        }
+        
+        let mut proof = Vec::new();
+        let (mut m, mut start, mut n, mut complete) = (from, 0, to, true);
+        while m != n {
+            let k = split(n);
+            if m <= k {
+                proof.push(self.subtree_hash(start + k, n - k));
+                n = k;
+            } else {
+                proof.push(self.subtree_hash(start, k));
+                m -= k;
+                start += k;
+                n -= k;
+                complete = false;
+            }
+        }
+        if !complete {
+            proof.push(self.subtree_hash(start, n));
+        }
+        proof.reverse();
+        Ok(proof)
     }
 }
 
@@ -183,5 +440,101 @@ mod tests {
         
         let checkpoint = log.create_checkpoint().unwrap();
         assert_eq!(checkpoint.tree_size, 1);
+        
+        // Persisted logs come back with the same root
+        let dir = std::env::temp_dir().join(format!("benteng-tlog-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log.jsonl");
+        let _ = std::fs::remove_file(&path);
+        {
+            let mut stored = TransparencyLog::open(&path).unwrap();
+            stored.append(entry.clone()).unwrap();
+            stored.append(entry).unwrap();
+        }
+        let reopened = TransparencyLog::open(&path).unwrap();
+        assert_eq!(reopened.len(), 2);
+        assert!(reopened.get_root_hash().is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    fn entry(i: usize) -> LogEntry {
+        LogEntry {
+            v: 1,
+            ten: b"tenant".to_vec(),
+            typ: "verify".to_string(),
+            ts: i as u64,
+            hdr_h: [0; 32],
+            sig_h: [i as u8; 32],
+            kid: "btk/test/key/v1".to_string(),
+            pol: b"policy".to_vec(),
+            rc: 0,
+        }
+    }
+    
+    #[test]
+    fn test_inclusion_and_consistency_proofs() {
+        let mut log = TransparencyLog::new();
+        let mut roots = vec![[0u8; 32]];
+        for i in 0..20 {
+            log.append(entry(i)).unwrap();
+            roots.push(log.get_root_hash().unwrap());
+        }
+        
+        for size in 1..=20 {
+            assert_eq!(log.root_at(size), Some(roots[size]));
+            for index in 0..size {
+                let leaf = leaf_hash(log.get_entry(index).unwrap());
+                let proof = log.inclusion_proof(index, size).unwrap();
+                assert!(verify_inclusion(&leaf, index, size, &proof, &roots[size]));
+                assert!(!verify_inclusion(&leaf, index, size, &proof, &roots[size - 1]));
+                if index > 0 {
+                    assert!(!verify_inclusion(&leaf, index - 1, size, &proof, &roots[size]));
+                }
+            }
+            for from in 1..=size {
+                let proof = log.consistency_proof(from, size).unwrap();
+                assert!(verify_consistency(from, size, &roots[from], &roots[size], &proof));
+                if from < size {
+                    assert!(!verify_consistency(from, size, &roots[from], &roots[size - 1], &proof));
+                }
+            }
+        }
+        assert!(log.inclusion_proof(20, 20).is_err());
+        assert!(log.consistency_proof(0, 5).is_err());
+        assert!(log.consistency_proof(5, 21).is_err());
+    }
+    
+    #[test]
+    fn test_signed_checkpoint() {
+        let (public_key, secret_key) = sig::dilithium3_keypair().unwrap();
+        let mut log = TransparencyLog::new().with_signer(CheckpointSigner {
+            kid: "tlog-1".to_string(),
+            secret_key: Zeroizing::new(secret_key),
+        });
+        log.append(entry(0)).unwrap();
+        
+        let mut checkpoint = log.create_checkpoint().unwrap();
+        assert_eq!(checkpoint.kid, "tlog-1");
+        assert!(checkpoint.verify(&public_key));
+        assert_eq!(log.uncheckpointed(), 0);
+        
+        checkpoint.tree_size += 1;
+        assert!(!checkpoint.verify(&public_key));
+        
+        // Drafts are signed apart from the log and only move it forward
+        log.append(entry(1)).unwrap();
+        log.append(entry(2)).unwrap();
+        assert_eq!(log.uncheckpointed(), 2);
+        let mut draft = log.draft_checkpoint().unwrap();
+        draft.sign(&log.signer().unwrap()).unwrap();
+        assert!(draft.verify(&public_key));
+        let stale = log.get_latest_checkpoint().unwrap().clone();
+        log.install_checkpoint(draft.clone()).unwrap();
+        log.install_checkpoint(stale).unwrap();
+        assert_eq!(log.get_latest_checkpoint().unwrap().tree_size, 3);
+        
+        let mut forged = draft;
+        forged.root_hash = [0; 32];
+        assert!(log.install_checkpoint(forged).is_err());
     }
 }